@@ -0,0 +1,139 @@
+//! Compares ``AnyVec`` against ``Vec<T>`` for common operations, to track the
+//! overhead of type-erased dispatch. Run with ``cargo bench``.
+//!
+//! Each operation is measured for ``u8``, ``u64`` and ``String`` elements, at
+//! each of ``SIZES``, once on a ``Vec`` and once on an ``AnyVec``:
+//!
+//! - ``push``: pushing clones of every element onto an empty vector.
+//! - ``iterate``: visiting every element (downcast, for ``AnyVec``).
+//! - ``clone``: cloning the whole vector.
+//! - ``sort``: sorting a shuffled copy; ``AnyVec`` goes through ``argsort``
+//!   and ``take``, calling ``cmp`` through the vtable.
+//! - ``extend``: appending the vector to a clone of itself.
+//!
+//! The ``new`` group measures creating an empty vector, which for
+//! ``AnyVec`` means looking up its vtable, both from one thread and from four
+//! at once.
+
+use std::any::Any;
+use std::thread;
+use std::time::Instant;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use anyvector::AnyVec;
+
+const SIZES: &[usize] = &[100, 10_000];
+
+// A fixed permutation of ``0..size``, so sorts have real work to do.
+fn shuffled(i: usize, size: usize) -> usize {
+    i.wrapping_mul(2_654_435_761) % size
+}
+
+fn bench_type<T: Any + Clone + Ord>(c: &mut Criterion, name: &str, make: fn(usize) -> T) {
+    for &size in SIZES {
+        let values: Vec<T> = (0..size).map(make).collect();
+        let dynamic = AnyVec::from_vec(values.clone());
+        let unsorted: Vec<T> = (0..size).map(|i| make(shuffled(i, size))).collect();
+        let unsorted_dynamic = AnyVec::from_vec(unsorted.clone());
+
+        let mut group = c.benchmark_group(format!("{}/{}", name, size));
+        group.bench_function("push/Vec", |b| {
+            b.iter(|| {
+                let mut vec = Vec::new();
+                for value in &values {
+                    vec.push(value.clone());
+                }
+                vec
+            })
+        });
+        group.bench_function("push/AnyVec", |b| {
+            b.iter(|| {
+                let mut vec = AnyVec::new::<T>();
+                for value in &values {
+                    vec.push(value.clone());
+                }
+                vec
+            })
+        });
+
+        group.bench_function("iterate/Vec", |b| {
+            b.iter(|| {
+                values.iter().for_each(|value| {
+                    black_box(value);
+                })
+            })
+        });
+        group.bench_function("iterate/AnyVec", |b| {
+            b.iter(|| {
+                dynamic.as_any_slice().iter().for_each(|value| {
+                    black_box(value.downcast_ref::<T>());
+                })
+            })
+        });
+
+        group.bench_function("clone/Vec", |b| b.iter(|| values.clone()));
+        group.bench_function("clone/AnyVec", |b| b.iter(|| dynamic.clone()));
+
+        group.bench_function("sort/Vec", |b| {
+            b.iter(|| {
+                let mut vec = unsorted.clone();
+                vec.sort();
+                vec
+            })
+        });
+        group.bench_function("sort/AnyVec", |b| {
+            b.iter(|| unsorted_dynamic.take(&unsorted_dynamic.argsort()))
+        });
+
+        group.bench_function("extend/Vec", |b| {
+            b.iter(|| {
+                let mut vec = values.clone();
+                vec.extend_from_slice(&values);
+                vec
+            })
+        });
+        group.bench_function("extend/AnyVec", |b| {
+            b.iter(|| {
+                let mut vec = dynamic.clone();
+                vec.extend_from_anyvec(&dynamic);
+                vec
+            })
+        });
+        group.finish();
+    }
+}
+
+// Creating a vector looks up its interned vtable, from every thread at once in
+// the contended case.
+fn bench_new(c: &mut Criterion) {
+    let mut group = c.benchmark_group("new");
+    group.bench_function("Vec", |b| b.iter(Vec::<u64>::new));
+    group.bench_function("AnyVec", |b| b.iter(AnyVec::new::<u64>));
+    group.bench_function("AnyVec/4 threads", |b| {
+        b.iter_custom(|iters| {
+            let start = Instant::now();
+            thread::scope(|scope| {
+                for _ in 0..4 {
+                    scope.spawn(|| {
+                        for _ in 0..iters {
+                            black_box(AnyVec::new::<u64>());
+                        }
+                    });
+                }
+            });
+            start.elapsed()
+        })
+    });
+    group.finish();
+}
+
+fn bench(c: &mut Criterion) {
+    bench_new(c);
+    bench_type(c, "u8", |i| i as u8);
+    bench_type(c, "u64", |i| i as u64);
+    bench_type(c, "String", |i| i.to_string());
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,149 @@
+//! Derive macros for ``anyvector``. Use them through the ``derive`` feature of
+//! that crate, which re-exports them.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields};
+
+// Methods we generate on the columns struct, which fields can't share names
+// with, since each field also gets an accessor.
+const RESERVED: &[&str] = &["new", "len", "is_empty", "push", "columns"];
+
+/// Derive ``anyvector::AnyColumns`` for a struct with named fields, generating
+/// a ``<Name>Columns`` struct holding one ``AnyVec`` per field.
+#[proc_macro_derive(AnyColumns)]
+pub fn derive_any_columns(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "AnyColumns can't be derived for generic structs",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "AnyColumns can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "AnyColumns can only be derived for structs",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let columns = format_ident!("{}Columns", name);
+    let idents: Vec<_> = fields.iter().map(|f| f.ident.clone().unwrap()).collect();
+    let types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    let names: Vec<String> = idents.iter().map(|i| i.to_string()).collect();
+    let locals: Vec<_> = idents.iter().map(|i| format_ident!("__{}", i)).collect();
+
+    for ident in &idents {
+        if RESERVED.contains(&ident.to_string().as_str()) {
+            return Err(Error::new_spanned(
+                ident,
+                format!("field `{}` clashes with a method of {}", ident, columns),
+            ));
+        }
+    }
+
+    let len = match idents.first() {
+        Some(first) => quote!(self.#first.len()),
+        None => quote!(0),
+    };
+    let columns_doc = format!("The fields of many ``{}``s, one ``AnyVec`` each.", name);
+
+    Ok(quote! {
+        #[doc = #columns_doc]
+        #vis struct #columns {
+            #(#vis #idents: ::anyvector::AnyVec,)*
+        }
+
+        impl #columns {
+            #vis fn new() -> #columns {
+                #columns {
+                    #(#idents: ::anyvector::AnyVec::new::<#types>(),)*
+                }
+            }
+
+            #vis fn len(&self) -> usize {
+                #len
+            }
+
+            #vis fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            #vis fn push(&mut self, row: #name) {
+                let #name { #(#idents: #locals),* } = row;
+                #(self.#idents.push(#locals);)*
+            }
+
+            #(
+                #vis fn #idents(&self) -> &[#types] {
+                    self.#idents.get::<#types, _>(..).unwrap()
+                }
+            )*
+
+            /// Each column with its field name, in declaration order.
+            #vis fn columns(&self) -> ::std::vec::Vec<(&'static str, &::anyvector::AnyVec)> {
+                ::std::vec![#((#names, &self.#idents)),*]
+            }
+        }
+
+        impl ::std::default::Default for #columns {
+            fn default() -> #columns {
+                #columns::new()
+            }
+        }
+
+        impl ::anyvector::AnyColumns for #name {
+            type Columns = #columns;
+
+            const FIELDS: &'static [&'static str] = &[#(#names),*];
+
+            fn into_columns(rows: ::std::vec::Vec<#name>) -> #columns {
+                let mut columns = #columns::new();
+                #(columns.#idents.reserve(rows.len());)*
+                for row in rows {
+                    columns.push(row);
+                }
+                columns
+            }
+
+            fn from_columns(columns: #columns) -> ::std::vec::Vec<#name> {
+                let len = columns.len();
+                for (name, column) in columns.columns() {
+                    if column.len() != len {
+                        panic!(
+                            "Columns have different lengths ({} has {}, expected {})",
+                            name,
+                            column.len(),
+                            len
+                        );
+                    }
+                }
+                let #columns { #(#idents: #locals),* } = columns;
+                #(let mut #locals = #locals.into_vec_in::<#types>().into_iter();)*
+                (0..len)
+                    .map(|_| #name { #(#idents: #locals.next().unwrap()),* })
+                    .collect()
+            }
+        }
+    })
+}
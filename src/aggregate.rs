@@ -0,0 +1,412 @@
+//! Incremental aggregation over erased columns.
+//!
+//! An [`Aggregator`] folds in one chunk of a column at a time, and partial
+//! aggregators built over separate chunks (e.g. on separate threads) can be
+//! merged before finishing.
+
+use std::cmp::Ordering;
+
+use crate::numeric::Number;
+use crate::vtable::VTable;
+use crate::{Allocator, AnyRef, AnySlice, AnyValue, AnyVec};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregation {
+    Count,
+    /// Requires numeric elements.
+    Sum,
+    /// Requires ordered, cloneable elements.
+    Min,
+    /// Requires ordered, cloneable elements.
+    Max,
+}
+
+enum State {
+    Count(usize),
+    Sum(Number),
+    Extreme(Option<AnyValue>),
+}
+
+/// Running state of one aggregation over a column of a single type.
+pub struct Aggregator {
+    aggregation: Aggregation,
+    vtable: &'static VTable,
+    state: State,
+}
+
+impl Aggregator {
+    /// An empty aggregator for columns of ``column``'s type.
+    ///
+    /// Panics if the type lacks a capability ``aggregation`` needs.
+    pub fn new(aggregation: Aggregation, column: &AnySlice) -> Aggregator {
+        let vtable = column.vtable();
+        let state = match aggregation {
+            Aggregation::Count => State::Count(0),
+            Aggregation::Sum => {
+                vtable.require_numeric();
+                State::Sum(Number::Int(0))
+            }
+            Aggregation::Min | Aggregation::Max => {
+                vtable.require_cmp();
+                vtable.require_clone();
+                State::Extreme(None)
+            }
+        };
+        Aggregator {
+            aggregation,
+            vtable,
+            state,
+        }
+    }
+
+    pub fn aggregation(&self) -> Aggregation {
+        self.aggregation
+    }
+
+    /// Fold ``values`` into the running state.
+    ///
+    /// Panics if their type doesn't match the column we were created for.
+    pub fn update(&mut self, values: &AnySlice) {
+        self.vtable.assert_same_type(values.vtable());
+        match &mut self.state {
+            State::Count(count) => *count += values.len(),
+            State::Sum(total) => *total = add(*total, sum(values), self.vtable),
+            State::Extreme(best) => {
+                let order = match self.aggregation {
+                    Aggregation::Min => Ordering::Less,
+                    _ => Ordering::Greater,
+                };
+                if let Some(index) = extreme_index(values, order) {
+                    let value = values.get_ref(index).unwrap();
+                    let better = match best {
+                        Some(current) => {
+                            is_better(self.aggregation, self.vtable, value.data(), current)
+                        }
+                        None => true,
+                    };
+                    if better {
+                        *best = Some(value.to_value());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fold in the state of another aggregator over the same column type.
+    ///
+    /// Panics if ``other`` computes a different aggregation or covers a
+    /// different type.
+    pub fn merge(&mut self, other: &Aggregator) {
+        if self.aggregation != other.aggregation {
+            panic!(
+                "Can't merge {:?} aggregator into {:?} aggregator",
+                other.aggregation, self.aggregation
+            );
+        }
+        self.vtable.assert_same_type(other.vtable);
+        match (&mut self.state, &other.state) {
+            (State::Count(count), State::Count(other)) => *count += other,
+            (State::Sum(total), State::Sum(other)) => *total = add(*total, *other, self.vtable),
+            (State::Extreme(best), State::Extreme(Some(other))) => {
+                let better = match best {
+                    Some(current) => {
+                        let other = other.as_any_ref();
+                        is_better(self.aggregation, self.vtable, other.data(), current)
+                    }
+                    None => true,
+                };
+                if better {
+                    *best = Some(other.as_any_ref().to_value());
+                }
+            }
+            (State::Extreme(_), State::Extreme(None)) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    /// The aggregate of everything folded in so far.
+    ///
+    /// ``Count`` produces a ``usize`` and ``Sum`` produces the column's own
+    /// type (zero for an empty column). ``Min`` and ``Max`` return ``None``
+    /// for an empty column. Panics if an integer sum overflows the column's
+    /// type.
+    pub fn finish(self) -> Option<AnyValue> {
+        match self.state {
+            State::Count(count) => Some(AnyValue::new(count)),
+            State::Sum(total) => {
+                let from_number = self.vtable.require_numeric().from_number;
+                match from_number(total) {
+                    Some(value) => Some(value),
+                    None => panic!("Sum overflows {}", self.vtable.display_name),
+                }
+            }
+            State::Extreme(best) => best,
+        }
+    }
+}
+
+fn add(a: Number, b: Number, vtable: &VTable) -> Number {
+    match (a, b) {
+        (Number::Int(a), Number::Int(b)) => match a.checked_add(b) {
+            Some(sum) => Number::Int(sum),
+            None => panic!("Sum overflows {}", vtable.display_name),
+        },
+        // Our starting zero is an integer, even for float columns.
+        (Number::Int(a), Number::Float(b)) | (Number::Float(b), Number::Int(a)) => {
+            Number::Float(a as f64 + b)
+        }
+        (Number::Float(a), Number::Float(b)) => Number::Float(a + b),
+    }
+}
+
+/// The sum of ``values``, which must be numeric, using the type's kernel if
+/// it has one.
+fn sum(values: &AnySlice) -> Number {
+    let vtable = values.vtable();
+    let numeric = vtable.require_numeric();
+    match numeric.kernels {
+        Some(kernels) => (kernels.sum)(values.data(), values.len()),
+        None => values.iter().fold(Number::Int(0), |total, value| {
+            add(total, (numeric.to_number)(value.data()), vtable)
+        }),
+    }
+}
+
+/// The index of the first element of ``values`` that no other compares
+/// ``better`` than, using the type's kernel if it has one.
+fn extreme_index(values: &AnySlice, better: Ordering) -> Option<usize> {
+    let vtable = values.vtable();
+    let cmp = vtable.require_cmp();
+    if let Some(kernels) = vtable.numeric.and_then(|numeric| numeric.kernels) {
+        let kernel = match better {
+            Ordering::Less => kernels.argmin,
+            _ => kernels.argmax,
+        };
+        return kernel(values.data(), values.len());
+    }
+    if values.is_empty() {
+        return None;
+    }
+    let element = |i| values.get_ref(i).unwrap().data();
+    let mut best = 0;
+    for i in 1..values.len() {
+        if cmp(element(i), element(best)) == better {
+            best = i;
+        }
+    }
+    Some(best)
+}
+
+/// Whether ``candidate`` should replace ``current`` as the running extreme.
+/// Ties keep the earlier value.
+fn is_better(
+    aggregation: Aggregation,
+    vtable: &VTable,
+    candidate: *const u8,
+    current: &AnyValue,
+) -> bool {
+    let ordering = (vtable.require_cmp())(candidate, current.as_any_ref().data());
+    match aggregation {
+        Aggregation::Min => ordering == Ordering::Less,
+        Aggregation::Max => ordering == Ordering::Greater,
+        Aggregation::Count | Aggregation::Sum => unreachable!(),
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// An empty aggregator for columns of our type.
+    pub fn aggregator(&self, aggregation: Aggregation) -> Aggregator {
+        Aggregator::new(aggregation, &self.as_any_slice())
+    }
+
+    /// Aggregate all of our elements in one go.
+    pub fn aggregate(&self, aggregation: Aggregation) -> Option<AnyValue> {
+        let mut aggregator = self.aggregator(aggregation);
+        aggregator.update(&self.as_any_slice());
+        aggregator.finish()
+    }
+
+    /// The index of our least element, the first one if there are ties, or
+    /// ``None`` if we're empty.
+    ///
+    /// Panics if the element type doesn't support ordering.
+    pub fn argmin(&self) -> Option<usize> {
+        extreme_index(&self.as_any_slice(), Ordering::Less)
+    }
+
+    /// The index of our greatest element, the first one if there are ties,
+    /// or ``None`` if we're empty.
+    ///
+    /// Panics if the element type doesn't support ordering.
+    pub fn argmax(&self) -> Option<usize> {
+        extreme_index(&self.as_any_slice(), Ordering::Greater)
+    }
+
+    /// Like [`AnyVec::argmin`], but returns the element itself.
+    pub fn min(&self) -> Option<AnyRef<'_>> {
+        self.argmin().and_then(|index| self.get_ref(index))
+    }
+
+    /// Like [`AnyVec::argmax`], but returns the element itself.
+    pub fn max(&self) -> Option<AnyRef<'_>> {
+        self.argmax().and_then(|index| self.get_ref(index))
+    }
+
+    /// The sum of our elements on the cast lattice, so it can't overflow
+    /// for the primitive number types.
+    ///
+    /// Panics if the element type isn't numeric.
+    pub fn sum(&self) -> Number {
+        sum(&self.as_any_slice())
+    }
+
+    /// The arithmetic mean of our elements, or ``None`` if we're empty.
+    ///
+    /// Panics if the element type isn't numeric.
+    pub fn mean(&self) -> Option<f64> {
+        if self.is_empty() {
+            return None;
+        }
+        let total = match self.sum() {
+            Number::Int(i) => i as f64,
+            Number::Float(f) => f,
+        };
+        Some(total / self.length as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aggregation, Aggregator};
+    use crate::{AnyVec, Number, Numeric};
+
+    #[test]
+    fn test_aggregate() {
+        let dynamic = AnyVec::from_vec::<i32>(vec![3, -1, 4, 1, 5]);
+        let aggregate = |aggregation| dynamic.aggregate(aggregation).unwrap();
+
+        assert_eq!(
+            aggregate(Aggregation::Count).downcast::<usize>().unwrap(),
+            5
+        );
+        assert_eq!(aggregate(Aggregation::Sum).downcast::<i32>().unwrap(), 12);
+        assert_eq!(aggregate(Aggregation::Min).downcast::<i32>().unwrap(), -1);
+        assert_eq!(aggregate(Aggregation::Max).downcast::<i32>().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_update_and_merge_chunks() {
+        let dynamic = AnyVec::from_vec::<f64>(vec![1.5, 2.5, -4.0, 8.0, 0.25]);
+        let slice = dynamic.as_any_slice();
+
+        for (aggregation, expected) in [
+            (Aggregation::Sum, 8.25),
+            (Aggregation::Min, -4.0),
+            (Aggregation::Max, 8.0),
+        ] {
+            let mut left = Aggregator::new(aggregation, &slice);
+            left.update(&slice.slice(..2));
+            let mut right = dynamic.aggregator(aggregation);
+            right.update(&slice.slice(2..3));
+            right.update(&slice.slice(3..));
+
+            left.merge(&right);
+            assert_eq!(left.finish().unwrap().downcast::<f64>().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let dynamic = AnyVec::new::<u8>();
+        assert_eq!(
+            dynamic
+                .aggregate(Aggregation::Sum)
+                .unwrap()
+                .downcast::<u8>()
+                .unwrap(),
+            0
+        );
+        assert!(dynamic.aggregate(Aggregation::Max).is_none());
+
+        let mut min = dynamic.aggregator(Aggregation::Min);
+        min.merge(&dynamic.aggregator(Aggregation::Min));
+        assert!(min.finish().is_none());
+    }
+
+    #[test]
+    fn test_strings() {
+        let dynamic = AnyVec::from_vec(vec![String::from("pear"), String::from("apple")]);
+        let min = dynamic.aggregate(Aggregation::Min).unwrap();
+        assert_eq!(min.downcast::<String>().unwrap(), "apple");
+    }
+
+    #[test]
+    fn test_min_max_refs() {
+        let dynamic = AnyVec::from_vec::<i32>(vec![3, -1, 5, -1, 5]);
+        assert_eq!((dynamic.argmin(), dynamic.argmax()), (Some(1), Some(2)));
+        assert_eq!(dynamic.min().unwrap().downcast_ref::<i32>(), Some(&-1));
+        assert_eq!(dynamic.max().unwrap().downcast_ref::<i32>(), Some(&5));
+
+        let empty = AnyVec::new::<i32>();
+        assert_eq!((empty.argmin(), empty.argmax()), (None, None));
+        assert!(empty.min().is_none());
+    }
+
+    #[test]
+    fn test_sum_and_mean() {
+        let ints = AnyVec::from_vec::<u8>(vec![200, 100, 3]);
+        assert_eq!(ints.sum(), Number::Int(303));
+        assert_eq!(ints.mean(), Some(101.0));
+
+        let floats = AnyVec::from_vec::<f32>(vec![0.5, 1.5, -1.0]);
+        assert_eq!(floats.sum(), Number::Float(1.0));
+        assert_eq!(AnyVec::new::<f64>().mean(), None);
+    }
+
+    #[test]
+    fn test_without_kernels() {
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+        struct Cents(i64);
+
+        impl Numeric for Cents {
+            fn to_number(self) -> Number {
+                Number::Int(self.0 as i128)
+            }
+
+            fn from_number(number: Number) -> Option<Cents> {
+                i64::from_number(number).map(Cents)
+            }
+        }
+
+        let dynamic = AnyVec::from_vec(vec![Cents(5), Cents(-2), Cents(5)])
+            .with_numeric::<Cents>()
+            .with_ord::<Cents>()
+            .with_clone::<Cents>();
+        assert_eq!(dynamic.sum(), Number::Int(8));
+        assert_eq!((dynamic.argmin(), dynamic.argmax()), (Some(1), Some(0)));
+        let max = dynamic.aggregate(Aggregation::Max).unwrap();
+        assert!(max.downcast::<Cents>().ok().unwrap() == Cents(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Sum overflows u8")]
+    fn test_sum_overflow() {
+        AnyVec::from_vec::<u8>(vec![200, 100]).aggregate(Aggregation::Sum);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support ordering")]
+    fn test_requires_capability() {
+        #[derive(Clone)]
+        struct Opaque;
+        AnyVec::from_vec(vec![Opaque]).aggregator(Aggregation::Max);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't merge")]
+    fn test_merge_mismatched_aggregation() {
+        let dynamic = AnyVec::from_vec::<i64>(vec![1]);
+        let mut sum = dynamic.aggregator(Aggregation::Sum);
+        sum.merge(&dynamic.aggregator(Aggregation::Count));
+    }
+}
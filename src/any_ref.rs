@@ -0,0 +1,144 @@
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::any_value::AnyValue;
+use crate::numeric::{Number, NumericCoercion};
+use crate::vtable::VTable;
+
+/// A borrowed reference to a single element whose type is only known at
+/// runtime.
+#[derive(Clone, Copy)]
+pub struct AnyRef<'a> {
+    data: *const u8,
+    vtable: &'static VTable,
+    _marker: PhantomData<&'a u8>,
+}
+
+impl<'a> AnyRef<'a> {
+    /// ``data`` must point to a live value of ``vtable``'s type that outlives
+    /// ``'a``.
+    pub(crate) unsafe fn new(data: *const u8, vtable: &'static VTable) -> AnyRef<'a> {
+        AnyRef {
+            data,
+            vtable,
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn data(&self) -> *const u8 {
+        self.data
+    }
+
+    pub(crate) fn vtable(&self) -> &'static VTable {
+        self.vtable
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.vtable.is::<T>()
+    }
+
+    /// The ``TypeId`` of the element's type.
+    pub fn type_id(&self) -> TypeId {
+        self.vtable.id()
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.vtable.display_name
+    }
+
+    /// The size of the element in bytes.
+    pub fn element_size(&self) -> usize {
+        self.vtable.size
+    }
+
+    /// The element rendered with ``Display``, or ``None`` if its type
+    /// doesn't support it.
+    pub fn to_display_string(&self) -> Option<String> {
+        self.vtable.display.map(|_| self.to_string())
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&'a T> {
+        if self.is::<T>() {
+            Some(unsafe { &*(self.data as *const T) })
+        } else {
+            None
+        }
+    }
+
+    /// Clone the element into an owned value.
+    ///
+    /// Panics if the element's type doesn't support cloning.
+    pub fn to_value(&self) -> AnyValue {
+        let boxed = (self.vtable.require_clone().clone_box)(self.data);
+        unsafe { AnyValue::from_raw(boxed, self.vtable) }
+    }
+
+    /// The element widened onto the numeric cast lattice, if it's a number.
+    pub fn to_number(&self) -> Option<Number> {
+        self.vtable
+            .numeric
+            .map(|numeric| (numeric.to_number)(self.data))
+    }
+
+    /// Compare with ``other`` under the given coercion policy.
+    ///
+    /// Panics if the elements need to be compared by type and their type
+    /// doesn't support equality.
+    pub fn eq_with(&self, other: &AnyRef, coercion: NumericCoercion) -> bool {
+        if coercion == NumericCoercion::Lossless {
+            if let (Some(a), Some(b)) = (self.to_number(), other.to_number()) {
+                return a == b;
+            }
+        }
+
+        if !self.vtable.same_type(other.vtable) {
+            return false;
+        }
+        (self.vtable.require_eq())(self.data, other.data)
+    }
+
+    /// Compare with a concrete value under the given coercion policy.
+    pub fn eq_value_with<T: Any + PartialEq>(&self, value: &T, coercion: NumericCoercion) -> bool {
+        if coercion == NumericCoercion::Lossless {
+            // ``VTable::new`` is cached per thread, so this doesn't intern.
+            if let (Some(a), Some(numeric)) = (self.to_number(), VTable::new::<T>().numeric) {
+                return a == (numeric.to_number)(value as *const T as *const u8);
+            }
+        }
+        self.downcast_ref::<T>() == Some(value)
+    }
+}
+
+impl PartialEq for AnyRef<'_> {
+    fn eq(&self, other: &AnyRef) -> bool {
+        self.eq_with(other, NumericCoercion::Strict)
+    }
+}
+
+impl<T: Any + PartialEq> PartialEq<&T> for AnyRef<'_> {
+    fn eq(&self, other: &&T) -> bool {
+        self.downcast_ref::<T>() == Some(*other)
+    }
+}
+
+/// Formats the element itself if its type supports ``Debug``, or just its
+/// type name otherwise.
+impl fmt::Debug for AnyRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.vtable.debug {
+            Some(debug) => debug(self.data, f),
+            None => f
+                .debug_struct("AnyRef")
+                .field("type", &self.vtable.display_name)
+                .finish(),
+        }
+    }
+}
+
+/// Panics if the element's type doesn't support ``Display``.
+impl fmt::Display for AnyRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (self.vtable.require_display())(self.data, f)
+    }
+}
@@ -0,0 +1,304 @@
+use std::any::Any;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+use crate::any_ref::AnyRef;
+use crate::vtable::VTable;
+
+/// A borrowed, contiguous run of elements whose type is only known at
+/// runtime.
+#[derive(Clone, Copy)]
+pub struct AnySlice<'a> {
+    data: *const u8,
+    length: usize,
+    vtable: &'static VTable,
+    _marker: PhantomData<&'a u8>,
+}
+
+impl<'a> AnySlice<'a> {
+    /// ``data`` must point to ``length`` live values of ``vtable``'s type that
+    /// outlive ``'a``.
+    pub(crate) unsafe fn new(
+        data: *const u8,
+        length: usize,
+        vtable: &'static VTable,
+    ) -> AnySlice<'a> {
+        AnySlice {
+            data,
+            length,
+            vtable,
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn vtable(&self) -> &'static VTable {
+        self.vtable
+    }
+
+    pub(crate) fn data(&self) -> *const u8 {
+        self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.vtable.is::<T>()
+    }
+
+    pub fn downcast<T: Any>(&self) -> Option<&'a [T]> {
+        if self.is::<T>() {
+            Some(unsafe { std::slice::from_raw_parts(self.data as *const T, self.length) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'a>> {
+        if index < self.length {
+            Some(unsafe { AnyRef::new(self.data.add(index * self.vtable.size), self.vtable) })
+        } else {
+            None
+        }
+    }
+
+    /// A sub-slice covering ``range``. Panics if it's out of bounds.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> AnySlice<'a> {
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => i + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&i) => i + 1,
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => self.length,
+        };
+        if start > end || end > self.length {
+            panic!(
+                "Range {}..{} out of bounds for slice of length {}",
+                start, end, self.length
+            );
+        }
+        unsafe {
+            AnySlice::new(
+                self.data.add(start * self.vtable.size),
+                end - start,
+                self.vtable,
+            )
+        }
+    }
+
+    /// Our first element and the rest, or ``None`` if we're empty.
+    pub fn split_first(&self) -> Option<(AnyRef<'a>, AnySlice<'a>)> {
+        let first = self.get_ref(0)?;
+        Some((first, self.slice(1..)))
+    }
+
+    /// Our last element and the rest, or ``None`` if we're empty.
+    pub fn split_last(&self) -> Option<(AnyRef<'a>, AnySlice<'a>)> {
+        let last = self.get_ref(self.length.checked_sub(1)?)?;
+        Some((last, self.slice(..self.length - 1)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = AnyRef<'a>> + 'a {
+        let this = *self;
+        (0..this.length).map(move |i| this.get_ref(i).unwrap())
+    }
+
+    /// Whether any element is equal to ``value``.
+    ///
+    /// Panics if ``value``'s type doesn't match ours, or doesn't support
+    /// equality.
+    pub fn contains(&self, value: AnyRef<'_>) -> bool {
+        self.vtable.assert_same_type(value.vtable());
+        if let Some(byte) = self.search_byte(value) {
+            return memchr::memchr(byte, self.as_bytes()).is_some();
+        }
+        if let Some(kernels) = self.vtable.slice_kernels {
+            return (kernels.contains)(self.data, self.length, value.data());
+        }
+        self.position(value).is_some()
+    }
+
+    /// The index of the first element equal to ``value``.
+    ///
+    /// Panics if ``value``'s type doesn't match ours, or doesn't support
+    /// equality.
+    pub fn position(&self, value: AnyRef<'_>) -> Option<usize> {
+        self.vtable.assert_same_type(value.vtable());
+        if let Some(byte) = self.search_byte(value) {
+            return memchr::memchr(byte, self.as_bytes());
+        }
+        let eq = self.vtable.require_eq();
+        (0..self.length)
+            .position(|i| eq(unsafe { self.data.add(i * self.vtable.size) }, value.data()))
+    }
+
+    // Single-byte elements that are equal exactly when their bytes are, like
+    // ``u8``s and ``bool``s, can be searched for with ``memchr``.
+    fn search_byte(&self, value: AnyRef<'_>) -> Option<u8> {
+        if self.vtable.size == 1 && self.vtable.bytewise_eq {
+            Some(unsafe { *value.data() })
+        } else {
+            None
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data, self.length * self.vtable.size) }
+    }
+}
+
+/// A mutably borrowed, contiguous run of elements whose type is only known
+/// at runtime.
+pub struct AnySliceMut<'a> {
+    data: *mut u8,
+    length: usize,
+    vtable: &'static VTable,
+    _marker: PhantomData<&'a mut u8>,
+}
+
+impl<'a> AnySliceMut<'a> {
+    /// ``data`` must point to ``length`` live values of ``vtable``'s type that
+    /// outlive ``'a`` and aren't otherwise borrowed.
+    pub(crate) unsafe fn new(
+        data: *mut u8,
+        length: usize,
+        vtable: &'static VTable,
+    ) -> AnySliceMut<'a> {
+        AnySliceMut {
+            data,
+            length,
+            vtable,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.vtable.is::<T>()
+    }
+
+    pub fn as_any_slice(&self) -> AnySlice<'_> {
+        unsafe { AnySlice::new(self.data, self.length, self.vtable) }
+    }
+
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut [T]> {
+        if self.is::<T>() {
+            Some(unsafe { std::slice::from_raw_parts_mut(self.data as *mut T, self.length) })
+        } else {
+            None
+        }
+    }
+
+    /// Split into the elements before ``mid`` and the rest. Panics if ``mid``
+    /// is out of bounds.
+    pub fn split_at_mut(self, mid: usize) -> (AnySliceMut<'a>, AnySliceMut<'a>) {
+        if mid > self.length {
+            panic!(
+                "Index {} out of bounds for slice of length {}",
+                mid, self.length
+            );
+        }
+        unsafe {
+            (
+                AnySliceMut::new(self.data, mid, self.vtable),
+                AnySliceMut::new(
+                    self.data.add(mid * self.vtable.size),
+                    self.length - mid,
+                    self.vtable,
+                ),
+            )
+        }
+    }
+}
+
+impl fmt::Debug for AnySliceMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AnySliceMut")
+            .field("type", &self.vtable.display_name)
+            .field("len", &self.length)
+            .finish()
+    }
+}
+
+impl fmt::Debug for AnySlice<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AnySlice")
+            .field("type", &self.vtable.display_name)
+            .field("len", &self.length)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    #[test]
+    fn test_slice() {
+        let dynamic = AnyVec::from_vec::<u32>(vec![1, 2, 3, 4, 5]);
+        let slice = dynamic.as_any_slice();
+        assert_eq!(slice.len(), 5);
+
+        let middle = slice.slice(1..4);
+        assert_eq!(middle.downcast::<u32>(), Some(&[2, 3, 4][..]));
+        assert_eq!(middle.downcast::<u64>(), None);
+        assert_eq!(middle.get_ref(0).unwrap(), &2u32);
+        assert!(middle.get_ref(3).is_none());
+
+        let tail: Vec<u32> = slice
+            .slice(3..)
+            .iter()
+            .map(|item| *item.downcast_ref::<u32>().unwrap())
+            .collect();
+        assert_eq!(tail, vec![4, 5]);
+        assert!(slice.slice(..0).is_empty());
+    }
+
+    #[test]
+    fn test_split_first_and_last() {
+        let rows = AnyVec::from_vec(vec![String::from("name"), String::from("a")]);
+        let (header, body) = rows.as_any_slice().split_first().unwrap();
+        assert_eq!(header, &String::from("name"));
+        assert_eq!(body.downcast::<String>(), Some(&[String::from("a")][..]));
+
+        let (last, rest) = body.split_last().unwrap();
+        assert_eq!(last, &String::from("a"));
+        assert!(rest.is_empty());
+        assert!(rest.split_first().is_none());
+        assert!(rest.split_last().is_none());
+    }
+
+    #[test]
+    fn test_slice_mut() {
+        let mut dynamic = AnyVec::from_vec::<u32>(vec![1, 2, 3]);
+        let (mut head, mut tail) = dynamic.as_any_slice_mut().split_at_mut(1);
+        head.downcast_mut::<u32>().unwrap()[0] = 10;
+        tail.downcast_mut::<u32>().unwrap().reverse();
+        assert!(tail.downcast_mut::<u64>().is_none());
+        assert_eq!(tail.as_any_slice().len(), 2);
+        assert_eq!(dynamic.into_vec::<u32>(), vec![10, 3, 2]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_slice_out_of_bounds() {
+        let dynamic = AnyVec::from_vec::<u32>(vec![1, 2, 3]);
+        dynamic.as_any_slice().slice(2..5);
+    }
+}
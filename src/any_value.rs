@@ -0,0 +1,243 @@
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::mem;
+
+use crate::any_ref::AnyRef;
+use crate::vtable::VTable;
+
+/// An owned, boxed value whose type is only known at runtime.
+///
+/// Unlike ``Box<dyn Any>``, an ``AnyValue`` carries the same vtable as an
+/// ``AnyVec``, so it supports the same erased capabilities.
+pub struct AnyValue {
+    data: *mut u8,
+    vtable: &'static VTable,
+}
+
+impl AnyValue {
+    pub fn new<T: Any>(value: T) -> AnyValue {
+        AnyValue {
+            data: Box::into_raw(Box::new(value)) as *mut u8,
+            vtable: VTable::new::<T>(),
+        }
+    }
+
+    /// Adopt a box holding a value of ``vtable``'s type.
+    pub(crate) unsafe fn from_raw(data: *mut u8, vtable: &'static VTable) -> AnyValue {
+        AnyValue { data, vtable }
+    }
+
+    /// Release ownership of our box, without dropping the value.
+    pub(crate) fn into_raw(self) -> (*mut u8, &'static VTable) {
+        let this = mem::ManuallyDrop::new(self);
+        (this.data, this.vtable)
+    }
+
+    pub(crate) fn vtable(&self) -> &'static VTable {
+        self.vtable
+    }
+
+    /// The ``TypeId`` of the value's type.
+    pub fn type_id(&self) -> TypeId {
+        self.vtable.id()
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.vtable.display_name
+    }
+
+    /// The size of the value in bytes.
+    pub fn element_size(&self) -> usize {
+        self.vtable.size
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.vtable.is::<T>()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.as_any_ref().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        if self.is::<T>() {
+            Some(unsafe { &mut *(self.data as *mut T) })
+        } else {
+            None
+        }
+    }
+
+    /// Move the value out, or get ``self`` back if it isn't a ``T``.
+    pub fn downcast<T: Any>(self) -> Result<T, AnyValue> {
+        if !self.is::<T>() {
+            return Err(self);
+        }
+        let boxed = unsafe { Box::from_raw(self.data as *mut T) };
+        // The box owns our allocation now.
+        mem::forget(self);
+        Ok(*boxed)
+    }
+
+    pub fn as_any_ref(&self) -> AnyRef<'_> {
+        unsafe { AnyRef::new(self.data, self.vtable) }
+    }
+}
+
+impl PartialEq for AnyValue {
+    fn eq(&self, other: &AnyValue) -> bool {
+        self.as_any_ref() == other.as_any_ref()
+    }
+}
+
+impl fmt::Debug for AnyValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.vtable.debug {
+            Some(debug) => debug(self.data, f),
+            None => f
+                .debug_struct("AnyValue")
+                .field("type", &self.vtable.display_name)
+                .finish(),
+        }
+    }
+}
+
+/// Panics if the value's type doesn't support ``Display``.
+impl fmt::Display for AnyValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.as_any_ref(), f)
+    }
+}
+
+impl Drop for AnyValue {
+    fn drop(&mut self) {
+        (self.vtable.drop_box)(self.data)
+    }
+}
+
+/// Dispatch on the runtime type of an [`AnyValue`], moving the value into the
+/// first arm whose type matches.
+///
+/// ```
+/// use anyvector::{match_any, AnyValue};
+///
+/// let value = AnyValue::new(3i64);
+/// let described = match_any!(value,
+///     i64 => |x| format!("int {}", x),
+///     f64 => |x| format!("float {}", x),
+///     String => |s| s,
+///     _ => |other| format!("unsupported {}", other.type_name()),
+/// );
+/// assert_eq!(described, "int 3");
+/// ```
+///
+/// The fallback arm may also be written ``_ => expr`` if it doesn't need the
+/// value. It's required, so that every value is handled:
+///
+/// ```compile_fail
+/// use anyvector::{match_any, AnyValue};
+///
+/// match_any!(AnyValue::new(1u8), i64 => |x| x);
+/// ```
+#[macro_export]
+macro_rules! match_any {
+    ($value:expr, $($arms:tt)*) => {{
+        let value: $crate::AnyValue = $value;
+        $crate::__match_any_arms!(value, $($arms)*)
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __match_any_arms {
+    ($value:ident, _ => |$rest:pat_param| $body:expr $(,)?) => {{
+        let $rest = $value;
+        $body
+    }};
+    ($value:ident, _ => $body:expr $(,)?) => {{
+        let _ = $value;
+        $body
+    }};
+    ($value:ident $(,)?) => {
+        compile_error!("match_any! needs a fallback arm: `_ => ...`")
+    };
+    ($value:ident, $t:ty => |$x:pat_param| $body:expr $(, $($rest:tt)*)?) => {
+        match $value.downcast::<$t>() {
+            Ok($x) => $body,
+            Err($value) => $crate::__match_any_arms!($value $(, $($rest)*)?),
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnyValue;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn describe(value: AnyValue) -> String {
+        match_any!(value,
+            i64 => |x| format!("i64 {}", x),
+            f64 => |x| format!("f64 {}", x),
+            String => |s| format!("string {}", s),
+            _ => |other| format!("other {}", other.type_name()),
+        )
+    }
+
+    #[test]
+    fn test_match_any() {
+        assert_eq!(describe(AnyValue::new(3i64)), "i64 3");
+        assert_eq!(describe(AnyValue::new(2.5f64)), "f64 2.5");
+        assert_eq!(describe(AnyValue::new(String::from("x"))), "string x");
+        assert_eq!(describe(AnyValue::new(1u8)), "other u8");
+    }
+
+    #[test]
+    fn test_match_any_moves_value() {
+        let chan: Rc<RefCell<Vec<i64>>> = Rc::new(RefCell::new(vec![]));
+        let value = AnyValue::new(chan.clone());
+
+        let moved = match_any!(value, Rc<RefCell<Vec<i64>>> => |c| c, _ => panic!());
+        moved.borrow_mut().push(1);
+        assert_eq!(Rc::strong_count(&chan), 2);
+        drop(moved);
+        assert_eq!(Rc::strong_count(&chan), 1);
+        assert_eq!(*chan.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn test_match_any_fallback_without_binding() {
+        let result = match_any!(AnyValue::new(1u8), i64 => |x| x, _ => -1);
+        assert_eq!(result, -1);
+    }
+
+    #[test]
+    fn test_downcast() {
+        let mut value = AnyValue::new(5u64);
+        assert!(value.is::<u64>());
+        *value.downcast_mut::<u64>().unwrap() += 1;
+        assert_eq!(value.downcast_ref::<u64>(), Some(&6));
+
+        let value = value.downcast::<i32>().unwrap_err();
+        assert_eq!(value.downcast::<u64>().unwrap(), 6);
+    }
+
+    #[test]
+    fn test_formatting() {
+        let value = AnyValue::new(2.5f64);
+        assert_eq!(format!("{:?} {}", value, value), "2.5 2.5");
+
+        struct Opaque;
+        let value = AnyValue::new(Opaque);
+        assert!(format!("{:?}", value).contains("Opaque"));
+    }
+
+    #[test]
+    fn test_drop() {
+        let chan = Rc::new(());
+        let value = AnyValue::new(chan.clone());
+        assert_eq!(Rc::strong_count(&chan), 2);
+        drop(value);
+        assert_eq!(Rc::strong_count(&chan), 1);
+    }
+}
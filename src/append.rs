@@ -0,0 +1,393 @@
+//! An append-only vector that many threads can push to at once.
+
+use std::alloc::{self, Layout};
+use std::any::Any;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+use crate::vtable::VTable;
+use crate::{AnyRef, AnyVec, Global};
+
+// Bucket ``b`` holds ``FIRST_BUCKET << b`` elements, so bucket sizes double
+// and together they cover every index. Buckets never move once allocated,
+// which keeps element addresses stable while other threads push.
+const FIRST_BUCKET_SHIFT: u32 = 5;
+const BUCKETS: usize = (usize::BITS - FIRST_BUCKET_SHIFT) as usize;
+
+struct Bucket {
+    data: *mut u8,
+    // Whether each slot has been written.
+    ready: Box<[AtomicBool]>,
+}
+
+/// Split an index into its bucket and the offset within it.
+pub(crate) fn locate(index: usize) -> (usize, usize) {
+    let shifted = index + (1 << FIRST_BUCKET_SHIFT);
+    let bit = usize::BITS - 1 - shifted.leading_zeros();
+    ((bit - FIRST_BUCKET_SHIFT) as usize, shifted - (1 << bit))
+}
+
+pub(crate) fn bucket_len(bucket: usize) -> usize {
+    1 << (bucket as u32 + FIRST_BUCKET_SHIFT)
+}
+
+/// A vector that supports concurrent ``push`` through a shared reference.
+///
+/// Each push returns its element's index, which stays valid for as long as
+/// the vector lives. Readers see the elements that have been fully pushed
+/// through [`AppendAnyVec::snapshot`].
+///
+/// ```
+/// use anyvector::AppendAnyVec;
+/// use std::thread;
+///
+/// let events = AppendAnyVec::new::<u64>();
+/// thread::scope(|scope| {
+///     for i in 0..4 {
+///         let events = &events;
+///         scope.spawn(move || events.push(i as u64));
+///     }
+/// });
+/// assert_eq!(events.snapshot().len(), 4);
+/// ```
+pub struct AppendAnyVec {
+    vtable: &'static VTable,
+    buckets: [AtomicPtr<Bucket>; BUCKETS],
+    // The number of slots handed out to pushers.
+    reserved: AtomicUsize,
+    // The length of the prefix of slots that have all been written.
+    committed: AtomicUsize,
+}
+
+// Only built for element types that are ``Send`` and ``Sync``.
+unsafe impl Send for AppendAnyVec {}
+unsafe impl Sync for AppendAnyVec {}
+
+impl AppendAnyVec {
+    pub fn new<T: Any + Send + Sync>() -> AppendAnyVec {
+        AppendAnyVec {
+            vtable: VTable::new::<T>().with_send::<T>().with_sync::<T>(),
+            buckets: std::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            reserved: AtomicUsize::new(0),
+            committed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Append ``value``, returning its index.
+    pub fn push<T: Any>(&self, value: T) -> usize {
+        self.vtable.assert_typecheck::<T>();
+        let index = self.claim();
+        let (bucket, offset) = locate(index);
+        let bucket = unsafe { &*self.bucket(bucket) };
+        unsafe { ptr::write((bucket.data as *mut T).add(offset), value) };
+        // SeqCst, paired with the loads in ``advance_committed``: either we
+        // see the prefix reach our slot, or whoever moved it there sees our
+        // slot ready. With weaker orderings both could miss the other and
+        // the prefix would stall.
+        bucket.ready[offset].store(true, Ordering::SeqCst);
+        self.advance_committed();
+        index
+    }
+
+    /// Reserve the next slot. Its bucket's layout is checked first, so once
+    /// a slot is claimed nothing can stop it being written, which would
+    /// stall the committed prefix behind it.
+    fn claim(&self) -> usize {
+        let mut index = self.reserved.load(Ordering::Relaxed);
+        loop {
+            self.layout(locate(index).0);
+            match self.reserved.compare_exchange_weak(
+                index,
+                index + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return index,
+                Err(current) => index = current,
+            }
+        }
+    }
+
+    /// The bucket at ``index``, allocating it if no one has yet.
+    fn bucket(&self, index: usize) -> *mut Bucket {
+        let existing = self.buckets[index].load(Ordering::Acquire);
+        if !existing.is_null() {
+            return existing;
+        }
+        let fresh = Box::into_raw(Box::new(self.allocate_bucket(index)));
+        match self.buckets[index].compare_exchange(
+            ptr::null_mut(),
+            fresh,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => fresh,
+            Err(winner) => {
+                unsafe { self.free_bucket(index, fresh, 0) };
+                winner
+            }
+        }
+    }
+
+    fn layout(&self, bucket: usize) -> Layout {
+        Layout::from_size_align(self.vtable.size * bucket_len(bucket), self.vtable.align)
+            .expect("AppendAnyVec bucket is too large")
+    }
+
+    fn allocate_bucket(&self, index: usize) -> Bucket {
+        let layout = self.layout(index);
+        let data = if layout.size() == 0 {
+            self.vtable.align as *mut u8
+        } else {
+            let data = unsafe { alloc::alloc(layout) };
+            if data.is_null() {
+                alloc::handle_alloc_error(layout);
+            }
+            data
+        };
+        Bucket {
+            data,
+            ready: (0..bucket_len(index))
+                .map(|_| AtomicBool::new(false))
+                .collect(),
+        }
+    }
+
+    /// Drop the first ``len`` elements of ``bucket`` and free it.
+    unsafe fn free_bucket(&self, index: usize, bucket: *mut Bucket, len: usize) {
+        let bucket = Box::from_raw(bucket);
+        if self.vtable.needs_drop {
+            (self.vtable.drop_slice)(bucket.data, len);
+        }
+        let layout = self.layout(index);
+        if layout.size() != 0 {
+            alloc::dealloc(bucket.data, layout);
+        }
+    }
+
+    fn is_ready(&self, index: usize) -> bool {
+        let (bucket, offset) = locate(index);
+        let bucket = self.buckets[bucket].load(Ordering::Acquire);
+        !bucket.is_null() && unsafe { (*bucket).ready[offset].load(Ordering::SeqCst) }
+    }
+
+    // Extend the committed prefix over every written slot after it. Whoever
+    // writes the slot just past the prefix carries it over the slots written
+    // out of order before them.
+    fn advance_committed(&self) {
+        let mut committed = self.committed.load(Ordering::SeqCst);
+        while self.is_ready(committed) {
+            match self.committed.compare_exchange_weak(
+                committed,
+                committed + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => committed += 1,
+                Err(current) => committed = current,
+            }
+        }
+    }
+
+    /// A view of every element pushed so far with no unfinished pushes
+    /// before it.
+    pub fn snapshot(&self) -> AppendSnapshot<'_> {
+        AppendSnapshot {
+            vec: self,
+            len: self.committed.load(Ordering::Acquire),
+        }
+    }
+
+    /// Move our elements into an ``AnyVec``.
+    pub fn into_anyvec(mut self) -> AnyVec {
+        // Every push has finished, since they borrow us, so every reserved
+        // slot has been written.
+        let len = *self.reserved.get_mut();
+        let mut result = AnyVec::from_vtable_in(self.vtable, Global);
+        result.reserve(len);
+        let size = self.vtable.size;
+        let mut moved = 0;
+        for index in 0..BUCKETS {
+            let bucket = std::mem::replace(self.buckets[index].get_mut(), ptr::null_mut());
+            if bucket.is_null() {
+                break;
+            }
+            let count = bucket_len(index).min(len - moved);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    (*bucket).data,
+                    result.data.add(moved * size),
+                    count * size,
+                );
+                self.free_bucket(index, bucket, 0);
+            }
+            moved += count;
+        }
+        result.length = len;
+        result
+    }
+}
+
+impl Drop for AppendAnyVec {
+    fn drop(&mut self) {
+        // Every push has finished, since they borrow us, so every reserved
+        // slot has been written.
+        let mut remaining = *self.reserved.get_mut();
+        for index in 0..BUCKETS {
+            let bucket = *self.buckets[index].get_mut();
+            if bucket.is_null() {
+                break;
+            }
+            let len = bucket_len(index).min(remaining);
+            unsafe { self.free_bucket(index, bucket, len) };
+            remaining -= len;
+        }
+    }
+}
+
+/// The elements of an [`AppendAnyVec`] at the time [`AppendAnyVec::snapshot`]
+/// was called. Later pushes don't show up.
+#[derive(Clone, Copy)]
+pub struct AppendSnapshot<'a> {
+    vec: &'a AppendAnyVec,
+    len: usize,
+}
+
+impl<'a> AppendSnapshot<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn element_ptr(&self, index: usize) -> *const u8 {
+        let (bucket, offset) = locate(index);
+        let bucket = self.vec.buckets[bucket].load(Ordering::Acquire);
+        unsafe { (*bucket).data.add(offset * self.vec.vtable.size) }
+    }
+
+    pub fn get<T: Any>(&self, index: usize) -> Option<&'a T> {
+        self.vec.vtable.assert_typecheck::<T>();
+        if index < self.len {
+            Some(unsafe { &*(self.element_ptr(index) as *const T) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'a>> {
+        if index < self.len {
+            Some(unsafe { AnyRef::new(self.element_ptr(index), self.vec.vtable) })
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = AnyRef<'a>> + 'a {
+        let snapshot = *self;
+        (0..self.len).map(move |i| snapshot.get_ref(i).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{locate, AppendAnyVec};
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_locate() {
+        assert_eq!(locate(0), (0, 0));
+        assert_eq!(locate(31), (0, 31));
+        assert_eq!(locate(32), (1, 0));
+        assert_eq!(locate(95), (1, 63));
+        assert_eq!(locate(96), (2, 0));
+    }
+
+    #[test]
+    fn test_concurrent_push() {
+        let events = AppendAnyVec::new::<String>();
+        thread::scope(|scope| {
+            for t in 0..4 {
+                let events = &events;
+                scope.spawn(move || {
+                    for i in 0..250 {
+                        let index = events.push(format!("{}-{}", t, i));
+                        assert!(events.snapshot().len() <= 1000);
+                        assert!(index < 1000);
+                    }
+                });
+            }
+        });
+
+        let snapshot = events.snapshot();
+        assert_eq!(snapshot.len(), 1000);
+        let mut seen: Vec<&String> = (0..1000).map(|i| snapshot.get(i).unwrap()).collect();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 1000);
+
+        let mut all = events.into_anyvec().into_vec::<String>();
+        all.sort();
+        assert_eq!(all.len(), 1000);
+        assert_eq!(all[0], "0-0");
+    }
+
+    #[test]
+    fn test_indices_are_stable() {
+        let values = AppendAnyVec::new::<u32>();
+        let first = values.push(7u32);
+        let address = values.snapshot().get::<u32>(first).unwrap() as *const u32;
+        for i in 0..100u32 {
+            values.push(i);
+        }
+        let snapshot = values.snapshot();
+        assert_eq!(snapshot.get::<u32>(first).unwrap() as *const u32, address);
+        assert_eq!(snapshot.get::<u32>(100), Some(&99));
+        assert_eq!(snapshot.get::<u32>(101), None);
+        assert_eq!(snapshot.iter().count(), 101);
+    }
+
+    #[test]
+    fn test_drops_elements() {
+        let counter = Arc::new(());
+        let values = AppendAnyVec::new::<Arc<()>>();
+        for _ in 0..40 {
+            values.push(Arc::clone(&counter));
+        }
+        assert_eq!(Arc::strong_count(&counter), 41);
+        drop(values);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn test_owner_sees_every_push() {
+        // Even if the committed prefix lagged behind, the owner takes every
+        // element that was pushed.
+        let counter = Arc::new(());
+        let mut values = AppendAnyVec::new::<Arc<()>>();
+        for _ in 0..3 {
+            values.push(Arc::clone(&counter));
+        }
+        *values.committed.get_mut() = 1;
+        let all = values.into_anyvec();
+        assert_eq!(all.len(), 3);
+        drop(all);
+        assert_eq!(Arc::strong_count(&counter), 1);
+
+        let mut values = AppendAnyVec::new::<Arc<()>>();
+        values.push(Arc::clone(&counter));
+        *values.committed.get_mut() = 0;
+        drop(values);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Static type (u32) does not match runtime type (i32)")]
+    fn test_push_typechecks() {
+        AppendAnyVec::new::<u32>().push(1i32);
+    }
+}
@@ -0,0 +1,200 @@
+//! Zero-copy archives of plain-old-data vectors in ``rkyv``'s format, enabled
+//! by the ``rkyv`` feature.
+//!
+//! An archive holds the same bytes ``rkyv`` writes for a ``Vec<T>``: the
+//! elements, then (aligned to 4 bytes) a root holding a 32-bit relative
+//! pointer to them and a 32-bit length. ``rkyv::access`` can read our
+//! archives as ``ArchivedVec<T>``, and we can view archives it wrote of
+//! ``Vec<T>``s of primitive numbers without copying them.
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+use rkyv::util::AlignedVec;
+
+use crate::{Allocator, AnySlice, AnyVec, TypeRegistry};
+
+// An ``ArchivedVec``: its elements' offset from itself, then its length.
+const ROOT_SIZE: usize = 8;
+const ROOT_ALIGN: usize = 4;
+
+/// Why an archive couldn't be written or viewed.
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The element type isn't plain old data, or can't be archived in
+    /// ``rkyv``'s little-endian format on this target.
+    Unsupported(String),
+    /// The element type isn't registered.
+    UnknownType(String),
+    /// The bytes aren't an archive of elements of the expected type.
+    Invalid(String),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveError::Unsupported(what) => write!(f, "Unsupported: {}", what),
+            ArchiveError::UnknownType(name) => write!(f, "{} is not registered", name),
+            ArchiveError::Invalid(what) => write!(f, "Invalid archive: {}", what),
+        }
+    }
+}
+
+impl Error for ArchiveError {}
+
+fn invalid(what: &str) -> ArchiveError {
+    ArchiveError::Invalid(what.to_string())
+}
+
+fn check_archivable(name: &str, pod: bool, size: usize, align: usize) -> Result<(), ArchiveError> {
+    if !pod {
+        return Err(ArchiveError::Unsupported(format!(
+            "{} is not plain old data",
+            name
+        )));
+    }
+    // rkyv archives are little-endian, and our buffers are 16-byte aligned.
+    if (cfg!(target_endian = "big") && size > 1) || align > AlignedVec::<16>::ALIGNMENT {
+        return Err(ArchiveError::Unsupported(format!("archiving {}", name)));
+    }
+    Ok(())
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Copy our elements into a new archive, readable by ``rkyv`` as an
+    /// ``ArchivedVec`` of our element type.
+    pub fn to_archive(&self) -> Result<AlignedVec, ArchiveError> {
+        let vtable = self.vtable;
+        check_archivable(vtable.display_name, vtable.pod, vtable.size, vtable.align)?;
+        let length: u32 = self
+            .length
+            .try_into()
+            .map_err(|_| ArchiveError::Unsupported(format!("{} elements", self.length)))?;
+
+        let bytes = self.length * vtable.size;
+        let root = bytes.next_multiple_of(ROOT_ALIGN);
+        let mut archive = AlignedVec::with_capacity(root + ROOT_SIZE);
+        archive.extend_from_slice(unsafe { std::slice::from_raw_parts(self.data, bytes) });
+        archive.resize(root, 0);
+        // Like rkyv, point an empty vector at its root.
+        let offset = if self.length == 0 { 0 } else { -(root as i64) };
+        let offset: i32 = offset
+            .try_into()
+            .map_err(|_| ArchiveError::Unsupported(format!("{} bytes", bytes)))?;
+        archive.extend_from_slice(&offset.to_le_bytes());
+        archive.extend_from_slice(&length.to_le_bytes());
+        Ok(archive)
+    }
+}
+
+impl<'a> AnySlice<'a> {
+    /// View the elements of an archive of a vector of the type registered as
+    /// ``type_name``, without copying them. ``bytes`` must be aligned for
+    /// the element type, as an ``AlignedVec`` or a mapped file is.
+    pub fn from_archive(
+        bytes: &'a [u8],
+        type_name: &str,
+        registry: &TypeRegistry,
+    ) -> Result<AnySlice<'a>, ArchiveError> {
+        let vtable = registry
+            .get(type_name)
+            .ok_or_else(|| ArchiveError::UnknownType(type_name.to_string()))?
+            .vtable;
+        check_archivable(type_name, vtable.pod, vtable.size, vtable.align)?;
+
+        let root = bytes
+            .len()
+            .checked_sub(ROOT_SIZE)
+            .ok_or_else(|| invalid("too short"))?;
+        let offset = i32::from_le_bytes(bytes[root..root + 4].try_into().unwrap());
+        let length = u32::from_le_bytes(bytes[root + 4..].try_into().unwrap()) as usize;
+        let start = (root as i64 + offset as i64)
+            .try_into()
+            .map_err(|_| invalid("elements start before the archive"))?;
+        let end = length
+            .checked_mul(vtable.size)
+            .and_then(|size| size.checked_add(start))
+            .filter(|&end| end <= root)
+            .ok_or_else(|| invalid("elements overlap the root"))?;
+        let data = bytes[start..end].as_ptr();
+        if !(data as usize).is_multiple_of(vtable.align) {
+            return Err(invalid("elements are misaligned"));
+        }
+        // Plain old data is valid for any bytes.
+        Ok(unsafe { AnySlice::new(data, length, vtable) })
+    }
+}
+
+// rkyv's own root alignment, which ours must match.
+const _: () = assert!(mem::align_of::<rkyv::Archived<u32>>() == ROOT_ALIGN);
+
+#[cfg(test)]
+mod tests {
+    use super::ArchiveError;
+    use crate::{AnySlice, AnyVec, TypeRegistry};
+
+    use rkyv::rancor;
+    use rkyv::vec::ArchivedVec;
+
+    #[test]
+    fn test_round_trip() {
+        let registry = TypeRegistry::with_primitives();
+        let prices = AnyVec::from_vec::<f64>(vec![9.5, 10.0, -1.25]);
+        let archive = prices.to_archive().unwrap();
+        let view = AnySlice::from_archive(&archive, "f64", &registry).unwrap();
+        assert_eq!(view.downcast::<f64>(), Some(&[9.5, 10.0, -1.25][..]));
+
+        let empty = AnyVec::new::<u16>().to_archive().unwrap();
+        let view = AnySlice::from_archive(&empty, "u16", &registry).unwrap();
+        assert!(view.is_empty());
+    }
+
+    #[test]
+    fn test_rkyv_compatible() {
+        let ids = AnyVec::from_vec::<u16>(vec![1, 2, 3]);
+        let archive = ids.to_archive().unwrap();
+        let archived =
+            rkyv::access::<ArchivedVec<rkyv::Archived<u16>>, rancor::Error>(&archive).unwrap();
+        let values: Vec<u16> = archived.iter().map(|v| v.to_native()).collect();
+        assert_eq!(values, vec![1, 2, 3]);
+
+        let written = rkyv::to_bytes::<rancor::Error>(&vec![7u64, 8]).unwrap();
+        assert_eq!(
+            &written[..],
+            &AnyVec::from_vec::<u64>(vec![7, 8]).to_archive().unwrap()[..]
+        );
+        let registry = TypeRegistry::with_primitives();
+        let view = AnySlice::from_archive(&written, "u64", &registry).unwrap();
+        assert_eq!(view.downcast::<u64>(), Some(&[7, 8][..]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = AnyVec::from_vec(vec![String::new()])
+            .to_archive()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported: alloc::string::String is not plain old data"
+        );
+
+        let registry = TypeRegistry::with_primitives();
+        let mut archive = AnyVec::from_vec::<u32>(vec![1, 2]).to_archive().unwrap();
+        assert!(matches!(
+            AnySlice::from_archive(&archive, "u128", &TypeRegistry::new()),
+            Err(ArchiveError::UnknownType(_))
+        ));
+        assert!(AnySlice::from_archive(&archive, "u64", &registry).is_err());
+        archive[12..16].copy_from_slice(&100u32.to_le_bytes());
+        let error = AnySlice::from_archive(&archive, "u32", &registry)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Invalid archive: elements overlap the root"
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Element-wise arithmetic on numeric vectors.
+
+use std::any::{type_name, Any};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Sub};
+use std::slice;
+
+use crate::numeric::{Number, Numeric};
+use crate::vtable::VTable;
+use crate::{Allocator, AnyVec};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl fmt::Display for ArithOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ArithOp::Add => "Addition",
+            ArithOp::Sub => "Subtraction",
+            ArithOp::Mul => "Multiplication",
+            ArithOp::Div => "Division",
+        })
+    }
+}
+
+/// The primitive number types, which get typed arithmetic kernels.
+pub(crate) trait Arithmetic: Numeric {
+    /// ``None`` if the result overflows. Integer division by zero panics.
+    fn apply(op: ArithOp, a: Self, b: Self) -> Option<Self>;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Arithmetic for $t {
+                fn apply(op: ArithOp, a: $t, b: $t) -> Option<$t> {
+                    match op {
+                        ArithOp::Add => a.checked_add(b),
+                        ArithOp::Sub => a.checked_sub(b),
+                        ArithOp::Mul => a.checked_mul(b),
+                        ArithOp::Div => Some(a / b),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_float {
+    ($($t:ty),*) => {
+        $(
+            impl Arithmetic for $t {
+                fn apply(op: ArithOp, a: $t, b: $t) -> Option<$t> {
+                    Some(match op {
+                        ArithOp::Add => a + b,
+                        ArithOp::Sub => a - b,
+                        ArithOp::Mul => a * b,
+                        ArithOp::Div => a / b,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+impl_float!(f32, f64);
+
+/// Apply ``op`` to ``count`` pairs of elements, storing the results in
+/// ``lhs``.
+pub(crate) fn arith<T: Arithmetic>(op: ArithOp, lhs: *mut u8, rhs: *const u8, count: usize) {
+    let lhs = unsafe { slice::from_raw_parts_mut(lhs as *mut T, count) };
+    let rhs = unsafe { slice::from_raw_parts(rhs as *const T, count) };
+    for (a, &b) in lhs.iter_mut().zip(rhs) {
+        *a = T::apply(op, *a, b).unwrap_or_else(|| overflow(op, type_name::<T>()));
+    }
+}
+
+fn overflow(op: ArithOp, type_name: &str) -> ! {
+    panic!("{} overflows {}", op, type_name)
+}
+
+/// Apply ``op`` to one pair of elements of a numeric type without kernels,
+/// by way of the cast lattice.
+fn arith_element(vtable: &VTable, op: ArithOp, lhs: *mut u8, rhs: *const u8) {
+    let numeric = vtable.require_numeric();
+    let result = match ((numeric.to_number)(lhs), (numeric.to_number)(rhs)) {
+        (Number::Int(a), Number::Int(b)) => match op {
+            ArithOp::Add => a.checked_add(b),
+            ArithOp::Sub => a.checked_sub(b),
+            ArithOp::Mul => a.checked_mul(b),
+            ArithOp::Div => Some(a / b),
+        }
+        .map(Number::Int),
+        (a, b) => {
+            let (a, b) = (as_f64(a), as_f64(b));
+            Some(Number::Float(match op {
+                ArithOp::Add => a + b,
+                ArithOp::Sub => a - b,
+                ArithOp::Mul => a * b,
+                ArithOp::Div => a / b,
+            }))
+        }
+    };
+    match result.and_then(numeric.from_number) {
+        // Numeric types are ``Copy``, so there's nothing to drop in ``lhs``.
+        Some(value) => {
+            let (data, _) = value.into_raw();
+            (vtable.unbox_into)(data, lhs);
+        }
+        None => overflow(op, vtable.display_name),
+    }
+}
+
+fn as_f64(number: Number) -> f64 {
+    match number {
+        Number::Int(i) => i as f64,
+        Number::Float(f) => f,
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Apply ``op`` element-wise with ``other``, in place.
+    fn arith_assign<B: Allocator>(&mut self, op: ArithOp, other: &AnyVec<B>) {
+        self.vtable.assert_same_type(other.vtable);
+        let numeric = self.vtable.require_numeric();
+        if self.length != other.length {
+            panic!(
+                "Columns have different lengths ({} != {})",
+                self.length, other.length
+            );
+        }
+        match numeric.kernels {
+            Some(kernels) => (kernels.arith)(op, self.data, other.data, self.length),
+            None => {
+                for i in 0..self.length {
+                    let lhs = unsafe { self.data.add(i * self.vtable.size) };
+                    arith_element(self.vtable, op, lhs, other.element_ptr(i));
+                }
+            }
+        }
+    }
+
+    /// Add ``other`` to us element-wise.
+    ///
+    /// Panics if the element types or lengths differ, if the type isn't
+    /// numeric, or if an integer result overflows. An element that has
+    /// already been updated when a later one overflows stays updated.
+    pub fn add_assign<B: Allocator>(&mut self, other: &AnyVec<B>) {
+        self.arith_assign(ArithOp::Add, other);
+    }
+
+    /// Subtract ``other`` from us element-wise. Panics as
+    /// [`AnyVec::add_assign`] does.
+    pub fn sub_assign<B: Allocator>(&mut self, other: &AnyVec<B>) {
+        self.arith_assign(ArithOp::Sub, other);
+    }
+
+    /// Multiply us by ``other`` element-wise. Panics as
+    /// [`AnyVec::add_assign`] does.
+    pub fn mul_assign<B: Allocator>(&mut self, other: &AnyVec<B>) {
+        self.arith_assign(ArithOp::Mul, other);
+    }
+
+    /// Divide us by ``other`` element-wise. Panics as
+    /// [`AnyVec::add_assign`] does, and on integer division by zero.
+    pub fn div_assign<B: Allocator>(&mut self, other: &AnyVec<B>) {
+        self.arith_assign(ArithOp::Div, other);
+    }
+
+    /// Replace each element ``v`` with ``f(v, x)``, in one typed loop.
+    fn scalar_assign<T: Any + Copy>(&mut self, x: T, f: impl Fn(T, T) -> T) {
+        self.assert_typecheck::<T>();
+        let values = unsafe { slice::from_raw_parts_mut(self.data as *mut T, self.length) };
+        for v in values {
+            *v = f(*v, x);
+        }
+    }
+
+    /// Add ``x`` to every element, in place. Overflow behaves as ``+`` on
+    /// ``T`` does.
+    ///
+    /// Panics if the element type isn't ``T``.
+    pub fn add_scalar<T: Any + Copy + Add<Output = T>>(&mut self, x: T) {
+        self.scalar_assign(x, T::add);
+    }
+
+    /// Subtract ``x`` from every element, in place. Panics as
+    /// [`AnyVec::add_scalar`] does.
+    pub fn sub_scalar<T: Any + Copy + Sub<Output = T>>(&mut self, x: T) {
+        self.scalar_assign(x, T::sub);
+    }
+
+    /// Multiply every element by ``x``, in place. Panics as
+    /// [`AnyVec::add_scalar`] does.
+    pub fn mul_scalar<T: Any + Copy + Mul<Output = T>>(&mut self, x: T) {
+        self.scalar_assign(x, T::mul);
+    }
+
+    /// Divide every element by ``x``, in place. Panics as
+    /// [`AnyVec::add_scalar`] does.
+    pub fn div_scalar<T: Any + Copy + Div<Output = T>>(&mut self, x: T) {
+        self.scalar_assign(x, T::div);
+    }
+}
+
+impl<A: Allocator + Clone> AnyVec<A> {
+    fn arith<B: Allocator>(&self, op: ArithOp, other: &AnyVec<B>) -> AnyVec<A> {
+        let mut result = self.clone();
+        result.arith_assign(op, other);
+        result
+    }
+
+    /// The element-wise sum of us and ``other``, as a new vector. Panics as
+    /// [`AnyVec::add_assign`] does.
+    pub fn add<B: Allocator>(&self, other: &AnyVec<B>) -> AnyVec<A> {
+        self.arith(ArithOp::Add, other)
+    }
+
+    /// The element-wise difference of us and ``other``, as a new vector.
+    /// Panics as [`AnyVec::sub_assign`] does.
+    pub fn sub<B: Allocator>(&self, other: &AnyVec<B>) -> AnyVec<A> {
+        self.arith(ArithOp::Sub, other)
+    }
+
+    /// The element-wise product of us and ``other``, as a new vector.
+    /// Panics as [`AnyVec::mul_assign`] does.
+    pub fn mul<B: Allocator>(&self, other: &AnyVec<B>) -> AnyVec<A> {
+        self.arith(ArithOp::Mul, other)
+    }
+
+    /// The element-wise quotient of us and ``other``, as a new vector.
+    /// Panics as [`AnyVec::div_assign`] does.
+    pub fn div<B: Allocator>(&self, other: &AnyVec<B>) -> AnyVec<A> {
+        self.arith(ArithOp::Div, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyVec, Number, Numeric};
+
+    #[test]
+    fn test_column_expressions() {
+        let price = AnyVec::from_vec::<f64>(vec![10.0, 20.0, 30.0]);
+        let quantity = AnyVec::from_vec::<f64>(vec![2.0, 0.5, 1.0]);
+        let notional = price.mul(&quantity);
+        assert_eq!(notional.get::<f64, _>(..).unwrap(), &[20.0, 10.0, 30.0]);
+        assert_eq!(
+            notional.sub(&price).div(&price).into_vec::<f64>(),
+            vec![1.0, -0.5, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut totals = AnyVec::from_vec::<i32>(vec![1, 2, 3]);
+        totals.add_assign(&AnyVec::from_vec::<i32>(vec![10, 20, 30]));
+        totals.div_assign(&AnyVec::from_vec::<i32>(vec![2, 2, 2]));
+        assert_eq!(totals.into_vec::<i32>(), vec![5, 11, 16]);
+    }
+
+    #[test]
+    fn test_without_kernels() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Cents(u32);
+
+        impl Numeric for Cents {
+            fn to_number(self) -> Number {
+                Number::Int(self.0 as i128)
+            }
+
+            fn from_number(number: Number) -> Option<Cents> {
+                u32::from_number(number).map(Cents)
+            }
+        }
+
+        let cents = |v: Vec<u32>| {
+            AnyVec::from_vec(v.into_iter().map(Cents).collect())
+                .with_numeric::<Cents>()
+                .with_clone::<Cents>()
+        };
+        let sum = cents(vec![1, 2]).add(&cents(vec![3, 4]));
+        assert_eq!(sum.into_vec::<Cents>(), vec![Cents(4), Cents(6)]);
+    }
+
+    #[test]
+    fn test_scalar() {
+        let mut celsius = AnyVec::from_vec::<f64>(vec![0.0, 100.0, -40.0]);
+        celsius.mul_scalar(1.8f64);
+        celsius.add_scalar(32.0f64);
+        assert_eq!(celsius.into_vec::<f64>(), vec![32.0, 212.0, -40.0]);
+
+        let mut counts = AnyVec::from_vec::<u32>(vec![10, 25]);
+        counts.sub_scalar(4u32);
+        counts.div_scalar(3u32);
+        assert_eq!(counts.into_vec::<u32>(), vec![2, 7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Static type (f64) does not match runtime type (f32)")]
+    fn test_scalar_typecheck() {
+        AnyVec::from_vec::<f64>(vec![1.0]).add_scalar(1.0f32);
+    }
+
+    #[test]
+    #[should_panic(expected = "Subtraction overflows u8")]
+    fn test_overflow() {
+        let mut small = AnyVec::from_vec::<u8>(vec![1]);
+        small.sub_assign(&AnyVec::from_vec::<u8>(vec![2]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Columns have different lengths (2 != 1)")]
+    fn test_lengths() {
+        AnyVec::from_vec::<u8>(vec![1, 2]).add(&AnyVec::from_vec::<u8>(vec![1]));
+    }
+}
@@ -0,0 +1,80 @@
+//! Conversions to and from ``ndarray`` arrays, enabled by the ``ndarray``
+//! feature, so numeric columns can be handed to linear algebra code.
+
+use ndarray::{Array1, ArrayView1};
+
+use crate::{Allocator, AnyVec, Numeric};
+
+impl AnyVec {
+    /// Convert into a one-dimensional array, reusing our buffer.
+    ///
+    /// Panics if ``T`` isn't our element type.
+    pub fn into_array1<T: Numeric>(self) -> Array1<T> {
+        Array1::from_vec(self.into_vec::<T>())
+    }
+
+    /// Build a vector from a one-dimensional array. This reuses the array's
+    /// buffer unless its elements aren't stored contiguously in order.
+    pub fn from_array1<T: Numeric>(array: Array1<T>) -> AnyVec {
+        if !array.is_standard_layout() {
+            return AnyVec::from_vec(array.to_vec());
+        }
+        let len = array.len();
+        let (mut vec, offset) = array.into_raw_vec_and_offset();
+        // The array may only cover part of its buffer.
+        let offset = offset.unwrap_or(0);
+        vec.truncate(offset + len);
+        vec.drain(..offset);
+        AnyVec::from_vec(vec)
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// A one-dimensional view of our elements, without copying.
+    ///
+    /// Panics if ``T`` isn't our element type.
+    pub fn as_array1<T: Numeric>(&self) -> ArrayView1<'_, T> {
+        ArrayView1::from(self.get::<T, _>(..).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    use ndarray::{array, s};
+
+    #[test]
+    fn test_zero_copy() {
+        let dynamic = AnyVec::from_vec::<f64>(vec![1.0, 2.0, 3.0]);
+        let data = dynamic.data;
+        assert_eq!(dynamic.as_array1::<f64>().dot(&array![1.0, 1.0, 1.0]), 6.0);
+
+        let array = dynamic.into_array1::<f64>();
+        assert_eq!(array.as_ptr() as *const u8, data as *const u8);
+
+        let data = array.as_ptr();
+        let back = AnyVec::from_array1(array * 2.0);
+        assert_eq!(back.data as *const f64, data);
+        assert_eq!(back.into_vec::<f64>(), vec![2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn test_from_sliced_array() {
+        let mut array = array![1i32, 2, 3, 4, 5];
+        array.slice_collapse(s![1..4]);
+        assert_eq!(AnyVec::from_array1(array).into_vec::<i32>(), vec![2, 3, 4]);
+
+        let reversed = array![1u8, 2, 3].slice_move(s![..;-1]);
+        assert_eq!(
+            AnyVec::from_array1(reversed).into_vec::<u8>(),
+            vec![3, 2, 1]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match runtime type")]
+    fn test_into_array1_typecheck() {
+        AnyVec::from_vec::<f32>(vec![1.0]).into_array1::<f64>();
+    }
+}
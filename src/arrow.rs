@@ -0,0 +1,474 @@
+//! Converting vectors to and from Apache Arrow, enabled by the ``arrow``
+//! feature.
+//!
+//! Vectors are exchanged through the [Arrow C data interface], a stable ABI
+//! that every Arrow implementation can import (e.g. arrow-rs with
+//! ``arrow::ffi::from_ffi``), so we don't depend on any of them. Primitive
+//! numbers are handed over without copying: the exported array keeps the
+//! vector alive and frees it when released. ``bool`` and ``String`` columns
+//! are converted, since Arrow stores them as bitmaps and offset buffers.
+//! Imports always copy, since our buffers must come from our allocator.
+//!
+//! Arrow has no pointer-sized integers, so ``isize`` and ``usize`` are
+//! exported as the fixed-width integers of the same size, and import as
+//! those: ``i64`` and ``u64`` on 64-bit targets.
+//!
+//! [Arrow C data interface]: https://arrow.apache.org/docs/format/CDataInterface.html
+
+use std::any::Any;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::ffi::{c_void, CStr, CString};
+use std::fmt;
+use std::iter;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::{Allocator, AnyVec};
+
+/// Why an Arrow array couldn't be imported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArrowError {
+    /// The array's format string has no element type equivalent.
+    UnsupportedFormat(String),
+    /// The array has null slots, which vectors can't represent.
+    Nulls,
+    InvalidUtf8 {
+        index: usize,
+    },
+}
+
+impl fmt::Display for ArrowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArrowError::UnsupportedFormat(format) => {
+                write!(f, "Arrow format {:?} is not supported", format)
+            }
+            ArrowError::Nulls => write!(f, "Arrow array contains nulls"),
+            ArrowError::InvalidUtf8 { index } => {
+                write!(f, "Arrow string {} is not valid UTF-8", index)
+            }
+        }
+    }
+}
+
+impl Error for ArrowError {}
+
+/// ``struct ArrowSchema`` from the Arrow C data interface.
+///
+/// Dropping one that hasn't been moved out by a consumer releases it.
+#[repr(C)]
+pub struct ArrowSchema {
+    pub format: *const c_char,
+    pub name: *const c_char,
+    pub metadata: *const c_char,
+    pub flags: i64,
+    pub n_children: i64,
+    pub children: *mut *mut ArrowSchema,
+    pub dictionary: *mut ArrowSchema,
+    pub release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    pub private_data: *mut c_void,
+}
+
+/// ``struct ArrowArray`` from the Arrow C data interface.
+///
+/// Dropping one that hasn't been moved out by a consumer releases it.
+#[repr(C)]
+pub struct ArrowArray {
+    pub length: i64,
+    pub null_count: i64,
+    pub offset: i64,
+    pub n_buffers: i64,
+    pub n_children: i64,
+    pub buffers: *mut *const c_void,
+    pub children: *mut *mut ArrowArray,
+    pub dictionary: *mut ArrowArray,
+    pub release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    pub private_data: *mut c_void,
+}
+
+impl Drop for ArrowSchema {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) };
+        }
+    }
+}
+
+impl Drop for ArrowArray {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) };
+        }
+    }
+}
+
+// What an exported schema owns.
+struct SchemaData {
+    _format: CString,
+}
+
+// What an exported array owns: the buffer pointer table, plus whatever the
+// buffers point into.
+struct ArrayData {
+    buffers: Vec<*const c_void>,
+    _owner: Box<dyn Any>,
+}
+
+unsafe extern "C" fn release_schema(schema: *mut ArrowSchema) {
+    let schema = &mut *schema;
+    drop(Box::from_raw(schema.private_data as *mut SchemaData));
+    schema.release = None;
+}
+
+unsafe extern "C" fn release_array(array: *mut ArrowArray) {
+    let array = &mut *array;
+    drop(Box::from_raw(array.private_data as *mut ArrayData));
+    array.release = None;
+}
+
+/// The Arrow format string for a zero-copy primitive type, if it is one.
+fn primitive_format<A: Allocator>(vec: &AnyVec<A>) -> Option<&'static str> {
+    macro_rules! formats {
+        ($($t:ty => $format:expr),*) => {
+            $(
+                if vec.vtable.is::<$t>() {
+                    return Some($format);
+                }
+            )*
+        };
+    }
+
+    formats!(
+        i8 => "c", u8 => "C", i16 => "s", u16 => "S", i32 => "i", u32 => "I",
+        i64 => "l", u64 => "L", f32 => "f", f64 => "g"
+    );
+    #[cfg(target_pointer_width = "64")]
+    formats!(isize => "l", usize => "L");
+    #[cfg(target_pointer_width = "32")]
+    formats!(isize => "i", usize => "I");
+    None
+}
+
+fn schema(format: &str) -> ArrowSchema {
+    let format = CString::new(format).unwrap();
+    let format_ptr = format.as_ptr();
+    ArrowSchema {
+        format: format_ptr,
+        name: ptr::null(),
+        metadata: ptr::null(),
+        flags: 0,
+        n_children: 0,
+        children: ptr::null_mut(),
+        dictionary: ptr::null_mut(),
+        release: Some(release_schema),
+        private_data: Box::into_raw(Box::new(SchemaData { _format: format })) as *mut c_void,
+    }
+}
+
+fn array(length: usize, buffers: Vec<*const c_void>, owner: Box<dyn Any>) -> ArrowArray {
+    let mut data = Box::new(ArrayData {
+        buffers,
+        _owner: owner,
+    });
+    ArrowArray {
+        length: length as i64,
+        null_count: 0,
+        offset: 0,
+        n_buffers: data.buffers.len() as i64,
+        n_children: 0,
+        buffers: data.buffers.as_mut_ptr(),
+        children: ptr::null_mut(),
+        dictionary: ptr::null_mut(),
+        release: Some(release_array),
+        private_data: Box::into_raw(data) as *mut c_void,
+    }
+}
+
+// Arrow packs booleans into bits, least significant first.
+fn pack_bools(bools: &[bool]) -> Vec<u8> {
+    let mut bits = vec![0u8; bools.len().div_ceil(8)];
+    for (i, _) in bools.iter().enumerate().filter(|(_, &b)| b) {
+        bits[i / 8] |= 1 << (i % 8);
+    }
+    bits
+}
+
+// Where each string starts in the concatenated bytes, followed by the total
+// length, or ``None`` if that doesn't fit in ``O``.
+fn offsets<O: TryFrom<usize>>(strings: &[String]) -> Option<Vec<O>> {
+    let mut end = 0;
+    iter::once(0)
+        .chain(strings.iter().map(|s| {
+            end += s.len();
+            end
+        }))
+        .map(|offset| O::try_from(offset).ok())
+        .collect()
+}
+
+fn export_strings(strings: &[String]) -> (ArrowArray, ArrowSchema) {
+    let bytes: Vec<u8> = strings.iter().flat_map(|s| s.bytes()).collect();
+    let bytes_ptr = bytes.as_ptr() as *const c_void;
+    // Offsets are 32 bits unless the data is too large for them.
+    let (format, offsets_ptr, owner): (&str, *const c_void, Box<dyn Any>) =
+        match offsets::<i32>(strings) {
+            Some(offsets) => ("u", offsets.as_ptr() as _, Box::new((offsets, bytes))),
+            None => {
+                let offsets = offsets::<i64>(strings).unwrap();
+                ("U", offsets.as_ptr() as _, Box::new((offsets, bytes)))
+            }
+        };
+    let buffers = vec![ptr::null(), offsets_ptr, bytes_ptr];
+    (array(strings.len(), buffers, owner), schema(format))
+}
+
+impl<A: Allocator + 'static> AnyVec<A> {
+    /// Whether [`AnyVec::into_arrow`] supports our element type.
+    pub fn is_arrow_compatible(&self) -> bool {
+        primitive_format(self).is_some() || self.vtable.is::<bool>() || self.vtable.is::<String>()
+    }
+
+    /// Export this vector as an Arrow array and its schema.
+    ///
+    /// Primitive numbers are exported without copying; ``bool`` and
+    /// ``String`` are converted. ``isize`` and ``usize`` are exported as
+    /// fixed-width integers, so they don't import back as themselves. Panics
+    /// if our element type has no Arrow equivalent.
+    pub fn into_arrow(self) -> (ArrowArray, ArrowSchema) {
+        if let Some(format) = primitive_format(&self) {
+            let length = self.length;
+            let buffers = vec![ptr::null(), self.data as *const c_void];
+            return (array(length, buffers, Box::new(self)), schema(format));
+        }
+        if let Some(bools) = self.as_any_slice().downcast::<bool>() {
+            let bits = pack_bools(bools);
+            let buffers = vec![ptr::null(), bits.as_ptr() as *const c_void];
+            return (array(bools.len(), buffers, Box::new(bits)), schema("b"));
+        }
+        if let Some(strings) = self.as_any_slice().downcast::<String>() {
+            return export_strings(strings);
+        }
+        panic!("{} has no Arrow equivalent", self.vtable.display_name);
+    }
+}
+
+// Whether any of the ``length`` slots from ``offset`` is marked null in the
+// validity bitmap (if there is one).
+unsafe fn has_nulls(validity: *const u8, offset: usize, length: usize) -> bool {
+    !validity.is_null()
+        && (offset..offset + length).any(|i| *validity.add(i / 8) & (1 << (i % 8)) == 0)
+}
+
+unsafe fn import_primitive<T: Any + Copy>(
+    buffer: *const c_void,
+    offset: usize,
+    length: usize,
+) -> AnyVec {
+    // Empty arrays may have null buffers.
+    if length == 0 {
+        return AnyVec::new::<T>();
+    }
+    let values = std::slice::from_raw_parts((buffer as *const T).add(offset), length);
+    AnyVec::from_vec(values.to_vec())
+}
+
+unsafe fn import_strings<O: Copy + Into<i64>>(
+    array: &ArrowArray,
+    offset: usize,
+    length: usize,
+) -> Result<AnyVec, ArrowError> {
+    // Empty arrays may have null buffers.
+    if length == 0 {
+        return Ok(AnyVec::new::<String>());
+    }
+    let offsets =
+        std::slice::from_raw_parts((*array.buffers.add(1) as *const O).add(offset), length + 1);
+    let offsets: Vec<usize> = offsets.iter().map(|&o| o.into() as usize).collect();
+    // So may arrays of empty strings, whose values buffer is empty.
+    let bytes = *array.buffers.add(2) as *const u8;
+    let bytes: &[u8] = if bytes.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(bytes, offsets[length])
+    };
+    let strings = offsets
+        .windows(2)
+        .enumerate()
+        .map(|(index, window)| {
+            let raw = &bytes[window[0]..window[1]];
+            String::from_utf8(raw.to_vec()).map_err(|_| ArrowError::InvalidUtf8 { index })
+        })
+        .collect::<Result<Vec<String>, ArrowError>>()?;
+    Ok(AnyVec::from_vec(strings))
+}
+
+impl AnyVec {
+    /// Import an Arrow array described by ``schema``, releasing it.
+    ///
+    /// Supports the primitive number formats, booleans and UTF-8 strings.
+    ///
+    /// # Safety
+    ///
+    /// ``array`` and ``schema`` must be valid according to the Arrow C data
+    /// interface, and describe the same data.
+    pub unsafe fn from_arrow(
+        array: ArrowArray,
+        schema: &ArrowSchema,
+    ) -> Result<AnyVec, ArrowError> {
+        let format = CStr::from_ptr(schema.format).to_string_lossy();
+        let (offset, length) = (array.offset as usize, array.length as usize);
+        if array.null_count != 0 && has_nulls(*array.buffers as *const u8, offset, length) {
+            return Err(ArrowError::Nulls);
+        }
+
+        macro_rules! primitives {
+            ($($format:expr => $t:ty),*) => {
+                match format.as_ref() {
+                    $($format => Ok(import_primitive::<$t>(*array.buffers.add(1), offset, length)),)*
+                    "b" => {
+                        let bits = *array.buffers.add(1) as *const u8;
+                        let bools = (offset..offset + length)
+                            .map(|i| *bits.add(i / 8) & (1 << (i % 8)) != 0)
+                            .collect();
+                        Ok(AnyVec::from_vec::<bool>(bools))
+                    }
+                    "u" => import_strings::<i32>(&array, offset, length),
+                    "U" => import_strings::<i64>(&array, offset, length),
+                    other => Err(ArrowError::UnsupportedFormat(other.to_string())),
+                }
+            };
+        }
+
+        primitives!(
+            "c" => i8, "C" => u8, "s" => i16, "S" => u16, "i" => i32, "I" => u32,
+            "l" => i64, "L" => u64, "f" => f32, "g" => f64
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArrowError;
+    use crate::AnyVec;
+
+    use std::ffi::CStr;
+
+    unsafe fn buffer<T: Copy>(array: &super::ArrowArray, index: usize, len: usize) -> Vec<T> {
+        std::slice::from_raw_parts(*array.buffers.add(index) as *const T, len).to_vec()
+    }
+
+    #[test]
+    fn test_zero_copy_primitives() {
+        let dynamic = AnyVec::from_vec::<f64>(vec![1.5, -2.0, 3.0]);
+        let data = dynamic.data;
+        let (array, schema) = dynamic.into_arrow();
+
+        assert_eq!(unsafe { CStr::from_ptr(schema.format) }.to_str(), Ok("g"));
+        assert_eq!((array.length, array.n_buffers, array.null_count), (3, 2, 0));
+        unsafe {
+            assert!((*array.buffers).is_null());
+            assert_eq!(*array.buffers.add(1), data as *const _);
+            assert_eq!(buffer::<f64>(&array, 1, 3), vec![1.5, -2.0, 3.0]);
+        }
+    }
+
+    #[test]
+    fn test_converted_types() {
+        let bools = AnyVec::from_vec(vec![
+            true, false, true, true, false, false, false, false, true,
+        ]);
+        let (array, schema) = bools.into_arrow();
+        assert_eq!(unsafe { CStr::from_ptr(schema.format) }.to_str(), Ok("b"));
+        assert_eq!(unsafe { buffer::<u8>(&array, 1, 2) }, vec![0b1101, 0b1]);
+
+        let strings = AnyVec::from_vec(vec![String::from("ab"), String::new(), String::from("c")]);
+        let (array, schema) = strings.into_arrow();
+        assert_eq!(unsafe { CStr::from_ptr(schema.format) }.to_str(), Ok("u"));
+        assert_eq!(array.n_buffers, 3);
+        assert_eq!(unsafe { buffer::<i32>(&array, 1, 4) }, vec![0, 2, 2, 3]);
+        assert_eq!(unsafe { buffer::<u8>(&array, 2, 3) }, b"abc".to_vec());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let floats = AnyVec::from_vec::<f32>(vec![0.5, 1.0]);
+        let (array, schema) = floats.into_arrow();
+        let imported = unsafe { AnyVec::from_arrow(array, &schema) }.unwrap();
+        assert_eq!(imported.into_vec::<f32>(), vec![0.5, 1.0]);
+
+        let bools = AnyVec::from_vec(vec![false, true, true]);
+        let (mut array, schema) = bools.into_arrow();
+        array.offset = 1;
+        array.length = 2;
+        let imported = unsafe { AnyVec::from_arrow(array, &schema) }.unwrap();
+        assert_eq!(imported.into_vec::<bool>(), vec![true, true]);
+
+        let strings = AnyVec::from_vec(vec![String::from("x"), String::from("yz")]);
+        let (mut array, schema) = strings.into_arrow();
+        array.offset = 1;
+        array.length = 1;
+        let imported = unsafe { AnyVec::from_arrow(array, &schema) }.unwrap();
+        assert_eq!(imported.into_vec::<String>(), vec!["yz"]);
+    }
+
+    #[test]
+    fn test_import_null_buffers() {
+        // Empty buffers may be null in the C data interface.
+        let (array, schema) = AnyVec::new::<u64>().into_arrow();
+        let buffers = unsafe { std::slice::from_raw_parts_mut(array.buffers, 2) };
+        buffers[1] = std::ptr::null();
+        let imported = unsafe { AnyVec::from_arrow(array, &schema) }.unwrap();
+        assert!(imported.into_vec::<u64>().is_empty());
+
+        let strings = AnyVec::from_vec(vec![String::new(), String::new()]);
+        let (array, schema) = strings.into_arrow();
+        let buffers = unsafe { std::slice::from_raw_parts_mut(array.buffers, 3) };
+        buffers[2] = std::ptr::null();
+        let imported = unsafe { AnyVec::from_arrow(array, &schema) }.unwrap();
+        assert_eq!(imported.into_vec::<String>(), vec!["", ""]);
+
+        let (array, schema) = AnyVec::new::<String>().into_arrow();
+        let buffers = unsafe { std::slice::from_raw_parts_mut(array.buffers, 3) };
+        buffers[1] = std::ptr::null();
+        buffers[2] = std::ptr::null();
+        let imported = unsafe { AnyVec::from_arrow(array, &schema) }.unwrap();
+        assert!(imported.is_empty());
+    }
+
+    #[test]
+    fn test_pointer_sized_integers() {
+        let sizes = AnyVec::from_vec::<usize>(vec![1, 2]);
+        let (array, schema) = sizes.into_arrow();
+        let imported = unsafe { AnyVec::from_arrow(array, &schema) }.unwrap();
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(imported.into_vec::<u64>(), vec![1, 2]);
+        #[cfg(target_pointer_width = "32")]
+        assert_eq!(imported.into_vec::<u32>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let (array, mut schema) = AnyVec::from_vec::<u8>(vec![1]).into_arrow();
+        let format = std::ffi::CString::new("+s").unwrap();
+        let original = std::mem::replace(&mut schema.format, format.as_ptr());
+        let err = unsafe { AnyVec::from_arrow(array, &schema) }.err().unwrap();
+        assert_eq!(err, ArrowError::UnsupportedFormat(String::from("+s")));
+        schema.format = original;
+
+        let validity = [0b10u8];
+        let (mut array, schema) = AnyVec::from_vec::<u8>(vec![1, 2]).into_arrow();
+        let buffers = unsafe { std::slice::from_raw_parts_mut(array.buffers, 2) };
+        buffers[0] = validity.as_ptr() as *const _;
+        array.null_count = 1;
+        let err = unsafe { AnyVec::from_arrow(array, &schema) }.err().unwrap();
+        assert_eq!(err, ArrowError::Nulls);
+    }
+
+    #[test]
+    fn test_unsupported_type() {
+        let dynamic = AnyVec::from_vec(vec![(1u8, 2u8)]);
+        assert!(!dynamic.is_arrow_compatible());
+        assert!(AnyVec::new::<u32>().is_arrow_compatible());
+    }
+}
@@ -0,0 +1,160 @@
+//! Converting vectors of ``Box<dyn Any>`` into ``AnyVec``s.
+
+use std::any::Any;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::{AnyVec, Global, TypeRegistry};
+
+/// Why a vector of boxes couldn't be unboxed. Every variant but ``Empty``
+/// hands the boxes back untouched.
+#[derive(Debug)]
+pub enum FromBoxesError {
+    /// There was no first box to infer the element type from.
+    Empty,
+    /// The first box's type isn't registered in the global
+    /// [`TypeRegistry`], so there's no vtable for it.
+    Unregistered(Vec<Box<dyn Any>>),
+    /// The box at ``index`` doesn't hold the expected type.
+    Mismatch {
+        index: usize,
+        expected: &'static str,
+        boxes: Vec<Box<dyn Any>>,
+    },
+}
+
+impl FromBoxesError {
+    pub fn into_boxes(self) -> Vec<Box<dyn Any>> {
+        match self {
+            FromBoxesError::Empty => Vec::new(),
+            FromBoxesError::Unregistered(boxes) | FromBoxesError::Mismatch { boxes, .. } => boxes,
+        }
+    }
+}
+
+impl fmt::Display for FromBoxesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromBoxesError::Empty => write!(f, "Can't infer an element type from no boxes"),
+            FromBoxesError::Unregistered(_) => {
+                write!(f, "The first box's type is not registered")
+            }
+            FromBoxesError::Mismatch {
+                index, expected, ..
+            } => write!(f, "Box {} is not of type {}", index, expected),
+        }
+    }
+}
+
+impl Error for FromBoxesError {}
+
+impl AnyVec {
+    /// Unbox every element of ``boxes`` onto the end of the vector, freeing
+    /// the boxes.
+    ///
+    /// Every box is checked before any is unboxed, so on a mismatch the
+    /// vector is unchanged.
+    pub fn extend_boxed(&mut self, boxes: Vec<Box<dyn Any>>) -> Result<(), FromBoxesError> {
+        let id = self.vtable.id();
+        if let Some(index) = boxes.iter().position(|boxed| (**boxed).type_id() != id) {
+            return Err(FromBoxesError::Mismatch {
+                index,
+                expected: self.vtable.display_name,
+                boxes,
+            });
+        }
+        self.reserve(boxes.len());
+        for boxed in boxes {
+            // Can't fail, since the type was checked above.
+            let _ = self.push_boxed(boxed);
+        }
+        Ok(())
+    }
+}
+
+/// Infers the element type from the first box, which must be registered in
+/// the global [`TypeRegistry`].
+impl TryFrom<Vec<Box<dyn Any>>> for AnyVec {
+    type Error = FromBoxesError;
+
+    fn try_from(boxes: Vec<Box<dyn Any>>) -> Result<AnyVec, FromBoxesError> {
+        let first = match boxes.first() {
+            Some(first) => (**first).type_id(),
+            None => return Err(FromBoxesError::Empty),
+        };
+        let vtable = TypeRegistry::with_global(|registry| {
+            let name = registry.name_of(first)?;
+            registry.get(name).map(|entry| entry.vtable)
+        });
+        let mut vec = match vtable {
+            Some(vtable) => AnyVec::from_vtable_in(vtable, Global),
+            None => return Err(FromBoxesError::Unregistered(boxes)),
+        };
+        vec.extend_boxed(boxes)?;
+        Ok(vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FromBoxesError;
+    use crate::{AnyVec, CapabilitySet};
+
+    use std::any::Any;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_infers_registered_type() {
+        let boxes: Vec<Box<dyn Any>> = vec![Box::new(1.5f64), Box::new(2.5f64)];
+        let dynamic = AnyVec::try_from(boxes).ok().unwrap();
+        assert!(dynamic.capabilities().contains(CapabilitySet::NUMERIC));
+        assert_eq!(dynamic.into_vec::<f64>(), vec![1.5, 2.5]);
+    }
+
+    #[test]
+    fn test_reports_first_mismatch() {
+        let boxes: Vec<Box<dyn Any>> = vec![Box::new(1u8), Box::new(2u8), Box::new("x")];
+        match AnyVec::try_from(boxes).err().unwrap() {
+            FromBoxesError::Mismatch {
+                index,
+                expected,
+                boxes,
+            } => {
+                assert_eq!((index, expected), (2, "u8"));
+                assert_eq!(boxes.len(), 3);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_unregistered_and_empty() {
+        struct Unregistered;
+
+        let boxes: Vec<Box<dyn Any>> = vec![Box::new(Unregistered)];
+        let error = AnyVec::try_from(boxes).err().unwrap();
+        assert_eq!(error.to_string(), "The first box's type is not registered");
+        assert_eq!(error.into_boxes().len(), 1);
+
+        let error = AnyVec::try_from(Vec::<Box<dyn Any>>::new()).err().unwrap();
+        assert!(matches!(error, FromBoxesError::Empty));
+    }
+
+    #[test]
+    fn test_extend_boxed() {
+        let mut dynamic = AnyVec::new::<String>();
+        let boxes: Vec<Box<dyn Any>> = vec![Box::new(String::from("a")), Box::new(1u8)];
+        let error = dynamic.extend_boxed(boxes).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Box 1 is not of type alloc::string::String"
+        );
+        assert!(dynamic.is_empty());
+
+        dynamic
+            .extend_boxed(vec![Box::new(String::from("b"))])
+            .unwrap();
+        assert_eq!(dynamic.into_vec::<String>(), vec!["b"]);
+    }
+}
@@ -0,0 +1,372 @@
+//! An ordered map whose key and value types are only known at runtime.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+use std::ptr;
+
+use crate::vtable::CmpFn;
+use crate::{AnyRef, AnySlice, AnyValue, AnyVec, Global};
+
+/// A map from keys to values whose types are chosen at runtime, kept sorted
+/// by the key vtable's ``cmp`` entry.
+///
+/// Entries live in two parallel columns sorted by key, so lookups are binary
+/// searches and any range of keys is a contiguous run of both columns.
+/// Inserting or removing shifts the entries after it, which suits maps that
+/// are read far more often than they're written.
+pub struct AnyBTreeMap {
+    keys: AnyVec,
+    values: AnyVec,
+    cmp: CmpFn,
+}
+
+impl AnyBTreeMap {
+    pub fn new<K: Any + Ord, V: Any>() -> AnyBTreeMap {
+        AnyBTreeMap::from_anyvecs(AnyVec::new::<K>().with_ord::<K>(), AnyVec::new::<V>())
+    }
+
+    /// Build a map from parallel columns of keys and values, in any order.
+    /// Later entries win when a key repeats.
+    ///
+    /// Panics if the columns have different lengths, or if the key type
+    /// doesn't support ordering.
+    pub fn from_anyvecs(keys: AnyVec, values: AnyVec) -> AnyBTreeMap {
+        if keys.len() != values.len() {
+            panic!(
+                "Columns have different lengths ({} != {})",
+                keys.len(),
+                values.len()
+            );
+        }
+        let cmp = keys.vtable.require_cmp();
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        // Stable, so the last of each run of equal keys is the latest entry.
+        order.sort_by(|&i, &j| cmp(keys.element_ptr(i), keys.element_ptr(j)));
+        let mut latest: Vec<usize> = Vec::with_capacity(order.len());
+        for (k, &i) in order.iter().enumerate() {
+            let superseded = order
+                .get(k + 1)
+                .is_some_and(|&j| cmp(keys.element_ptr(i), keys.element_ptr(j)) == Ordering::Equal);
+            if !superseded {
+                latest.push(i);
+            }
+        }
+
+        let mut map = AnyBTreeMap {
+            keys: AnyVec::from_vtable_in(keys.vtable, Global),
+            values: AnyVec::from_vtable_in(values.vtable, Global),
+            cmp,
+        };
+        map.keys.reserve(latest.len());
+        map.values.reserve(latest.len());
+        let mut key_values: Vec<Option<AnyValue>> =
+            keys.into_values().into_iter().map(Some).collect();
+        let mut value_values: Vec<Option<AnyValue>> =
+            values.into_values().into_iter().map(Some).collect();
+        for i in latest {
+            map.keys.push_value(key_values[i].take().unwrap());
+            map.values.push_value(value_values[i].take().unwrap());
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Where ``key`` is, or where it would be inserted.
+    fn search(&self, key: *const u8) -> Result<usize, usize> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match (self.cmp)(self.keys.element_ptr(mid), key) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+
+    fn value_ptr(&self, index: usize) -> *mut u8 {
+        unsafe { self.values.data.add(index * self.values.vtable.size) }
+    }
+
+    fn typed_key<K: Any>(&self, key: &K) -> AnyRef<'_> {
+        self.keys.vtable.assert_typecheck::<K>();
+        unsafe { AnyRef::new(key as *const K as *const u8, self.keys.vtable) }
+    }
+
+    /// Insert ``value`` under ``key``, returning the value it replaced.
+    pub fn insert<K: Any, V: Any>(&mut self, key: K, value: V) -> Option<V> {
+        self.keys.vtable.assert_typecheck::<K>();
+        self.values.vtable.assert_typecheck::<V>();
+        match self.search(&key as *const K as *const u8) {
+            Ok(i) => Some(unsafe { ptr::replace(self.value_ptr(i) as *mut V, value) }),
+            Err(i) => {
+                unsafe {
+                    ptr::write(open_gap(&mut self.keys, i) as *mut K, key);
+                    ptr::write(open_gap(&mut self.values, i) as *mut V, value);
+                }
+                None
+            }
+        }
+    }
+
+    /// Insert an erased entry, returning the value it replaced. Panics if
+    /// either type doesn't match ours.
+    pub fn insert_value(&mut self, key: AnyValue, value: AnyValue) -> Option<AnyValue> {
+        self.keys.vtable.assert_same_type(key.vtable());
+        self.values.vtable.assert_same_type(value.vtable());
+        let vtable = self.values.vtable;
+        match self.search(key.as_any_ref().data()) {
+            Ok(i) => {
+                let slot = self.value_ptr(i);
+                let old = unsafe { AnyValue::from_raw((vtable.box_value)(slot), vtable) };
+                (vtable.unbox_into)(value.into_raw().0, slot);
+                Some(old)
+            }
+            Err(i) => {
+                let key_vtable = self.keys.vtable;
+                (key_vtable.unbox_into)(key.into_raw().0, open_gap(&mut self.keys, i));
+                (vtable.unbox_into)(value.into_raw().0, open_gap(&mut self.values, i));
+                None
+            }
+        }
+    }
+
+    pub fn get<K: Any, V: Any>(&self, key: &K) -> Option<&V> {
+        self.values.vtable.assert_typecheck::<V>();
+        self.get_ref(self.typed_key(key))
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<K: Any, V: Any>(&mut self, key: &K) -> Option<&mut V> {
+        self.values.vtable.assert_typecheck::<V>();
+        let i = self.search(self.typed_key(key).data()).ok()?;
+        Some(unsafe { &mut *(self.value_ptr(i) as *mut V) })
+    }
+
+    /// Look up an erased key. Panics if its type doesn't match ours.
+    pub fn get_ref(&self, key: AnyRef<'_>) -> Option<AnyRef<'_>> {
+        self.keys.vtable.assert_same_type(key.vtable());
+        let i = self.search(key.data()).ok()?;
+        self.values.get_ref(i)
+    }
+
+    pub fn contains_key<K: Any>(&self, key: &K) -> bool {
+        self.search(self.typed_key(key).data()).is_ok()
+    }
+
+    pub fn remove<K: Any, V: Any>(&mut self, key: &K) -> Option<V> {
+        self.values.vtable.assert_typecheck::<V>();
+        let i = self.search(self.typed_key(key).data()).ok()?;
+        Some(self.remove_at(i).downcast().ok().unwrap())
+    }
+
+    /// Remove the entry with an erased key, returning its value. Panics if
+    /// the key's type doesn't match ours.
+    pub fn remove_ref(&mut self, key: AnyRef<'_>) -> Option<AnyValue> {
+        self.keys.vtable.assert_same_type(key.vtable());
+        let i = self.search(key.data()).ok()?;
+        Some(self.remove_at(i))
+    }
+
+    fn remove_at(&mut self, i: usize) -> AnyValue {
+        // Unlink both before dropping the key, in case its destructor panics.
+        let key = close_gap(&mut self.keys, i);
+        let value = close_gap(&mut self.values, i);
+        drop(key);
+        value
+    }
+
+    /// The entry with the smallest key.
+    pub fn first(&self) -> Option<(AnyRef<'_>, AnyRef<'_>)> {
+        self.entry(0)
+    }
+
+    /// The entry with the greatest key.
+    pub fn last(&self) -> Option<(AnyRef<'_>, AnyRef<'_>)> {
+        self.len().checked_sub(1).and_then(|i| self.entry(i))
+    }
+
+    fn entry(&self, i: usize) -> Option<(AnyRef<'_>, AnyRef<'_>)> {
+        Some((self.keys.get_ref(i)?, self.values.get_ref(i)?))
+    }
+
+    /// The entries whose keys fall in ``range``, in key order.
+    pub fn range<K: Any, R: RangeBounds<K>>(
+        &self,
+        range: R,
+    ) -> impl Iterator<Item = (AnyRef<'_>, AnyRef<'_>)> {
+        let start = range.start_bound().map(|key| self.typed_key(key));
+        let end = range.end_bound().map(|key| self.typed_key(key));
+        self.range_refs(start, end)
+    }
+
+    /// The entries whose keys fall between erased bounds, in key order.
+    ///
+    /// Panics if a bound's type doesn't match our keys, or if ``start`` is
+    /// after ``end``.
+    pub fn range_refs(
+        &self,
+        start: Bound<AnyRef<'_>>,
+        end: Bound<AnyRef<'_>>,
+    ) -> impl Iterator<Item = (AnyRef<'_>, AnyRef<'_>)> {
+        let low = match start {
+            Bound::Included(key) => self.search_erased(key).unwrap_or_else(|i| i),
+            Bound::Excluded(key) => self.search_erased(key).map_or_else(|i| i, |i| i + 1),
+            Bound::Unbounded => 0,
+        };
+        let high = match end {
+            Bound::Included(key) => self.search_erased(key).map_or_else(|i| i, |i| i + 1),
+            Bound::Excluded(key) => self.search_erased(key).unwrap_or_else(|i| i),
+            Bound::Unbounded => self.len(),
+        };
+        if low > high {
+            panic!("Range start is greater than range end");
+        }
+        (low..high).map(move |i| self.entry(i).unwrap())
+    }
+
+    fn search_erased(&self, key: AnyRef<'_>) -> Result<usize, usize> {
+        self.keys.vtable.assert_same_type(key.vtable());
+        self.search(key.data())
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.values.clear();
+    }
+
+    /// Our keys, in ascending order.
+    pub fn keys(&self) -> AnySlice<'_> {
+        self.keys.as_any_slice()
+    }
+
+    /// Our values, in key order.
+    pub fn values(&self) -> AnySlice<'_> {
+        self.values.as_any_slice()
+    }
+
+    /// Our entries, in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (AnyRef<'_>, AnyRef<'_>)> {
+        self.keys
+            .as_any_slice()
+            .iter()
+            .zip(self.values.as_any_slice().iter())
+    }
+
+    /// Convert into our sorted columns of keys and values.
+    pub fn into_anyvecs(self) -> (AnyVec, AnyVec) {
+        (self.keys, self.values)
+    }
+}
+
+/// Shift the elements from ``index`` on up by one, returning the
+/// uninitialized slot left at ``index``. The caller must fill it.
+fn open_gap(vec: &mut AnyVec, index: usize) -> *mut u8 {
+    vec.reserve(1);
+    let size = vec.vtable.size;
+    unsafe {
+        let slot = vec.data.add(index * size);
+        ptr::copy(slot, slot.add(size), (vec.length - index) * size);
+        vec.length += 1;
+        slot
+    }
+}
+
+/// Move the element at ``index`` out of ``vec``, shifting the elements after
+/// it down.
+fn close_gap(vec: &mut AnyVec, index: usize) -> AnyValue {
+    let vtable = vec.vtable;
+    let size = vtable.size;
+    unsafe {
+        let slot = vec.data.add(index * size);
+        let value = AnyValue::from_raw((vtable.box_value)(slot), vtable);
+        ptr::copy(slot.add(size), slot, (vec.length - index - 1) * size);
+        vec.length -= 1;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnyBTreeMap;
+    use crate::{AnyValue, AnyVec};
+
+    use std::ops::Bound;
+
+    #[test]
+    fn test_sorted_access() {
+        let mut map = AnyBTreeMap::new::<String, u32>();
+        for (i, name) in ["pear", "apple", "fig", "kiwi"].iter().enumerate() {
+            map.insert(name.to_string(), i as u32);
+        }
+        assert_eq!(map.insert(String::from("fig"), 10u32), Some(2));
+        assert_eq!(map.len(), 4);
+        assert_eq!(
+            map.keys().downcast::<String>().unwrap(),
+            &["apple", "fig", "kiwi", "pear"]
+        );
+        assert_eq!(map.get::<String, u32>(&String::from("fig")), Some(&10));
+        *map.get_mut::<String, u32>(&String::from("kiwi")).unwrap() += 1;
+        assert_eq!(map.remove::<String, u32>(&String::from("kiwi")), Some(4));
+        assert!(!map.contains_key(&String::from("kiwi")));
+
+        let (first, _) = map.first().unwrap();
+        assert_eq!(first.downcast_ref::<String>().unwrap(), "apple");
+        let (_, last) = map.last().unwrap();
+        assert_eq!(last.downcast_ref::<u32>(), Some(&0));
+    }
+
+    #[test]
+    fn test_range() {
+        let mut map = AnyBTreeMap::new::<i32, char>();
+        for (i, c) in "abcdefghij".chars().enumerate() {
+            map.insert(i as i32 * 10, c);
+        }
+        let found: String = map
+            .range(15..=40)
+            .map(|(_, value)| *value.downcast_ref::<char>().unwrap())
+            .collect();
+        assert_eq!(found, "cde");
+        assert_eq!(map.range(..0).count(), 0);
+        assert_eq!(map.range(85..).count(), 1);
+
+        let low = AnyValue::new(20i32);
+        let found: Vec<i32> = map
+            .range_refs(Bound::Excluded(low.as_any_ref()), Bound::Unbounded)
+            .map(|(key, _)| *key.downcast_ref::<i32>().unwrap())
+            .collect();
+        assert_eq!(found, vec![30, 40, 50, 60, 70, 80, 90]);
+    }
+
+    #[test]
+    fn test_from_anyvecs() {
+        let keys = AnyVec::from_vec::<u8>(vec![3, 1, 3, 2]);
+        let values = AnyVec::from_vec(vec!["a", "b", "c", "d"]);
+        let mut map = AnyBTreeMap::from_anyvecs(keys, values);
+        let old = map.insert_value(AnyValue::new(1u8), AnyValue::new("e"));
+        assert_eq!(old.unwrap().downcast::<&str>().ok(), Some("b"));
+        map.insert_value(AnyValue::new(0u8), AnyValue::new("f"));
+
+        let (keys, values) = map.into_anyvecs();
+        assert_eq!(keys.into_vec::<u8>(), vec![0, 1, 2, 3]);
+        assert_eq!(values.into_vec::<&str>(), vec!["f", "e", "d", "c"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support ordering")]
+    fn test_requires_ord() {
+        AnyBTreeMap::from_anyvecs(
+            AnyVec::from_vec(vec![vec![1u8]]),
+            AnyVec::from_vec(vec![1u8]),
+        );
+    }
+}
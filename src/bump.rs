@@ -0,0 +1,95 @@
+//! Arena-backed vectors, enabled by the ``bumpalo`` feature.
+//!
+//! A ``BumpAnyVec`` borrows its arena, so it can't outlive it. Freeing is a
+//! no-op until the arena itself is reset or dropped, which makes these a good
+//! fit for per-frame or per-request data.
+
+use std::any::Any;
+use std::mem;
+use std::slice;
+
+use bumpalo::Bump;
+
+use crate::AnyVec;
+
+/// An ``AnyVec`` whose buffer is allocated from a bump arena.
+pub type BumpAnyVec<'bump> = AnyVec<&'bump Bump>;
+
+impl<'bump> AnyVec<&'bump Bump> {
+    /// Convert into a slice that lives as long as the arena.
+    ///
+    /// The elements are never dropped, only released in bulk along with the
+    /// arena.
+    pub fn into_bump_slice_mut<T: Any>(self) -> &'bump mut [T] {
+        self.assert_typecheck::<T>();
+        let (data, length) = (self.data as *mut T, self.length);
+        // The arena owns our buffer now.
+        mem::forget(self);
+        unsafe { slice::from_raw_parts_mut(data, length) }
+    }
+
+    pub fn into_bump_slice<T: Any>(self) -> &'bump [T] {
+        self.into_bump_slice_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BumpAnyVec;
+    use crate::AnyVec;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use bumpalo::Bump;
+
+    #[test]
+    fn test_push_into_arena() {
+        let bump = Bump::new();
+        let mut dynamic: BumpAnyVec = AnyVec::new_in::<u64>(&bump);
+
+        for i in 0..1000 {
+            dynamic.push(i as u64);
+        }
+        assert!(bump.allocated_bytes() >= 1000 * std::mem::size_of::<u64>());
+
+        let result = dynamic.into_bump_slice::<u64>();
+        let expected: Vec<u64> = (0..1000).collect();
+        assert_eq!(result, &expected[..]);
+    }
+
+    // Counts its drops in a shared counter.
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_drop_in_arena() {
+        let drops = Rc::new(Cell::new(0));
+        let mut bump = Bump::new();
+        let mut dynamic: BumpAnyVec = AnyVec::new_in::<Counted>(&bump);
+        for _ in 0..3 {
+            dynamic.push(Counted(drops.clone()));
+        }
+        assert_eq!(drops.get(), 0);
+        drop(dynamic);
+        assert_eq!(drops.get(), 3);
+
+        // Freeing the arena doesn't drop anything again.
+        bump.reset();
+        drop(bump);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_into_bump_slice_typecheck() {
+        let bump = Bump::new();
+        let dynamic: BumpAnyVec = AnyVec::new_in::<u64>(&bump);
+        dynamic.into_bump_slice::<f64>();
+    }
+}
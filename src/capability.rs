@@ -0,0 +1,191 @@
+//! The set of optional operations an element type supports.
+
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Sub};
+
+/// A set of optional capabilities, as reported by ``AnyVec::capabilities``.
+///
+/// Sets combine with ``|``, so code can check everything it needs up front:
+///
+/// ```
+/// use anyvector::{AnyVec, CapabilitySet};
+///
+/// let dynamic = AnyVec::from_vec::<u64>(vec![3, 1, 2]);
+/// assert!(dynamic.capabilities().contains(CapabilitySet::ORD | CapabilitySet::HASH));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CapabilitySet(u32);
+
+const NAMES: &[(CapabilitySet, &str)] = &[
+    (CapabilitySet::CLONE, "CLONE"),
+    (CapabilitySet::COPY, "COPY"),
+    (CapabilitySet::EQ, "EQ"),
+    (CapabilitySet::ORD, "ORD"),
+    (CapabilitySet::HASH, "HASH"),
+    (CapabilitySet::DEBUG, "DEBUG"),
+    (CapabilitySet::DISPLAY, "DISPLAY"),
+    (CapabilitySet::SEND, "SEND"),
+    (CapabilitySet::SYNC, "SYNC"),
+    (CapabilitySet::POD, "POD"),
+    (CapabilitySet::NUMERIC, "NUMERIC"),
+    (CapabilitySet::SERIALIZE, "SERIALIZE"),
+    (CapabilitySet::PARSE, "PARSE"),
+    (CapabilitySet::HEAP_SIZE, "HEAP_SIZE"),
+];
+
+impl CapabilitySet {
+    pub const CLONE: CapabilitySet = CapabilitySet(1 << 0);
+    /// Cloning is a plain memcpy.
+    pub const COPY: CapabilitySet = CapabilitySet(1 << 1);
+    pub const EQ: CapabilitySet = CapabilitySet(1 << 2);
+    pub const ORD: CapabilitySet = CapabilitySet(1 << 3);
+    pub const HASH: CapabilitySet = CapabilitySet(1 << 4);
+    pub const DEBUG: CapabilitySet = CapabilitySet(1 << 5);
+    pub const DISPLAY: CapabilitySet = CapabilitySet(1 << 6);
+    pub const SEND: CapabilitySet = CapabilitySet(1 << 7);
+    pub const SYNC: CapabilitySet = CapabilitySet(1 << 8);
+    pub const POD: CapabilitySet = CapabilitySet(1 << 9);
+    pub const NUMERIC: CapabilitySet = CapabilitySet(1 << 10);
+    /// Only ever present with the ``serde`` feature.
+    pub const SERIALIZE: CapabilitySet = CapabilitySet(1 << 11);
+    /// Elements can be parsed from strings.
+    pub const PARSE: CapabilitySet = CapabilitySet(1 << 12);
+    /// Elements can report the heap memory they own.
+    pub const HEAP_SIZE: CapabilitySet = CapabilitySet(1 << 13);
+
+    pub const fn empty() -> CapabilitySet {
+        CapabilitySet(0)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every capability in ``other`` is also in ``self``.
+    pub const fn contains(self, other: CapabilitySet) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: CapabilitySet) {
+        self.0 |= other.0;
+    }
+
+    /// Add ``other`` if ``present`` is true.
+    pub(crate) fn with_if(self, other: CapabilitySet, present: bool) -> CapabilitySet {
+        if present {
+            self | other
+        } else {
+            self
+        }
+    }
+
+    /// The names of our capabilities, e.g. ``["EQ", "HASH"]``.
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        NAMES
+            .iter()
+            .filter(move |(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+    }
+}
+
+impl BitOr for CapabilitySet {
+    type Output = CapabilitySet;
+
+    fn bitor(self, other: CapabilitySet) -> CapabilitySet {
+        CapabilitySet(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for CapabilitySet {
+    fn bitor_assign(&mut self, other: CapabilitySet) {
+        self.insert(other);
+    }
+}
+
+impl BitAnd for CapabilitySet {
+    type Output = CapabilitySet;
+
+    fn bitand(self, other: CapabilitySet) -> CapabilitySet {
+        CapabilitySet(self.0 & other.0)
+    }
+}
+
+/// The capabilities in ``self`` but not in ``other``.
+impl Sub for CapabilitySet {
+    type Output = CapabilitySet;
+
+    fn sub(self, other: CapabilitySet) -> CapabilitySet {
+        CapabilitySet(self.0 & !other.0)
+    }
+}
+
+impl fmt::Debug for CapabilitySet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("(empty)");
+        }
+        let names: Vec<&str> = self.names().collect();
+        f.write_str(&names.join(" | "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CapabilitySet;
+    use crate::AnyVec;
+
+    #[test]
+    fn test_set_operations() {
+        let set = CapabilitySet::EQ | CapabilitySet::HASH;
+        assert!(set.contains(CapabilitySet::EQ));
+        assert!(!set.contains(CapabilitySet::EQ | CapabilitySet::ORD));
+        assert_eq!(set - CapabilitySet::EQ, CapabilitySet::HASH);
+        assert_eq!(set & CapabilitySet::ORD, CapabilitySet::empty());
+        assert_eq!(format!("{:?}", set), "EQ | HASH");
+        assert_eq!(format!("{:?}", CapabilitySet::empty()), "(empty)");
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let floats = AnyVec::new::<f64>().capabilities();
+        for expected in [
+            CapabilitySet::CLONE,
+            CapabilitySet::COPY,
+            CapabilitySet::EQ,
+            CapabilitySet::ORD,
+            CapabilitySet::DEBUG,
+            CapabilitySet::DISPLAY,
+            CapabilitySet::SEND,
+            CapabilitySet::SYNC,
+            CapabilitySet::POD,
+            CapabilitySet::NUMERIC,
+        ] {
+            assert!(floats.contains(expected), "{:?}", expected);
+        }
+        assert!(!floats.contains(CapabilitySet::HASH));
+
+        #[derive(Clone, Debug)]
+        struct Opaque;
+        let opaque = AnyVec::new::<Opaque>();
+        assert!(opaque.capabilities().is_empty());
+        let opaque = opaque
+            .with_clone::<Opaque>()
+            .with_debug::<Opaque>()
+            .with_send::<Opaque>();
+        assert_eq!(
+            opaque.capabilities(),
+            CapabilitySet::CLONE | CapabilitySet::DEBUG | CapabilitySet::SEND
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing capabilities: ORD | HASH")]
+    fn test_assert_capabilities() {
+        struct Opaque;
+        AnyVec::new::<Opaque>().assert_capabilities(CapabilitySet::ORD | CapabilitySet::HASH);
+    }
+}
@@ -0,0 +1,241 @@
+//! Converting numeric vectors between element types.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::numeric::{Number, Numeric};
+use crate::vtable::VTable;
+use crate::{Allocator, AnyVec, Global, TypeRegistry};
+
+/// What to do with elements the target type can't represent exactly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CastPolicy {
+    /// Fail on the first such element. Integers must fit exactly; floats
+    /// round to the nearest representable value.
+    Checked,
+    /// Clamp to the target type's range. ``NaN`` becomes zero.
+    Saturating,
+    /// Keep the low bits, as ``as`` does between integers. Floats are
+    /// truncated toward zero first.
+    Wrapping,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CastError {
+    /// No type is registered under this name in the global
+    /// [`TypeRegistry`].
+    UnknownType(String),
+    /// Element ``index`` doesn't fit in the target type.
+    Lossy {
+        index: usize,
+        from: &'static str,
+        to: &'static str,
+    },
+}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CastError::UnknownType(name) => write!(f, "{} is not registered", name),
+            CastError::Lossy { index, from, to } => write!(
+                f,
+                "Element {} can't be cast from {} to {} without loss",
+                index, from, to
+            ),
+        }
+    }
+}
+
+impl Error for CastError {}
+
+/// The primitive number types, which can be cast to under every policy.
+pub(crate) trait Cast: Numeric {
+    fn saturating(number: Number) -> Self;
+    fn wrapping(number: Number) -> Self;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Cast for $t {
+                fn saturating(number: Number) -> $t {
+                    match number {
+                        Number::Int(i) => i.clamp(<$t>::MIN as i128, <$t>::MAX as i128) as $t,
+                        Number::Float(f) => f as $t,
+                    }
+                }
+
+                fn wrapping(number: Number) -> $t {
+                    match number {
+                        Number::Int(i) => i as $t,
+                        Number::Float(f) => f as i128 as $t,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_float {
+    ($($t:ty),*) => {
+        $(
+            impl Cast for $t {
+                fn saturating(number: Number) -> $t {
+                    match number {
+                        Number::Int(i) => i as $t,
+                        Number::Float(f) => f as $t,
+                    }
+                }
+
+                fn wrapping(number: Number) -> $t {
+                    <$t>::saturating(number)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+impl_float!(f32, f64);
+
+/// Write ``number`` into ``dest`` as a ``T``, under ``policy``. Returns false,
+/// writing nothing, if the cast is checked and would lose information.
+pub(crate) fn cast_into<T: Cast>(number: Number, policy: CastPolicy, dest: *mut u8) -> bool {
+    let value = match policy {
+        CastPolicy::Checked => match T::from_number(number) {
+            Some(value) => value,
+            None => return false,
+        },
+        CastPolicy::Saturating => T::saturating(number),
+        CastPolicy::Wrapping => T::wrapping(number),
+    };
+    unsafe { (dest as *mut T).write(value) };
+    true
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Convert every element to a ``U`` under ``policy``.
+    ///
+    /// Panics if our element type isn't numeric.
+    pub fn cast_to<U: Numeric>(&self, policy: CastPolicy) -> Result<AnyVec, CastError> {
+        let target = VTable::new::<U>();
+        let target = match target.numeric {
+            Some(_) => target,
+            None => target.with_numeric::<U>(),
+        };
+        self.cast_to_vtable(target, policy)
+    }
+
+    /// Convert every element to the numeric type registered as ``name`` in
+    /// the global [`TypeRegistry`], e.g. ``"f64"``, under ``policy``.
+    ///
+    /// Panics if either element type isn't numeric.
+    pub fn cast_to_type_name(&self, name: &str, policy: CastPolicy) -> Result<AnyVec, CastError> {
+        let target = TypeRegistry::with_global(|registry| registry.get(name).map(|e| e.vtable));
+        match target {
+            Some(target) => self.cast_to_vtable(target, policy),
+            None => Err(CastError::UnknownType(name.to_string())),
+        }
+    }
+
+    fn cast_to_vtable(
+        &self,
+        target: &'static VTable,
+        policy: CastPolicy,
+    ) -> Result<AnyVec, CastError> {
+        let to_number = self.vtable.require_numeric().to_number;
+        let numeric = target.require_numeric();
+        // Types without kernels can only be cast to through ``from_number``,
+        // which boxes every element.
+        let cast = match (policy, numeric.kernels) {
+            (_, Some(kernels)) => Some(kernels.cast),
+            (CastPolicy::Checked, None) => None,
+            (_, None) => panic!("{} doesn't support {:?} casts", target.display_name, policy),
+        };
+        let mut result = AnyVec::from_vtable_in(target, Global);
+        result.reserve(self.length);
+        for i in 0..self.length {
+            let number = to_number(self.element_ptr(i));
+            let dest = unsafe { result.data.add(i * target.size) };
+            let cast = match cast {
+                Some(cast) => cast(number, policy, dest),
+                None => (numeric.from_number)(number)
+                    .map(|value| {
+                        let (data, _) = value.into_raw();
+                        (target.unbox_into)(data, dest);
+                    })
+                    .is_some(),
+            };
+            if !cast {
+                return Err(CastError::Lossy {
+                    index: i,
+                    from: self.vtable.display_name,
+                    to: target.display_name,
+                });
+            }
+            result.length += 1;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CastError, CastPolicy};
+    use crate::AnyVec;
+
+    #[test]
+    fn test_checked() {
+        let small = AnyVec::from_vec::<i32>(vec![1, -2, 300]);
+        let wide = small.cast_to::<i64>(CastPolicy::Checked).unwrap();
+        assert_eq!(wide.into_vec::<i64>(), vec![1, -2, 300]);
+
+        let error = small.cast_to::<u8>(CastPolicy::Checked).err().unwrap();
+        assert_eq!(
+            error,
+            CastError::Lossy {
+                index: 1,
+                from: "i32",
+                to: "u8"
+            }
+        );
+        let floats = AnyVec::from_vec::<f64>(vec![2.0, 2.5]);
+        assert!(floats.cast_to::<i8>(CastPolicy::Checked).is_err());
+    }
+
+    #[test]
+    fn test_saturating_and_wrapping() {
+        let values = AnyVec::from_vec::<i32>(vec![-1, 255, 256, 1000]);
+        let saturated = values.cast_to::<u8>(CastPolicy::Saturating).unwrap();
+        assert_eq!(saturated.into_vec::<u8>(), vec![0, 255, 255, 255]);
+        let wrapped = values.cast_to::<u8>(CastPolicy::Wrapping).unwrap();
+        assert_eq!(wrapped.into_vec::<u8>(), vec![255, 255, 0, 232]);
+
+        let floats = AnyVec::from_vec::<f64>(vec![-3.7, f64::NAN, 1e10]);
+        let saturated = floats.cast_to::<i16>(CastPolicy::Saturating).unwrap();
+        assert_eq!(saturated.into_vec::<i16>(), vec![-3, 0, i16::MAX]);
+    }
+
+    #[test]
+    fn test_cast_to_type_name() {
+        let values = AnyVec::from_vec::<u16>(vec![1, 2]);
+        let floats = values
+            .cast_to_type_name("f64", CastPolicy::Checked)
+            .unwrap();
+        assert_eq!(floats.into_vec::<f64>(), vec![1.0, 2.0]);
+
+        let error = values
+            .cast_to_type_name("f128", CastPolicy::Checked)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "f128 is not registered");
+    }
+
+    #[test]
+    #[should_panic(expected = "String is not numeric")]
+    fn test_requires_numeric() {
+        AnyVec::from_vec::<u8>(vec![1])
+            .cast_to_type_name("String", CastPolicy::Checked)
+            .ok();
+    }
+}
@@ -0,0 +1,167 @@
+//! A vector stored as a list of chunks, so growing never moves elements.
+
+use std::any::Any;
+use std::ptr;
+
+use crate::append::{bucket_len, locate};
+use crate::vtable::VTable;
+use crate::{AnyRef, AnyValue, AnyVec, Global};
+
+/// A vector whose elements never move once pushed, for appending very many
+/// elements without the copies an ``AnyVec`` makes each time it outgrows its
+/// buffer.
+///
+/// Elements are stored in chunks that double in size, like
+/// [`AppendAnyVec`](crate::AppendAnyVec)'s buckets: once a chunk is full,
+/// the next push allocates a new one rather than reallocating. Indexing
+/// takes constant time. [`ChunkedAnyVec::compact`] copies everything into
+/// one contiguous ``AnyVec``.
+pub struct ChunkedAnyVec {
+    vtable: &'static VTable,
+    // Chunk ``c`` has room for ``bucket_len(c)`` elements. All but the last
+    // are full.
+    chunks: Vec<AnyVec>,
+    length: usize,
+}
+
+impl ChunkedAnyVec {
+    pub fn new<T: Any>() -> ChunkedAnyVec {
+        ChunkedAnyVec {
+            vtable: VTable::new::<T>(),
+            chunks: Vec::new(),
+            length: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// The chunk the next element goes in, allocating it if need be.
+    fn tail(&mut self) -> &mut AnyVec {
+        let (chunk, _) = locate(self.length);
+        if chunk == self.chunks.len() {
+            let mut fresh = AnyVec::from_vtable_in(self.vtable, Global);
+            fresh.reserve(bucket_len(chunk));
+            self.chunks.push(fresh);
+        }
+        &mut self.chunks[chunk]
+    }
+
+    pub fn push<T: Any>(&mut self, value: T) {
+        self.vtable.assert_typecheck::<T>();
+        self.tail().push(value);
+        self.length += 1;
+    }
+
+    /// Append an erased value. Panics if its type doesn't match ours.
+    pub fn push_value(&mut self, value: AnyValue) {
+        self.vtable.assert_same_type(value.vtable());
+        self.tail().push_value(value);
+        self.length += 1;
+    }
+
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T> {
+        self.vtable.assert_typecheck::<T>();
+        self.get_ref(index).and_then(|item| item.downcast_ref())
+    }
+
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'_>> {
+        if index < self.length {
+            let (chunk, offset) = locate(index);
+            self.chunks[chunk].get_ref(offset)
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = AnyRef<'_>> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| chunk.as_any_slice().iter())
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.length = 0;
+    }
+
+    /// Move our elements into one contiguous ``AnyVec``.
+    pub fn compact(self) -> AnyVec {
+        let mut result = AnyVec::from_vtable_in(self.vtable, Global);
+        result.reserve(self.length);
+        let size = self.vtable.size;
+        for mut chunk in self.chunks {
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    chunk.data,
+                    result.data.add(result.length * size),
+                    chunk.length * size,
+                );
+            }
+            result.length += chunk.length;
+            // The elements have moved, so only free the buffer.
+            chunk.length = 0;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkedAnyVec;
+    use crate::AnyValue;
+
+    use std::rc::Rc;
+
+    #[test]
+    fn test_push_and_index() {
+        let mut ids = ChunkedAnyVec::new::<u64>();
+        for i in 0..1000u64 {
+            ids.push(i);
+        }
+        let first = ids.get_ref(0).unwrap().data();
+        ids.push(1000u64);
+        assert_eq!(ids.get_ref(0).unwrap().data(), first);
+
+        assert_eq!(ids.len(), 1001);
+        assert_eq!(ids.get::<u64>(31), Some(&31));
+        assert_eq!(ids.get::<u64>(32), Some(&32));
+        assert_eq!(ids.get::<u64>(1001), None);
+        assert!(ids.iter().enumerate().all(|(i, id)| id == &(i as u64)));
+        assert_eq!(
+            ids.compact().into_vec::<u64>(),
+            (0..=1000).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_drops_elements() {
+        let chan = Rc::new(());
+        let mut handles = ChunkedAnyVec::new::<Rc<()>>();
+        for _ in 0..100 {
+            handles.push_value(AnyValue::new(chan.clone()));
+        }
+        assert_eq!(Rc::strong_count(&chan), 101);
+        let compacted = handles.compact();
+        assert_eq!(Rc::strong_count(&chan), 101);
+        drop(compacted);
+        assert_eq!(Rc::strong_count(&chan), 1);
+
+        let mut handles = ChunkedAnyVec::new::<Rc<()>>();
+        handles.push(chan.clone());
+        handles.clear();
+        assert!(handles.is_empty());
+        assert_eq!(Rc::strong_count(&chan), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_typecheck() {
+        ChunkedAnyVec::new::<u32>().push(1u64);
+    }
+}
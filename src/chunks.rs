@@ -0,0 +1,96 @@
+//! Splitting a vector into disjoint mutable chunks that can be handed to
+//! other threads, for running per-chunk kernels in parallel.
+
+use std::ops::{Deref, DerefMut};
+
+use crate::{Allocator, AnySliceMut, AnyVec, CapabilitySet};
+
+/// An [`AnySliceMut`] whose element type is ``Send``, which makes it safe to
+/// move to another thread.
+#[derive(Debug)]
+pub struct SendSliceMut<'a>(AnySliceMut<'a>);
+
+// Only created for elements whose vtable has the ``SEND`` capability.
+unsafe impl Send for SendSliceMut<'_> {}
+
+impl<'a> SendSliceMut<'a> {
+    pub fn into_inner(self) -> AnySliceMut<'a> {
+        self.0
+    }
+}
+
+impl<'a> Deref for SendSliceMut<'a> {
+    type Target = AnySliceMut<'a>;
+
+    fn deref(&self) -> &AnySliceMut<'a> {
+        &self.0
+    }
+}
+
+impl<'a> DerefMut for SendSliceMut<'a> {
+    fn deref_mut(&mut self) -> &mut AnySliceMut<'a> {
+        &mut self.0
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Split our elements into disjoint chunks of ``chunk_size`` elements
+    /// (the last may be shorter) that can be sent to scoped threads, or to
+    /// rayon by collecting them and calling ``into_par_iter``.
+    ///
+    /// Panics if ``chunk_size`` is 0, or if the element type doesn't have the
+    /// ``SEND`` capability.
+    pub fn par_chunks_mut(&mut self, chunk_size: usize) -> Vec<SendSliceMut<'_>> {
+        if chunk_size == 0 {
+            panic!("Chunk size must be non-zero");
+        }
+        self.assert_capabilities(CapabilitySet::SEND);
+
+        let mut chunks = Vec::with_capacity(self.len().div_ceil(chunk_size));
+        let mut rest = self.as_any_slice_mut();
+        while !rest.is_empty() {
+            let mid = chunk_size.min(rest.len());
+            let (chunk, tail) = rest.split_at_mut(mid);
+            chunks.push(SendSliceMut(chunk));
+            rest = tail;
+        }
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    use std::rc::Rc;
+    use std::thread;
+
+    #[test]
+    fn test_scoped_threads() {
+        let mut dynamic = AnyVec::from_vec::<u64>((0..10).collect());
+        let chunks = dynamic.par_chunks_mut(4);
+        let lens: Vec<usize> = chunks.iter().map(|chunk| chunk.len()).collect();
+        assert_eq!(lens, vec![4, 4, 2]);
+
+        thread::scope(|scope| {
+            for mut chunk in chunks {
+                scope.spawn(move || {
+                    for value in chunk.downcast_mut::<u64>().unwrap() {
+                        *value *= 2;
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            dynamic.into_vec::<u64>(),
+            (0..10).map(|i| i * 2).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing capabilities: SEND")]
+    fn test_requires_send() {
+        let mut shared = AnyVec::from_vec(vec![Rc::new(1)]);
+        shared.par_chunks_mut(1);
+    }
+}
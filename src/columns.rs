@@ -0,0 +1,98 @@
+//! Converting between a struct's instances and one column per field, for
+//! struct-of-arrays layouts of user types.
+
+/// Types whose instances can be split into one ``AnyVec`` per field, and put
+/// back together.
+///
+/// With the ``derive`` feature, ``#[derive(AnyColumns)]`` implements this for
+/// structs with named fields, generating a ``<Name>Columns`` struct with a
+/// public ``AnyVec`` and a typed slice accessor for each field:
+///
+/// ```
+/// # #[cfg(feature = "derive")] {
+/// use anyvector::AnyColumns;
+///
+/// #[derive(AnyColumns)]
+/// struct Trade {
+///     id: u64,
+///     price: f64,
+/// }
+///
+/// let columns = Trade::into_columns(vec![
+///     Trade { id: 1, price: 9.5 },
+///     Trade { id: 2, price: 10.0 },
+/// ]);
+/// assert_eq!(columns.price(), &[9.5, 10.0]);
+/// assert_eq!(Trade::from_columns(columns).len(), 2);
+/// # }
+/// ```
+pub trait AnyColumns: Sized {
+    /// Holds one ``AnyVec`` per field.
+    type Columns;
+
+    /// Our field names, in declaration order.
+    const FIELDS: &'static [&'static str];
+
+    fn into_columns(rows: Vec<Self>) -> Self::Columns;
+
+    /// Panics if the columns have different lengths.
+    fn from_columns(columns: Self::Columns) -> Vec<Self>;
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use crate::{AnyColumns, AnyVec};
+
+    #[derive(AnyColumns, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+        symbol: String,
+        quantity: i64,
+    }
+
+    fn orders() -> Vec<Order> {
+        vec![
+            Order {
+                id: 1,
+                symbol: String::from("AAPL"),
+                quantity: 10,
+            },
+            Order {
+                id: 2,
+                symbol: String::from("MSFT"),
+                quantity: -5,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let columns = Order::into_columns(orders());
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns.id(), &[1, 2]);
+        assert_eq!(columns.symbol(), &["AAPL", "MSFT"]);
+        assert_eq!(columns.quantity(), &[10, -5]);
+        assert_eq!(columns.symbol.get::<String, _>(1).unwrap(), "MSFT");
+        assert_eq!(Order::from_columns(columns), orders());
+    }
+
+    #[test]
+    fn test_columns_by_name() {
+        let mut columns = OrderColumns::new();
+        assert!(columns.is_empty());
+        for order in orders() {
+            columns.push(order);
+        }
+        let names: Vec<&str> = columns.columns().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, Order::FIELDS);
+        assert_eq!(Order::FIELDS, &["id", "symbol", "quantity"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Columns have different lengths (symbol has 1, expected 2)")]
+    fn test_from_columns_lengths() {
+        let mut columns = Order::into_columns(orders());
+        columns.symbol = AnyVec::from_vec(vec![String::from("AAPL")]);
+        Order::from_columns(columns);
+    }
+}
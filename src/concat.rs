@@ -0,0 +1,84 @@
+//! Joining vectors end to end, like slices' ``concat`` and ``repeat``.
+
+use crate::{Allocator, AnyVec};
+
+impl<A: Allocator + Clone> AnyVec<A> {
+    /// Clone the elements of every one of ``parts`` into a new vector, in
+    /// order, with a single allocation. The result uses the first part's
+    /// allocator and capabilities; there's no result if ``parts`` is empty.
+    ///
+    /// Panics if the parts' element types differ, or don't support cloning.
+    pub fn concat(parts: &[&AnyVec<A>]) -> Option<AnyVec<A>> {
+        let first = parts.first()?;
+        for part in parts {
+            first.vtable.assert_same_type(part.vtable);
+        }
+        let clone_into = first.vtable.require_clone().clone_into;
+        let mut result = AnyVec::from_vtable_in(first.vtable, first.alloc.clone());
+        result.reserve(parts.iter().map(|part| part.length).sum());
+        for part in parts {
+            let dest = unsafe { result.data.add(result.length * result.vtable.size) };
+            clone_into(part.data, dest, part.length);
+            result.length += part.length;
+        }
+        result.record_clones(result.length);
+        Some(result)
+    }
+
+    /// A new vector holding ``n`` copies of our elements, one after another.
+    ///
+    /// Panics if the length would overflow, or if the element type doesn't
+    /// support cloning.
+    pub fn repeat(&self, n: usize) -> AnyVec<A> {
+        let length = self
+            .length
+            .checked_mul(n)
+            .expect("Repeated length overflows usize");
+        let clone_into = self.vtable.require_clone().clone_into;
+        let mut result = AnyVec::from_vtable_in(self.vtable, self.alloc.clone());
+        result.reserve(length);
+        for _ in 0..n {
+            let dest = unsafe { result.data.add(result.length * self.vtable.size) };
+            clone_into(self.data, dest, self.length);
+            result.length += self.length;
+        }
+        result.record_clones(result.length);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyVec, Global};
+
+    #[test]
+    fn test_concat() {
+        let a = AnyVec::from_vec(vec![String::from("a")]).with_clone::<String>();
+        let b = AnyVec::from_vec(Vec::<String>::new());
+        let c = AnyVec::from_vec(vec![String::from("b"), String::from("c")]);
+        let joined = AnyVec::concat(&[&a, &b, &c]).unwrap();
+        assert_eq!(joined.into_vec::<String>(), vec!["a", "b", "c"]);
+        assert_eq!(a.len(), 1);
+        assert!(AnyVec::<Global>::concat(&[]).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match (u8 != i8)")]
+    fn test_concat_types() {
+        AnyVec::concat(&[&AnyVec::new::<u8>(), &AnyVec::new::<i8>()]);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let dynamic = AnyVec::from_vec::<u16>(vec![1, 2]);
+        assert_eq!(dynamic.repeat(3).into_vec::<u16>(), vec![1, 2, 1, 2, 1, 2]);
+        assert!(dynamic.repeat(0).is_empty());
+        assert!(AnyVec::new::<u16>().repeat(5).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Repeated length overflows usize")]
+    fn test_repeat_overflow() {
+        AnyVec::from_vec::<u8>(vec![1, 2]).repeat(usize::MAX);
+    }
+}
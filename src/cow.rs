@@ -0,0 +1,114 @@
+//! Copy-on-write vectors for taking cheap snapshots.
+
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::{Allocator, AnyVec, CapabilitySet, Global};
+
+/// An ``AnyVec`` whose clones share elements until one of them is mutated.
+///
+/// Cloning bumps a reference count. [`CowAnyVec::make_mut`] copies the
+/// elements, using the vtable's clone function, only if another clone still
+/// refers to them.
+pub struct CowAnyVec<A: Allocator + Clone = Global>(Rc<AnyVec<A>>);
+
+impl<A: Allocator + Clone> CowAnyVec<A> {
+    /// Panics if the element type doesn't have the ``CLONE`` capability, so
+    /// that a later copy can't fail.
+    pub fn new(vec: AnyVec<A>) -> CowAnyVec<A> {
+        vec.assert_capabilities(CapabilitySet::CLONE);
+        CowAnyVec(Rc::new(vec))
+    }
+
+    /// Get mutable access to our elements, copying them first if they're
+    /// shared with another clone.
+    pub fn make_mut(&mut self) -> &mut AnyVec<A> {
+        Rc::make_mut(&mut self.0)
+    }
+
+    pub fn is_shared(&self) -> bool {
+        Rc::strong_count(&self.0) > 1
+    }
+
+    /// Get the vector back, copying it if it's shared.
+    pub fn into_inner(self) -> AnyVec<A> {
+        Rc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+}
+
+impl<A: Allocator + Clone> Clone for CowAnyVec<A> {
+    fn clone(&self) -> CowAnyVec<A> {
+        CowAnyVec(Rc::clone(&self.0))
+    }
+}
+
+impl<A: Allocator + Clone> Deref for CowAnyVec<A> {
+    type Target = AnyVec<A>;
+
+    fn deref(&self) -> &AnyVec<A> {
+        &self.0
+    }
+}
+
+impl<A: Allocator + Clone> From<AnyVec<A>> for CowAnyVec<A> {
+    fn from(vec: AnyVec<A>) -> CowAnyVec<A> {
+        CowAnyVec::new(vec)
+    }
+}
+
+impl<A: Allocator + Clone> fmt::Debug for CowAnyVec<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_any_slice().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CowAnyVec;
+    use crate::AnyVec;
+
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_copy_on_write() {
+        let original = CowAnyVec::new(AnyVec::from_vec(vec![String::from("a")]));
+        let mut snapshot = original.clone();
+        assert!(original.is_shared());
+        assert_eq!(
+            original.get::<String, _>(0).unwrap().as_ptr(),
+            snapshot.get::<String, _>(0).unwrap().as_ptr()
+        );
+
+        snapshot.make_mut().push(String::from("b"));
+        assert!(!original.is_shared());
+        assert_eq!(original.len(), 1);
+        assert_eq!(
+            snapshot.into_inner().into_vec::<String>(),
+            vec!["a".to_string(), "b".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_unshared_mutation_doesnt_copy() {
+        let mut dynamic = CowAnyVec::new(AnyVec::from_vec::<u8>(vec![1, 2]));
+        let before = dynamic.get::<u8, _>(..).unwrap().as_ptr();
+        *dynamic.make_mut().first_mut::<u8>().unwrap() = 9;
+        assert_eq!(dynamic.get::<u8, _>(..).unwrap().as_ptr(), before);
+        assert_eq!(dynamic.into_inner().into_vec::<u8>(), vec![9, 2]);
+    }
+
+    #[test]
+    fn test_into_inner_copies_shared() {
+        let dynamic = CowAnyVec::new(AnyVec::from_vec::<u8>(vec![1, 2]));
+        let copy = dynamic.clone();
+        assert_eq!(copy.into_inner().into_vec::<u8>(), vec![1, 2]);
+        assert!(!dynamic.is_shared());
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing capabilities: CLONE")]
+    fn test_requires_clone() {
+        CowAnyVec::new(AnyVec::from_vec(vec![Mutex::new(1)]));
+    }
+}
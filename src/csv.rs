@@ -0,0 +1,270 @@
+//! Reading CSV into columns, enabled by the ``csv`` feature.
+//!
+//! Column types come from a schema of names looked up in a [`TypeRegistry`],
+//! and every cell is parsed with its column type's parse capability. Quoted
+//! fields may contain delimiters, newlines and doubled quotes.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead};
+
+use crate::{AnyVec, Global, ParseError, TypeRegistry};
+
+/// How to split the input into fields.
+#[derive(Clone, Copy, Debug)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    /// Whether to skip the first record.
+    pub has_header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            delimiter: ',',
+            has_header: true,
+        }
+    }
+}
+
+/// Why reading CSV failed. Line numbers are one-based and refer to the line
+/// a record starts on; column numbers are zero-based.
+#[derive(Debug)]
+pub enum CsvError {
+    Io(io::Error),
+    /// A schema entry isn't registered, or its type can't be parsed.
+    UnknownType(String),
+    WrongFieldCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnterminatedQuote {
+        line: usize,
+    },
+    Parse {
+        line: usize,
+        column: usize,
+        error: ParseError,
+    },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Io(err) => write!(f, "{}", err),
+            CsvError::UnknownType(name) => write!(f, "{} is not registered for parsing", name),
+            CsvError::WrongFieldCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Line {}: expected {} fields, found {}",
+                line, expected, found
+            ),
+            CsvError::UnterminatedQuote { line } => {
+                write!(f, "Line {}: unterminated quoted field", line)
+            }
+            CsvError::Parse {
+                line,
+                column,
+                error,
+            } => write!(f, "Line {}, column {}: {}", line, column, error),
+        }
+    }
+}
+
+impl Error for CsvError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CsvError::Io(err) => Some(err),
+            CsvError::Parse { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CsvError {
+    fn from(err: io::Error) -> CsvError {
+        CsvError::Io(err)
+    }
+}
+
+impl TypeRegistry {
+    /// Read ``reader`` as CSV into one vector per schema entry, looking up
+    /// each entry's type by name.
+    pub fn read_csv<R: BufRead>(
+        &self,
+        reader: R,
+        schema: &[&str],
+        options: CsvOptions,
+    ) -> Result<Vec<AnyVec>, CsvError> {
+        let mut columns = schema
+            .iter()
+            .map(|name| match self.get(name) {
+                Some(entry) if entry.vtable.parse.is_some() => {
+                    Ok(AnyVec::from_vtable_in(entry.vtable, Global))
+                }
+                _ => Err(CsvError::UnknownType(name.to_string())),
+            })
+            .collect::<Result<Vec<AnyVec>, CsvError>>()?;
+
+        let mut records = Records {
+            lines: reader.lines(),
+            delimiter: options.delimiter,
+            line: 0,
+        };
+        if options.has_header {
+            records.next_record()?;
+        }
+        while let Some((line, fields)) = records.next_record()? {
+            if fields.len() != columns.len() {
+                return Err(CsvError::WrongFieldCount {
+                    line,
+                    expected: columns.len(),
+                    found: fields.len(),
+                });
+            }
+            for (column, (vec, field)) in columns.iter_mut().zip(&fields).enumerate() {
+                vec.push_parsed(field).map_err(|error| CsvError::Parse {
+                    line,
+                    column,
+                    error,
+                })?;
+            }
+        }
+        Ok(columns)
+    }
+}
+
+/// Read ``reader`` as CSV into one vector per schema entry, looking up each
+/// entry's type in the global registry.
+pub fn read_csv<R: BufRead>(
+    reader: R,
+    schema: &[&str],
+    options: CsvOptions,
+) -> Result<Vec<AnyVec>, CsvError> {
+    TypeRegistry::with_global(|registry| registry.read_csv(reader, schema, options))
+}
+
+struct Records<R> {
+    lines: io::Lines<R>,
+    delimiter: char,
+    // The number of lines consumed so far.
+    line: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    // The next non-empty record and the line it started on.
+    fn next_record(&mut self) -> Result<Option<(usize, Vec<String>)>, CsvError> {
+        loop {
+            let text = match self.lines.next() {
+                Some(text) => text?,
+                None => return Ok(None),
+            };
+            self.line += 1;
+            if !text.trim_end_matches('\r').is_empty() {
+                let start = self.line;
+                return self.split(text, start).map(|fields| Some((start, fields)));
+            }
+        }
+    }
+
+    fn split(&mut self, mut text: String, start: usize) -> Result<Vec<String>, CsvError> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            let mut chars = text.trim_end_matches('\r').chars().peekable();
+            while let Some(c) = chars.next() {
+                if quoted {
+                    if c != '"' {
+                        field.push(c);
+                    } else if chars.peek() == Some(&'"') {
+                        chars.next();
+                        field.push('"');
+                    } else {
+                        quoted = false;
+                    }
+                } else if c == '"' {
+                    quoted = true;
+                } else if c == self.delimiter {
+                    fields.push(std::mem::take(&mut field));
+                } else {
+                    field.push(c);
+                }
+            }
+            if !quoted {
+                fields.push(field);
+                return Ok(fields);
+            }
+            // A quoted field continues onto the next line.
+            text = match self.lines.next() {
+                Some(text) => text?,
+                None => return Err(CsvError::UnterminatedQuote { line: start }),
+            };
+            self.line += 1;
+            field.push('\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_csv, CsvError, CsvOptions};
+
+    #[test]
+    fn test_read_csv() {
+        let input = "id,name,score\n1,alice,1.5\n\n2,\"bob, \"\"the\"\"\nbuilder\",-3\n";
+        let columns = read_csv(
+            input.as_bytes(),
+            &["u32", "String", "f64"],
+            CsvOptions::default(),
+        )
+        .unwrap();
+        let mut columns = columns.into_iter();
+        assert_eq!(columns.next().unwrap().into_vec::<u32>(), vec![1, 2]);
+        assert_eq!(
+            columns.next().unwrap().into_vec::<String>(),
+            vec!["alice", "bob, \"the\"\nbuilder"]
+        );
+        assert_eq!(columns.next().unwrap().into_vec::<f64>(), vec![1.5, -3.0]);
+    }
+
+    #[test]
+    fn test_cell_errors() {
+        let options = CsvOptions {
+            delimiter: '\t',
+            has_header: false,
+        };
+        let err = read_csv("1\t2\n3\tx\n".as_bytes(), &["u8", "u8"], options)
+            .err()
+            .unwrap();
+        match &err {
+            CsvError::Parse { line, column, .. } => assert_eq!((*line, *column), (2, 1)),
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert!(err
+            .to_string()
+            .starts_with("Line 2, column 1: Can't parse \"x\" as u8"));
+
+        let err = read_csv("1\n".as_bytes(), &["u8", "u8"], options)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Line 1: expected 2 fields, found 1");
+
+        let err = read_csv("\"1\n".as_bytes(), &["u8"], options)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Line 1: unterminated quoted field");
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        let err = read_csv("".as_bytes(), &["f16"], CsvOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "f16 is not registered for parsing");
+    }
+}
@@ -0,0 +1,191 @@
+//! Deserialization of vectors whose element types are looked up in a
+//! [`TypeRegistry`], enabled by the ``serde`` feature.
+//!
+//! This reads the format written by ``AnyVec``'s ``Serialize`` impl.
+
+use std::any::Any;
+use std::fmt;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::Deserializer;
+
+use crate::registry::{Entry, TypeRegistry};
+use crate::vtable::VTable;
+use crate::AnyVec;
+
+pub(crate) type DeserializeFn =
+    fn(&mut dyn erased_serde::Deserializer) -> Result<AnyVec, erased_serde::Error>;
+
+fn deserialize_vec<T: Any + DeserializeOwned>(
+    deserializer: &mut dyn erased_serde::Deserializer,
+) -> Result<AnyVec, erased_serde::Error> {
+    erased_serde::deserialize::<Vec<T>>(deserializer).map(AnyVec::from_vec)
+}
+
+impl TypeRegistry {
+    /// Register ``T`` so that vectors of it can be deserialized.
+    pub fn register_deserialize<T: Any + DeserializeOwned>(&mut self) -> &mut TypeRegistry {
+        self.insert(Entry {
+            vtable: VTable::new::<T>(),
+            deserialize: Some(deserialize_vec::<T>),
+        })
+    }
+}
+
+impl AnyVec {
+    /// Deserialize a vector saved by ``AnyVec``'s ``Serialize`` impl, looking
+    /// up its element type in ``registry``.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        registry: &TypeRegistry,
+    ) -> Result<AnyVec, D::Error> {
+        AnyVecSeed(registry).deserialize(deserializer)
+    }
+}
+
+/// A ``DeserializeSeed`` for ``AnyVec``s, for deserializing them as part of
+/// larger structures.
+pub struct AnyVecSeed<'a>(pub &'a TypeRegistry);
+
+const FIELDS: &[&str] = &["type", "values"];
+
+impl<'de> DeserializeSeed<'de> for AnyVecSeed<'_> {
+    type Value = AnyVec;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<AnyVec, D::Error> {
+        deserializer.deserialize_struct("AnyVec", FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for AnyVecSeed<'_> {
+    type Value = AnyVec;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an AnyVec")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<AnyVec, S::Error> {
+        let type_name: String = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let seed = ValuesSeed(self.lookup(&type_name)?);
+        seq.next_element_seed(seed)?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<AnyVec, M::Error> {
+        // We need the type before we can read the values, which is the order
+        // we write them in.
+        match map.next_key::<String>()?.as_deref() {
+            Some("type") => {}
+            Some(other) => return Err(de::Error::unknown_field(other, FIELDS)),
+            None => return Err(de::Error::missing_field("type")),
+        }
+        let type_name: String = map.next_value()?;
+        let seed = ValuesSeed(self.lookup(&type_name)?);
+
+        match map.next_key::<String>()?.as_deref() {
+            Some("values") => map.next_value_seed(seed),
+            Some(other) => Err(de::Error::unknown_field(other, FIELDS)),
+            None => Err(de::Error::missing_field("values")),
+        }
+    }
+}
+
+impl<'a> AnyVecSeed<'a> {
+    fn lookup<E: de::Error>(&self, type_name: &str) -> Result<&'a Entry, E> {
+        self.0
+            .get(type_name)
+            .filter(|entry| entry.deserialize.is_some())
+            .ok_or_else(|| {
+                E::custom(format!(
+                    "{} is not registered for deserialization",
+                    type_name
+                ))
+            })
+    }
+}
+
+struct ValuesSeed<'a>(&'a Entry);
+
+impl<'de> DeserializeSeed<'de> for ValuesSeed<'_> {
+    type Value = AnyVec;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<AnyVec, D::Error> {
+        let deserialize = self.0.deserialize.unwrap();
+        let mut erased = <dyn erased_serde::Deserializer>::erase(deserializer);
+        let mut result = deserialize(&mut erased).map_err(de::Error::custom)?;
+        // Pick up any capabilities the registered vtable has.
+        result.vtable = self.0.vtable;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyVec, TypeRegistry};
+
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn test_round_trip() {
+        let mut registry = TypeRegistry::new();
+        registry
+            .register_deserialize::<f64>()
+            .register_deserialize::<String>();
+
+        let floats = AnyVec::from_vec::<f64>(vec![1.5, -2.0]);
+        let json = serde_json::to_string(&floats).unwrap();
+        let loaded =
+            AnyVec::deserialize(&mut serde_json::Deserializer::from_str(&json), &registry).unwrap();
+        assert!(loaded == floats);
+
+        let strings = AnyVec::from_vec(vec![String::from("x")]);
+        let json = serde_json::to_string(&strings).unwrap();
+        let loaded =
+            AnyVec::deserialize(&mut serde_json::Deserializer::from_str(&json), &registry).unwrap();
+        assert_eq!(loaded.into_vec::<String>(), vec!["x"]);
+    }
+
+    #[test]
+    fn test_custom_type() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let mut registry = TypeRegistry::new();
+        registry.register_deserialize::<Point>();
+
+        let points = AnyVec::from_vec(vec![Point { x: 1, y: 2 }]).with_serialize::<Point>();
+        let json = serde_json::to_string(&points).unwrap();
+        let loaded =
+            AnyVec::deserialize(&mut serde_json::Deserializer::from_str(&json), &registry).unwrap();
+        assert_eq!(loaded.into_vec::<Point>(), vec![Point { x: 1, y: 2 }]);
+    }
+
+    #[test]
+    fn test_register_macro() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Reading(u16);
+        crate::register_anyvec_type!(deserialize Reading as "reading");
+
+        let json = r#"{"type": "reading", "values": [7]}"#;
+        let loaded = TypeRegistry::with_global(|registry| {
+            AnyVec::deserialize(&mut serde_json::Deserializer::from_str(json), registry)
+        })
+        .unwrap();
+        assert_eq!(loaded.into_vec::<Reading>(), vec![Reading(7)]);
+    }
+
+    #[test]
+    fn test_unregistered_type() {
+        let registry = TypeRegistry::new();
+        let json = r#"{"type": "u8", "values": [1]}"#;
+        let err = AnyVec::deserialize(&mut serde_json::Deserializer::from_str(json), &registry)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("u8 is not registered"));
+    }
+}
@@ -0,0 +1,357 @@
+//! A growable double-ended queue, the erased counterpart of ``VecDeque``.
+
+use std::any::Any;
+use std::mem;
+use std::ptr;
+
+use crate::vtable::VTable;
+use crate::{AnyRef, AnySlice, AnySliceMut, AnyValue, AnyVec, Global};
+
+/// A ring buffer of elements whose type is only known at runtime, supporting
+/// pushes and pops at both ends.
+///
+/// Indices are logical: index 0 is always the front element.
+pub struct AnyVecDeque {
+    // Only supplies storage. Its length stays 0, since our elements may wrap
+    // around the end of the buffer; we drop them ourselves.
+    buffer: AnyVec,
+    // Physical index of the front element.
+    head: usize,
+    len: usize,
+}
+
+impl AnyVecDeque {
+    pub fn new<T: Any>() -> AnyVecDeque {
+        AnyVecDeque::from_vtable(VTable::new::<T>())
+    }
+
+    pub fn with_capacity<T: Any>(capacity: usize) -> AnyVecDeque {
+        let mut deque = AnyVecDeque::new::<T>();
+        deque.buffer.reserve(capacity);
+        deque
+    }
+
+    fn from_vtable(vtable: &'static VTable) -> AnyVecDeque {
+        AnyVecDeque {
+            buffer: AnyVec::from_vtable_in(vtable, Global),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity
+    }
+
+    fn vtable(&self) -> &'static VTable {
+        self.buffer.vtable
+    }
+
+    /// The physical index ``offset`` slots after the physical index ``from``.
+    fn wrap_add(&self, from: usize, offset: usize) -> usize {
+        let until_end = self.capacity() - from;
+        if offset >= until_end {
+            offset - until_end
+        } else {
+            from + offset
+        }
+    }
+
+    fn slot(&self, physical: usize) -> *mut u8 {
+        unsafe { self.buffer.data.add(physical * self.vtable().size) }
+    }
+
+    /// The physical ranges holding our elements, front first, as
+    /// ``(start, len)`` pairs. The second is empty unless we wrap around.
+    fn segments(&self) -> ((usize, usize), (usize, usize)) {
+        let first = self.len.min(self.capacity() - self.head);
+        ((self.head, first), (0, self.len - first))
+    }
+
+    /// Move our elements, front first, to the start of a new buffer with room
+    /// for at least ``capacity`` elements.
+    fn relocate(&mut self, capacity: usize) {
+        let mut buffer = AnyVec::from_vtable_in(self.vtable(), Global);
+        buffer.reserve(capacity);
+        let size = self.vtable().size;
+        let ((head, first), (_, second)) = self.segments();
+        unsafe {
+            ptr::copy_nonoverlapping(self.slot(head), buffer.data, first * size);
+            ptr::copy_nonoverlapping(self.slot(0), buffer.data.add(first * size), second * size);
+        }
+        // The old buffer has length 0, so this only frees it.
+        self.buffer = buffer;
+        self.head = 0;
+    }
+
+    fn reserve_one(&mut self) {
+        if self.len == self.capacity() {
+            self.relocate((self.len * 2).max(4));
+        }
+    }
+
+    pub fn push_back<T: Any>(&mut self, value: T) {
+        self.vtable().assert_typecheck::<T>();
+        self.reserve_one();
+        let slot = self.slot(self.wrap_add(self.head, self.len));
+        unsafe { ptr::write(slot as *mut T, value) };
+        self.len += 1;
+    }
+
+    pub fn push_front<T: Any>(&mut self, value: T) {
+        self.vtable().assert_typecheck::<T>();
+        self.reserve_one();
+        self.head = self.wrap_add(self.head, self.capacity() - 1);
+        unsafe { ptr::write(self.slot(self.head) as *mut T, value) };
+        self.len += 1;
+    }
+
+    /// Append an erased value. Panics if its type doesn't match ours.
+    pub fn push_back_value(&mut self, value: AnyValue) {
+        self.vtable().assert_same_type(value.vtable());
+        self.reserve_one();
+        let slot = self.slot(self.wrap_add(self.head, self.len));
+        let (boxed, _) = value.into_raw();
+        (self.vtable().unbox_into)(boxed, slot);
+        self.len += 1;
+    }
+
+    /// Prepend an erased value. Panics if its type doesn't match ours.
+    pub fn push_front_value(&mut self, value: AnyValue) {
+        self.vtable().assert_same_type(value.vtable());
+        self.reserve_one();
+        self.head = self.wrap_add(self.head, self.capacity() - 1);
+        let (boxed, _) = value.into_raw();
+        (self.vtable().unbox_into)(boxed, self.slot(self.head));
+        self.len += 1;
+    }
+
+    /// Unlink the front element, returning the slot that holds it.
+    fn take_front(&mut self) -> Option<*mut u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let slot = self.slot(self.head);
+        self.head = self.wrap_add(self.head, 1);
+        self.len -= 1;
+        Some(slot)
+    }
+
+    /// Unlink the back element, returning the slot that holds it.
+    fn take_back(&mut self) -> Option<*mut u8> {
+        if self.is_empty() {
+            return None;
+        }
+        self.len -= 1;
+        Some(self.slot(self.wrap_add(self.head, self.len)))
+    }
+
+    pub fn pop_front<T: Any>(&mut self) -> Option<T> {
+        self.vtable().assert_typecheck::<T>();
+        self.take_front()
+            .map(|slot| unsafe { ptr::read(slot as *const T) })
+    }
+
+    pub fn pop_back<T: Any>(&mut self) -> Option<T> {
+        self.vtable().assert_typecheck::<T>();
+        self.take_back()
+            .map(|slot| unsafe { ptr::read(slot as *const T) })
+    }
+
+    pub fn pop_front_value(&mut self) -> Option<AnyValue> {
+        let vtable = self.vtable();
+        self.take_front()
+            .map(|slot| unsafe { AnyValue::from_raw((vtable.box_value)(slot), vtable) })
+    }
+
+    pub fn pop_back_value(&mut self) -> Option<AnyValue> {
+        let vtable = self.vtable();
+        self.take_back()
+            .map(|slot| unsafe { AnyValue::from_raw((vtable.box_value)(slot), vtable) })
+    }
+
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T> {
+        self.vtable().assert_typecheck::<T>();
+        self.get_ref(index).and_then(|item| item.downcast_ref())
+    }
+
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'_>> {
+        if index < self.len {
+            let slot = self.slot(self.wrap_add(self.head, index));
+            Some(unsafe { AnyRef::new(slot, self.vtable()) })
+        } else {
+            None
+        }
+    }
+
+    pub fn front_ref(&self) -> Option<AnyRef<'_>> {
+        self.get_ref(0)
+    }
+
+    pub fn back_ref(&self) -> Option<AnyRef<'_>> {
+        self.len.checked_sub(1).and_then(|last| self.get_ref(last))
+    }
+
+    /// Our elements, front first.
+    pub fn iter(&self) -> impl Iterator<Item = AnyRef<'_>> {
+        (0..self.len).map(move |i| self.get_ref(i).unwrap())
+    }
+
+    /// Our elements as two slices, front first. The second is empty unless
+    /// the elements wrap around the end of the buffer.
+    pub fn as_slices(&self) -> (AnySlice<'_>, AnySlice<'_>) {
+        let ((head, first), (start, second)) = self.segments();
+        unsafe {
+            (
+                AnySlice::new(self.slot(head), first, self.vtable()),
+                AnySlice::new(self.slot(start), second, self.vtable()),
+            )
+        }
+    }
+
+    /// Rearrange our elements so they're contiguous, returning them as one
+    /// slice, front first.
+    pub fn make_contiguous(&mut self) -> AnySliceMut<'_> {
+        if self.head + self.len > self.capacity() {
+            self.relocate(self.capacity());
+        }
+        unsafe { AnySliceMut::new(self.slot(self.head), self.len, self.vtable()) }
+    }
+
+    pub fn clear(&mut self) {
+        self.drop_elements();
+        self.head = 0;
+        self.len = 0;
+    }
+
+    fn drop_elements(&mut self) {
+        if self.vtable().needs_drop {
+            let ((head, first), (start, second)) = self.segments();
+            // Forget the elements first, in case a destructor panics.
+            self.len = 0;
+            (self.vtable().drop_slice)(self.slot(head), first);
+            (self.vtable().drop_slice)(self.slot(start), second);
+        }
+    }
+
+    /// Convert into an ``AnyVec`` holding our elements, front first.
+    pub fn into_anyvec(mut self) -> AnyVec {
+        self.make_contiguous();
+        let size = self.vtable().size;
+        unsafe { ptr::copy(self.slot(self.head), self.buffer.data, self.len * size) };
+        let empty = AnyVec::from_vtable_in(self.vtable(), Global);
+        let mut result = mem::replace(&mut self.buffer, empty);
+        result.length = mem::take(&mut self.len);
+        result
+    }
+}
+
+impl From<AnyVec> for AnyVecDeque {
+    fn from(mut vec: AnyVec) -> AnyVecDeque {
+        let len = mem::take(&mut vec.length);
+        AnyVecDeque {
+            buffer: vec,
+            head: 0,
+            len,
+        }
+    }
+}
+
+impl Drop for AnyVecDeque {
+    fn drop(&mut self) {
+        self.drop_elements();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnyVecDeque;
+    use crate::{AnyValue, AnyVec};
+
+    use std::rc::Rc;
+
+    fn contents(deque: &AnyVecDeque) -> Vec<u32> {
+        deque.iter().map(|x| *x.downcast_ref().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_both_ends() {
+        let mut deque = AnyVecDeque::new::<u32>();
+        deque.push_back(2u32);
+        deque.push_back(3u32);
+        deque.push_front(1u32);
+        deque.push_front(0u32);
+        deque.push_back_value(AnyValue::new(4u32));
+        assert_eq!(contents(&deque), vec![0, 1, 2, 3, 4]);
+        assert_eq!(deque.get::<u32>(1), Some(&1));
+        assert_eq!(deque.back_ref().unwrap().downcast_ref::<u32>(), Some(&4));
+
+        assert_eq!(deque.pop_front::<u32>(), Some(0));
+        assert_eq!(deque.pop_back::<u32>(), Some(4));
+        let value = deque.pop_front_value().unwrap();
+        assert_eq!(value.downcast::<u32>().ok(), Some(1));
+        assert_eq!(contents(&deque), vec![2, 3]);
+
+        deque.clear();
+        assert_eq!(deque.pop_back::<u32>(), None);
+    }
+
+    #[test]
+    fn test_fifo_wraps_around() {
+        let mut deque = AnyVecDeque::with_capacity::<u32>(4);
+        let capacity = deque.capacity();
+        for i in 0..100u32 {
+            deque.push_back(i);
+            if i >= 2 {
+                assert_eq!(deque.pop_front::<u32>(), Some(i - 2));
+            }
+        }
+        assert_eq!(deque.capacity(), capacity);
+        assert_eq!(contents(&deque), vec![98, 99]);
+    }
+
+    #[test]
+    fn test_make_contiguous() {
+        let mut deque = AnyVecDeque::with_capacity::<u32>(4);
+        for i in 0..4u32 {
+            deque.push_back(i);
+        }
+        deque.pop_front::<u32>();
+        deque.pop_front::<u32>();
+        deque.push_back(4u32);
+        deque.push_back(5u32);
+        let (front, back) = deque.as_slices();
+        assert_eq!(front.len() + back.len(), 4);
+        assert!(!back.is_empty());
+
+        let mut slice = deque.make_contiguous();
+        assert_eq!(slice.downcast_mut::<u32>().unwrap(), &[2, 3, 4, 5][..]);
+        assert_eq!(deque.as_slices().1.len(), 0);
+        assert_eq!(deque.into_anyvec().into_vec::<u32>(), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_drops_elements() {
+        let chan = Rc::new(());
+        let mut deque = AnyVecDeque::from(AnyVec::from_vec(vec![chan.clone(); 3]));
+        deque.push_front(chan.clone());
+        drop(deque.pop_back::<Rc<()>>());
+        assert_eq!(Rc::strong_count(&chan), 4);
+        drop(deque);
+        assert_eq!(Rc::strong_count(&chan), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Static type (u32) does not match runtime type (u64)")]
+    fn test_push_typecheck() {
+        AnyVecDeque::new::<u32>().push_front(1u64);
+    }
+}
@@ -0,0 +1,81 @@
+//! Deterministic execution mode.
+//!
+//! The approximate kernels, like
+//! [`estimate_group_counts`](crate::AnyVec::estimate_group_counts), draw
+//! random samples, so their estimates vary between runs. When deterministic
+//! mode is enabled, they sample from a fixed seed instead, so that results can
+//! be compared byte-for-byte in regression tests.
+//!
+//! Exact hash-based kernels (unique, value counts, dictionary encoding,
+//! joins) don't depend on the mode: their output is always ordered by
+//! position in the input.
+//!
+//! The mode can be set crate-wide with [`set_deterministic`], or overridden
+//! for the duration of a single call with [`deterministic`].
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static OVERRIDE: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Enable or disable deterministic mode for every thread.
+pub fn set_deterministic(enabled: bool) {
+    DETERMINISTIC.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether sampling kernels called from this thread should use a fixed seed.
+pub fn is_deterministic() -> bool {
+    OVERRIDE
+        .with(|o| o.get())
+        .unwrap_or_else(|| DETERMINISTIC.load(Ordering::SeqCst))
+}
+
+/// Run ``f`` with deterministic mode set to ``enabled`` on the current thread,
+/// regardless of the crate-wide setting.
+pub fn deterministic<F, R>(enabled: bool, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    // Restore the previous override even if ``f`` panics.
+    struct Restore(Option<bool>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            OVERRIDE.with(|o| o.set(self.0));
+        }
+    }
+
+    let _restore = Restore(OVERRIDE.with(|o| o.replace(Some(enabled))));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deterministic, is_deterministic};
+
+    #[test]
+    fn test_scoped_override() {
+        let outer = is_deterministic();
+
+        deterministic(true, || {
+            assert!(is_deterministic());
+            deterministic(false, || assert!(!is_deterministic()));
+            assert!(is_deterministic());
+        });
+
+        assert_eq!(is_deterministic(), outer);
+    }
+
+    #[test]
+    fn test_scoped_override_restored_on_panic() {
+        let outer = is_deterministic();
+
+        let result = std::panic::catch_unwind(|| deterministic(!outer, || panic!("boom")));
+        assert!(result.is_err());
+        assert_eq!(is_deterministic(), outer);
+    }
+}
@@ -0,0 +1,130 @@
+//! Grouping equal elements: dictionary encoding, frequency counts and
+//! deduplication.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+
+use crate::{Allocator, AnyVec};
+
+impl<A: Allocator + Clone> AnyVec<A> {
+    /// Split the vector into its distinct values, in order of first
+    /// appearance, and the code of each element: the index of its value in
+    /// the dictionary. ``dictionary.take(&codes)`` rebuilds the vector.
+    ///
+    /// Panics if the element type doesn't support hashing and cloning, or if
+    /// there are more than ``u32::MAX`` distinct values.
+    pub fn dictionary_encode(&self) -> (AnyVec<A>, Vec<u32>) {
+        let (firsts, codes) = self.group();
+        (self.take(&firsts), codes)
+    }
+
+    /// The distinct values, in order of first appearance, and how many times
+    /// each appears.
+    ///
+    /// Panics if the element type doesn't support hashing and cloning.
+    pub fn value_counts(&self) -> (AnyVec<A>, Vec<usize>) {
+        let (firsts, codes) = self.group();
+        let mut counts = vec![0; firsts.len()];
+        for code in codes {
+            counts[code as usize] += 1;
+        }
+        (self.take(&firsts), counts)
+    }
+
+    /// A copy with only the first of each set of equal elements, in their
+    /// original order. Unlike deduplicating a sorted vector, this doesn't
+    /// need equal elements to be adjacent.
+    ///
+    /// Panics if the element type doesn't support hashing and cloning.
+    pub fn unique(&self) -> AnyVec<A> {
+        self.take(&self.group().0)
+    }
+
+    /// The position of each distinct value's first appearance, and the code
+    /// of each element: the index of its value's first appearance in the
+    /// former.
+    fn group(&self) -> (Vec<usize>, Vec<u32>) {
+        let (eq, hash) = self.vtable.require_hash();
+        self.vtable.require_clone();
+        let mut firsts: Vec<usize> = Vec::new();
+        let mut by_hash: HashMap<u64, Vec<u32>> = HashMap::new();
+        let mut codes = Vec::with_capacity(self.length);
+        for i in 0..self.length {
+            let element = self.element_ptr(i);
+            let mut hasher = DefaultHasher::new();
+            hash(element, &mut hasher);
+            let candidates = by_hash.entry(hasher.finish()).or_default();
+            let found = candidates
+                .iter()
+                .copied()
+                .find(|&code| eq(self.element_ptr(firsts[code as usize]), element));
+            let code = match found {
+                Some(code) => code,
+                None => {
+                    if firsts.len() > u32::MAX as usize {
+                        panic!("Too many distinct values for u32 codes");
+                    }
+                    let code = firsts.len() as u32;
+                    firsts.push(i);
+                    candidates.push(code);
+                    code
+                }
+            };
+            codes.push(code);
+        }
+        (firsts, codes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    #[test]
+    fn test_dictionary_encode() {
+        let symbols: Vec<String> = ["AAPL", "MSFT", "AAPL", "GOOG", "MSFT"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let column = AnyVec::from_vec(symbols.clone())
+            .with_hash::<String>()
+            .with_clone::<String>();
+        let (dictionary, codes) = column.dictionary_encode();
+        assert_eq!(codes, vec![0, 1, 0, 2, 1]);
+        assert_eq!(
+            dictionary.get::<String, _>(..).unwrap(),
+            &["AAPL", "MSFT", "GOOG"]
+        );
+        let indices: Vec<usize> = codes.iter().map(|&code| code as usize).collect();
+        assert_eq!(dictionary.take(&indices).into_vec::<String>(), symbols);
+    }
+
+    #[test]
+    fn test_dictionary_encode_empty() {
+        let (dictionary, codes) = AnyVec::new::<u32>().dictionary_encode();
+        assert!(dictionary.is_empty());
+        assert!(codes.is_empty());
+    }
+
+    #[test]
+    fn test_value_counts() {
+        let column = AnyVec::from_vec::<u8>(vec![3, 1, 3, 3, 2, 1]);
+        let (values, counts) = column.value_counts();
+        assert_eq!(values.into_vec::<u8>(), vec![3, 1, 2]);
+        assert_eq!(counts, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_unique_keeps_first_appearances() {
+        let column = AnyVec::from_vec::<i64>(vec![5, -1, 5, 0, -1, 7]);
+        assert_eq!(column.unique().into_vec::<i64>(), vec![5, -1, 0, 7]);
+        assert_eq!(column.len(), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support hashing")]
+    fn test_dictionary_encode_requires_hash() {
+        AnyVec::from_vec::<f64>(vec![1.0]).dictionary_encode();
+    }
+}
@@ -0,0 +1,264 @@
+//! Budgeted-accuracy approximate kernels.
+//!
+//! These run on a uniform sample of a configurable size instead of the whole
+//! column, trading exactness for bounded cost. Every result comes with a 95%
+//! confidence interval. Sampling is seeded from the deterministic mode, so
+//! estimates are reproducible when it's enabled.
+
+use std::collections::HashMap;
+
+use crate::sample::{sample_indices, Rng};
+use crate::vtable::HashKey;
+use crate::{Allocator, AnyVec};
+
+// Two-sided 95% critical value of the standard normal distribution.
+const Z_95: f64 = 1.96;
+
+/// An approximate value together with a 95% confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Estimate {
+    fn exact(value: f64) -> Estimate {
+        Estimate {
+            value,
+            lower: value,
+            upper: value,
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.lower == self.upper
+    }
+}
+
+// Finite population correction for a sample of ``s`` out of ``n``.
+fn fpc(n: usize, s: usize) -> f64 {
+    if n <= 1 {
+        0.0
+    } else {
+        (n - s) as f64 / (n - 1) as f64
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Estimate the number of rows in each group of equal elements, using a
+    /// sample of at most ``sample_size`` rows.
+    ///
+    /// Returns, for each group seen in the sample, the index of a
+    /// representative element alongside the estimated group size. Groups are
+    /// ordered by their representative's position. Groups too rare to appear
+    /// in the sample aren't reported. Panics if the element type doesn't
+    /// support hashing.
+    pub fn estimate_group_counts(&self, sample_size: usize) -> Vec<(usize, Estimate)> {
+        let fns = self.vtable.require_hash();
+        let n = self.len();
+        let sample = sample_indices(&mut Rng::new(), n, sample_size);
+        let s = sample.len();
+
+        // Sample indices are sorted, so the first hit of a group is its
+        // earliest sampled occurrence.
+        let mut slots: HashMap<HashKey, usize> = HashMap::new();
+        let mut groups: Vec<(usize, usize)> = Vec::new();
+        for &i in &sample {
+            let slot = *slots
+                .entry(HashKey::new(self.element_ptr(i), fns))
+                .or_insert_with(|| {
+                    groups.push((i, 0));
+                    groups.len() - 1
+                });
+            groups[slot].1 += 1;
+        }
+
+        let correction = fpc(n, s);
+        groups
+            .into_iter()
+            .map(|(representative, hits)| {
+                if s == n {
+                    return (representative, Estimate::exact(hits as f64));
+                }
+                let p = hits as f64 / s as f64;
+                let value = p * n as f64;
+                let error = Z_95 * n as f64 * (p * (1.0 - p) / s as f64 * correction).sqrt();
+                // We saw ``hits`` members of the group, and ``s - hits`` rows
+                // that definitely aren't members.
+                let estimate = Estimate {
+                    value,
+                    lower: (value - error).max(hits as f64),
+                    upper: (value + error).min((n - (s - hits)) as f64),
+                };
+                (representative, estimate)
+            })
+            .collect()
+    }
+
+    /// Estimate the number of pairs ``(i, j)`` with ``self[i] == other[j]``,
+    /// using samples of at most ``sample_size`` rows from each side.
+    ///
+    /// The confidence interval accounts for the variance from sampling
+    /// ``self``; it's exact with respect to ``other`` only if ``sample_size >=
+    /// other.len()``. Panics if ``sample_size`` is zero, or if the element
+    /// types differ or don't support hashing.
+    pub fn estimate_join_cardinality<B: Allocator>(
+        &self,
+        other: &AnyVec<B>,
+        sample_size: usize,
+    ) -> Estimate {
+        if sample_size == 0 {
+            panic!("Sample size must be non-zero");
+        }
+        self.vtable.assert_same_type(other.vtable);
+        let fns = self.vtable.require_hash();
+        let (n, m) = (self.len(), other.len());
+        if n == 0 || m == 0 {
+            return Estimate::exact(0.0);
+        }
+
+        let mut rng = Rng::new();
+        let left = sample_indices(&mut rng, n, sample_size);
+        let right = sample_indices(&mut rng, m, sample_size);
+        let (s1, s2) = (left.len(), right.len());
+
+        let mut counts: HashMap<HashKey, usize> = HashMap::new();
+        for &j in &right {
+            *counts
+                .entry(HashKey::new(other.element_ptr(j), fns))
+                .or_insert(0) += 1;
+        }
+
+        // Matches of each sampled left row, scaled up to the whole of ``other``.
+        let scale = m as f64 / s2 as f64;
+        let mut observed = 0;
+        let matches: Vec<f64> = left
+            .iter()
+            .map(|&i| {
+                let hits = counts
+                    .get(&HashKey::new(self.element_ptr(i), fns))
+                    .copied()
+                    .unwrap_or(0);
+                observed += hits;
+                hits as f64 * scale
+            })
+            .collect();
+
+        let mean = matches.iter().sum::<f64>() / s1 as f64;
+        let value = mean * n as f64;
+        if s1 == n && s2 == m {
+            return Estimate::exact(value);
+        }
+
+        // In floating point, since the product can overflow usize.
+        let bound = n as f64 * m as f64;
+        let error = if s1 == n {
+            0.0
+        } else if s1 < 2 {
+            bound
+        } else {
+            let variance =
+                matches.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (s1 - 1) as f64;
+            Z_95 * n as f64 * (variance / s1 as f64 * fpc(n, s1)).sqrt()
+        };
+
+        Estimate {
+            value,
+            lower: (value - error).max(observed as f64),
+            upper: (value + error).min(bound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Estimate;
+    use crate::determinism::deterministic;
+    use crate::AnyVec;
+
+    fn column(values: Vec<u64>) -> AnyVec {
+        AnyVec::from_vec(values).with_hash::<u64>()
+    }
+
+    #[test]
+    fn test_group_counts_exact_when_sample_covers_column() {
+        let dynamic = column(vec![5, 3, 5, 5, 3, 7]);
+
+        let result = dynamic.estimate_group_counts(100);
+        let expected = vec![
+            (0, Estimate::exact(3.0)),
+            (1, Estimate::exact(2.0)),
+            (5, Estimate::exact(1.0)),
+        ];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_group_counts_sampled() {
+        // 75% zeros, 25% ones.
+        let dynamic = column((0..100_000).map(|i| (i % 4 == 0) as u64).collect());
+
+        let result = deterministic(true, || dynamic.estimate_group_counts(2000));
+        assert_eq!(result.len(), 2);
+
+        let total: f64 = result.iter().map(|(_, e)| e.value).sum();
+        assert!((total - 100_000.0).abs() < 1e-6);
+
+        for (representative, estimate) in result {
+            let truth = if representative % 4 == 0 {
+                25_000.0
+            } else {
+                75_000.0
+            };
+            assert!(!estimate.is_exact());
+            assert!(estimate.lower <= truth && truth <= estimate.upper);
+        }
+    }
+
+    #[test]
+    fn test_group_counts_reproducible() {
+        let dynamic = column((0..10_000).map(|i| i % 37).collect());
+
+        let run = || dynamic.estimate_group_counts(50);
+        assert_eq!(deterministic(true, run), deterministic(true, run));
+    }
+
+    #[test]
+    fn test_join_cardinality() {
+        let left = column((0..10_000).map(|i| i % 10).collect());
+        let right = column((0..500).map(|i| i % 5).collect());
+        // Keys 0..5 match: 1000 left rows each, 100 right rows each.
+        let truth = 5.0 * 1000.0 * 100.0;
+
+        let exact = left.estimate_join_cardinality(&right, usize::MAX);
+        assert_eq!(exact, Estimate::exact(truth));
+
+        let estimate = deterministic(true, || left.estimate_join_cardinality(&right, 1000));
+        assert!(estimate.lower <= estimate.value && estimate.value <= estimate.upper);
+        assert!((estimate.value - truth).abs() / truth < 0.25);
+    }
+
+    #[test]
+    fn test_join_cardinality_empty() {
+        let left = column(vec![]);
+        let right = column(vec![1, 2, 3]);
+        assert_eq!(
+            left.estimate_join_cardinality(&right, 10),
+            Estimate::exact(0.0)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Sample size must be non-zero")]
+    fn test_join_cardinality_empty_sample() {
+        let dynamic = column(vec![1, 2, 3]);
+        dynamic.estimate_join_cardinality(&dynamic, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_group_counts_requires_hash() {
+        AnyVec::from_vec::<f64>(vec![1.0, 2.0, 3.0]).estimate_group_counts(10);
+    }
+}
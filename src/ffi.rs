@@ -0,0 +1,262 @@
+//! A C interface for building vectors, enabled by the ``ffi`` feature.
+//!
+//! C and C++ hosts handle vectors through opaque ``AnyVec *`` handles: they
+//! create them with ``anyvec_new_<type>``, fill them, and either free them
+//! with ``anyvec_free`` or hand them to Rust, which takes ownership with
+//! [`AnyVec::from_ffi_handle`]. Element types are the primitive numbers,
+//! with functions named after their Rust types, e.g. ``anyvec_push_f64``.
+//!
+//! None of these functions unwind into C. Misuse, like pushing a value of
+//! the wrong type, and failures, like a capacity overflow while growing, are
+//! reported through their return values.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::AnyVec;
+
+impl AnyVec {
+    /// Move the vector behind an opaque handle for C code, which must free it
+    /// with ``anyvec_free`` or give it back with [`AnyVec::from_ffi_handle`].
+    pub fn into_ffi_handle(self) -> *mut AnyVec {
+        Box::into_raw(Box::new(self))
+    }
+
+    /// Take ownership of a vector from C code.
+    ///
+    /// # Safety
+    ///
+    /// ``handle`` must have come from ``anyvec_new_*`` or
+    /// [`AnyVec::into_ffi_handle`], and not have been freed or taken back
+    /// already. C code must not use it afterwards.
+    pub unsafe fn from_ffi_handle(handle: *mut AnyVec) -> AnyVec {
+        *Box::from_raw(handle)
+    }
+}
+
+// Run ``f``, returning ``failed`` instead if it panics, since unwinding out
+// of an ``extern "C"`` function aborts. The vector may be left partly
+// updated, but never invalid.
+fn catch<R>(failed: R, f: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(failed)
+}
+
+macro_rules! ffi_functions {
+    ($($t:ident => $new:ident, $push:ident, $get:ident, $data:ident;)*) => {
+        $(
+            /// Create an empty vector, to be freed with ``anyvec_free``.
+            /// Returns null on failure.
+            #[no_mangle]
+            pub extern "C" fn $new() -> *mut AnyVec {
+                catch(ptr::null_mut(), || AnyVec::new::<$t>().into_ffi_handle())
+            }
+
+            /// Append ``value``. Returns false, leaving the vector unchanged,
+            /// if ``vec`` is null or holds another type, or if growing fails.
+            ///
+            /// # Safety
+            ///
+            /// ``vec`` must be null or a live handle.
+            #[no_mangle]
+            pub unsafe extern "C" fn $push(vec: *mut AnyVec, value: $t) -> bool {
+                catch(false, || match vec.as_mut() {
+                    Some(vec) if vec.is::<$t>() => {
+                        vec.push(value);
+                        true
+                    }
+                    _ => false,
+                })
+            }
+
+            /// Write element ``index`` to ``out``. Returns false, writing
+            /// nothing, if ``vec`` is null, holds another type, or is too
+            /// short.
+            ///
+            /// # Safety
+            ///
+            /// ``vec`` must be null or a live handle, and ``out`` must be
+            /// valid for writes.
+            #[no_mangle]
+            pub unsafe extern "C" fn $get(vec: *const AnyVec, index: usize, out: *mut $t) -> bool {
+                let value = vec
+                    .as_ref()
+                    .and_then(|vec| vec.as_any_slice().downcast::<$t>())
+                    .and_then(|values| values.get(index));
+                match value {
+                    Some(&value) => {
+                        out.write(value);
+                        true
+                    }
+                    None => false,
+                }
+            }
+
+            /// The vector's elements, valid until it's next modified or
+            /// freed, or null if ``vec`` is null or holds another type.
+            ///
+            /// # Safety
+            ///
+            /// ``vec`` must be null or a live handle.
+            #[no_mangle]
+            pub unsafe extern "C" fn $data(vec: *const AnyVec) -> *const $t {
+                vec.as_ref()
+                    .and_then(|vec| vec.as_any_slice().downcast::<$t>())
+                    .map_or(ptr::null(), <[$t]>::as_ptr)
+            }
+        )*
+    };
+}
+
+ffi_functions! {
+    i8 => anyvec_new_i8, anyvec_push_i8, anyvec_get_i8, anyvec_data_i8;
+    i16 => anyvec_new_i16, anyvec_push_i16, anyvec_get_i16, anyvec_data_i16;
+    i32 => anyvec_new_i32, anyvec_push_i32, anyvec_get_i32, anyvec_data_i32;
+    i64 => anyvec_new_i64, anyvec_push_i64, anyvec_get_i64, anyvec_data_i64;
+    u8 => anyvec_new_u8, anyvec_push_u8, anyvec_get_u8, anyvec_data_u8;
+    u16 => anyvec_new_u16, anyvec_push_u16, anyvec_get_u16, anyvec_data_u16;
+    u32 => anyvec_new_u32, anyvec_push_u32, anyvec_get_u32, anyvec_data_u32;
+    u64 => anyvec_new_u64, anyvec_push_u64, anyvec_get_u64, anyvec_data_u64;
+    f32 => anyvec_new_f32, anyvec_push_f32, anyvec_get_f32, anyvec_data_f32;
+    f64 => anyvec_new_f64, anyvec_push_f64, anyvec_get_f64, anyvec_data_f64;
+}
+
+/// The number of elements, or 0 if ``vec`` is null.
+///
+/// # Safety
+///
+/// ``vec`` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn anyvec_len(vec: *const AnyVec) -> usize {
+    vec.as_ref().map_or(0, AnyVec::len)
+}
+
+/// The size of an element in bytes, or 0 if ``vec`` is null.
+///
+/// # Safety
+///
+/// ``vec`` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn anyvec_element_size(vec: *const AnyVec) -> usize {
+    vec.as_ref().map_or(0, AnyVec::element_size)
+}
+
+/// The vector's buffer as untyped bytes, valid until it's next modified or
+/// freed, or null if ``vec`` is null.
+///
+/// # Safety
+///
+/// ``vec`` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn anyvec_data_ptr(vec: *const AnyVec) -> *const u8 {
+    vec.as_ref()
+        .map_or(ptr::null(), |vec| vec.as_any_slice().data())
+}
+
+/// Reserve space for at least ``additional`` more elements. Returns false,
+/// leaving the vector unchanged, if ``vec`` is null or the new capacity
+/// overflows.
+///
+/// # Safety
+///
+/// ``vec`` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn anyvec_reserve(vec: *mut AnyVec, additional: usize) -> bool {
+    catch(false, || match vec.as_mut() {
+        Some(vec) => {
+            vec.reserve(additional);
+            true
+        }
+        None => false,
+    })
+}
+
+/// Drop every element, keeping the buffer. Does nothing if ``vec`` is null.
+///
+/// # Safety
+///
+/// ``vec`` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn anyvec_clear(vec: *mut AnyVec) {
+    // Our element types don't panic when dropped, but the guard keeps that
+    // from being load-bearing.
+    catch((), || {
+        if let Some(vec) = vec.as_mut() {
+            vec.clear();
+        }
+    })
+}
+
+/// Free a vector. Does nothing if ``vec`` is null.
+///
+/// # Safety
+///
+/// ``vec`` must be null or a live handle, which mustn't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn anyvec_free(vec: *mut AnyVec) {
+    catch((), || {
+        if !vec.is_null() {
+            drop(AnyVec::from_ffi_handle(vec));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ptr;
+
+    #[test]
+    fn test_build_from_c() {
+        unsafe {
+            let vec = anyvec_new_f64();
+            assert!(anyvec_reserve(vec, 2));
+            assert!(anyvec_push_f64(vec, 1.5));
+            assert!(anyvec_push_f64(vec, 2.5));
+            assert!(!anyvec_push_i32(vec, 3));
+            assert_eq!(anyvec_len(vec), 2);
+            assert_eq!(anyvec_element_size(vec), 8);
+            assert_eq!(*anyvec_data_f64(vec).add(1), 2.5);
+            assert_eq!(anyvec_data_ptr(vec), anyvec_data_f64(vec) as *const u8);
+            assert!(anyvec_data_i32(vec).is_null());
+
+            let mut out = 0.0;
+            assert!(anyvec_get_f64(vec, 0, &mut out));
+            assert_eq!(out, 1.5);
+            assert!(!anyvec_get_f64(vec, 2, &mut out));
+
+            let dynamic = AnyVec::from_ffi_handle(vec);
+            assert_eq!(dynamic.into_vec::<f64>(), vec![1.5, 2.5]);
+        }
+    }
+
+    #[test]
+    fn test_null_handles() {
+        unsafe {
+            assert!(!anyvec_push_u8(ptr::null_mut(), 1));
+            assert_eq!(anyvec_len(ptr::null()), 0);
+            assert!(anyvec_data_ptr(ptr::null()).is_null());
+            assert!(!anyvec_reserve(ptr::null_mut(), 1));
+            anyvec_clear(ptr::null_mut());
+            anyvec_free(ptr::null_mut());
+
+            let vec = AnyVec::from_vec::<u32>(vec![1, 2]).into_ffi_handle();
+            anyvec_clear(vec);
+            assert_eq!(anyvec_len(vec), 0);
+            anyvec_free(vec);
+        }
+    }
+
+    #[test]
+    fn test_failures_dont_unwind() {
+        unsafe {
+            let vec = anyvec_new_u64();
+            assert!(anyvec_push_u64(vec, 1));
+            // Overflows the capacity, which panics inside Rust.
+            assert!(!anyvec_reserve(vec, usize::MAX));
+            assert_eq!(anyvec_len(vec), 1);
+            assert!(anyvec_push_u64(vec, 2));
+            anyvec_free(vec);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Immutable vectors that are cheap to clone and share between threads.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::{Allocator, AnyVec, Global, SendAnyVec};
+
+/// A read-only ``AnyVec``, produced by [`AnyVec::freeze`].
+///
+/// Cloning bumps a reference count rather than copying elements, so many
+/// workers can hold the same reference data.
+pub struct AnyFrozenVec<A: Allocator = Global>(Arc<SendAnyVec<A>>);
+
+impl<A: Allocator> AnyVec<A> {
+    /// Make our elements immutable and shareable.
+    ///
+    /// Panics if the element type doesn't have the ``SEND`` and ``SYNC``
+    /// capabilities.
+    pub fn freeze(self) -> AnyFrozenVec<A> {
+        AnyFrozenVec(Arc::new(SendAnyVec::from_anyvec(self)))
+    }
+}
+
+impl<A: Allocator> AnyFrozenVec<A> {
+    /// Get the vector back if we're its only handle.
+    pub fn try_unwrap(self) -> Result<AnyVec<A>, AnyFrozenVec<A>> {
+        Arc::try_unwrap(self.0)
+            .map(SendAnyVec::into_inner)
+            .map_err(AnyFrozenVec)
+    }
+
+    /// The number of handles sharing our elements.
+    pub fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+
+    pub fn ptr_eq(&self, other: &AnyFrozenVec<A>) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<A: Allocator> Clone for AnyFrozenVec<A> {
+    fn clone(&self) -> AnyFrozenVec<A> {
+        AnyFrozenVec(Arc::clone(&self.0))
+    }
+}
+
+impl<A: Allocator> Deref for AnyFrozenVec<A> {
+    type Target = AnyVec<A>;
+
+    fn deref(&self) -> &AnyVec<A> {
+        &self.0
+    }
+}
+
+impl<A: Allocator> fmt::Debug for AnyFrozenVec<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_any_slice().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    use std::cell::Cell;
+    use std::thread;
+
+    #[test]
+    fn test_clone_shares() {
+        let frozen = AnyVec::from_vec::<u32>(vec![1, 2, 3]).freeze();
+        let copy = frozen.clone();
+        assert!(frozen.ptr_eq(&copy));
+        assert_eq!(frozen.handle_count(), 2);
+        assert_eq!(copy.get::<u32, _>(..), Some(&[1, 2, 3][..]));
+
+        let frozen = frozen.try_unwrap().err().unwrap();
+        drop(copy);
+        assert_eq!(
+            frozen.try_unwrap().ok().unwrap().into_vec::<u32>(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_share_between_threads() {
+        let frozen = AnyVec::from_vec(vec![String::from("x"); 4]).freeze();
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let frozen = frozen.clone();
+                thread::spawn(move || frozen.get::<String, _>(i).unwrap().len())
+            })
+            .collect();
+        let total: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(total, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing capabilities: SYNC")]
+    fn test_requires_sync() {
+        AnyVec::from_vec(vec![Cell::new(1)])
+            .with_send::<Cell<i32>>()
+            .freeze();
+    }
+}
@@ -0,0 +1,168 @@
+//! Writing runs of elements into uninitialized memory without leaking them if
+//! a clone panics partway.
+
+use std::mem;
+
+use crate::vtable::VTable;
+
+/// Tracks the elements written so far to the start of an uninitialized run.
+/// If it's dropped before [`InitGuard::finish`], e.g. while unwinding from a
+/// panicking clone, it drops them, so they neither leak nor get counted by a
+/// vector that would drop them again.
+pub(crate) struct InitGuard {
+    dest: *mut u8,
+    size: usize,
+    drop_slice: fn(*mut u8, usize),
+    written: usize,
+}
+
+impl InitGuard {
+    pub fn new(dest: *mut u8, size: usize, drop_slice: fn(*mut u8, usize)) -> InitGuard {
+        InitGuard {
+            dest,
+            size,
+            drop_slice,
+            written: 0,
+        }
+    }
+
+    pub fn for_vtable(dest: *mut u8, vtable: &VTable) -> InitGuard {
+        InitGuard::new(dest, vtable.size, vtable.drop_slice)
+    }
+
+    /// Where the next element goes.
+    pub fn next(&self) -> *mut u8 {
+        unsafe { self.dest.add(self.written * self.size) }
+    }
+
+    /// Record that one more element has been written at [`InitGuard::next`].
+    pub fn wrote_one(&mut self) {
+        self.written += 1;
+    }
+
+    /// Keep the elements written, returning how many there are. The caller
+    /// now owns them.
+    pub fn finish(self) -> usize {
+        let written = self.written;
+        mem::forget(self);
+        written
+    }
+}
+
+impl Drop for InitGuard {
+    fn drop(&mut self) {
+        (self.drop_slice)(self.dest, self.written);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    // Counts live instances, and panics when cloned once ``fuse`` runs out.
+    struct Fragile {
+        live: Rc<Cell<isize>>,
+        fuse: Rc<Cell<usize>>,
+    }
+
+    impl Fragile {
+        fn vec(count: usize, fuse: usize) -> (AnyVec, Rc<Cell<isize>>) {
+            let live = Rc::new(Cell::new(count as isize));
+            let fuse = Rc::new(Cell::new(fuse));
+            let elements = (0..count)
+                .map(|_| Fragile {
+                    live: live.clone(),
+                    fuse: fuse.clone(),
+                })
+                .collect();
+            (
+                AnyVec::from_vec::<Fragile>(elements).with_clone::<Fragile>(),
+                live,
+            )
+        }
+    }
+
+    impl Clone for Fragile {
+        fn clone(&self) -> Fragile {
+            if self.fuse.get() == 0 {
+                panic!("clone failed");
+            }
+            self.fuse.set(self.fuse.get() - 1);
+            self.live.set(self.live.get() + 1);
+            Fragile {
+                live: self.live.clone(),
+                fuse: self.fuse.clone(),
+            }
+        }
+    }
+
+    impl Drop for Fragile {
+        fn drop(&mut self) {
+            self.live.set(self.live.get() - 1);
+        }
+    }
+
+    #[test]
+    fn test_clone_panic_drops_partial_clones() {
+        let (dynamic, live) = Fragile::vec(4, 2);
+        let result = catch_unwind(AssertUnwindSafe(|| dynamic.clone()));
+        assert!(result.is_err());
+        assert_eq!(live.get(), 4);
+        drop(dynamic);
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn test_extend_from_anyvec_panic() {
+        let (mut dynamic, live) = Fragile::vec(3, 4);
+        let source = dynamic.clone();
+        let result = catch_unwind(AssertUnwindSafe(|| dynamic.extend_from_anyvec(&source)));
+        assert!(result.is_err());
+        assert_eq!((dynamic.len(), live.get()), (3, 6));
+        drop((dynamic, source));
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn test_extend_with_panic() {
+        let (mut dynamic, live) = Fragile::vec(2, 0);
+        let value = Fragile {
+            live: live.clone(),
+            fuse: Rc::new(Cell::new(2)),
+        };
+        live.set(3);
+        let result = catch_unwind(AssertUnwindSafe(|| dynamic.extend_with(5, value)));
+        assert!(result.is_err());
+        assert_eq!((dynamic.len(), live.get()), (2, 2));
+        drop(dynamic);
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn test_resize_panic() {
+        let (mut dynamic, live) = Fragile::vec(2, 3);
+        let value = dynamic.clone();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            dynamic.resize(6, value.get_ref(0).unwrap());
+        }));
+        assert!(result.is_err());
+        assert_eq!((dynamic.len(), live.get()), (2, 4));
+        drop((dynamic, value));
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn test_fill_panic() {
+        let (mut dynamic, live) = Fragile::vec(3, 3);
+        let value = dynamic.take(&[0]);
+        let result = catch_unwind(AssertUnwindSafe(|| dynamic.fill(value.get_ref(0).unwrap())));
+        assert!(result.is_err());
+        assert_eq!((dynamic.len(), live.get()), (3, 4));
+        drop((dynamic, value));
+        assert_eq!(live.get(), 0);
+    }
+}
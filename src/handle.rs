@@ -0,0 +1,98 @@
+//! Typed access to a vector whose type has been checked once.
+
+use std::any::Any;
+use std::marker::PhantomData;
+use std::slice;
+
+use crate::{Allocator, AnyVec};
+
+/// A mutable borrow of an ``AnyVec`` known to hold ``T``s.
+///
+/// The element type is checked when the handle is made, so its operations
+/// work directly on the buffer, without going through the vtable or
+/// checking the type again. Use it in hot loops.
+pub struct TypedHandle<'a, T, A: Allocator> {
+    vec: &'a mut AnyVec<A>,
+    _marker: PhantomData<T>,
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// A handle for working with our elements as ``T``s, or ``None`` if
+    /// that isn't our element type.
+    pub fn typed_handle<T: Any>(&mut self) -> Option<TypedHandle<'_, T, A>> {
+        if self.vtable.is::<T>() {
+            Some(TypedHandle {
+                vec: self,
+                _marker: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T: Any, A: Allocator> TypedHandle<'a, T, A> {
+    pub fn len(&self) -> usize {
+        self.vec.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.length == 0
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.vec.length == self.vec.capacity {
+            self.vec.reserve(1);
+        }
+        unsafe { (self.vec.data as *mut T).add(self.vec.length).write(value) };
+        self.vec.length += 1;
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.vec.data as *const T, self.vec.length) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.vec.data as *mut T, self.vec.length) }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.as_slice().get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.as_mut_slice().get_mut(index)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    #[test]
+    fn test_push_and_read() {
+        let mut dynamic = AnyVec::new::<String>();
+        {
+            let mut handle = dynamic.typed_handle::<String>().unwrap();
+            for i in 0..100 {
+                handle.push(i.to_string());
+            }
+            handle.get_mut(0).unwrap().push('!');
+            assert_eq!(handle.len(), 100);
+            assert_eq!(handle.get(99).unwrap(), "99");
+            assert_eq!(handle.iter().filter(|s| s.len() == 1).count(), 9);
+        }
+        assert_eq!(dynamic.get::<String, _>(0).unwrap(), "0!");
+        assert_eq!(dynamic.len(), 100);
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut dynamic = AnyVec::new::<u32>();
+        assert!(dynamic.typed_handle::<i32>().is_none());
+    }
+}
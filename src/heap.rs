@@ -0,0 +1,209 @@
+//! A priority queue over elements whose type is only known at runtime.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::ptr;
+
+use crate::vtable::{CmpFn, VTable};
+use crate::{AnyRef, AnyValue, AnyVec, Global};
+
+/// A max-heap, the erased counterpart of ``BinaryHeap``, ordered by the
+/// vtable's ``cmp`` entry.
+///
+/// Elements are stored unboxed in one buffer; only [`AnyBinaryHeap::pop`]
+/// boxes, to hand the element back as an ``AnyValue``.
+pub struct AnyBinaryHeap {
+    // Kept in heap order: every element is at least as great as its
+    // children.
+    buffer: AnyVec,
+    cmp: CmpFn,
+}
+
+impl AnyBinaryHeap {
+    pub fn new<T: Any + Ord>() -> AnyBinaryHeap {
+        AnyBinaryHeap::from_anyvec(AnyVec::new::<T>().with_ord::<T>())
+    }
+
+    /// Build a heap holding ``vec``'s elements.
+    ///
+    /// Panics if the element type doesn't support ordering.
+    pub fn from_anyvec(vec: AnyVec) -> AnyBinaryHeap {
+        let cmp = vec.vtable.require_cmp();
+        let mut heap = AnyBinaryHeap { buffer: vec, cmp };
+        for index in (0..heap.len() / 2).rev() {
+            heap.sift_down(index, heap.len());
+        }
+        heap
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    fn vtable(&self) -> &'static VTable {
+        self.buffer.vtable
+    }
+
+    fn slot(&self, index: usize) -> *mut u8 {
+        unsafe { self.buffer.data.add(index * self.vtable().size) }
+    }
+
+    fn less(&self, a: usize, b: usize) -> bool {
+        (self.cmp)(self.slot(a), self.slot(b)) == Ordering::Less
+    }
+
+    // Sifting swaps elements rather than moving a hole around, so the buffer
+    // stays fully initialized if a comparison panics.
+    fn swap(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        unsafe { ptr::swap_nonoverlapping(self.slot(a), self.slot(b), self.vtable().size) };
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if !self.less(parent, index) {
+                break;
+            }
+            self.swap(parent, index);
+            index = parent;
+        }
+    }
+
+    /// Restore heap order below ``index``, considering only the first
+    /// ``end`` elements.
+    fn sift_down(&mut self, mut index: usize, end: usize) {
+        loop {
+            let mut child = 2 * index + 1;
+            if child >= end {
+                break;
+            }
+            if child + 1 < end && self.less(child, child + 1) {
+                child += 1;
+            }
+            if !self.less(index, child) {
+                break;
+            }
+            self.swap(index, child);
+            index = child;
+        }
+    }
+
+    pub fn push<T: Any>(&mut self, value: T) {
+        self.buffer.push(value);
+        self.sift_up(self.len() - 1);
+    }
+
+    /// Add an erased value. Panics if its type doesn't match ours.
+    pub fn push_value(&mut self, value: AnyValue) {
+        self.buffer.push_value(value);
+        self.sift_up(self.len() - 1);
+    }
+
+    /// The greatest element.
+    pub fn peek(&self) -> Option<AnyRef<'_>> {
+        self.buffer.get_ref(0)
+    }
+
+    /// Remove and return the greatest element.
+    pub fn pop(&mut self) -> Option<AnyValue> {
+        let last = self.len().checked_sub(1)?;
+        self.swap(0, last);
+        self.buffer.length = last;
+        let vtable = self.vtable();
+        let value = unsafe { AnyValue::from_raw((vtable.box_value)(self.slot(last)), vtable) };
+        self.sift_down(0, last);
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Our elements, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = AnyRef<'_>> {
+        (0..self.len()).map(move |i| self.buffer.get_ref(i).unwrap())
+    }
+
+    /// Convert into an ``AnyVec`` holding our elements, in no particular
+    /// order.
+    pub fn into_anyvec(self) -> AnyVec {
+        self.buffer
+    }
+
+    /// Convert into an ``AnyVec`` holding our elements in ascending order.
+    pub fn into_sorted_anyvec(mut self) -> AnyVec {
+        for end in (1..self.len()).rev() {
+            self.swap(0, end);
+            self.sift_down(0, end);
+        }
+        self.buffer
+    }
+}
+
+impl From<AnyVec<Global>> for AnyBinaryHeap {
+    fn from(vec: AnyVec) -> AnyBinaryHeap {
+        AnyBinaryHeap::from_anyvec(vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnyBinaryHeap;
+    use crate::{AnyValue, AnyVec};
+
+    use std::cmp::Reverse;
+
+    #[test]
+    fn test_pops_in_order() {
+        let mut heap = AnyBinaryHeap::new::<u32>();
+        for value in [5u32, 1, 8, 3, 9, 2] {
+            heap.push(value);
+        }
+        heap.push_value(AnyValue::new(7u32));
+        assert_eq!(heap.peek().unwrap().downcast_ref::<u32>(), Some(&9));
+
+        let mut popped = Vec::new();
+        while let Some(value) = heap.pop() {
+            popped.push(value.downcast::<u32>().ok().unwrap());
+        }
+        assert_eq!(popped, vec![9, 8, 7, 5, 3, 2, 1]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_min_heap_of_strings() {
+        let vec = AnyVec::from_vec(vec![
+            Reverse(String::from("pear")),
+            Reverse(String::from("apple")),
+            Reverse(String::from("fig")),
+        ])
+        .with_ord::<Reverse<String>>();
+        let mut heap = AnyBinaryHeap::from(vec);
+        let first = heap.pop().unwrap().downcast::<Reverse<String>>().ok();
+        assert_eq!(first, Some(Reverse(String::from("apple"))));
+        assert_eq!(heap.len(), 2);
+    }
+
+    #[test]
+    fn test_into_sorted_anyvec() {
+        let heap = AnyBinaryHeap::from_anyvec(AnyVec::from_vec::<i64>(vec![4, -1, 7, 0, 7]));
+        assert_eq!(heap.iter().count(), 5);
+        assert_eq!(
+            heap.into_sorted_anyvec().into_vec::<i64>(),
+            vec![-1, 0, 4, 7, 7]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support ordering")]
+    fn test_requires_ord() {
+        AnyBinaryHeap::from_anyvec(AnyVec::from_vec(vec![vec![1u8]]));
+    }
+}
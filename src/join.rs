@@ -0,0 +1,98 @@
+//! Equality joins between columns.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+
+use crate::vtable::HashFn;
+use crate::{Allocator, AnyVec};
+
+impl<A: Allocator> AnyVec<A> {
+    /// Every pair ``(i, j)`` where ``self[i] == other[j]``, sorted by ``i``
+    /// and then ``j``.
+    ///
+    /// Builds a hash table over the shorter vector and probes it with the
+    /// longer one. Panics if the element types differ, or if the type doesn't
+    /// support hashing.
+    pub fn join_indices<B: Allocator>(&self, other: &AnyVec<B>) -> Vec<(usize, usize)> {
+        self.vtable.assert_same_type(other.vtable);
+        let (eq, hash) = self.vtable.require_hash();
+        let mut pairs = Vec::new();
+        if other.length <= self.length {
+            let table = build(other, hash);
+            for i in 0..self.length {
+                let left = self.element_ptr(i);
+                if let Some(candidates) = table.get(&hash_of(left, hash)) {
+                    let matches = candidates
+                        .iter()
+                        .filter(|&&j| eq(left, other.element_ptr(j)));
+                    pairs.extend(matches.map(|&j| (i, j)));
+                }
+            }
+        } else {
+            let table = build(self, hash);
+            for j in 0..other.length {
+                let right = other.element_ptr(j);
+                if let Some(candidates) = table.get(&hash_of(right, hash)) {
+                    let matches = candidates
+                        .iter()
+                        .filter(|&&i| eq(self.element_ptr(i), right));
+                    pairs.extend(matches.map(|&i| (i, j)));
+                }
+            }
+            pairs.sort_unstable();
+        }
+        pairs
+    }
+}
+
+fn hash_of(element: *const u8, hash: HashFn) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hash(element, &mut hasher);
+    hasher.finish()
+}
+
+/// The indices of ``vec``'s elements, by hash.
+fn build<A: Allocator>(vec: &AnyVec<A>, hash: HashFn) -> HashMap<u64, Vec<usize>> {
+    let mut table: HashMap<u64, Vec<usize>> = HashMap::new();
+    for i in 0..vec.length {
+        table
+            .entry(hash_of(vec.element_ptr(i), hash))
+            .or_default()
+            .push(i);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    #[test]
+    fn test_join_indices() {
+        let orders = AnyVec::from_vec(vec!["AAPL", "MSFT", "AAPL", "IBM"]).with_hash::<&str>();
+        let quotes = AnyVec::from_vec(vec!["MSFT", "AAPL"]).with_hash::<&str>();
+        let expected = vec![(0, 1), (1, 0), (2, 1)];
+        assert_eq!(orders.join_indices(&quotes), expected);
+
+        // Builds on ``quotes`` this time, with the same pairs swapped.
+        assert_eq!(quotes.join_indices(&orders), vec![(0, 1), (1, 0), (1, 2)]);
+    }
+
+    #[test]
+    fn test_join_indices_many_to_many() {
+        let left = AnyVec::from_vec::<u8>(vec![1, 1, 2]);
+        let right = AnyVec::from_vec::<u8>(vec![1, 3, 1]);
+        assert_eq!(
+            left.join_indices(&right),
+            vec![(0, 0), (0, 2), (1, 0), (1, 2)]
+        );
+        assert!(left.join_indices(&AnyVec::new::<u8>()).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match (u8 != u16)")]
+    fn test_join_indices_types() {
+        AnyVec::from_vec::<u8>(vec![1]).join_indices(&AnyVec::from_vec::<u16>(vec![1]));
+    }
+}
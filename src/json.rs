@@ -0,0 +1,199 @@
+//! Loading JSON arrays into columns, enabled by the ``json`` feature.
+//!
+//! The element type is looked up by name in a [`TypeRegistry`]. Each array
+//! element is coerced to it as well as we can: numbers convert losslessly
+//! into numeric types, strings and scalars are parsed with the type's parse
+//! capability, and anything else is deserialized with its registered
+//! deserializer.
+
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+
+use serde_json::Value;
+
+use crate::{AnyVec, Global, Number, TypeRegistry};
+
+/// Why loading a JSON array failed.
+#[derive(Debug)]
+pub enum JsonError {
+    Json(serde_json::Error),
+    /// The element type isn't registered.
+    UnknownType(String),
+    NotAnArray,
+    /// The element at ``index`` couldn't be converted to the element type.
+    Element {
+        index: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Json(err) => write!(f, "{}", err),
+            JsonError::UnknownType(name) => write!(f, "{} is not registered", name),
+            JsonError::NotAnArray => write!(f, "Expected a JSON array"),
+            JsonError::Element { index, message } => write!(f, "Element {}: {}", index, message),
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(err: serde_json::Error) -> JsonError {
+        JsonError::Json(err)
+    }
+}
+
+fn to_number(number: &serde_json::Number) -> Option<Number> {
+    if let Some(n) = number.as_i64() {
+        Some(Number::Int(n.into()))
+    } else if let Some(n) = number.as_u64() {
+        Some(Number::Int(n.into()))
+    } else {
+        number.as_f64().map(Number::Float)
+    }
+}
+
+impl TypeRegistry {
+    /// Convert a JSON array into a vector of the type registered as
+    /// ``type_name``.
+    pub fn from_json(&self, values: &Value, type_name: &str) -> Result<AnyVec, JsonError> {
+        let entry = *self
+            .get(type_name)
+            .ok_or_else(|| JsonError::UnknownType(type_name.to_string()))?;
+        let values = values.as_array().ok_or(JsonError::NotAnArray)?;
+
+        let mut result = AnyVec::from_vtable_in(entry.vtable, Global);
+        result.reserve(values.len());
+        for (index, value) in values.iter().enumerate() {
+            let error = |message: String| JsonError::Element { index, message };
+            let vtable = result.vtable;
+
+            // Numbers go straight into numeric types when they fit exactly.
+            if let (Value::Number(n), Some(numeric)) = (value, vtable.numeric) {
+                match to_number(n).and_then(numeric.from_number) {
+                    Some(converted) => result.push_value(converted),
+                    None => {
+                        return Err(error(format!(
+                            "{} doesn't fit in {}",
+                            n, vtable.display_name
+                        )))
+                    }
+                }
+                continue;
+            }
+
+            let text = match value {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                Value::Bool(b) => Some(b.to_string()),
+                _ => None,
+            };
+            if let (Some(text), Some(_)) = (&text, vtable.parse) {
+                result
+                    .push_parsed(text)
+                    .map_err(|err| error(err.to_string()))?;
+                continue;
+            }
+
+            match entry.deserialize {
+                Some(deserialize) => {
+                    let single = Value::Array(vec![value.clone()]);
+                    let mut erased = <dyn erased_serde::Deserializer>::erase(single);
+                    let parsed = deserialize(&mut erased).map_err(|err| error(err.to_string()))?;
+                    for element in parsed.into_values() {
+                        result.push_value(element);
+                    }
+                }
+                None => {
+                    return Err(error(format!(
+                        "Can't convert {} to {}",
+                        value, vtable.display_name
+                    )))
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Read a JSON array from ``reader`` into a vector of the type registered
+    /// as ``type_name``.
+    pub fn from_json_reader<R: Read>(
+        &self,
+        reader: R,
+        type_name: &str,
+    ) -> Result<AnyVec, JsonError> {
+        let values: Value = serde_json::from_reader(reader)?;
+        self.from_json(&values, type_name)
+    }
+}
+
+/// Convert a JSON array into a vector of the type registered as
+/// ``type_name`` in the global registry.
+pub fn from_json(values: &Value, type_name: &str) -> Result<AnyVec, JsonError> {
+    TypeRegistry::with_global(|registry| registry.from_json(values, type_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::from_json;
+    use crate::TypeRegistry;
+
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[test]
+    fn test_coercion() {
+        let ints = from_json(&json!([1, "2", 3.0]), "i64").unwrap();
+        assert_eq!(ints.into_vec::<i64>(), vec![1, 2, 3]);
+
+        let floats = from_json(&json!([1, 2.5, "-0.5"]), "f64").unwrap();
+        assert_eq!(floats.into_vec::<f64>(), vec![1.0, 2.5, -0.5]);
+
+        let strings = from_json(&json!(["a", 1, true]), "String").unwrap();
+        assert_eq!(strings.into_vec::<String>(), vec!["a", "1", "true"]);
+    }
+
+    #[test]
+    fn test_element_errors() {
+        let err = from_json(&json!([1, 300]), "u8").err().unwrap();
+        assert_eq!(err.to_string(), "Element 1: 300 doesn't fit in u8");
+
+        let err = from_json(&json!(["x"]), "u8").err().unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("Element 0: Can't parse \"x\" as u8"));
+
+        let err = from_json(&json!({"a": 1}), "u8").err().unwrap();
+        assert_eq!(err.to_string(), "Expected a JSON array");
+    }
+
+    #[test]
+    fn test_deserialized_elements() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let mut registry = TypeRegistry::new();
+        registry.register_deserialize::<Point>();
+        let name = std::any::type_name::<Point>();
+        let input = r#"[{"x": 1, "y": 2}, {"x": 3, "y": 4}]"#;
+        let points = registry.from_json_reader(input.as_bytes(), name).unwrap();
+        assert_eq!(
+            points.into_vec::<Point>(),
+            vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }]
+        );
+    }
+}
@@ -1,69 +1,205 @@
-#![feature(vec_into_raw_parts)]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
 
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::fmt::{self, Write};
+use std::hash::Hash;
 use std::mem;
-use std::slice::SliceIndex;
-
-mod vtable {
-    use std::any::{type_name, Any, TypeId};
-
-    pub struct VTable {
-        id: TypeId,
-        pub display_name: &'static str,
-        pub drop_vec: fn(*mut u8, usize, usize),
-        pub drop_slice: fn(*mut u8, usize),
-        pub size: usize,
-    }
-
-    impl VTable {
-        pub fn new<T: Any>() -> VTable {
-            VTable {
-                id: TypeId::of::<T>(),
-                display_name: type_name::<T>(),
-                drop_vec: drop_vec::<T>,
-                drop_slice: drop_slice::<T>,
-                size: std::mem::size_of::<T>(),
-            }
-        }
+use std::ptr;
+use std::slice::{self, SliceIndex};
 
-        pub fn is<T: Any>(&self) -> bool {
-            TypeId::of::<T>() == self.id
-        }
+use allocator_api2::vec::Vec as AllocVec;
 
-        fn typecheck<T: Any>(&self) -> bool {
-            self.is::<T>()
-        }
+// Lets code generated by our derive macros, which names ``::anyvector``, be
+// used in our own tests.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as anyvector;
 
-        pub fn assert_typecheck<T: Any>(&self) {
-            if !self.typecheck::<T>() {
-                panic!(
-                    "Static type ({}) does not match runtime type ({})",
-                    self.display_name,
-                    type_name::<T>()
-                );
-            }
-        }
-    }
+mod aggregate;
+mod any_ref;
+mod any_slice;
+mod any_value;
+mod append;
+#[cfg(feature = "rkyv")]
+mod archive;
+mod arith;
+#[cfg(feature = "ndarray")]
+mod array;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod boxed;
+mod btree;
+#[cfg(feature = "bumpalo")]
+mod bump;
+mod capability;
+mod cast;
+mod chunked;
+mod chunks;
+mod columns;
+mod concat;
+mod cow;
+#[cfg(feature = "csv")]
+mod csv;
+#[cfg(feature = "serde")]
+mod de;
+mod deque;
+mod determinism;
+mod encode;
+mod estimate;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod frozen;
+mod guard;
+mod handle;
+mod heap;
+mod join;
+#[cfg(feature = "json")]
+mod json;
+mod map;
+mod memory;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod nullable;
+mod numeric;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod parse;
+#[cfg(feature = "bytemuck")]
+mod pod;
+#[cfg(feature = "pyo3")]
+mod python;
+mod registry;
+mod retention;
+mod ring;
+mod rle;
+mod rows;
+mod sample;
+mod select;
+mod send;
+#[cfg(feature = "serde")]
+mod ser;
+mod shared;
+#[cfg(all(feature = "mmap", unix))]
+mod shm;
+mod shrink;
+mod small;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+mod sort;
+mod stats;
+mod strategy;
+mod table;
+#[cfg(feature = "test-util")]
+mod test_util;
+mod tombstone;
+mod transform;
+mod update;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-    fn drop_vec<T>(data: *mut u8, length: usize, capacity: usize) {
-        unsafe { Vec::from_raw_parts(data as *mut T, length, capacity) };
-    }
+pub use aggregate::{Aggregation, Aggregator};
+pub use allocator_api2::alloc::{Allocator, Global};
+pub use any_ref::AnyRef;
+pub use any_slice::{AnySlice, AnySliceMut};
+pub use any_value::AnyValue;
+#[cfg(feature = "derive")]
+pub use anyvector_derive::AnyColumns;
+pub use append::{AppendAnyVec, AppendSnapshot};
+#[cfg(feature = "rkyv")]
+pub use archive::ArchiveError;
+#[cfg(feature = "arrow")]
+pub use arrow::{ArrowArray, ArrowError, ArrowSchema};
+pub use boxed::FromBoxesError;
+pub use btree::AnyBTreeMap;
+#[cfg(feature = "bumpalo")]
+pub use bump::BumpAnyVec;
+pub use capability::CapabilitySet;
+pub use cast::{CastError, CastPolicy};
+pub use chunked::ChunkedAnyVec;
+pub use chunks::SendSliceMut;
+pub use columns::AnyColumns;
+pub use cow::CowAnyVec;
+#[cfg(feature = "csv")]
+pub use csv::{read_csv, CsvError, CsvOptions};
+#[cfg(feature = "serde")]
+pub use de::AnyVecSeed;
+pub use deque::AnyVecDeque;
+pub use determinism::{deterministic, is_deterministic, set_deterministic};
+pub use estimate::Estimate;
+pub use frozen::AnyFrozenVec;
+pub use handle::TypedHandle;
+pub use heap::AnyBinaryHeap;
+#[cfg(feature = "json")]
+pub use json::{from_json, JsonError};
+pub use map::AnyHashMap;
+pub use memory::HeapSize;
+#[cfg(feature = "mmap")]
+pub use mmap::{MmapAnyVec, MmapStore};
+pub use nullable::NullableAnyVec;
+pub use numeric::{Number, Numeric, NumericCoercion};
+#[cfg(feature = "rayon")]
+pub use par::SyncRef;
+#[cfg(feature = "parquet")]
+pub use parquet::{read_parquet, write_parquet, ParquetError};
+pub use parse::ParseError;
+#[cfg(feature = "pyo3")]
+pub use python::PyAnyVec;
+pub use registry::TypeRegistry;
+pub use retention::expire_rows_before;
+pub use ring::RingAnyVec;
+pub use rle::RleAnyVec;
+pub use rows::{extend_from_rows, from_rows, get_row, push_row, to_rows, AnyRow};
+pub use send::SendAnyVec;
+#[cfg(feature = "serde")]
+pub use ser::TaggedRef;
+pub use shared::SharedAnyVec;
+#[cfg(all(feature = "mmap", unix))]
+pub use shm::unlink_shared;
+pub use shrink::ShrinkPolicy;
+pub use small::SmallAnyVec;
+#[cfg(feature = "snapshot")]
+pub use snapshot::SnapshotError;
+#[cfg(feature = "stats")]
+pub use stats::Stats;
+pub use strategy::{Strategy, Tier};
+pub use table::AnyTable;
+#[cfg(feature = "test-util")]
+pub use test_util::{any_vec_of, any_vec_with_operations, ElementKind, Operation};
+pub use tombstone::TombstoneTable;
+pub use update::Conflict;
 
-    fn drop_slice<T>(data: *mut u8, length: usize) {
-        unsafe {
-            let s: &mut [T] = std::slice::from_raw_parts_mut(data as *mut T, length);
-            std::ptr::drop_in_place(s);
-        }
-    }
-}
+mod vtable;
 
+use guard::InitGuard;
 use vtable::VTable;
 
-pub struct AnyVec {
+/// Decompose ``vec`` into its raw components without freeing its buffer.
+///
+/// Equivalent to ``Vec::into_raw_parts``, which isn't available on older
+/// stable toolchains.
+fn into_raw_parts<T>(vec: Vec<T>) -> (*mut T, usize, usize) {
+    let mut vec = mem::ManuallyDrop::new(vec);
+    (vec.as_mut_ptr(), vec.len(), vec.capacity())
+}
+
+/// A vector whose element type is chosen at runtime.
+///
+/// Like ``Vec<T, A>``, storage is obtained from an [`Allocator`], which
+/// defaults to the global heap.
+pub struct AnyVec<A: Allocator = Global> {
     data: *mut u8,
     length: usize,
     capacity: usize,
-    vtable: VTable,
+    vtable: &'static VTable,
+    alloc: A,
+    shrink_policy: ShrinkPolicy,
+    #[cfg(feature = "stats")]
+    stats: Stats,
+    // Run before we're dropped. Growable mmap-backed vectors use this to trim
+    // their files.
+    #[cfg(feature = "mmap")]
+    drop_hook: Option<fn(&mut AnyVec<A>)>,
 }
 
 impl AnyVec {
@@ -72,77 +208,447 @@ impl AnyVec {
     }
 
     pub fn from_vec<T: Any>(vec: Vec<T>) -> AnyVec {
-        let (data, length, capacity) = vec.into_raw_parts();
+        // Global's buffers come from the same heap as std's Vec, so we can
+        // adopt this one as-is.
+        let (data, length, capacity) = into_raw_parts(vec);
         AnyVec {
             data: data as *mut u8,
             length,
             capacity,
             vtable: VTable::new::<T>(),
+            alloc: Global,
+            shrink_policy: ShrinkPolicy::Never,
+            #[cfg(feature = "stats")]
+            stats: Stats::default(),
+            #[cfg(feature = "mmap")]
+            drop_hook: None,
         }
     }
 
+    fn into_vec<T: Any>(self) -> Vec<T> {
+        self.assert_typecheck::<T>();
+        let moved = unsafe { Vec::from_raw_parts(self.data as *mut T, self.length, self.capacity) };
+        // We're transferring ownership of the memory we own into ``moved``, so
+        // don't run our destructor.
+        mem::forget(self);
+        moved
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Create an empty vector whose buffer will be allocated from ``alloc``.
+    pub fn new_in<T: Any>(alloc: A) -> AnyVec<A> {
+        AnyVec::from_vec_in(AllocVec::<T, A>::new_in(alloc))
+    }
+
+    pub fn from_vec_in<T: Any>(vec: AllocVec<T, A>) -> AnyVec<A> {
+        let (data, length, capacity, alloc) = vec.into_raw_parts_with_alloc();
+        AnyVec {
+            data: data as *mut u8,
+            length,
+            capacity,
+            vtable: VTable::new::<T>(),
+            alloc,
+            shrink_policy: ShrinkPolicy::Never,
+            #[cfg(feature = "stats")]
+            stats: Stats::default(),
+            #[cfg(feature = "mmap")]
+            drop_hook: None,
+        }
+    }
+
+    /// Create an empty vector of the type described by ``vtable``.
+    pub(crate) fn from_vtable_in(vtable: &'static VTable, alloc: A) -> AnyVec<A> {
+        AnyVec {
+            // Like Vec, an empty vector holds a dangling, well-aligned
+            // pointer.
+            data: vtable.align as *mut u8,
+            length: 0,
+            capacity: 0,
+            vtable,
+            alloc,
+            shrink_policy: ShrinkPolicy::Never,
+            #[cfg(feature = "stats")]
+            stats: Stats::default(),
+            #[cfg(feature = "mmap")]
+            drop_hook: None,
+        }
+    }
+
+    pub fn into_vec_in<T: Any>(self) -> AllocVec<T, A> {
+        self.assert_typecheck::<T>();
+        let this = mem::ManuallyDrop::new(self);
+        unsafe {
+            let alloc = ptr::read(&this.alloc);
+            AllocVec::from_raw_parts_in(this.data as *mut T, this.length, this.capacity, alloc)
+        }
+    }
+
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Enable erased equality comparisons between this vector's elements.
+    pub fn with_eq<T: Any + PartialEq>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_eq::<T>();
+        self
+    }
+
+    /// Enable erased hashing (and equality) of this vector's elements, which
+    /// is required by hash-based kernels.
+    pub fn with_hash<T: Any + Hash + Eq>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_hash::<T>();
+        self
+    }
+
+    /// Enable erased ordering of this vector's elements, which is required by
+    /// min/max aggregations.
+    pub fn with_ord<T: Any + Ord>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_ord::<T>();
+        self
+    }
+
+    /// Enable cloning of individual elements into ``AnyValue``s.
+    pub fn with_clone<T: Any + Clone>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_clone::<T>();
+        self
+    }
+
+    /// Like ``with_clone``, but clones elements in bulk with a memcpy.
+    /// Primitive types have this enabled already.
+    pub fn with_copy<T: Any + Copy>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_copy::<T>();
+        self
+    }
+
+    /// Enable ``Debug`` formatting of individual elements.
+    pub fn with_debug<T: Any + fmt::Debug>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_debug::<T>();
+        self
+    }
+
+    /// Enable ``Display`` formatting of individual elements.
+    pub fn with_display<T: Any + fmt::Display>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_display::<T>();
+        self
+    }
+
+    /// Let [`AnyVec::heap_size`] count the heap memory our elements own.
+    /// ``String`` has this enabled already.
+    pub fn with_heap_size<T: Any + HeapSize>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_heap_size::<T>();
+        self
+    }
+
+    /// Record that our elements are ``Send``.
+    pub fn with_send<T: Any + Send>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_send::<T>();
+        self
+    }
+
+    /// Record that our elements are ``Sync``.
+    pub fn with_sync<T: Any + Sync>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_sync::<T>();
+        self
+    }
+
+    /// Enable numeric coercion of this vector's elements. Primitive number
+    /// types have this enabled already.
+    pub fn with_numeric<T: Numeric>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_numeric::<T>();
+        self
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.vtable.is::<T>()
+    }
+
+    /// The ``TypeId`` of our element type.
+    pub fn type_id(&self) -> TypeId {
+        self.vtable.id()
+    }
+
+    /// The name of our element type, as ``std::any::type_name`` gives it.
+    pub fn type_name(&self) -> &'static str {
+        self.vtable.display_name
+    }
+
+    /// The size of one element in bytes.
+    pub fn element_size(&self) -> usize {
+        self.vtable.size
+    }
+
+    /// The size and alignment of one element.
+    pub fn element_layout(&self) -> std::alloc::Layout {
+        std::alloc::Layout::from_size_align(self.vtable.size, self.vtable.align).unwrap()
+    }
+
+    /// Whether ``other`` holds the same element type as us, whatever the
+    /// capabilities either has been given.
+    pub fn same_type_as<B: Allocator>(&self, other: &AnyVec<B>) -> bool {
+        self.vtable.same_type(other.vtable)
+    }
+
+    /// The optional capabilities our element type has been given.
+    pub fn capabilities(&self) -> CapabilitySet {
+        self.vtable.capabilities()
+    }
+
+    /// Panic, listing what's missing, unless our element type has every
+    /// capability in ``required``.
+    pub fn assert_capabilities(&self, required: CapabilitySet) {
+        self.vtable.assert_capabilities(required);
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
     fn assert_typecheck<T: Any>(&self) {
         self.vtable.assert_typecheck::<T>();
     }
 
-    unsafe fn typed<T: Any>(&self) -> std::mem::ManuallyDrop<Vec<T>> {
-        std::mem::ManuallyDrop::new(Vec::from_raw_parts(
+    /// Panic if our raw parts are inconsistent: ``len`` past capacity, or a
+    /// buffer too large or misaligned for the element type. Vtables are
+    /// checked once, when they're built, so this doesn't take any locks.
+    ///
+    /// Our own mutating methods call this in debug builds, but it's public
+    /// so that unsafe code built on top of ``AnyVec`` can check its work. It
+    /// does nothing when ``debug_assertions`` are off.
+    pub fn debug_assert_invariants(&self) {
+        #[cfg(debug_assertions)]
+        {
+            let vtable = self.vtable;
+            if self.length > self.capacity {
+                panic!(
+                    "Invariant violated: length {} exceeds capacity {}",
+                    self.length, self.capacity
+                );
+            }
+            if vtable.size != 0 && self.capacity > isize::MAX as usize / vtable.size {
+                panic!(
+                    "Invariant violated: capacity {} is too large for {}",
+                    self.capacity, vtable.display_name
+                );
+            }
+            if !(self.data as usize).is_multiple_of(vtable.align) {
+                panic!(
+                    "Invariant violated: buffer {:p} is not aligned to {} for {}",
+                    self.data, vtable.align, vtable.display_name
+                );
+            }
+        }
+    }
+
+    /// Pointer to the element at ``index``, which must be in bounds.
+    fn element_ptr(&self, index: usize) -> *const u8 {
+        debug_assert!(index < self.length);
+        unsafe { self.data.add(index * self.vtable.size) }
+    }
+
+    /// Materialize a typed view of our buffer.
+    ///
+    /// The returned vector's allocator reference isn't tied to the borrow of
+    /// ``self``, so that ``with_mut_vec`` can write back our raw parts while
+    /// a result borrowed from the vector is still alive. Callers must not let
+    /// the vector outlive ``self``.
+    unsafe fn typed<'b, T: Any>(&self) -> mem::ManuallyDrop<AllocVec<T, &'b A>> {
+        mem::ManuallyDrop::new(AllocVec::from_raw_parts_in(
             self.data as *mut T,
             self.length,
             self.capacity,
+            &*(&self.alloc as *const A),
         ))
     }
 
-    fn into_vec<T: Any>(self) -> Vec<T> {
-        self.assert_typecheck::<T>();
-        let moved = unsafe { self.typed() };
-        // We're transferring ownership of the memory we own into ``moved``, so
-        // don't run our destructor.
-        mem::forget(self);
-        std::mem::ManuallyDrop::into_inner(moved)
-    }
-
     fn with_vec<'a, T: Any, F, R>(&'a self, f: F) -> R
     where
-        F: FnOnce(&'a Vec<T>) -> R,
+        F: FnOnce(&'a AllocVec<T, &'a A>) -> R,
     {
         self.assert_typecheck::<T>();
 
         unsafe {
-            // Temporarily materialize a vector and pass it through to ``f``,
+            // Temporarily materialize a vector and pass it through to ``f``.
+            // ``vec`` is wrapped in ManuallyDrop, so our buffer survives when
+            // it goes out of scope.
             let vec = self.typed::<T>();
-            let vec_ptr = &*vec as *const Vec<T>;
-            let result: R = f(&*vec_ptr);
-            mem::forget(vec);
-            result
+            let vec_ptr = &*vec as *const AllocVec<T, &A>;
+            f(&*vec_ptr)
         }
     }
 
     fn with_mut_vec<'a, T: Any, F, R>(&'a mut self, f: F) -> R
     where
-        F: FnOnce(&'a mut Vec<T>) -> R,
+        F: FnOnce(&'a mut AllocVec<T, &'a A>) -> R,
     {
         self.assert_typecheck::<T>();
 
         let (result, (data, length, capacity)) = unsafe {
             let mut vec = self.typed::<T>();
-            let vec_ptr = &mut *vec as *mut Vec<T>;
+            let vec_ptr = &mut *vec as *mut AllocVec<T, &A>;
             let result: R = f(&mut *vec_ptr);
-            (
-                result,
-                std::mem::ManuallyDrop::into_inner(vec).into_raw_parts(),
-            )
+            (result, (vec.as_mut_ptr(), vec.len(), vec.capacity()))
         };
 
+        let (old_data, old_capacity) = (self.data, self.capacity);
         self.data = data as *mut u8;
         self.length = length;
         self.capacity = capacity;
-        return result;
+        self.record_realloc(old_data, old_capacity);
+        self.debug_assert_invariants();
+        result
     }
 
     // Vec API
     pub fn push<T: Any>(&mut self, value: T) {
-        self.with_mut_vec(|vec: &mut Vec<T>| vec.push(value));
+        // Unlike going through ``with_mut_vec``, this only calls into the
+        // vtable when we need to grow.
+        self.assert_typecheck::<T>();
+        if self.length == self.capacity {
+            self.grow(1);
+        }
+        unsafe { ptr::write((self.data as *mut T).add(self.length), value) };
+        self.length += 1;
+        self.debug_assert_invariants();
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        if self.capacity - self.length < additional {
+            self.grow(additional);
+        }
+    }
+
+    // Kept out of line, so the check in ``reserve`` and ``push`` inlines
+    // into callers.
+    #[cold]
+    #[inline(never)]
+    fn grow(&mut self, additional: usize) {
+        let (data, capacity) = (self.vtable.reserve)(
+            self.data,
+            self.length,
+            self.capacity,
+            additional,
+            &self.alloc,
+        );
+        let (old_data, old_capacity) = (self.data, self.capacity);
+        self.data = data;
+        self.capacity = capacity;
+        self.record_realloc(old_data, old_capacity);
+        self.debug_assert_invariants();
+    }
+
+    /// Append an erased value. Panics if its type doesn't match ours.
+    pub fn push_value(&mut self, value: AnyValue) {
+        self.vtable.assert_same_type(value.vtable());
+        self.reserve(1);
+        let (boxed, _) = value.into_raw();
+        unsafe {
+            (self.vtable.unbox_into)(boxed, self.data.add(self.length * self.vtable.size));
+        }
+        self.length += 1;
+        self.debug_assert_invariants();
+    }
+
+    /// Append an already-boxed value, freeing its box. If it isn't of our
+    /// element type, the box is handed back.
+    pub fn push_boxed(&mut self, value: Box<dyn Any>) -> Result<(), Box<dyn Any>> {
+        if (*value).type_id() != self.vtable.id() {
+            return Err(value);
+        }
+        self.reserve(1);
+        let boxed = Box::into_raw(value) as *mut u8;
+        unsafe {
+            (self.vtable.unbox_into)(boxed, self.data.add(self.length * self.vtable.size));
+        }
+        self.length += 1;
+        self.debug_assert_invariants();
+        Ok(())
+    }
+
+    /// Append a clone of ``value``.
+    ///
+    /// Panics if its type doesn't match ours, or doesn't support cloning.
+    pub fn push_cloned(&mut self, value: AnyRef<'_>) {
+        let index = self.length;
+        self.insert_cloned(index, value);
+    }
+
+    /// Insert a clone of ``value`` at ``index``, shifting later elements up.
+    ///
+    /// Panics if ``index > len``, if ``value``'s type doesn't match ours, or
+    /// if it doesn't support cloning.
+    pub fn insert_cloned(&mut self, index: usize, value: AnyRef<'_>) {
+        if index > self.length {
+            panic!(
+                "Insertion index {} out of bounds for vector of length {}",
+                index, self.length
+            );
+        }
+        self.vtable.assert_same_type(value.vtable());
+        let clone_into = self.vtable.require_clone().clone_into;
+        self.reserve(1);
+        let size = self.vtable.size;
+        // Clone into the spare slot first, so a panicking clone leaves us
+        // untouched, then rotate it into place.
+        let end = unsafe { self.data.add(self.length * size) };
+        clone_into(value.data(), end, 1);
+        self.record_clones(1);
+        if index < self.length {
+            // Rotate in place, as bytes that may include padding.
+            let tail = unsafe {
+                slice::from_raw_parts_mut(
+                    self.data.add(index * size) as *mut mem::MaybeUninit<u8>,
+                    (self.length - index + 1) * size,
+                )
+            };
+            tail.rotate_right(size);
+        }
+        self.length += 1;
+        self.debug_assert_invariants();
+    }
+
+    /// Replace our contents with ``other``'s, returning the old vector, like
+    /// ``mem::replace``.
+    ///
+    /// Panics if ``other``'s element type differs from ours.
+    pub fn replace(&mut self, other: AnyVec<A>) -> AnyVec<A> {
+        self.vtable.assert_same_type(other.vtable);
+        mem::replace(self, other)
+    }
+
+    /// Exchange buffers (and allocators) with ``other`` in O(1), e.g. to
+    /// alternate between a current and a next column. Each vector keeps its
+    /// own capabilities; anything tied to a buffer, like its stats, moves
+    /// with it.
+    ///
+    /// Panics if ``other``'s element type differs from ours.
+    pub fn swap_storage(&mut self, other: &mut AnyVec<A>) {
+        self.vtable.assert_same_type(other.vtable);
+        mem::swap(&mut self.data, &mut other.data);
+        mem::swap(&mut self.length, &mut other.length);
+        mem::swap(&mut self.capacity, &mut other.capacity);
+        mem::swap(&mut self.alloc, &mut other.alloc);
+        #[cfg(feature = "stats")]
+        mem::swap(&mut self.stats, &mut other.stats);
+        #[cfg(feature = "mmap")]
+        mem::swap(&mut self.drop_hook, &mut other.drop_hook);
+    }
+
+    /// Move every element out into its own ``AnyValue``.
+    pub fn into_values(mut self) -> Vec<AnyValue> {
+        let values = (0..self.length)
+            .map(|i| unsafe {
+                let boxed = (self.vtable.box_value)(self.element_ptr(i));
+                AnyValue::from_raw(boxed, self.vtable)
+            })
+            .collect();
+        // Our elements have all been moved out, so only free the buffer.
+        self.length = 0;
+        values
     }
 
     pub fn truncate(&mut self, length: usize) {
@@ -153,47 +659,304 @@ impl AnyVec {
         // See Vec::truncate impl.
         let ndropped: usize = self.length - length;
         self.length = length;
+        self.record_drops(ndropped);
+        self.debug_assert_invariants();
+        if !self.vtable.needs_drop {
+            self.apply_shrink_policy();
+            return;
+        }
         (self.vtable.drop_slice)(
             unsafe { self.data.add(length * self.vtable.size) },
             ndropped,
         );
+        self.apply_shrink_policy();
     }
 
     pub fn clear(&mut self) {
         self.truncate(0);
     }
 
+    /// Append clones of all of ``other``'s elements.
+    ///
+    /// Panics if the types differ, or if they don't support cloning.
+    pub fn extend_from_anyvec<B: Allocator>(&mut self, other: &AnyVec<B>) {
+        self.vtable.assert_same_type(other.vtable);
+        let clone_into = self.vtable.require_clone().clone_into;
+        self.reserve(other.length);
+        clone_into(
+            other.data,
+            unsafe { self.data.add(self.length * self.vtable.size) },
+            other.length,
+        );
+        self.length += other.length;
+        self.record_clones(other.length);
+        self.debug_assert_invariants();
+    }
+
+    /// Append every element of ``values``, reserving space for all of them
+    /// up front and writing them straight into our buffer.
+    ///
+    /// If the iterator reports too high a length, only the elements it does
+    /// yield are kept; if too low, the extras are ignored. If it panics, the
+    /// elements already written are dropped and we're left unchanged. Panics
+    /// if our element type isn't ``T``.
+    pub fn extend_exact<T, I>(&mut self, values: I)
+    where
+        T: Any,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        self.assert_typecheck::<T>();
+        let values = values.into_iter();
+        let count = values.len();
+        self.reserve(count);
+        let end = unsafe { self.data.add(self.length * self.vtable.size) };
+        let mut guard = InitGuard::for_vtable(end, self.vtable);
+        for value in values.take(count) {
+            unsafe { (guard.next() as *mut T).write(value) };
+            guard.wrote_one();
+        }
+        self.length += guard.finish();
+        self.debug_assert_invariants();
+    }
+
+    /// Append ``n`` copies of ``value``: ``n - 1`` clones, then ``value``
+    /// itself.
+    ///
+    /// If a clone panics, the clones already made are dropped and we're left
+    /// unchanged. Panics if our element type isn't ``T``.
+    pub fn extend_with<T: Any + Clone>(&mut self, n: usize, value: T) {
+        self.extend_exact(std::iter::repeat_n(value, n));
+    }
+
+    /// Resize to ``new_len``, dropping elements past it or appending clones
+    /// of ``value``.
+    ///
+    /// If a clone panics, the clones already made are dropped and we're left
+    /// unchanged. Panics if ``value``'s type doesn't match ours, or doesn't
+    /// support cloning.
+    pub fn resize(&mut self, new_len: usize, value: AnyRef<'_>) {
+        if new_len <= self.length {
+            self.truncate(new_len);
+            return;
+        }
+        self.vtable.assert_same_type(value.vtable());
+        let clone_into = self.vtable.require_clone().clone_into;
+        let additional = new_len - self.length;
+        self.reserve(additional);
+        let end = unsafe { self.data.add(self.length * self.vtable.size) };
+        let mut guard = InitGuard::for_vtable(end, self.vtable);
+        for _ in 0..additional {
+            clone_into(value.data(), guard.next(), 1);
+            guard.wrote_one();
+        }
+        self.length += guard.finish();
+        self.record_clones(additional);
+        self.debug_assert_invariants();
+    }
+
+    /// Replace every element with a clone of ``value``.
+    ///
+    /// Each element is only dropped once its replacement has been cloned, so
+    /// if a clone panics, the elements before it have been replaced and the
+    /// rest are untouched. Panics if ``value``'s type doesn't match ours, or
+    /// doesn't support cloning.
+    pub fn fill(&mut self, value: AnyRef<'_>) {
+        self.vtable.assert_same_type(value.vtable());
+        let clone_into = self.vtable.require_clone().clone_into;
+        if self.length == 0 {
+            return;
+        }
+        if let Some(kernels) = self.vtable.slice_kernels {
+            (kernels.fill)(self.data, self.length, value.data());
+            self.record_clones(self.length);
+            self.record_drops(self.length);
+            return;
+        }
+        // Clone into our spare slot and swap it into place, so the element
+        // being replaced is only dropped once its slot holds the clone.
+        self.reserve(1);
+        let size = self.vtable.size;
+        let spare = unsafe { self.data.add(self.length * size) };
+        for i in 0..self.length {
+            clone_into(value.data(), spare, 1);
+            unsafe { ptr::swap_nonoverlapping(spare, self.data.add(i * size), size) };
+            (self.vtable.drop_slice)(spare, 1);
+        }
+        self.record_clones(self.length);
+        self.record_drops(self.length);
+    }
+
+    /// Whether any element is equal to ``value``.
+    ///
+    /// Panics if ``value``'s type doesn't match ours, or doesn't support
+    /// equality.
+    pub fn contains(&self, value: AnyRef<'_>) -> bool {
+        self.as_any_slice().contains(value)
+    }
+
+    /// The index of the first element equal to ``value``.
+    ///
+    /// Panics if ``value``'s type doesn't match ours, or doesn't support
+    /// equality.
+    pub fn position(&self, value: AnyRef<'_>) -> Option<usize> {
+        self.as_any_slice().position(value)
+    }
+
     // Slice API
 
     pub fn get<'a, T: Any, I>(&'a self, index: I) -> Option<&'a <I as SliceIndex<[T]>>::Output>
     where
         I: SliceIndex<[T]>,
     {
-        self.with_vec(|vec: &'a Vec<T>| vec.get(index))
+        self.with_vec(|vec: &'a AllocVec<T, &'a A>| vec.get(index))
     }
 
     pub fn first<'a, T: Any>(&'a self) -> Option<&'a T> {
-        self.with_vec(|vec: &'a Vec<T>| vec.first())
+        self.with_vec(|vec: &'a AllocVec<T, &'a A>| vec.first())
     }
 
     pub fn first_mut<'a, T: Any>(&'a mut self) -> Option<&'a mut T> {
-        self.with_mut_vec(|vec: &'a mut Vec<T>| vec.first_mut())
+        self.with_mut_vec(|vec: &'a mut AllocVec<T, &'a A>| vec.first_mut())
+    }
+
+    /// Typecheck once, then pass our elements to ``f`` as a plain slice.
+    ///
+    /// Panics if the element type isn't ``T``.
+    pub fn with_typed<T: Any, R>(&self, f: impl FnOnce(&[T]) -> R) -> R {
+        self.with_vec(|vec: &AllocVec<T, &A>| f(vec))
+    }
+
+    /// Typecheck once, then pass our elements to ``f`` as a plain mutable
+    /// slice.
+    ///
+    /// Panics if the element type isn't ``T``.
+    pub fn with_typed_mut<T: Any, R>(&mut self, f: impl FnOnce(&mut [T]) -> R) -> R {
+        self.with_mut_vec(|vec: &mut AllocVec<T, &A>| f(vec))
+    }
+
+    /// Erased reference to the element at ``index``, or ``None`` if it's out
+    /// of bounds.
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'_>> {
+        if index < self.length {
+            Some(unsafe { AnyRef::new(self.element_ptr(index), self.vtable) })
+        } else {
+            None
+        }
+    }
+
+    /// Erased view of all our elements.
+    pub fn as_any_slice(&self) -> AnySlice<'_> {
+        unsafe { AnySlice::new(self.data, self.length, self.vtable) }
+    }
+
+    /// Erased mutable view of all our elements.
+    pub fn as_any_slice_mut(&mut self) -> AnySliceMut<'_> {
+        unsafe { AnySliceMut::new(self.data, self.length, self.vtable) }
+    }
+
+    /// Render every element with ``Display``, separated by ``separator``,
+    /// like ``[String]::join``.
+    ///
+    /// Panics if the element type doesn't support ``Display``.
+    pub fn join(&self, separator: &str) -> String {
+        // Check up front, so empty vectors panic too.
+        self.vtable.require_display();
+        let mut joined = String::new();
+        for i in 0..self.length {
+            if i > 0 {
+                joined.push_str(separator);
+            }
+            let element = unsafe { AnyRef::new(self.element_ptr(i), self.vtable) };
+            let _ = write!(joined, "{}", element);
+        }
+        joined
+    }
+
+    /// Compare element-wise with ``other`` under the given coercion policy.
+    pub fn eq_with<B: Allocator>(&self, other: &AnyVec<B>, coercion: NumericCoercion) -> bool {
+        // Check the types first, so that empty vectors of unrelated types
+        // aren't equal.
+        let numbers = self.vtable.numeric.is_some() && other.vtable.numeric.is_some();
+        let comparable = match coercion {
+            NumericCoercion::Strict => self.vtable.same_type(other.vtable),
+            NumericCoercion::Lossless => self.vtable.same_type(other.vtable) || numbers,
+        };
+        if !comparable || self.length != other.length {
+            return false;
+        }
+        if self.vtable.same_type(other.vtable) && self.vtable.bytewise_eq {
+            return self.bytes_eq(other);
+        }
+        if let (true, Some(kernels)) = (
+            self.vtable.same_type(other.vtable),
+            self.vtable.slice_kernels,
+        ) {
+            return (kernels.eq_slice)(self.data, other.data, self.length);
+        }
+        (0..self.length).all(|i| {
+            let (a, b) = (self.get_ref(i).unwrap(), other.get_ref(i).unwrap());
+            a.eq_with(&b, coercion)
+        })
     }
 
     // End Vec API
 }
 
-impl Drop for AnyVec {
+impl<A: Allocator, B: Allocator> PartialEq<AnyVec<B>> for AnyVec<A> {
+    fn eq(&self, other: &AnyVec<B>) -> bool {
+        self.eq_with(other, NumericCoercion::Strict)
+    }
+}
+
+/// Formats the type and length, like [`AnySlice`]'s ``Debug``.
+impl<A: Allocator> fmt::Debug for AnyVec<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_any_slice().fmt(f)
+    }
+}
+
+impl<A: Allocator + Clone> AnyVec<A> {
+    /// Move our elements out into a new vector, leaving us empty but with
+    /// the same element type and allocator, like ``mem::take``. (``take``
+    /// itself gathers elements by index.)
+    pub fn take_all(&mut self) -> AnyVec<A> {
+        let mut empty = AnyVec::from_vtable_in(self.vtable, self.alloc.clone());
+        empty.shrink_policy = self.shrink_policy;
+        mem::replace(self, empty)
+    }
+}
+
+/// Panics if the element type doesn't support cloning.
+impl<A: Allocator + Clone> Clone for AnyVec<A> {
+    fn clone(&self) -> AnyVec<A> {
+        let mut result = AnyVec::from_vtable_in(self.vtable, self.alloc.clone());
+        result.shrink_policy = self.shrink_policy;
+        result.extend_from_anyvec(self);
+        result
+    }
+}
+
+impl<A: Allocator> Drop for AnyVec<A> {
     fn drop(&mut self) {
-        (self.vtable.drop_vec)(self.data, self.length, self.capacity)
+        #[cfg(feature = "mmap")]
+        if let Some(hook) = self.drop_hook {
+            hook(self);
+        }
+        (self.vtable.drop_vec)(self.data, self.length, self.capacity, &self.alloc)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::AnyVec;
+    use super::{Allocator, AnyValue, AnyVec, Global, NumericCoercion};
 
-    use std::cell::RefCell;
+    use allocator_api2::alloc::{AllocError, Layout};
+    use std::any::TypeId;
+    use std::cell::{Cell, RefCell};
+    use std::mem;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::ptr::NonNull;
     use std::rc::Rc;
 
     #[test]
@@ -268,7 +1031,7 @@ mod tests {
         let mut dynamic: AnyVec = AnyVec::from_vec::<u64>(vec![3, 4, 5]);
 
         {
-            let mut result = dynamic.first_mut();
+            let result = dynamic.first_mut();
             let mut expected: u64 = 3;
             assert_eq!(result, Some(&mut expected));
 
@@ -281,6 +1044,328 @@ mod tests {
         assert_eq!(typed, vec![100, 4, 5]);
     }
 
+    #[test]
+    fn test_extend_exact() {
+        let mut dynamic = AnyVec::from_vec::<String>(vec![String::from("a")]);
+        dynamic.extend_exact((0..3).map(|i| i.to_string()));
+        dynamic.extend_exact(vec![String::from("z")]);
+        assert_eq!(dynamic.into_vec::<String>(), vec!["a", "0", "1", "2", "z"]);
+    }
+
+    #[test]
+    fn test_push_and_insert_cloned() {
+        let source =
+            AnyVec::from_vec(vec![String::from("x"), String::from("y")]).with_clone::<String>();
+        let mut dynamic =
+            AnyVec::from_vec(vec![String::from("a"), String::from("b")]).with_clone::<String>();
+        dynamic.push_cloned(source.get_ref(0).unwrap());
+        dynamic.insert_cloned(1, source.get_ref(1).unwrap());
+        dynamic.insert_cloned(0, source.get_ref(0).unwrap());
+        assert_eq!(dynamic.into_vec::<String>(), vec!["x", "a", "y", "b", "x"]);
+        assert_eq!(source.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match (u8 != u16)")]
+    fn test_push_cloned_type() {
+        let source = AnyVec::from_vec::<u16>(vec![1]);
+        AnyVec::new::<u8>().push_cloned(source.get_ref(0).unwrap());
+    }
+
+    #[test]
+    fn test_type_introspection() {
+        let dynamic = AnyVec::from_vec::<u16>(vec![7]);
+        assert!(dynamic.is::<u16>() && !dynamic.is::<i16>());
+        assert_eq!(dynamic.type_id(), TypeId::of::<u16>());
+        assert_eq!((dynamic.type_name(), dynamic.element_size()), ("u16", 2));
+
+        let item = dynamic.get_ref(0).unwrap();
+        assert_eq!(item.type_id(), TypeId::of::<u16>());
+        assert_eq!((item.type_name(), item.element_size()), ("u16", 2));
+
+        let value = item.to_value();
+        assert_eq!(value.type_id(), TypeId::of::<u16>());
+        assert_eq!(value.element_size(), 2);
+    }
+
+    #[test]
+    fn test_layout_and_same_type() {
+        let dynamic = AnyVec::new::<(u8, u32)>();
+        assert_eq!(
+            dynamic.element_layout(),
+            std::alloc::Layout::new::<(u8, u32)>()
+        );
+
+        let ordered = AnyVec::new::<u16>().with_ord::<u16>();
+        assert!(ordered.same_type_as(&AnyVec::new::<u16>()));
+        assert!(!ordered.same_type_as(&AnyVec::new::<i16>()));
+    }
+
+    #[test]
+    fn test_invariants_hold() {
+        let mut names = AnyVec::new::<String>().with_clone::<String>();
+        names.debug_assert_invariants();
+        names.push(String::from("a"));
+        names.extend_from_anyvec(&names.clone());
+        names.truncate(1);
+        names.debug_assert_invariants();
+        AnyVec::new::<()>().debug_assert_invariants();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Invariant violated: length 5 exceeds capacity 4")]
+    fn test_invariants_length() {
+        let mut dynamic = AnyVec::from_vec::<u32>(Vec::with_capacity(4));
+        dynamic.length = 5;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| dynamic.debug_assert_invariants()));
+        // Dropping a corrupted vector is undefined behavior, so put it back
+        // before propagating the panic.
+        dynamic.length = 0;
+        panic::resume_unwind(result.unwrap_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Invariant violated: buffer 0x2 is not aligned to 4 for u32")]
+    fn test_invariants_alignment() {
+        let mut dynamic = AnyVec::new::<u32>();
+        let data = mem::replace(&mut dynamic.data, 2 as *mut u8);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| dynamic.debug_assert_invariants()));
+        dynamic.data = data;
+        panic::resume_unwind(result.unwrap_err());
+    }
+
+    #[test]
+    fn test_extend_with() {
+        let mut dynamic = AnyVec::from_vec::<u8>(vec![1]);
+        dynamic.extend_with(3, 7u8);
+        dynamic.extend_with(0, 9u8);
+        assert_eq!(dynamic.into_vec::<u8>(), vec![1, 7, 7, 7]);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut dynamic = AnyVec::from_vec::<u32>(vec![1, 2]);
+        dynamic.resize(4, AnyVec::from_vec::<u32>(vec![7]).get_ref(0).unwrap());
+        assert_eq!(dynamic.get::<u32, _>(..), Some(&[1, 2, 7, 7][..]));
+        dynamic.resize(1, AnyVec::from_vec::<u32>(vec![7]).get_ref(0).unwrap());
+        assert_eq!(dynamic.into_vec::<u32>(), vec![1]);
+    }
+
+    #[test]
+    fn test_fill() {
+        let mut names =
+            AnyVec::from_vec(vec![String::from("a"), String::from("b")]).with_clone::<String>();
+        let value = AnyVec::from_vec(vec![String::from("z")]).with_clone::<String>();
+        names.fill(value.get_ref(0).unwrap());
+        assert_eq!(names.into_vec::<String>(), vec!["z", "z"]);
+
+        let mut empty = AnyVec::new::<String>().with_clone::<String>();
+        empty.fill(value.get_ref(0).unwrap());
+        assert_eq!((empty.len(), empty.capacity()), (0, 0));
+    }
+
+    #[test]
+    fn test_slice_kernels() {
+        let mut ids = AnyVec::from_vec::<u64>((0..1000).collect());
+        let needle = AnyValue::new(999u64);
+        assert!(ids.contains(needle.as_any_ref()));
+        assert!(!ids.contains(AnyValue::new(1000u64).as_any_ref()));
+        ids.fill(AnyValue::new(7u64).as_any_ref());
+        assert!(ids.with_typed(|ids: &[u64]| ids.iter().all(|&id| id == 7)));
+
+        let prices = AnyVec::from_vec(vec![1.0, f64::NAN, -0.0]);
+        assert!(!prices.contains(AnyValue::new(f64::NAN).as_any_ref()));
+        assert!(prices.contains(AnyValue::new(0.0).as_any_ref()));
+        assert!(prices != prices.clone());
+        let zeros = AnyVec::from_vec(vec![0.0f32; 100]);
+        assert!(zeros == AnyVec::from_vec(vec![-0.0f32; 100]));
+    }
+
+    #[test]
+    fn test_push_grows_geometrically() {
+        let mut ids = AnyVec::new::<u32>();
+        let mut capacities = vec![];
+        for i in 0..1000u32 {
+            ids.push(i);
+            if capacities.last() != Some(&ids.capacity) {
+                capacities.push(ids.capacity);
+            }
+        }
+        assert!(capacities.len() <= 10, "{:?}", capacities);
+        assert_eq!(ids.into_vec::<u32>(), (0..1000).collect::<Vec<_>>());
+
+        let mut units = AnyVec::new::<()>();
+        units.push(());
+        units.push(());
+        assert_eq!(units.len(), 2);
+    }
+
+    #[test]
+    fn test_byte_search() {
+        let codes = AnyVec::from_vec::<u8>(vec![3, 1, 4, 1, 5]);
+        assert_eq!(codes.position(AnyValue::new(1u8).as_any_ref()), Some(1));
+        assert_eq!(codes.position(AnyValue::new(9u8).as_any_ref()), None);
+        assert!(codes.contains(AnyValue::new(5u8).as_any_ref()));
+
+        let flags = AnyVec::from_vec(vec![false, false, true]);
+        assert_eq!(flags.position(AnyValue::new(true).as_any_ref()), Some(2));
+        let tail = flags.as_any_slice().slice(1..2);
+        assert!(!tail.contains(AnyValue::new(true).as_any_ref()));
+    }
+
+    #[test]
+    fn test_contains_without_kernels() {
+        let names = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        assert!(names.contains(AnyValue::new(String::from("b")).as_any_ref()));
+        assert!(!names.contains(AnyValue::new(String::from("c")).as_any_ref()));
+        assert_eq!(
+            names.position(AnyValue::new(String::from("b")).as_any_ref()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_take_all_and_replace() {
+        let mut names = AnyVec::from_vec(vec![String::from("a")]).with_clone::<String>();
+        let taken = names.take_all();
+        assert!(names.is_empty());
+        assert_eq!(names.capabilities(), taken.capabilities());
+
+        names.push(String::from("b"));
+        let old = names.replace(taken);
+        assert_eq!(old.into_vec::<String>(), vec!["b"]);
+        assert_eq!(names.into_vec::<String>(), vec!["a"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match (u8 != u16)")]
+    fn test_replace_type() {
+        AnyVec::new::<u8>().replace(AnyVec::new::<u16>());
+    }
+
+    #[test]
+    fn test_swap_storage() {
+        let mut current = AnyVec::from_vec::<u32>(vec![1, 2, 3]);
+        let mut next = AnyVec::from_vec::<u32>(Vec::with_capacity(8));
+        next.push(4u32);
+        current.swap_storage(&mut next);
+        assert_eq!(current.get::<u32, _>(..), Some(&[4][..]));
+        assert_eq!(next.get::<u32, _>(..), Some(&[1, 2, 3][..]));
+        current.debug_assert_invariants();
+        next.debug_assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match (u32 != i32)")]
+    fn test_swap_storage_type() {
+        AnyVec::new::<u32>().swap_storage(&mut AnyVec::new::<i32>());
+    }
+
+    #[test]
+    fn test_display() {
+        let prices = AnyVec::from_vec::<f64>(vec![9.5, 10.0]);
+        assert_eq!(prices.join(", "), "9.5, 10");
+        assert_eq!(
+            prices.get_ref(0).unwrap().to_display_string().unwrap(),
+            "9.5"
+        );
+        assert_eq!(AnyVec::new::<u8>().join(","), "");
+
+        let hosts = AnyVec::from_vec(vec![std::net::Ipv4Addr::LOCALHOST]);
+        assert_eq!(hosts.get_ref(0).unwrap().to_display_string(), None);
+        let hosts = hosts.with_display::<std::net::Ipv4Addr>();
+        assert_eq!(hosts.join(" "), "127.0.0.1");
+    }
+
+    #[test]
+    #[should_panic(expected = "alloc::vec::Vec<u8> does not support display")]
+    fn test_join_requires_display() {
+        AnyVec::new::<Vec<u8>>().join(",");
+    }
+
+    #[test]
+    fn test_with_typed() {
+        let mut dynamic: AnyVec = AnyVec::from_vec::<u64>(vec![5, 3, 4]);
+
+        dynamic.with_typed_mut(|values: &mut [u64]| values.sort());
+        let (max, total) = dynamic.with_typed(|values: &[u64]| {
+            (values.iter().max().copied(), values.iter().sum::<u64>())
+        });
+        assert_eq!((max, total), (Some(5), 12));
+        assert_eq!(dynamic.into_vec::<u64>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_get_ref() {
+        let dynamic: AnyVec = AnyVec::from_vec::<u64>(vec![3, 4, 5]);
+
+        let item = dynamic.get_ref(1).unwrap();
+        assert!(item.is::<u64>());
+        assert_eq!(item.downcast_ref::<u64>(), Some(&4));
+        assert_eq!(item.downcast_ref::<i64>(), None);
+        assert!(item == &4u64);
+        assert!(item != &4i64);
+
+        assert!(dynamic.get_ref(3).is_none());
+    }
+
+    #[test]
+    fn test_push_boxed() {
+        let mut dynamic: AnyVec = AnyVec::from_vec(vec![String::from("a")]);
+        assert!(dynamic.push_boxed(Box::new(String::from("b"))).is_ok());
+
+        let rejected = dynamic.push_boxed(Box::new(1u8)).err().unwrap();
+        assert_eq!(rejected.downcast_ref::<u8>(), Some(&1));
+        assert_eq!(dynamic.into_vec::<String>(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_eq_with_numeric_coercion() {
+        let ints: AnyVec = AnyVec::from_vec::<i64>(vec![1, 2, 3]);
+        let floats: AnyVec = AnyVec::from_vec::<f64>(vec![1.0, 2.0, 3.0]);
+        let halves: AnyVec = AnyVec::from_vec::<f64>(vec![1.0, 2.0, 3.5]);
+
+        assert!(ints != floats);
+        assert!(ints.eq_with(&floats, NumericCoercion::Lossless));
+        assert!(!ints.eq_with(&halves, NumericCoercion::Lossless));
+
+        let item = ints.get_ref(2).unwrap();
+        assert!(!item.eq_value_with(&3.0f64, NumericCoercion::Strict));
+        assert!(item.eq_value_with(&3.0f64, NumericCoercion::Lossless));
+        assert!(item.eq_value_with(&3u8, NumericCoercion::Lossless));
+        assert!(item.eq_with(&floats.get_ref(2).unwrap(), NumericCoercion::Lossless));
+    }
+
+    #[test]
+    fn test_eq_non_numeric() {
+        let strings: AnyVec = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        let same: AnyVec = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        let ints: AnyVec = AnyVec::from_vec::<i64>(vec![1, 2]);
+
+        assert!(strings == same);
+        assert!(strings != ints);
+        assert!(!strings.eq_with(&ints, NumericCoercion::Lossless));
+        let first = strings.get_ref(0).unwrap();
+        assert!(first.eq_value_with(&String::from("a"), NumericCoercion::Lossless));
+        assert!(!first.eq_value_with(&1i64, NumericCoercion::Lossless));
+
+        // Unrelated types differ even with no elements to compare.
+        let empty_strings: AnyVec = AnyVec::new::<String>();
+        let empty_bytes: AnyVec = AnyVec::new::<Vec<u8>>();
+        assert!(!empty_strings.eq_with(&empty_bytes, NumericCoercion::Lossless));
+        assert!(AnyVec::new::<u8>().eq_with(&AnyVec::new::<f32>(), NumericCoercion::Lossless));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_eq_requires_capability() {
+        let a: AnyVec = AnyVec::from_vec(vec![vec![1u8]]);
+        let b: AnyVec = AnyVec::from_vec(vec![vec![1u8]]);
+        let _ = a == b;
+    }
+
     #[test]
     fn test_drop_vec() {
         let chan: Rc<RefCell<Vec<i64>>> = Rc::new(RefCell::new(vec![]));
@@ -345,6 +1430,126 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_new_in() {
+        let alloc = CountingAlloc::default();
+        {
+            let mut dynamic = AnyVec::new_in::<u64>(&alloc);
+            for i in 0..100 {
+                dynamic.push(i as u64);
+            }
+            assert!(alloc.live.get() > 0);
+            assert_eq!(dynamic.get::<u64, _>(99), Some(&99));
+        }
+        assert_eq!(alloc.live.get(), 0);
+    }
+
+    #[test]
+    fn test_new_in_drops_elements() {
+        let alloc = CountingAlloc::default();
+        let chan: Rc<RefCell<Vec<i64>>> = Rc::new(RefCell::new(vec![]));
+        let mut dynamic = AnyVec::new_in::<HasDrop>(&alloc);
+        for id in 1..4 {
+            dynamic.push(HasDrop {
+                id,
+                chan: chan.clone(),
+            });
+        }
+        std::mem::drop(dynamic);
+
+        assert_eq!(*chan.borrow(), vec![1, 2, 3]);
+        assert_eq!(alloc.live.get(), 0);
+    }
+
+    #[test]
+    fn test_into_vec_in() {
+        let alloc = CountingAlloc::default();
+        let mut dynamic = AnyVec::new_in::<u64>(&alloc);
+        dynamic.push(1u64);
+        dynamic.push(2u64);
+
+        let typed = dynamic.into_vec_in::<u64>();
+        assert_eq!(typed.as_slice(), &[1, 2]);
+        assert_eq!(alloc.live.get(), 1);
+        std::mem::drop(typed);
+        assert_eq!(alloc.live.get(), 0);
+    }
+
+    // An allocator that forwards to the global heap, keeping count of how
+    // many of its allocations are still live.
+    #[derive(Default)]
+    struct CountingAlloc {
+        live: Cell<isize>,
+    }
+
+    unsafe impl Allocator for CountingAlloc {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.live.set(self.live.get() + 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.live.set(self.live.get() - 1);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn test_clone_trivial() {
+        let dynamic: AnyVec = AnyVec::from_vec::<f64>(vec![1.0, 2.5, -3.0]);
+        assert!(dynamic.vtable.clone.unwrap().trivial);
+        assert!(!dynamic.vtable.needs_drop);
+
+        let mut copy = dynamic.clone();
+        copy.extend_from_anyvec(&dynamic);
+        assert_eq!(copy.into_vec::<f64>(), vec![1.0, 2.5, -3.0, 1.0, 2.5, -3.0]);
+    }
+
+    #[test]
+    fn test_clone_with_copy() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Point(i32, i32);
+
+        let dynamic: AnyVec = AnyVec::from_vec(vec![Point(1, 2), Point(3, 4)]).with_copy::<Point>();
+        assert!(dynamic.vtable.clone.unwrap().trivial);
+        assert_eq!(
+            dynamic.clone().into_vec::<Point>(),
+            vec![Point(1, 2), Point(3, 4)]
+        );
+    }
+
+    #[test]
+    fn test_clone_non_trivial() {
+        let chan = Rc::new(());
+        let dynamic: AnyVec =
+            AnyVec::from_vec(vec![chan.clone(), chan.clone()]).with_clone::<Rc<()>>();
+        assert!(dynamic.vtable.needs_drop);
+
+        let mut copy = dynamic.clone();
+        assert_eq!(Rc::strong_count(&chan), 5);
+        copy.truncate(1);
+        assert_eq!(Rc::strong_count(&chan), 4);
+        drop(dynamic);
+        drop(copy);
+        assert_eq!(Rc::strong_count(&chan), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support cloning")]
+    fn test_clone_requires_capability() {
+        let dynamic: AnyVec = AnyVec::from_vec(vec![Rc::new(())]);
+        let _ = dynamic.clone();
+    }
+
+    #[test]
+    fn test_vtables_interned_across_threads() {
+        // Each thread caches its own lookups, which must still agree.
+        let here = AnyVec::new::<u16>().vtable as *const _ as usize;
+        let there = std::thread::spawn(|| AnyVec::new::<u16>().vtable as *const _ as usize);
+        assert_eq!(there.join().unwrap(), here);
+        AnyVec::new::<u16>().vtable.assert_consistent();
+    }
+
     // A struct that appends its id into a shared vector when it's dropped.
     // This is useful for testing that values of this type get dropped when
     // they should.
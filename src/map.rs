@@ -0,0 +1,332 @@
+//! A hash map whose key and value types are only known at runtime.
+
+use std::any::Any;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::ptr;
+
+use crate::vtable::{EqFn, HashFn};
+use crate::{AnyRef, AnySlice, AnyValue, AnyVec, Global};
+
+/// A map from keys to values whose types are chosen at runtime, hashed and
+/// compared with the key vtable's ``hash`` and ``eq`` entries.
+///
+/// Keys and values are stored unboxed in two parallel columns. Removing an
+/// entry moves the last entry into its place, so iteration order is
+/// insertion order until the first removal.
+pub struct AnyHashMap {
+    keys: AnyVec,
+    values: AnyVec,
+    // The hash of each key, so entries can be moved without rehashing.
+    hashes: Vec<u64>,
+    // Column indices of the entries with each hash.
+    index: HashMap<u64, Vec<usize>>,
+    state: RandomState,
+    eq: EqFn,
+    hash: HashFn,
+}
+
+impl AnyHashMap {
+    pub fn new<K: Any + Hash + Eq, V: Any>() -> AnyHashMap {
+        AnyHashMap::from_anyvecs(AnyVec::new::<K>().with_hash::<K>(), AnyVec::new::<V>())
+    }
+
+    /// Build a map from parallel columns of keys and values, for types
+    /// chosen at runtime (say, with ``TypeRegistry::new_by_name``). Later
+    /// entries win when a key repeats.
+    ///
+    /// Panics if the columns have different lengths, or if the key type
+    /// doesn't support hashing.
+    pub fn from_anyvecs(keys: AnyVec, values: AnyVec) -> AnyHashMap {
+        if keys.len() != values.len() {
+            panic!(
+                "Columns have different lengths ({} != {})",
+                keys.len(),
+                values.len()
+            );
+        }
+        let (eq, hash) = keys.vtable.require_hash();
+        let mut map = AnyHashMap {
+            keys: AnyVec::from_vtable_in(keys.vtable, Global),
+            values: AnyVec::from_vtable_in(values.vtable, Global),
+            hashes: Vec::new(),
+            index: HashMap::new(),
+            state: RandomState::new(),
+            eq,
+            hash,
+        };
+        for (key, value) in keys.into_values().into_iter().zip(values.into_values()) {
+            map.insert_value(key, value);
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn hash_of(&self, key: *const u8) -> u64 {
+        let mut hasher = self.state.build_hasher();
+        (self.hash)(key, &mut hasher);
+        hasher.finish()
+    }
+
+    /// The column index of the entry whose key equals ``key``.
+    fn find(&self, key: *const u8, hash: u64) -> Option<usize> {
+        let candidates = self.index.get(&hash)?;
+        candidates
+            .iter()
+            .copied()
+            .find(|&i| (self.eq)(self.keys.element_ptr(i), key))
+    }
+
+    fn value_ptr(&self, index: usize) -> *mut u8 {
+        unsafe { self.values.data.add(index * self.values.vtable.size) }
+    }
+
+    /// Insert ``value`` under ``key``, returning the value it replaced.
+    pub fn insert<K: Any, V: Any>(&mut self, key: K, value: V) -> Option<V> {
+        self.keys.vtable.assert_typecheck::<K>();
+        self.values.vtable.assert_typecheck::<V>();
+        let hash = self.hash_of(&key as *const K as *const u8);
+        if let Some(i) = self.find(&key as *const K as *const u8, hash) {
+            let slot = self.value_ptr(i) as *mut V;
+            return Some(unsafe { ptr::replace(slot, value) });
+        }
+        self.keys.push(key);
+        self.values.push(value);
+        self.link(hash);
+        None
+    }
+
+    /// Insert an erased entry, returning the value it replaced. Panics if
+    /// either type doesn't match ours.
+    pub fn insert_value(&mut self, key: AnyValue, value: AnyValue) -> Option<AnyValue> {
+        self.keys.vtable.assert_same_type(key.vtable());
+        self.values.vtable.assert_same_type(value.vtable());
+        let hash = self.hash_of(key.as_any_ref().data());
+        if let Some(i) = self.find(key.as_any_ref().data(), hash) {
+            let vtable = self.values.vtable;
+            let slot = self.value_ptr(i);
+            let old = unsafe { AnyValue::from_raw((vtable.box_value)(slot), vtable) };
+            let (boxed, _) = value.into_raw();
+            (vtable.unbox_into)(boxed, slot);
+            return Some(old);
+        }
+        self.keys.push_value(key);
+        self.values.push_value(value);
+        self.link(hash);
+        None
+    }
+
+    /// Record that the last entry has ``hash``.
+    fn link(&mut self, hash: u64) {
+        let last = self.len() - 1;
+        self.hashes.push(hash);
+        self.index.entry(hash).or_default().push(last);
+    }
+
+    pub fn get<K: Any, V: Any>(&self, key: &K) -> Option<&V> {
+        self.keys.vtable.assert_typecheck::<K>();
+        self.values.vtable.assert_typecheck::<V>();
+        self.get_ref(unsafe { AnyRef::new(key as *const K as *const u8, self.keys.vtable) })
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<K: Any, V: Any>(&mut self, key: &K) -> Option<&mut V> {
+        self.keys.vtable.assert_typecheck::<K>();
+        self.values.vtable.assert_typecheck::<V>();
+        let key = key as *const K as *const u8;
+        let i = self.find(key, self.hash_of(key))?;
+        Some(unsafe { &mut *(self.value_ptr(i) as *mut V) })
+    }
+
+    /// Look up an erased key. Panics if its type doesn't match ours.
+    pub fn get_ref(&self, key: AnyRef<'_>) -> Option<AnyRef<'_>> {
+        self.keys.vtable.assert_same_type(key.vtable());
+        let i = self.find(key.data(), self.hash_of(key.data()))?;
+        self.values.get_ref(i)
+    }
+
+    pub fn contains_key<K: Any>(&self, key: &K) -> bool {
+        self.keys.vtable.assert_typecheck::<K>();
+        let key = key as *const K as *const u8;
+        self.find(key, self.hash_of(key)).is_some()
+    }
+
+    pub fn remove<K: Any, V: Any>(&mut self, key: &K) -> Option<V> {
+        self.keys.vtable.assert_typecheck::<K>();
+        self.values.vtable.assert_typecheck::<V>();
+        self.remove_ref(unsafe { AnyRef::new(key as *const K as *const u8, self.keys.vtable) })
+            .map(|value| value.downcast().ok().unwrap())
+    }
+
+    /// Remove the entry with an erased key, returning its value. Panics if
+    /// the key's type doesn't match ours.
+    pub fn remove_ref(&mut self, key: AnyRef<'_>) -> Option<AnyValue> {
+        self.keys.vtable.assert_same_type(key.vtable());
+        let hash = self.hash_of(key.data());
+        let i = self.find(key.data(), hash)?;
+        Some(self.swap_remove(i))
+    }
+
+    /// Remove the entry at column index ``i``, moving the last entry into
+    /// its place.
+    fn swap_remove(&mut self, i: usize) -> AnyValue {
+        let last = self.len() - 1;
+        unlink(&mut self.index, self.hashes[i], i);
+        if i != last {
+            let moved = self.index.get_mut(&self.hashes[last]).unwrap();
+            *moved.iter_mut().find(|j| **j == last).unwrap() = i;
+        }
+        self.hashes.swap_remove(i);
+
+        // Unlink both before dropping the key, in case its destructor panics.
+        let key = take(&mut self.keys, i);
+        let value = take(&mut self.values, i);
+        drop(key);
+        value
+    }
+
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.hashes.clear();
+        self.keys.clear();
+        self.values.clear();
+    }
+
+    pub fn keys(&self) -> AnySlice<'_> {
+        self.keys.as_any_slice()
+    }
+
+    pub fn values(&self) -> AnySlice<'_> {
+        self.values.as_any_slice()
+    }
+
+    /// Our entries, in column order.
+    pub fn iter(&self) -> impl Iterator<Item = (AnyRef<'_>, AnyRef<'_>)> {
+        self.keys
+            .as_any_slice()
+            .iter()
+            .zip(self.values.as_any_slice().iter())
+    }
+
+    /// Convert into our columns of keys and values.
+    pub fn into_anyvecs(self) -> (AnyVec, AnyVec) {
+        (self.keys, self.values)
+    }
+}
+
+fn unlink(index: &mut HashMap<u64, Vec<usize>>, hash: u64, i: usize) {
+    let candidates = index.get_mut(&hash).unwrap();
+    candidates.retain(|&j| j != i);
+    if candidates.is_empty() {
+        index.remove(&hash);
+    }
+}
+
+/// Move the element at ``i`` out of ``vec``, moving the last element into its
+/// place.
+fn take(vec: &mut AnyVec, i: usize) -> AnyValue {
+    let vtable = vec.vtable;
+    let last = vec.len() - 1;
+    unsafe {
+        let slot = vec.data.add(i * vtable.size);
+        let value = AnyValue::from_raw((vtable.box_value)(slot), vtable);
+        if i != last {
+            ptr::copy_nonoverlapping(vec.data.add(last * vtable.size), slot, vtable.size);
+        }
+        vec.length = last;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnyHashMap;
+    use crate::{AnyValue, AnyVec};
+
+    use std::rc::Rc;
+
+    #[test]
+    fn test_typed_access() {
+        let mut map = AnyHashMap::new::<String, u32>();
+        assert_eq!(map.insert(String::from("a"), 1u32), None);
+        assert_eq!(map.insert(String::from("b"), 2u32), None);
+        assert_eq!(map.insert(String::from("a"), 3u32), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get::<String, u32>(&String::from("a")), Some(&3));
+        assert!(!map.contains_key(&String::from("c")));
+
+        *map.get_mut::<String, u32>(&String::from("b")).unwrap() += 10;
+        assert_eq!(map.remove::<String, u32>(&String::from("a")), Some(3));
+        assert_eq!(map.remove::<String, u32>(&String::from("a")), None);
+        assert_eq!(map.get::<String, u32>(&String::from("b")), Some(&12));
+    }
+
+    #[test]
+    fn test_erased_access() {
+        let keys = AnyVec::from_vec::<u8>(vec![1, 2, 1]);
+        let values = AnyVec::from_vec(vec!["x", "y", "z"]);
+        let mut map = AnyHashMap::from_anyvecs(keys, values);
+        assert_eq!(map.len(), 2);
+
+        let key = AnyValue::new(1u8);
+        let found = map.get_ref(key.as_any_ref()).unwrap();
+        assert_eq!(found.downcast_ref::<&str>(), Some(&"z"));
+
+        let old = map.insert_value(AnyValue::new(2u8), AnyValue::new("w"));
+        assert_eq!(old.unwrap().downcast::<&str>().ok(), Some("y"));
+        let removed = map.remove_ref(key.as_any_ref()).unwrap();
+        assert_eq!(removed.downcast::<&str>().ok(), Some("z"));
+
+        let (keys, values) = map.into_anyvecs();
+        assert_eq!(keys.into_vec::<u8>(), vec![2]);
+        assert_eq!(values.into_vec::<&str>(), vec!["w"]);
+    }
+
+    #[test]
+    fn test_removal_keeps_index_consistent() {
+        let mut map = AnyHashMap::new::<u32, u32>();
+        for i in 0..100u32 {
+            map.insert(i, i * 2);
+        }
+        for i in (0..100u32).step_by(3) {
+            assert_eq!(map.remove::<u32, u32>(&i), Some(i * 2));
+        }
+        assert_eq!(map.len(), 66);
+        for i in 0..100u32 {
+            let expected = if i % 3 == 0 { None } else { Some(i * 2) };
+            assert_eq!(map.get::<u32, u32>(&i).copied(), expected);
+        }
+        assert_eq!(map.iter().count(), 66);
+    }
+
+    #[test]
+    fn test_drops_entries() {
+        let chan = Rc::new(());
+        let mut map = AnyHashMap::new::<u8, Rc<()>>();
+        map.insert(1u8, chan.clone());
+        map.insert(2u8, chan.clone());
+        map.insert(1u8, chan.clone());
+        assert_eq!(Rc::strong_count(&chan), 3);
+        map.remove::<u8, Rc<()>>(&2);
+        assert_eq!(Rc::strong_count(&chan), 2);
+        drop(map);
+        assert_eq!(Rc::strong_count(&chan), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support hashing")]
+    fn test_requires_hash() {
+        AnyHashMap::from_anyvecs(
+            AnyVec::from_vec(vec![vec![1u8]]),
+            AnyVec::from_vec(vec![1u8]),
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Accounting for the memory vectors use.
+
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+
+use crate::{Allocator, AnyVec};
+
+/// Types that can report the heap memory they own, beyond their own inline
+/// size. Give a vector the ``HEAP_SIZE`` capability with
+/// ``AnyVec::with_heap_size`` so [`AnyVec::heap_size`] counts it.
+pub trait HeapSize {
+    /// Bytes of heap memory owned by ``self``, not counting
+    /// ``size_of_val(self)``.
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! impl_inline {
+    ($($t:ty),*) => {
+        $(
+            impl HeapSize for $t {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+impl_inline!(
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    f32,
+    f64,
+    bool,
+    char,
+    ()
+);
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        size_of::<T>() + (**self).heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+// Maps don't expose their exact allocation, so these count the entries'
+// inline sizes at the current capacity (hash maps) or length (B-trees).
+impl<K: HeapSize, V: HeapSize, S> HeapSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        let entries: usize = self
+            .iter()
+            .map(|(k, v)| k.heap_size() + v.heap_size())
+            .sum();
+        self.capacity() * size_of::<(K, V)>() + entries
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        let entries: usize = self
+            .iter()
+            .map(|(k, v)| k.heap_size() + v.heap_size())
+            .sum();
+        self.len() * size_of::<(K, V)>() + entries
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// The size of our buffer in bytes: ``capacity * element_size``.
+    pub fn buffer_size(&self) -> usize {
+        self.capacity * self.vtable.size
+    }
+
+    /// Our buffer size plus, if our element type has the ``HEAP_SIZE``
+    /// capability, the heap memory owned by each element. Without it, this
+    /// is just [`AnyVec::buffer_size`].
+    pub fn heap_size(&self) -> usize {
+        let elements = match self.vtable.heap_size {
+            Some(heap_size) => (0..self.length)
+                .map(|i| heap_size(self.element_ptr(i)))
+                .sum(),
+            None => 0,
+        };
+        self.buffer_size() + elements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyVec, CapabilitySet};
+
+    #[test]
+    fn test_shallow() {
+        let mut dynamic = AnyVec::new::<u64>();
+        dynamic.reserve(10);
+        let capacity_bytes = dynamic.buffer_size();
+        assert!(capacity_bytes >= 80);
+        assert_eq!(dynamic.heap_size(), capacity_bytes);
+    }
+
+    #[test]
+    fn test_deep() {
+        let strings = vec![String::with_capacity(100), String::with_capacity(50)];
+        let dynamic = AnyVec::from_vec(strings);
+        assert!(dynamic.capabilities().contains(CapabilitySet::HEAP_SIZE));
+        assert_eq!(dynamic.heap_size(), dynamic.buffer_size() + 150);
+
+        let nested = AnyVec::from_vec(vec![vec![1u32; 4]]);
+        assert_eq!(nested.heap_size(), nested.buffer_size());
+        let nested = nested.with_heap_size::<Vec<u32>>();
+        assert_eq!(nested.heap_size(), nested.buffer_size() + 16);
+    }
+}
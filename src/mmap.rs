@@ -0,0 +1,375 @@
+//! File-backed vectors of plain-old-data, enabled by the ``mmap`` feature.
+//!
+//! The backing store is just another allocator: an ``MmapStore`` hands out
+//! (at most) one buffer, which is a mapping of its file. Large numeric columns
+//! can then be used without reading them into memory up front.
+//!
+//! Mapping a file is only sound if nothing else modifies or truncates it while
+//! it's mapped.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::ptr::{self, NonNull};
+
+use allocator_api2::alloc::{AllocError, Layout};
+use bytemuck::Pod;
+use memmap2::{MmapMut, MmapOptions};
+
+use crate::vtable::VTable;
+use crate::{Allocator, AnyVec, Global};
+
+/// An allocator whose buffer is a memory-mapped file.
+///
+/// In read-only mode the mapping is copy-on-write: elements can still be
+/// modified, but the changes are never written back, and growing copies the
+/// buffer to the heap. In growable mode the mapping is shared, so changes go
+/// straight to the file, and growing extends the file.
+///
+/// I/O errors while growing surface as allocation failures.
+pub struct MmapStore {
+    pub(crate) file: File,
+    pub(crate) growable: bool,
+    pub(crate) map: RefCell<Option<MmapMut>>,
+    // Where our buffer starts within the mapping, for files with a header.
+    // Only fixed-size stores have one, since growing remaps from the start.
+    pub(crate) offset: usize,
+}
+
+/// An ``AnyVec`` whose buffer is a memory-mapped file.
+pub type MmapAnyVec = AnyVec<MmapStore>;
+
+impl MmapStore {
+    fn is_mapped(&self, ptr: NonNull<u8>) -> bool {
+        self.map
+            .borrow()
+            .as_ref()
+            .is_some_and(|map| ptr::eq(map.as_ptr().wrapping_add(self.offset), ptr.as_ptr()))
+    }
+
+    /// Resize our file to ``size`` bytes and map all of it.
+    fn remap(&self, size: usize) -> io::Result<NonNull<[u8]>> {
+        self.map.borrow_mut().take();
+        self.file.set_len(size as u64)?;
+        let mut map = unsafe { MmapOptions::new().len(size).map_mut(&self.file)? };
+        let ptr = NonNull::slice_from_raw_parts(NonNull::new(map.as_mut_ptr()).unwrap(), size);
+        *self.map.borrow_mut() = Some(map);
+        Ok(ptr)
+    }
+}
+
+unsafe impl Allocator for MmapStore {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if !self.growable {
+            return Global.allocate(layout);
+        }
+        if self.map.borrow().is_some() {
+            // We only have the one file to hand out.
+            return Err(AllocError);
+        }
+        self.remap(layout.size()).map_err(|_| AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if self.is_mapped(ptr) {
+            self.map.borrow_mut().take();
+        } else {
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if !self.is_mapped(ptr) {
+            return Global.grow(ptr, old_layout, new_layout);
+        }
+        if self.growable {
+            // The mapping is shared, so the file already holds our contents.
+            return self.remap(new_layout.size()).map_err(|_| AllocError);
+        }
+        let new = Global.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr() as *mut u8, old_layout.size());
+        self.deallocate(ptr, old_layout);
+        Ok(new)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if self.growable && self.is_mapped(ptr) && new_layout.size() > 0 {
+            self.remap(new_layout.size()).map_err(|_| AllocError)
+        } else if self.is_mapped(ptr) {
+            // Keep the mapping; a smaller layout than we really have is fine.
+            Ok(NonNull::slice_from_raw_parts(ptr, old_layout.size()))
+        } else {
+            Global.shrink(ptr, old_layout, new_layout)
+        }
+    }
+}
+
+impl AnyVec<MmapStore> {
+    /// Map ``file`` as a vector of ``T``s, without reading it into memory.
+    ///
+    /// The file is never modified. Fails if its length isn't a multiple of
+    /// ``T``'s size.
+    ///
+    /// # Safety
+    ///
+    /// Nothing, in this process or any other, may modify or truncate the file
+    /// while the vector is alive.
+    pub unsafe fn from_mmap<T: Pod>(file: File) -> io::Result<MmapAnyVec> {
+        AnyVec::map_file::<T>(file, false)
+    }
+
+    /// Map ``file`` as a vector of ``T``s that writes changes through to the
+    /// file and extends it as we grow.
+    ///
+    /// The file may contain unused capacity until [`AnyVec::flush`] is
+    /// called or the vector is dropped.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else, in this process or any other, may access the file while
+    /// the vector is alive.
+    pub unsafe fn mmap_growable<T: Pod>(file: File) -> io::Result<MmapAnyVec> {
+        let mut result = AnyVec::map_file::<T>(file, true)?;
+        // Errors can't be reported from a destructor, so they're ignored
+        // here, like ``BufWriter``'s.
+        result.drop_hook = Some(|vec| {
+            let _ = vec.flush();
+        });
+        Ok(result)
+    }
+
+    fn map_file<T: Pod>(file: File, growable: bool) -> io::Result<MmapAnyVec> {
+        let vtable = VTable::new::<T>().with_pod::<T>();
+        let size = file.metadata()?.len() as usize;
+        if vtable.size == 0 || !size.is_multiple_of(vtable.size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "File length {} is not a multiple of {}'s size",
+                    size, vtable.display_name
+                ),
+            ));
+        }
+
+        let store = MmapStore {
+            file,
+            growable,
+            map: RefCell::new(None),
+            offset: 0,
+        };
+        if size == 0 {
+            // Empty files can't be mapped, so start out unallocated.
+            return Ok(AnyVec::from_vtable_in(vtable, store));
+        }
+
+        let map = unsafe {
+            if growable {
+                MmapOptions::new().map_mut(&store.file)?
+            } else {
+                MmapOptions::new().map_copy(&store.file)?
+            }
+        };
+        let data = map.as_ptr() as *mut u8;
+        *store.map.borrow_mut() = Some(map);
+
+        let mut result = AnyVec::from_vtable_in(vtable, store);
+        result.data = data;
+        result.length = size / vtable.size;
+        result.capacity = result.length;
+        Ok(result)
+    }
+
+    /// Write our elements to the file, and trim off any unused capacity so
+    /// that the file holds exactly our elements.
+    ///
+    /// Does nothing for read-only vectors.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.alloc.growable {
+            return Ok(());
+        }
+        if let Some(map) = self.alloc.map.borrow().as_ref() {
+            map.flush()?;
+        }
+        if self.length == self.capacity {
+            return Ok(());
+        }
+
+        let size = self.length * self.vtable.size;
+        if size == 0 {
+            self.alloc.map.borrow_mut().take();
+            self.alloc.file.set_len(0)?;
+            self.data = self.vtable.align as *mut u8;
+        } else {
+            self.data = self.alloc.remap(size)?.as_ptr() as *mut u8;
+        }
+        self.capacity = self.length;
+        Ok(())
+    }
+
+    /// Release the mapping and get our file back.
+    pub fn into_file(mut self) -> io::Result<File> {
+        self.flush()?;
+        // Our elements don't need dropping, but our buffer needs freeing: it's
+        // either the store's mapping or, once a read-only vector has grown,
+        // on the heap.
+        let this = mem::ManuallyDrop::new(self);
+        (this.vtable.drop_vec)(this.data, 0, this.capacity, &this.alloc);
+        let MmapStore { file, .. } = unsafe { ptr::read(&this.alloc) };
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MmapAnyVec;
+    use crate::AnyVec;
+
+    use std::fs::{self, File, OpenOptions};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("anyvector-{}-{}", name, std::process::id()))
+    }
+
+    fn open(path: &PathBuf) -> File {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_from_mmap() {
+        let path = temp_path("from-mmap");
+        let values: Vec<u64> = (0..1000).collect();
+        let bytes: Vec<u8> = values.iter().flat_map(|x| x.to_ne_bytes()).collect();
+        File::create(&path).unwrap().write_all(&bytes).unwrap();
+
+        let mut dynamic =
+            unsafe { MmapAnyVec::from_mmap::<u64>(File::open(&path).unwrap()) }.unwrap();
+        assert_eq!(dynamic.len(), 1000);
+        assert_eq!(dynamic.get::<u64, _>(999), Some(&999));
+
+        // Copy-on-write: changes, including growth, stay in memory.
+        *dynamic.first_mut::<u64>().unwrap() = 7;
+        dynamic.push(1000u64);
+        assert_eq!(dynamic.first::<u64>(), Some(&7));
+        assert_eq!(dynamic.len(), 1001);
+        drop(dynamic);
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_from_mmap_bad_length() {
+        let path = temp_path("bad-length");
+        File::create(&path).unwrap().write_all(&[0; 7]).unwrap();
+        assert!(unsafe { MmapAnyVec::from_mmap::<u32>(File::open(&path).unwrap()) }.is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_mmap_growable() {
+        let path = temp_path("growable");
+        let _ = fs::remove_file(&path);
+
+        let mut dynamic = unsafe { MmapAnyVec::mmap_growable::<f64>(open(&path)) }.unwrap();
+        for i in 0..100 {
+            dynamic.push(i as f64);
+        }
+        let file = dynamic.into_file().unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 800);
+
+        // Reopen, and keep appending.
+        let mut dynamic = unsafe { MmapAnyVec::mmap_growable::<f64>(open(&path)) }.unwrap();
+        assert_eq!(dynamic.len(), 100);
+        dynamic.push(100.0f64);
+        dynamic.flush().unwrap();
+        drop(dynamic);
+
+        let reopened = unsafe { AnyVec::from_mmap::<f64>(File::open(&path).unwrap()) }.unwrap();
+        let expected: Vec<f64> = (0..101).map(|x| x as f64).collect();
+        assert_eq!(reopened.cast_slice::<f64>(), &expected[..]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_drop_trims_growable() {
+        let path = temp_path("drop-trims");
+        let _ = fs::remove_file(&path);
+
+        let mut dynamic = unsafe { MmapAnyVec::mmap_growable::<u32>(open(&path)) }.unwrap();
+        for i in 0..5u32 {
+            dynamic.push(i);
+        }
+        assert!(dynamic.capacity() > 5);
+        drop(dynamic);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 20);
+
+        let mut dynamic = unsafe { MmapAnyVec::mmap_growable::<u32>(open(&path)) }.unwrap();
+        dynamic.clear();
+        drop(dynamic);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_swap_storage_trims_growable() {
+        let path = temp_path("swap-trims");
+        let other_path = temp_path("swap-trims-other");
+        let _ = fs::remove_file(&path);
+        File::create(&other_path)
+            .unwrap()
+            .write_all(&[0; 4])
+            .unwrap();
+
+        let mut growable = unsafe { MmapAnyVec::mmap_growable::<u32>(open(&path)) }.unwrap();
+        for i in 0..5u32 {
+            growable.push(i);
+        }
+        let mut other =
+            unsafe { MmapAnyVec::from_mmap::<u32>(File::open(&other_path).unwrap()) }.unwrap();
+        // The growable file's buffer still gets trimmed by whoever holds it.
+        growable.swap_storage(&mut other);
+        drop(growable);
+        drop(other);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 20);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&other_path).unwrap();
+    }
+
+    #[test]
+    fn test_into_file_after_growing() {
+        let path = temp_path("into-file-grown");
+        File::create(&path).unwrap().write_all(&[1, 2]).unwrap();
+
+        // Growing moves a read-only vector onto the heap, which ``into_file``
+        // has to free.
+        let mut dynamic =
+            unsafe { MmapAnyVec::from_mmap::<u8>(File::open(&path).unwrap()) }.unwrap();
+        dynamic.push(3u8);
+        let file = dynamic.into_file().unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
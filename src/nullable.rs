@@ -0,0 +1,249 @@
+//! Vectors with missing values.
+
+use std::any::Any;
+
+use crate::{AnyRef, AnyValue, AnyVec};
+
+/// An ``AnyVec`` whose elements may be null, tracked by a validity bitmap.
+///
+/// Only the non-null elements are stored, densely, so no placeholder value
+/// is needed for the element type. Finding an element takes constant time,
+/// using a running count of the non-null elements before each word of the
+/// bitmap.
+pub struct NullableAnyVec {
+    // The non-null elements, in order.
+    values: AnyVec,
+    // Bit ``i % 64`` of word ``i / 64`` is set if element ``i`` isn't null.
+    // Bits at or past ``len`` are always clear.
+    validity: Vec<u64>,
+    // ``ranks[w]`` is the number of set bits in ``validity[..w]``.
+    ranks: Vec<usize>,
+    len: usize,
+}
+
+impl NullableAnyVec {
+    pub fn new<T: Any>() -> NullableAnyVec {
+        NullableAnyVec::from_anyvec(AnyVec::new::<T>())
+    }
+
+    /// Wrap ``values`` with no nulls.
+    pub fn from_anyvec(values: AnyVec) -> NullableAnyVec {
+        let len = values.len();
+        let mut validity = vec![u64::MAX; len / 64];
+        if len % 64 != 0 {
+            validity.push((1 << (len % 64)) - 1);
+        }
+        let ranks = (0..validity.len()).map(|w| w * 64).collect();
+        NullableAnyVec {
+            values,
+            validity,
+            ranks,
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.len - self.values.len()
+    }
+
+    /// The non-null elements, in order.
+    pub fn values(&self) -> &AnyVec {
+        &self.values
+    }
+
+    /// Panics if ``index`` is out of bounds.
+    pub fn is_null(&self, index: usize) -> bool {
+        self.check_bounds(index);
+        self.validity[index / 64] & (1 << (index % 64)) == 0
+    }
+
+    fn check_bounds(&self, index: usize) {
+        if index >= self.len {
+            panic!(
+                "Index {} out of bounds for vector of length {}",
+                index, self.len
+            );
+        }
+    }
+
+    /// The number of non-null elements before ``index``.
+    fn rank(&self, index: usize) -> usize {
+        let partial = self.validity[index / 64] & ((1 << (index % 64)) - 1);
+        self.ranks[index / 64] + partial.count_ones() as usize
+    }
+
+    fn push_validity(&mut self, valid: bool) {
+        if self.len.is_multiple_of(64) {
+            let rank = match (self.ranks.last(), self.validity.last()) {
+                (Some(rank), Some(word)) => rank + word.count_ones() as usize,
+                _ => 0,
+            };
+            self.ranks.push(rank);
+            self.validity.push(0);
+        }
+        if valid {
+            self.validity[self.len / 64] |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+
+    pub fn push<T: Any>(&mut self, value: T) {
+        self.values.push(value);
+        self.push_validity(true);
+    }
+
+    pub fn push_value(&mut self, value: AnyValue) {
+        self.values.push_value(value);
+        self.push_validity(true);
+    }
+
+    pub fn push_null(&mut self) {
+        self.push_validity(false);
+    }
+
+    /// The element at ``index``, or ``None`` if it's null.
+    ///
+    /// Panics if ``index`` is out of bounds.
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'_>> {
+        if self.is_null(index) {
+            None
+        } else {
+            self.values.get_ref(self.rank(index))
+        }
+    }
+
+    /// Panics if ``index`` is out of bounds or the element type isn't ``T``.
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T> {
+        self.values.vtable.assert_typecheck::<T>();
+        self.get_ref(index)
+            .map(|value| value.downcast_ref::<T>().unwrap())
+    }
+
+    /// Every element, with ``None`` for nulls.
+    pub fn iter(&self) -> impl Iterator<Item = Option<AnyRef<'_>>> + '_ {
+        let mut values = self.values.as_any_slice().iter();
+        (0..self.len).map(move |i| {
+            if self.validity[i / 64] & (1 << (i % 64)) == 0 {
+                None
+            } else {
+                values.next()
+            }
+        })
+    }
+
+    /// Panics if the element type isn't ``T``.
+    pub fn into_options<T: Any>(self) -> Vec<Option<T>> {
+        let NullableAnyVec {
+            values,
+            validity,
+            len,
+            ..
+        } = self;
+        let mut values = values.into_vec::<T>().into_iter();
+        (0..len)
+            .map(|i| {
+                if validity[i / 64] & (1 << (i % 64)) == 0 {
+                    None
+                } else {
+                    values.next()
+                }
+            })
+            .collect()
+    }
+}
+
+impl<T: Any> From<Vec<Option<T>>> for NullableAnyVec {
+    fn from(options: Vec<Option<T>>) -> NullableAnyVec {
+        let mut result = NullableAnyVec::new::<T>();
+        for option in options {
+            match option {
+                Some(value) => result.push(value),
+                None => result.push_null(),
+            }
+        }
+        result
+    }
+}
+
+/// Nulls equal each other and nothing else; non-null elements are compared
+/// as with ``AnyVec``'s ``==``.
+impl PartialEq for NullableAnyVec {
+    fn eq(&self, other: &NullableAnyVec) -> bool {
+        self.len == other.len && self.validity == other.validity && self.values == other.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NullableAnyVec;
+    use crate::AnyVec;
+
+    #[test]
+    fn test_push_and_get() {
+        let mut prices = NullableAnyVec::new::<f64>();
+        prices.push(9.5f64);
+        prices.push_null();
+        prices.push(10.0f64);
+        assert_eq!((prices.len(), prices.null_count()), (3, 1));
+        assert!(prices.is_null(1));
+        assert_eq!(prices.get::<f64>(0), Some(&9.5));
+        assert_eq!(prices.get::<f64>(1), None);
+        assert_eq!(prices.get::<f64>(2), Some(&10.0));
+        let nulls: Vec<bool> = prices.iter().map(|value| value.is_none()).collect();
+        assert_eq!(nulls, vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_options_round_trip() {
+        let mut options: Vec<Option<String>> = (0..100)
+            .map(|i| Some(i.to_string()).filter(|_| i % 3 != 0))
+            .collect();
+        options.push(None);
+        let nullable = NullableAnyVec::from(options.clone());
+        assert_eq!(nullable.null_count(), 35);
+        assert_eq!(nullable.get::<String>(98).unwrap(), "98");
+        assert_eq!(nullable.into_options::<String>(), options);
+    }
+
+    #[test]
+    fn test_null_aware_equality() {
+        let a = NullableAnyVec::from(vec![Some(1u32), None, Some(3)]);
+        assert!(a == NullableAnyVec::from(vec![Some(1u32), None, Some(3)]));
+        assert!(a != NullableAnyVec::from(vec![Some(1u32), Some(3), None]));
+        assert!(a != NullableAnyVec::from(vec![Some(1u64), None, Some(3)]));
+
+        let dense = NullableAnyVec::from_anyvec(AnyVec::from_vec::<u32>(vec![1; 64]));
+        assert!(dense == NullableAnyVec::from(vec![Some(1u32); 64]));
+    }
+
+    #[test]
+    fn test_get_across_words() {
+        let options: Vec<Option<u32>> = (0..1000).map(|i| Some(i).filter(|i| i % 7 != 0)).collect();
+        let mut nullable = NullableAnyVec::from(options.clone());
+        for (i, option) in options.iter().enumerate() {
+            assert_eq!(nullable.get::<u32>(i), option.as_ref());
+        }
+
+        let mut dense = NullableAnyVec::from_anyvec(AnyVec::from_vec::<u32>((0..130).collect()));
+        dense.push_null();
+        dense.push(131u32);
+        assert_eq!(dense.get::<u32>(129), Some(&129));
+        assert_eq!(dense.get::<u32>(131), Some(&131));
+        nullable.push(1000u32);
+        assert_eq!(nullable.get::<u32>(1000), Some(&1000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 out of bounds for vector of length 3")]
+    fn test_get_out_of_bounds() {
+        NullableAnyVec::from(vec![Some(1u32), None, None]).get_ref(3);
+    }
+}
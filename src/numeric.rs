@@ -0,0 +1,125 @@
+//! Numeric element types and coercion between them.
+
+use std::any::Any;
+use std::convert::TryFrom;
+
+/// A numeric element widened onto the cast lattice shared by every primitive
+/// number type: integers become ``i128`` and floats become ``f64``, both of
+/// which represent their inputs exactly.
+#[derive(Clone, Copy, Debug)]
+pub enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+// 2^127, the first float too large to convert to an ``i128``.
+const I128_LIMIT: f64 = 170_141_183_460_469_231_731_687_303_715_884_105_728.0;
+
+impl PartialEq for Number {
+    /// Compare by exact mathematical value, so ``Int(3) == Float(3.0)`` but
+    /// ``Int(3) != Float(3.5)``.
+    fn eq(&self, other: &Number) -> bool {
+        match (*self, *other) {
+            (Number::Int(a), Number::Int(b)) => a == b,
+            (Number::Float(a), Number::Float(b)) => a == b,
+            (Number::Int(i), Number::Float(f)) | (Number::Float(f), Number::Int(i)) => {
+                f.fract() == 0.0 && (-I128_LIMIT..I128_LIMIT).contains(&f) && f as i128 == i
+            }
+        }
+    }
+}
+
+/// A primitive number type that can take part in numeric coercion.
+pub trait Numeric: Any + Copy {
+    fn to_number(self) -> Number;
+
+    /// Convert back from the lattice. Integers return ``None`` unless the
+    /// value is represented exactly; floats round to the nearest value.
+    fn from_number(number: Number) -> Option<Self>;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Numeric for $t {
+                fn to_number(self) -> Number {
+                    Number::Int(self as i128)
+                }
+
+                fn from_number(number: Number) -> Option<$t> {
+                    let wide = match number {
+                        Number::Int(i) => i,
+                        // Saturates, but then the comparison below fails.
+                        Number::Float(f) if number == Number::Int(f as i128) => f as i128,
+                        Number::Float(_) => return None,
+                    };
+                    <$t>::try_from(wide).ok()
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_float {
+    ($($t:ty),*) => {
+        $(
+            impl Numeric for $t {
+                fn to_number(self) -> Number {
+                    Number::Float(self as f64)
+                }
+
+                fn from_number(number: Number) -> Option<$t> {
+                    Some(match number {
+                        Number::Int(i) => i as $t,
+                        Number::Float(f) => f as $t,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+impl_float!(f32, f64);
+
+/// How erased comparisons treat elements of different numeric types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericCoercion {
+    /// Elements are only equal if they have the same type (the default for
+    /// ``==``).
+    Strict,
+    /// Numeric elements are compared by value on the cast lattice, so ``3i64``
+    /// equals ``3.0f64``. Non-numeric elements are compared strictly.
+    Lossless,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Number, Numeric};
+
+    #[test]
+    fn test_number_eq() {
+        assert_eq!(3i64.to_number(), 3.0f64.to_number());
+        assert_eq!(3u8.to_number(), 3i32.to_number());
+        assert_eq!(0.5f32.to_number(), 0.5f64.to_number());
+        assert_eq!((1u64 << 60).to_number(), ((1u64 << 60) as f64).to_number());
+
+        assert_ne!(3i64.to_number(), 3.5f64.to_number());
+        assert_ne!((-1i8).to_number(), 255u8.to_number());
+        assert_ne!(0.1f32.to_number(), 0.1f64.to_number());
+        assert_ne!(Number::Int(0), Number::Float(f64::NAN));
+        assert_ne!(Number::Int(i128::MAX), Number::Float(f64::INFINITY));
+        assert_ne!(Number::Int(i128::MAX), Number::Float(1e39));
+    }
+
+    #[test]
+    fn test_from_number() {
+        assert_eq!(u8::from_number(Number::Int(255)), Some(255));
+        assert_eq!(u8::from_number(Number::Int(256)), None);
+        assert_eq!(i64::from_number(Number::Float(-3.0)), Some(-3));
+        assert_eq!(i64::from_number(Number::Float(3.5)), None);
+        assert_eq!(i64::from_number(Number::Float(f64::NAN)), None);
+        assert_eq!(f32::from_number(Number::Int(3)), Some(3.0));
+        assert_eq!(f64::from_number(Number::Float(0.5)), Some(0.5));
+    }
+}
@@ -0,0 +1,260 @@
+//! Parallel iteration and sorting over erased elements, enabled by the
+//! ``rayon`` feature.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Deref;
+use std::ptr;
+
+use rayon::prelude::*;
+
+use crate::{Allocator, AnyRef, AnySlice, AnyVec, CapabilitySet};
+
+/// An [`AnyRef`] to an element whose type is ``Sync``, which makes it safe
+/// to share between threads.
+#[derive(Clone, Copy)]
+pub struct SyncRef<'a>(AnyRef<'a>);
+
+// Only created for elements whose vtable has the ``SYNC`` capability.
+unsafe impl Send for SyncRef<'_> {}
+unsafe impl Sync for SyncRef<'_> {}
+
+impl<'a> SyncRef<'a> {
+    pub fn into_inner(self) -> AnyRef<'a> {
+        self.0
+    }
+}
+
+impl<'a> Deref for SyncRef<'a> {
+    type Target = AnyRef<'a>;
+
+    fn deref(&self) -> &AnyRef<'a> {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SyncRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+// A slice that worker threads may read from.
+#[derive(Clone, Copy)]
+struct Shared<'a>(AnySlice<'a>);
+
+unsafe impl Send for Shared<'_> {}
+unsafe impl Sync for Shared<'_> {}
+
+impl<'a> AnySlice<'a> {
+    /// A parallel iterator over references to our elements.
+    ///
+    /// Panics if the element type doesn't have the ``SYNC`` capability.
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = SyncRef<'a>> + 'a {
+        self.vtable().assert_capabilities(CapabilitySet::SYNC);
+        let shared = Shared(*self);
+        (0..self.len())
+            .into_par_iter()
+            .map(move |i| SyncRef(shared.0.get_ref(i).unwrap()))
+    }
+}
+
+// Runs no longer than this are sorted on a single thread.
+const SEQUENTIAL_LEN: usize = 4096;
+
+// A run of elements to sort, and scratch space of the same size to merge
+// through.
+#[derive(Clone, Copy)]
+struct Run {
+    data: *mut u8,
+    scratch: *mut u8,
+    len: usize,
+}
+
+// Only created for elements that are both ``Send`` and ``Sync``.
+unsafe impl Send for Run {}
+
+type RawCmp<'a> = dyn Fn(*const u8, *const u8) -> Ordering + Sync + 'a;
+
+// Elements are only ever moved by copying them into the scratch space and
+// then copying the scratch space back, so if ``cmp`` panics, ``run`` still
+// holds each element exactly once.
+unsafe fn sort_run(run: Run, size: usize, cmp: &RawCmp) {
+    let Run { data, scratch, len } = run;
+    if len <= SEQUENTIAL_LEN {
+        let mut order: Vec<usize> = (0..len).collect();
+        order.sort_by(|&i, &j| cmp(data.add(i * size), data.add(j * size)));
+        for (k, &i) in order.iter().enumerate() {
+            ptr::copy_nonoverlapping(data.add(i * size), scratch.add(k * size), size);
+        }
+        ptr::copy_nonoverlapping(scratch, data, len * size);
+        return;
+    }
+
+    let mid = len / 2;
+    let left = Run {
+        data,
+        scratch,
+        len: mid,
+    };
+    let right = Run {
+        data: data.add(mid * size),
+        scratch: scratch.add(mid * size),
+        len: len - mid,
+    };
+    rayon::join(
+        move || sort_run(left, size, cmp),
+        move || sort_run(right, size, cmp),
+    );
+
+    let (mut i, mut j) = (0, mid);
+    for k in 0..len {
+        // Take from the right half only when it's strictly smaller, which
+        // keeps the sort stable.
+        let take_right =
+            j < len && (i == mid || cmp(data.add(j * size), data.add(i * size)) == Ordering::Less);
+        let from = if take_right { &mut j } else { &mut i };
+        ptr::copy_nonoverlapping(data.add(*from * size), scratch.add(k * size), size);
+        *from += 1;
+    }
+    ptr::copy_nonoverlapping(scratch, data, len * size);
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// A parallel iterator over references to our elements.
+    ///
+    /// Panics if the element type doesn't have the ``SYNC`` capability.
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = SyncRef<'_>> + '_ {
+        self.as_any_slice().par_iter()
+    }
+
+    /// Sort our elements in parallel with a stable merge sort, using the
+    /// element type's ordering.
+    ///
+    /// Panics if the element type doesn't have the ``ORD``, ``SEND`` and
+    /// ``SYNC`` capabilities.
+    pub fn par_sort(&mut self) {
+        self.assert_capabilities(CapabilitySet::ORD);
+        let cmp = self.vtable.require_cmp();
+        self.par_sort_raw(&move |a, b| cmp(a, b));
+    }
+
+    /// Sort our elements in parallel with a stable merge sort, using
+    /// ``compare``.
+    ///
+    /// Panics if the element type doesn't have the ``SEND`` and ``SYNC``
+    /// capabilities.
+    pub fn par_sort_by<F>(&mut self, compare: F)
+    where
+        F: Fn(AnyRef, AnyRef) -> Ordering + Sync,
+    {
+        let vtable = self.vtable;
+        self.par_sort_raw(&move |a, b| unsafe {
+            compare(AnyRef::new(a, vtable), AnyRef::new(b, vtable))
+        });
+    }
+
+    fn par_sort_raw(&mut self, cmp: &RawCmp) {
+        self.assert_capabilities(CapabilitySet::SEND | CapabilitySet::SYNC);
+        let size = self.vtable.size;
+        if self.length < 2 || size == 0 {
+            return;
+        }
+        let mut scratch: Vec<u8> = Vec::with_capacity(self.length * size);
+        let run = Run {
+            data: self.data,
+            scratch: scratch.as_mut_ptr(),
+            len: self.length,
+        };
+        unsafe { sort_run(run, size, cmp) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    use rayon::prelude::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_par_iter() {
+        let dynamic = AnyVec::from_vec::<u64>((0..10_000).collect());
+        let sum: u64 = dynamic
+            .par_iter()
+            .map(|r| *r.downcast_ref::<u64>().unwrap())
+            .sum();
+        assert_eq!(sum, 49_995_000);
+
+        let evens = dynamic
+            .par_iter()
+            .filter(|r| r.downcast_ref::<u64>().unwrap() % 2 == 0)
+            .count();
+        assert_eq!(evens, 5_000);
+    }
+
+    #[test]
+    fn test_par_iter_order() {
+        let dynamic = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        let strings: Vec<String> = dynamic
+            .as_any_slice()
+            .par_iter()
+            .map(|r| r.downcast_ref::<String>().unwrap().clone())
+            .collect();
+        assert_eq!(strings, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_par_sort() {
+        // Big enough to split across threads several times.
+        let mut state = 1u64;
+        let values: Vec<u64> = (0..50_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                state >> 40
+            })
+            .collect();
+        let mut expected = values.clone();
+        expected.sort();
+
+        let mut dynamic = AnyVec::from_vec(values);
+        dynamic.par_sort();
+        assert_eq!(dynamic.into_vec::<u64>(), expected);
+
+        let mut strings = AnyVec::from_vec(vec![String::from("b"), String::from("a")]);
+        strings.par_sort();
+        assert_eq!(strings.into_vec::<String>(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_par_sort_by_is_stable() {
+        let pairs: Vec<(u32, usize)> = (0..20_000).map(|i| ((i % 7) as u32, i)).collect();
+        let mut dynamic = AnyVec::from_vec(pairs)
+            .with_send::<(u32, usize)>()
+            .with_sync::<(u32, usize)>();
+        dynamic.par_sort_by(|a, b| {
+            let (a, b) = (
+                a.downcast_ref::<(u32, usize)>().unwrap(),
+                b.downcast_ref::<(u32, usize)>().unwrap(),
+            );
+            a.0.cmp(&b.0)
+        });
+        let sorted = dynamic.into_vec::<(u32, usize)>();
+        assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing capabilities: SEND | SYNC")]
+    fn test_par_sort_requires_send_sync() {
+        let mut shared = AnyVec::from_vec(vec![Rc::new(1)]).with_ord::<Rc<i32>>();
+        shared.par_sort();
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing capabilities: SYNC")]
+    fn test_par_iter_requires_sync() {
+        let cells = AnyVec::from_vec(vec![Cell::new(1)]);
+        cells.par_iter().count();
+    }
+}
@@ -0,0 +1,542 @@
+//! Storing tables of vectors as Parquet files, enabled by the ``parquet``
+//! feature.
+//!
+//! Files are read and written with the [``parquet``](https://docs.rs/parquet)
+//! crate. We write a single row group of required columns, split into pages
+//! and encoded as that crate sees fit, and record each column's element type
+//! in the file's key/value metadata so that it reloads as the same type. The
+//! reader accepts any file of flat, required, uncompressed columns,
+//! inferring element types from the Parquet schema when our metadata is
+//! missing. Columns can hold primitive numbers, ``bool`` or ``String``.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+use parquet::column::reader::{get_typed_column_reader, ColumnReader};
+use parquet::data_type::{
+    BoolType, ByteArray, ByteArrayType, DataType, DoubleType, FloatType, Int32Type, Int64Type,
+};
+use parquet::errors::ParquetError as FormatError;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use parquet::schema::types::Type as SchemaType;
+
+use crate::AnyVec;
+
+const TYPE_KEY_PREFIX: &str = "anyvector.type.";
+
+// How many values to decode at a time.
+const BATCH_SIZE: usize = 8192;
+
+/// Why reading or writing a Parquet file failed.
+#[derive(Debug)]
+pub enum ParquetError {
+    Io(io::Error),
+    /// The file, or a column, uses something we can't handle.
+    Unsupported(String),
+    /// The file isn't valid Parquet.
+    Corrupt(String),
+}
+
+impl fmt::Display for ParquetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParquetError::Io(err) => write!(f, "{}", err),
+            ParquetError::Unsupported(what) => write!(f, "Unsupported: {}", what),
+            ParquetError::Corrupt(what) => write!(f, "Corrupt Parquet file: {}", what),
+        }
+    }
+}
+
+impl Error for ParquetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParquetError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParquetError {
+    fn from(err: io::Error) -> ParquetError {
+        ParquetError::Io(err)
+    }
+}
+
+impl From<FormatError> for ParquetError {
+    fn from(err: FormatError) -> ParquetError {
+        match err {
+            FormatError::External(err) => match err.downcast::<io::Error>() {
+                Ok(err) => ParquetError::Io(*err),
+                Err(err) => ParquetError::Corrupt(err.to_string()),
+            },
+            FormatError::NYI(what) => ParquetError::Unsupported(what),
+            err => ParquetError::Corrupt(err.to_string()),
+        }
+    }
+}
+
+/// How one of our element types is stored.
+struct Encoding {
+    type_name: &'static str,
+    physical: PhysicalType,
+    converted: ConvertedType,
+}
+
+macro_rules! encodings {
+    ($($t:ty => $physical:ident, $converted:ident);* $(;)?) => {
+        const ENCODINGS: &[Encoding] = &[
+            $(Encoding {
+                type_name: stringify!($t),
+                physical: PhysicalType::$physical,
+                converted: ConvertedType::$converted,
+            },)*
+        ];
+    };
+}
+
+// Earlier entries win when inferring types from a schema.
+encodings!(
+    i32 => INT32, NONE;
+    i8 => INT32, INT_8;
+    i16 => INT32, INT_16;
+    u8 => INT32, UINT_8;
+    u16 => INT32, UINT_16;
+    u32 => INT32, UINT_32;
+    i64 => INT64, NONE;
+    u64 => INT64, UINT_64;
+    isize => INT64, NONE;
+    usize => INT64, UINT_64;
+    f32 => FLOAT, NONE;
+    f64 => DOUBLE, NONE;
+    bool => BOOLEAN, NONE;
+    String => BYTE_ARRAY, UTF8;
+);
+
+fn encoding_of(vec: &AnyVec) -> Option<&'static Encoding> {
+    // ``String`` reports its full path.
+    let name = vec
+        .vtable
+        .display_name
+        .trim_start_matches("alloc::string::");
+    ENCODINGS.iter().find(|e| e.type_name == name)
+}
+
+// Write ``values`` to a column whose physical type is ``T``, which splits them
+// into pages as it goes.
+fn write_values<T: DataType>(
+    column: &mut SerializedColumnWriter<'_>,
+    values: &[T::T],
+) -> Result<(), FormatError> {
+    column.typed::<T>().write_batch(values, None, None)?;
+    Ok(())
+}
+
+fn write_column(column: &mut SerializedColumnWriter<'_>, vec: &AnyVec) -> Result<(), FormatError> {
+    let slice = vec.as_any_slice();
+    macro_rules! plain {
+        ($($t:ty => $physical:ty, $wide:ty),*) => {
+            $(
+                if let Some(values) = slice.downcast::<$t>() {
+                    let values: Vec<$wide> = values.iter().map(|&v| v as $wide).collect();
+                    return write_values::<$physical>(column, &values);
+                }
+            )*
+        };
+    }
+
+    plain!(
+        i8 => Int32Type, i32, i16 => Int32Type, i32, u8 => Int32Type, i32,
+        u16 => Int32Type, i32, u32 => Int32Type, i32, u64 => Int64Type, i64,
+        isize => Int64Type, i64, usize => Int64Type, i64
+    );
+    if let Some(values) = slice.downcast::<i32>() {
+        return write_values::<Int32Type>(column, values);
+    }
+    if let Some(values) = slice.downcast::<i64>() {
+        return write_values::<Int64Type>(column, values);
+    }
+    if let Some(values) = slice.downcast::<f32>() {
+        return write_values::<FloatType>(column, values);
+    }
+    if let Some(values) = slice.downcast::<f64>() {
+        return write_values::<DoubleType>(column, values);
+    }
+    if let Some(values) = slice.downcast::<bool>() {
+        return write_values::<BoolType>(column, values);
+    }
+    let strings = slice.downcast::<String>().unwrap();
+    let values: Vec<ByteArray> = strings.iter().map(|s| s.as_str().into()).collect();
+    write_values::<ByteArrayType>(column, &values)
+}
+
+// Read every value in ``column``, whose physical type is ``T``.
+fn read_values<T: DataType>(column: ColumnReader) -> Result<Vec<T::T>, FormatError> {
+    let mut reader = get_typed_column_reader::<T>(column);
+    let mut values = Vec::new();
+    loop {
+        let (records, _, _) = reader.read_records(BATCH_SIZE, None, None, &mut values)?;
+        if records == 0 {
+            return Ok(values);
+        }
+    }
+}
+
+// An empty vector of the type named ``type_name``, one of ``ENCODINGS``.
+fn empty_column(type_name: &str) -> AnyVec {
+    macro_rules! empty {
+        ($($t:ty),*) => {
+            $(
+                if type_name == stringify!($t) {
+                    return AnyVec::new::<$t>();
+                }
+            )*
+        };
+    }
+
+    empty!(i8, i16, i32, u8, u16, u32, i64, u64, isize, usize, f32, f64, bool, String);
+    unreachable!()
+}
+
+// Read ``column`` as values of the type named ``type_name``.
+fn read_column(type_name: &str, column: ColumnReader) -> Result<AnyVec, ParquetError> {
+    macro_rules! plain {
+        ($($t:ty => $physical:ty),*) => {
+            $(
+                if type_name == stringify!($t) {
+                    let values = read_values::<$physical>(column)?;
+                    return Ok(AnyVec::from_vec(values.into_iter().map(|v| v as $t).collect()));
+                }
+            )*
+        };
+    }
+
+    plain!(
+        i8 => Int32Type, i16 => Int32Type, i32 => Int32Type, u8 => Int32Type,
+        u16 => Int32Type, u32 => Int32Type, i64 => Int64Type, u64 => Int64Type,
+        isize => Int64Type, usize => Int64Type, f32 => FloatType, f64 => DoubleType
+    );
+    match type_name {
+        "bool" => Ok(AnyVec::from_vec(read_values::<BoolType>(column)?)),
+        "String" => {
+            let strings = read_values::<ByteArrayType>(column)?
+                .into_iter()
+                .map(|bytes| {
+                    String::from_utf8(bytes.data().to_vec())
+                        .map_err(|_| ParquetError::Corrupt("invalid UTF-8".to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(AnyVec::from_vec(strings))
+        }
+        _ => Err(ParquetError::Unsupported(format!(
+            "element type {}",
+            type_name
+        ))),
+    }
+}
+
+/// Write ``columns`` to a Parquet file at ``path``, as a table whose columns
+/// have the given names.
+///
+/// Panics if the columns have different lengths.
+pub fn write_parquet<P: AsRef<Path>>(
+    path: P,
+    columns: &[(&str, &AnyVec)],
+) -> Result<(), ParquetError> {
+    let rows = columns.first().map_or(0, |(_, vec)| vec.len());
+    if columns.iter().any(|(_, vec)| vec.len() != rows) {
+        panic!("Columns have different lengths");
+    }
+    let mut fields = Vec::new();
+    let mut types = Vec::new();
+    for (name, vec) in columns {
+        let encoding = encoding_of(vec).ok_or_else(|| {
+            ParquetError::Unsupported(format!(
+                "column {} has element type {}",
+                name, vec.vtable.display_name
+            ))
+        })?;
+        let field = SchemaType::primitive_type_builder(name, encoding.physical)
+            .with_repetition(Repetition::REQUIRED)
+            .with_converted_type(encoding.converted)
+            .build()?;
+        fields.push(Arc::new(field));
+        types.push(KeyValue::new(
+            format!("{}{}", TYPE_KEY_PREFIX, name),
+            encoding.type_name.to_string(),
+        ));
+    }
+    let schema = SchemaType::group_type_builder("schema")
+        .with_fields(fields)
+        .build()?;
+    let properties = WriterProperties::builder()
+        .set_created_by(concat!("anyvector ", env!("CARGO_PKG_VERSION")).to_string())
+        .set_key_value_metadata(Some(types))
+        .build();
+
+    let file = File::create(path)?;
+    let mut writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(properties))?;
+    let mut row_group = writer.next_row_group()?;
+    for (_, vec) in columns {
+        let mut column = row_group
+            .next_column()?
+            .expect("schema has a column per vector");
+        write_column(&mut column, vec)?;
+        column.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Read a table of flat, required columns from the Parquet file at ``path``,
+/// e.g. one written by [`write_parquet`], returning its columns and their
+/// names.
+pub fn read_parquet<P: AsRef<Path>>(path: P) -> Result<Vec<(String, AnyVec)>, ParquetError> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    let metadata = reader.metadata();
+
+    let mut recorded_types = BTreeMap::new();
+    for kv in metadata
+        .file_metadata()
+        .key_value_metadata()
+        .into_iter()
+        .flatten()
+    {
+        if let (Some(column), Some(type_name)) = (kv.key.strip_prefix(TYPE_KEY_PREFIX), &kv.value) {
+            recorded_types.insert(column.to_string(), type_name.clone());
+        }
+    }
+
+    let schema = metadata.file_metadata().schema_descr();
+    let mut columns = Vec::new();
+    for field in schema.root_schema().get_fields() {
+        let name = field.name().to_string();
+        if !field.is_primitive() {
+            return Err(ParquetError::Unsupported(format!("nested column {}", name)));
+        }
+        let info = field.get_basic_info();
+        if info.repetition() != Repetition::REQUIRED {
+            return Err(ParquetError::Unsupported(format!(
+                "nullable column {}",
+                name
+            )));
+        }
+        let physical = field.get_physical_type();
+        let type_name = match recorded_types.remove(&name) {
+            Some(type_name) => type_name,
+            None => ENCODINGS
+                .iter()
+                .find(|e| e.physical == physical && e.converted == info.converted_type())
+                .map(|e| e.type_name.to_string())
+                .ok_or_else(|| {
+                    ParquetError::Unsupported(format!(
+                        "column {} has physical type {}",
+                        name, physical
+                    ))
+                })?,
+        };
+        let expected = ENCODINGS
+            .iter()
+            .find(|e| e.type_name == type_name)
+            .ok_or_else(|| ParquetError::Unsupported(format!("element type {}", type_name)))?;
+        if expected.physical != physical {
+            return Err(ParquetError::Corrupt(format!(
+                "column {} is recorded as {} but stored as {}",
+                name, type_name, physical
+            )));
+        }
+        let empty = empty_column(expected.type_name);
+        columns.push((name, type_name, empty));
+    }
+
+    for i in 0..reader.num_row_groups() {
+        let row_group = reader.get_row_group(i)?;
+        for (j, (_, type_name, column)) in columns.iter_mut().enumerate() {
+            let values = read_column(type_name, row_group.get_column_reader(j)?)?;
+            column.extend_from_anyvec(&values);
+        }
+    }
+    Ok(columns
+        .into_iter()
+        .map(|(name, _, column)| (name, column))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_parquet, write_parquet, ParquetError};
+    use crate::AnyVec;
+
+    use std::fs::File;
+    use std::sync::Arc;
+
+    use parquet::data_type::{DoubleType, Int32Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("anyvector-{}-{}.parquet", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let ids = AnyVec::from_vec::<u16>(vec![1, 2, 65535]);
+        let scores = AnyVec::from_vec::<f64>(vec![0.5, -1.0, 2.25]);
+        let flags = AnyVec::from_vec(vec![true, false, true]);
+        let names = AnyVec::from_vec(vec![String::from("a"), String::new(), String::from("ccc")]);
+        let sizes = AnyVec::from_vec::<usize>(vec![3, 2, 1]);
+
+        let path = temp_path("round-trip");
+        write_parquet(
+            &path,
+            &[
+                ("id", &ids),
+                ("score", &scores),
+                ("flag", &flags),
+                ("name", &names),
+                ("size", &sizes),
+            ],
+        )
+        .unwrap();
+        let mut columns = read_parquet(&path).unwrap().into_iter();
+        std::fs::remove_file(&path).unwrap();
+
+        let (name, id) = columns.next().unwrap();
+        assert_eq!(name, "id");
+        assert!(id == ids);
+        assert!(columns.next().unwrap().1 == scores);
+        assert!(columns.next().unwrap().1 == flags);
+        assert!(columns.next().unwrap().1 == names);
+        // The recorded type name keeps this from reloading as u64.
+        assert_eq!(columns.next().unwrap().1.into_vec::<usize>(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_multiple_pages() {
+        let ids = AnyVec::from_vec::<u64>((0..500_000).collect());
+        let path = temp_path("multiple-pages");
+        write_parquet(&path, &[("id", &ids)]).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let pages = reader
+            .get_row_group(0)
+            .unwrap()
+            .get_column_page_reader(0)
+            .unwrap()
+            .count();
+        assert!(pages > 1);
+        let mut columns = read_parquet(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(columns.pop().unwrap().1 == ids);
+    }
+
+    #[test]
+    fn test_read_with_parquet_crate() {
+        let ids = AnyVec::from_vec::<i64>(vec![7, -8]);
+        let names = AnyVec::from_vec(vec![String::from("a"), String::from("bb")]);
+        let path = temp_path("read-with-parquet-crate");
+        write_parquet(&path, &[("id", &ids), ("name", &names)]).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let rows: Vec<String> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_string())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            rows,
+            vec![r#"{id: 7, name: "a"}"#, r#"{id: -8, name: "bb"}"#]
+        );
+    }
+
+    #[test]
+    fn test_read_parquet_crate_file() {
+        let path = temp_path("parquet-crate-file");
+        let schema = parse_message_type(
+            "message schema { required int32 small (INT_16); required double score; }",
+        )
+        .unwrap();
+        let file = File::create(&path).unwrap();
+        let properties = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(file, Arc::new(schema), properties).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int32Type>()
+            .write_batch(&[1, -2, 1], None, None)
+            .unwrap();
+        column.close().unwrap();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<DoubleType>()
+            .write_batch(&[0.5, 0.5, 1.5], None, None)
+            .unwrap();
+        column.close().unwrap();
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        // Without our metadata, types come from the schema.
+        let mut columns = read_parquet(&path).unwrap().into_iter();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(columns.next().unwrap().1.into_vec::<i16>(), vec![1, -2, 1]);
+        assert_eq!(
+            columns.next().unwrap().1.into_vec::<f64>(),
+            vec![0.5, 0.5, 1.5]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_column() {
+        let pairs = AnyVec::from_vec(vec![(1u8, 2u8)]);
+        let err = write_parquet(temp_path("unsupported"), &[("pair", &pairs)])
+            .err()
+            .unwrap();
+        assert!(matches!(err, ParquetError::Unsupported(_)));
+    }
+
+    #[test]
+    fn test_corrupt_file() {
+        let path = temp_path("corrupt");
+        std::fs::write(&path, b"PAR1 not really").unwrap();
+        let err = read_parquet(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(err, ParquetError::Corrupt(_)));
+    }
+
+    #[test]
+    fn test_malicious_metadata() {
+        let path = temp_path("malicious");
+        let read = |meta: Vec<u8>| {
+            let mut file = b"PAR1".to_vec();
+            file.extend_from_slice(&meta);
+            file.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+            file.extend_from_slice(b"PAR1");
+            std::fs::write(&path, file).unwrap();
+            read_parquet(&path).err().unwrap()
+        };
+
+        // Lists nested far too deeply.
+        let mut nested = vec![0x19];
+        nested.resize(100_000, 0x19);
+        // A map claiming a huge number of entries.
+        let huge_map = vec![0x1b, 0xff, 0xff, 0xff, 0xff, 0x0f];
+        // A field id that overflows i16: 32767, then a delta of 1.
+        let overflowing_id = vec![0x05, 0xfe, 0xff, 0x03, 0x00, 0x15, 0x00];
+        for meta in [nested, huge_map, overflowing_id] {
+            assert!(matches!(read(meta), ParquetError::Corrupt(_)));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+//! Appending elements parsed from text, for ingesting CSV and the like into
+//! columns whose types are only known at runtime.
+
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::registry::{Entry, TypeRegistry};
+use crate::vtable::VTable;
+use crate::{Allocator, AnyVec};
+
+/// Returned when a string couldn't be parsed as an element.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The name of the element type we tried to parse.
+    pub type_name: &'static str,
+    pub input: String,
+    /// The element type's own description of the failure.
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Can't parse {:?} as {}: {}",
+            self.input, self.type_name, self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+impl TypeRegistry {
+    /// Register ``T`` with parsing enabled, so that vectors of it can be
+    /// read from text.
+    pub fn register_parse<T: Any + FromStr>(&mut self) -> &mut TypeRegistry
+    where
+        T::Err: fmt::Display,
+    {
+        self.insert(Entry {
+            vtable: VTable::new::<T>().with_parse::<T>(),
+            #[cfg(feature = "serde")]
+            deserialize: None,
+        })
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Enable parsing elements from strings. Primitive types and ``String``
+    /// have this enabled already.
+    pub fn with_parse<T: Any + FromStr>(mut self) -> AnyVec<A>
+    where
+        T::Err: fmt::Display,
+    {
+        self.vtable = self.vtable.with_parse::<T>();
+        self
+    }
+
+    /// Parse ``input`` with our element type's ``FromStr`` impl and append
+    /// the result, leaving the vector unchanged if parsing fails.
+    ///
+    /// Panics if our element type doesn't support parsing.
+    pub fn push_parsed(&mut self, input: &str) -> Result<(), ParseError> {
+        let parse = match self.vtable.parse {
+            Some(parse) => parse,
+            None => panic!("{} does not support parsing", self.vtable.display_name),
+        };
+        self.reserve(1);
+        let slot = unsafe { self.data.add(self.length * self.vtable.size) };
+        parse(input, slot)?;
+        self.length += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyVec, CapabilitySet, TypeRegistry};
+
+    use std::str::FromStr;
+
+    #[test]
+    fn test_push_parsed() {
+        let mut floats = AnyVec::new::<f64>();
+        floats.push_parsed("1.5").unwrap();
+        floats.push_parsed("-2").unwrap();
+        let err = floats.push_parsed("abc").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Can't parse "abc" as f64: invalid float literal"#
+        );
+        assert_eq!(floats.into_vec::<f64>(), vec![1.5, -2.0]);
+
+        let mut strings = AnyVec::new::<String>();
+        strings.push_parsed("text").unwrap();
+        assert_eq!(strings.into_vec::<String>(), vec!["text"]);
+    }
+
+    #[test]
+    fn test_with_parse() {
+        #[derive(Debug, PartialEq)]
+        struct Flag(bool);
+        impl FromStr for Flag {
+            type Err = &'static str;
+            fn from_str(s: &str) -> Result<Flag, &'static str> {
+                match s {
+                    "yes" => Ok(Flag(true)),
+                    "no" => Ok(Flag(false)),
+                    _ => Err("expected yes or no"),
+                }
+            }
+        }
+
+        let mut flags = AnyVec::new::<Flag>().with_parse::<Flag>();
+        assert!(flags.capabilities().contains(CapabilitySet::PARSE));
+        flags.push_parsed("yes").unwrap();
+        assert_eq!(
+            flags.push_parsed("maybe").unwrap_err().message,
+            "expected yes or no"
+        );
+        assert_eq!(flags.into_vec::<Flag>(), vec![Flag(true)]);
+
+        let mut registry = TypeRegistry::new();
+        registry.register_parse::<Flag>();
+        let mut flags = registry.new_by_name(std::any::type_name::<Flag>()).unwrap();
+        flags.push_parsed("no").unwrap();
+        assert_eq!(flags.into_vec::<Flag>(), vec![Flag(false)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support parsing")]
+    fn test_push_parsed_requires_capability() {
+        struct Opaque;
+        AnyVec::new::<Opaque>().push_parsed("x").unwrap();
+    }
+}
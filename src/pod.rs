@@ -0,0 +1,177 @@
+//! Byte-level views of plain-old-data vectors, enabled by the ``bytemuck``
+//! feature.
+
+use std::slice;
+
+use bytemuck::Pod;
+
+use crate::{Allocator, AnyVec, Global};
+
+impl AnyVec {
+    /// Build a vector of ``T``s by copying them out of ``bytes``, which
+    /// needn't be aligned.
+    ///
+    /// Panics if ``bytes``'s length isn't a multiple of ``T``'s size.
+    pub fn from_bytes<T: Pod>(bytes: &[u8]) -> AnyVec {
+        AnyVec::from_bytes_in::<T>(bytes, Global)
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    pub fn from_bytes_in<T: Pod>(bytes: &[u8], alloc: A) -> AnyVec<A> {
+        let size = std::mem::size_of::<T>();
+        if size == 0 || !bytes.len().is_multiple_of(size) {
+            panic!(
+                "Can't build {} elements from {} bytes",
+                std::any::type_name::<T>(),
+                bytes.len()
+            );
+        }
+
+        let mut result = AnyVec::new_in::<T>(alloc).with_pod::<T>();
+        result.reserve(bytes.len() / size);
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), result.data, bytes.len()) };
+        result.length = bytes.len() / size;
+        result
+    }
+
+    /// Mark our elements as plain old data, enabling ``as_bytes``. Primitive
+    /// number types have this enabled already.
+    pub fn with_pod<T: Pod>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_pod::<T>();
+        self
+    }
+
+    fn assert_pod(&self) {
+        if !self.vtable.pod {
+            panic!("{} is not plain old data", self.vtable.display_name);
+        }
+    }
+
+    /// View our elements' bytes, without copying.
+    ///
+    /// Panics if our type isn't plain old data.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.assert_pod();
+        unsafe { slice::from_raw_parts(self.data, self.length * self.vtable.size) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.assert_pod();
+        unsafe { slice::from_raw_parts_mut(self.data, self.length * self.vtable.size) }
+    }
+
+    /// Our elements' bytes along with their layout, as ``(bytes, element
+    /// size, length)``, ready to upload to a GPU vertex or storage buffer.
+    ///
+    /// Panics if our type isn't plain old data.
+    pub fn upload_view(&self) -> (&[u8], usize, usize) {
+        (self.as_bytes(), self.vtable.size, self.length)
+    }
+
+    /// Copy our elements' bytes to the start of ``dest``, e.g. a mapped
+    /// staging buffer, returning how many bytes were written.
+    ///
+    /// Panics if our type isn't plain old data, or ``dest`` is too short.
+    pub fn write_into(&self, dest: &mut [u8]) -> usize {
+        let bytes = self.as_bytes();
+        if dest.len() < bytes.len() {
+            panic!(
+                "Can't write {} bytes into a buffer of {}",
+                bytes.len(),
+                dest.len()
+            );
+        }
+        dest[..bytes.len()].copy_from_slice(bytes);
+        bytes.len()
+    }
+
+    /// View our elements as a typed slice, like ``bytemuck::cast_slice``.
+    pub fn cast_slice<T: Pod>(&self) -> &[T] {
+        bytemuck::cast_slice(self.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Vertex {
+        position: [f32; 2],
+        color: u32,
+    }
+
+    unsafe impl bytemuck::Zeroable for Vertex {}
+    unsafe impl bytemuck::Pod for Vertex {}
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let dynamic = AnyVec::from_vec::<u32>(vec![1, 2, 0xdead_beef]);
+        let bytes = dynamic.as_bytes();
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(&bytes[8..], &0xdead_beef_u32.to_ne_bytes());
+
+        // Deliberately misaligned.
+        let mut buffer = vec![0u8];
+        buffer.extend_from_slice(bytes);
+        let copy = AnyVec::from_bytes::<u32>(&buffer[1..]);
+        assert!(copy == dynamic);
+    }
+
+    #[test]
+    fn test_with_pod() {
+        let vertices = vec![
+            Vertex {
+                position: [0.0, 1.0],
+                color: 7,
+            },
+            Vertex {
+                position: [2.0, 3.0],
+                color: 9,
+            },
+        ];
+        let mut dynamic = AnyVec::from_vec(vertices.clone()).with_pod::<Vertex>();
+        assert_eq!(dynamic.as_bytes().len(), 24);
+
+        dynamic.as_bytes_mut()[8..12].copy_from_slice(&8u32.to_ne_bytes());
+        assert_eq!(dynamic.cast_slice::<Vertex>()[0].color, 8);
+        assert_eq!(dynamic.clone().into_vec::<Vertex>()[1], vertices[1]);
+    }
+
+    #[test]
+    fn test_upload() {
+        let vertices = AnyVec::from_vec(vec![Vertex {
+            position: [0.5, 1.5],
+            color: 3,
+        }])
+        .with_pod::<Vertex>();
+        let (bytes, size, len) = vertices.upload_view();
+        assert_eq!((bytes.len(), size, len), (12, 12, 1));
+
+        let mut staging = [0xffu8; 16];
+        assert_eq!(vertices.write_into(&mut staging), 12);
+        assert_eq!(&staging[..12], bytes);
+        assert_eq!(&staging[12..], &[0xff; 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't write 8 bytes into a buffer of 4")]
+    fn test_write_into_short_buffer() {
+        AnyVec::from_vec::<u32>(vec![1, 2]).write_into(&mut [0; 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not plain old data")]
+    fn test_as_bytes_requires_pod() {
+        let dynamic = AnyVec::from_vec(vec![true, false]);
+        dynamic.as_bytes();
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't build")]
+    fn test_from_bytes_length() {
+        AnyVec::from_bytes::<u32>(&[0; 7]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Sharing numeric vectors with Python through the buffer protocol, enabled
+//! by the ``pyo3`` feature.
+//!
+//! A [`PyAnyVec`] exposes its elements as a read-only buffer, so
+//! ``memoryview(v)`` or ``numpy.asarray(v)`` views them without copying. In
+//! the other direction, [`AnyVec::from_py_buffer`] copies the elements of any
+//! buffer (e.g. a numpy array) into a new vector. Both support the primitive
+//! number types, which have buffer format codes.
+
+use std::any::TypeId;
+use std::cell::Cell;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::ptr;
+
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyBufferError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::{ffi, IntoPyObjectExt};
+
+use crate::AnyVec;
+
+fn cstr(bytes: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(bytes).unwrap()
+}
+
+// The ``struct`` module format code of each supported element type.
+fn format_of(id: TypeId) -> Option<&'static CStr> {
+    let formats: [(TypeId, &'static CStr); 12] = [
+        (TypeId::of::<i8>(), cstr(b"b\0")),
+        (TypeId::of::<u8>(), cstr(b"B\0")),
+        (TypeId::of::<i16>(), cstr(b"h\0")),
+        (TypeId::of::<u16>(), cstr(b"H\0")),
+        (TypeId::of::<i32>(), cstr(b"i\0")),
+        (TypeId::of::<u32>(), cstr(b"I\0")),
+        (TypeId::of::<i64>(), cstr(b"q\0")),
+        (TypeId::of::<u64>(), cstr(b"Q\0")),
+        (TypeId::of::<isize>(), cstr(b"n\0")),
+        (TypeId::of::<usize>(), cstr(b"N\0")),
+        (TypeId::of::<f32>(), cstr(b"f\0")),
+        (TypeId::of::<f64>(), cstr(b"d\0")),
+    ];
+    formats
+        .iter()
+        .find(|(format_id, _)| *format_id == id)
+        .map(|&(_, format)| format)
+}
+
+/// An ``AnyVec`` of primitive numbers owned by Python, which exposes its
+/// elements through the buffer protocol.
+///
+/// While any buffer views are alive, the vector can't be modified or taken
+/// back, since that could move or free the memory they view.
+#[pyclass(unsendable, name = "AnyVec")]
+pub struct PyAnyVec {
+    vec: AnyVec,
+    format: &'static CStr,
+    exports: Cell<usize>,
+}
+
+impl PyAnyVec {
+    /// Fails if ``vec``'s element type isn't a primitive number.
+    pub fn new(vec: AnyVec) -> PyResult<PyAnyVec> {
+        match format_of(vec.type_id()) {
+            Some(format) => Ok(PyAnyVec {
+                vec,
+                format,
+                exports: Cell::new(0),
+            }),
+            None => Err(PyTypeError::new_err(format!(
+                "{} has no buffer format",
+                vec.type_name()
+            ))),
+        }
+    }
+
+    pub fn get(&self) -> &AnyVec {
+        &self.vec
+    }
+
+    /// The vector, unless a buffer view of it is alive.
+    pub fn get_mut(&mut self) -> Option<&mut AnyVec> {
+        if self.exports.get() == 0 {
+            Some(&mut self.vec)
+        } else {
+            None
+        }
+    }
+
+    /// Take the vector back, leaving an empty one of the same type behind,
+    /// unless a buffer view of it is alive.
+    pub fn take(&mut self) -> Option<AnyVec> {
+        self.get_mut().map(AnyVec::take_all)
+    }
+}
+
+#[pymethods]
+impl PyAnyVec {
+    fn __len__(&self) -> usize {
+        self.vec.len()
+    }
+
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if flags & ffi::PyBUF_WRITABLE == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("AnyVec buffers are read-only"));
+        }
+        let this = slf.borrow();
+        let size = this.vec.element_size();
+        // The shape and strides must outlive the view, so they're boxed and
+        // freed in ``__releasebuffer__``.
+        let layout = Box::new([this.vec.len() as isize, size as isize]);
+        unsafe {
+            (*view).buf = this.vec.as_any_slice().data() as *mut c_void;
+            (*view).len = (this.vec.len() * size) as isize;
+            (*view).readonly = 1;
+            (*view).itemsize = size as isize;
+            (*view).format = if flags & ffi::PyBUF_FORMAT == ffi::PyBUF_FORMAT {
+                this.format.as_ptr() as *mut c_char
+            } else {
+                ptr::null_mut()
+            };
+            (*view).ndim = 1;
+            let layout = Box::into_raw(layout);
+            (*view).shape = (*layout).as_mut_ptr();
+            (*view).strides = (*layout).as_mut_ptr().add(1);
+            (*view).suboffsets = ptr::null_mut();
+            (*view).internal = layout as *mut c_void;
+        }
+        this.exports.set(this.exports.get() + 1);
+        drop(this);
+        unsafe { (*view).obj = slf.into_any().into_ptr() };
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, view: *mut ffi::Py_buffer) {
+        drop(unsafe { Box::from_raw((*view).internal as *mut [isize; 2]) });
+        self.exports.set(self.exports.get() - 1);
+    }
+}
+
+macro_rules! from_py_buffer {
+    ($obj:expr, $($t:ty),*) => {
+        $(
+            if let Ok(buffer) = PyBuffer::<$t>::get($obj) {
+                return Ok(AnyVec::from_vec::<$t>(buffer.to_vec($obj.py())?));
+            }
+        )*
+    };
+}
+
+impl AnyVec {
+    /// Copy the elements of a one-dimensional Python buffer, e.g. a numpy
+    /// array or ``array.array``, into a new vector of the matching element
+    /// type.
+    pub fn from_py_buffer(obj: &Bound<'_, PyAny>) -> PyResult<AnyVec> {
+        from_py_buffer!(obj, i8, u8, i16, u16, i32, u32, i64, u64, isize, usize, f32, f64);
+        Err(PyTypeError::new_err(
+            "Expected a buffer of primitive numbers",
+        ))
+    }
+
+    /// Hand the vector to Python as a [`PyAnyVec`].
+    pub fn into_py_any_vec(self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        PyAnyVec::new(self)?.into_py_any(py)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cstr, PyAnyVec};
+    use crate::AnyVec;
+
+    use pyo3::prelude::*;
+    use pyo3::types::PyDict;
+
+    fn run<R>(f: impl FnOnce(Python<'_>) -> R) -> R {
+        Python::initialize();
+        Python::attach(f)
+    }
+
+    #[test]
+    fn test_buffer_view() {
+        run(|py| {
+            let prices = AnyVec::from_vec::<f64>(vec![9.5, 10.0]);
+            let locals = PyDict::new(py);
+            locals
+                .set_item("v", prices.into_py_any_vec(py).unwrap())
+                .unwrap();
+            let result = py
+                .eval(cstr(b"memoryview(v).tolist()\0"), None, Some(&locals))
+                .unwrap();
+            assert_eq!(result.extract::<Vec<f64>>().unwrap(), vec![9.5, 10.0]);
+            let format = py
+                .eval(
+                    cstr(b"(memoryview(v).format, len(v))\0"),
+                    None,
+                    Some(&locals),
+                )
+                .unwrap();
+            assert_eq!(
+                format.extract::<(String, usize)>().unwrap(),
+                ("d".into(), 2)
+            );
+        });
+    }
+
+    #[test]
+    fn test_views_guard_mutation() {
+        run(|py| {
+            let vec =
+                Bound::new(py, PyAnyVec::new(AnyVec::from_vec::<u8>(vec![1])).unwrap()).unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item("v", &vec).unwrap();
+            let view = py
+                .eval(cstr(b"memoryview(v)\0"), None, Some(&locals))
+                .unwrap();
+            assert!(vec.borrow_mut().get_mut().is_none());
+            view.call_method0("release").unwrap();
+            let taken = vec.borrow_mut().take().unwrap();
+            assert_eq!(taken.into_vec::<u8>(), vec![1]);
+        });
+    }
+
+    #[test]
+    fn test_from_py_buffer() {
+        run(|py| {
+            let array = py
+                .eval(
+                    cstr(b"__import__('array').array('i', [1, -2, 3])\0"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let dynamic = AnyVec::from_py_buffer(&array).unwrap();
+            assert_eq!(dynamic.into_vec::<i32>(), vec![1, -2, 3]);
+
+            let list = py.eval(cstr(b"[1, 2]\0"), None, None).unwrap();
+            assert!(AnyVec::from_py_buffer(&list).is_err());
+            assert!(PyAnyVec::new(AnyVec::new::<String>()).is_err());
+        });
+    }
+}
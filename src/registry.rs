@@ -0,0 +1,230 @@
+//! Looking up element types by name at runtime.
+
+use std::any::{Any, TypeId};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+
+use crate::vtable::VTable;
+use crate::{AnyVec, Global};
+
+#[derive(Clone, Copy)]
+pub(crate) struct Entry {
+    pub vtable: &'static VTable,
+    #[cfg(feature = "serde")]
+    pub deserialize: Option<crate::de::DeserializeFn>,
+}
+
+// The process-wide registry, created with the primitives on first use.
+static GLOBAL: Mutex<Option<TypeRegistry>> = Mutex::new(None);
+
+/// A mapping from type names to element types, for code that only learns
+/// which types it's dealing with at runtime (e.g. schema-driven readers, or
+/// loading saved vectors).
+///
+/// Types are registered under their ``std::any::type_name``, which is also
+/// the name ``AnyVec`` reports for them, and optionally under extra aliases.
+#[derive(Default)]
+pub struct TypeRegistry {
+    entries: BTreeMap<&'static str, Entry>,
+    names: BTreeMap<TypeId, &'static str>,
+}
+
+macro_rules! register_primitives {
+    ($registry:expr, $($t:ty),*) => {
+        $(
+            #[cfg(feature = "serde")]
+            $registry.register_deserialize::<$t>();
+            #[cfg(not(feature = "serde"))]
+            $registry.register::<$t>();
+        )*
+    };
+}
+
+impl TypeRegistry {
+    /// An empty registry.
+    pub fn new() -> TypeRegistry {
+        TypeRegistry::default()
+    }
+
+    /// A registry holding every primitive number type, ``bool``, ``char`` and
+    /// ``String`` (also available as ``"String"``).
+    pub fn with_primitives() -> TypeRegistry {
+        let mut registry = TypeRegistry::new();
+        register_primitives!(
+            registry, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, bool, char,
+            String
+        );
+        registry.alias("String", "alloc::string::String");
+        registry
+    }
+
+    /// Run ``f`` with the process-wide registry, which starts out holding the
+    /// same types as [`TypeRegistry::with_primitives`].
+    pub fn with_global<R, F: FnOnce(&mut TypeRegistry) -> R>(f: F) -> R {
+        let mut global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        f(global.get_or_insert_with(TypeRegistry::with_primitives))
+    }
+
+    /// Register ``T``, replacing any previous registration of it.
+    pub fn register<T: Any>(&mut self) -> &mut TypeRegistry {
+        self.insert(Entry {
+            vtable: VTable::new::<T>(),
+            #[cfg(feature = "serde")]
+            deserialize: None,
+        })
+    }
+
+    pub(crate) fn insert(&mut self, entry: Entry) -> &mut TypeRegistry {
+        let name = entry.vtable.display_name;
+        self.entries.insert(name, entry);
+        self.names.insert(entry.vtable.id(), name);
+        self
+    }
+
+    /// Make the type registered as ``name`` available as ``alias`` too.
+    ///
+    /// Panics if ``name`` isn't registered.
+    pub fn alias(&mut self, alias: &'static str, name: &str) -> &mut TypeRegistry {
+        let entry = match self.entries.get(name) {
+            Some(entry) => *entry,
+            None => panic!("{} is not registered", name),
+        };
+        self.entries.insert(alias, entry);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Names (including aliases) of every registered type, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.keys().copied()
+    }
+
+    /// The canonical name of the registered type with id ``id``.
+    pub fn name_of(&self, id: TypeId) -> Option<&'static str> {
+        self.names.get(&id).copied()
+    }
+
+    /// An empty vector of the type registered as ``name``.
+    pub fn new_by_name(&self, name: &str) -> Option<AnyVec> {
+        self.get(name)
+            .map(|entry| AnyVec::from_vtable_in(entry.vtable, Global))
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.get(name)
+    }
+}
+
+/// Register element types in the global [`TypeRegistry`], so that code which
+/// only knows them by name (plugins, deserializers) can find them.
+///
+/// Each type can be followed by ``as "alias"``, and prefixed with
+/// ``deserialize`` to register it for deserialization too (which needs the
+/// ``serde`` feature):
+///
+/// ```
+/// use anyvector::{register_anyvec_type, AnyVec};
+///
+/// struct Meters(f64);
+/// struct Feet(f64);
+///
+/// register_anyvec_type!(Meters as "meters", Feet);
+/// assert!(AnyVec::new_by_name("meters").is_some());
+/// ```
+///
+/// Call it from an initialization function of the crate defining the types,
+/// e.g. a plugin's entry point. Registering a type again replaces the earlier
+/// registration.
+#[macro_export]
+macro_rules! register_anyvec_type {
+    ($($types:tt)*) => {
+        $crate::TypeRegistry::with_global(|registry| {
+            $crate::__register_anyvec_types!(registry; $($types)*);
+        })
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_anyvec_types {
+    ($registry:ident;) => {};
+    ($registry:ident; deserialize $t:ty $(as $alias:literal)? $(, $($rest:tt)*)?) => {
+        $registry.register_deserialize::<$t>();
+        $($registry.alias($alias, ::std::any::type_name::<$t>());)?
+        $crate::__register_anyvec_types!($registry; $($($rest)*)?);
+    };
+    ($registry:ident; $t:ty $(as $alias:literal)? $(, $($rest:tt)*)?) => {
+        $registry.register::<$t>();
+        $($registry.alias($alias, ::std::any::type_name::<$t>());)?
+        $crate::__register_anyvec_types!($registry; $($($rest)*)?);
+    };
+}
+
+impl AnyVec {
+    /// An empty vector of the type registered as ``name`` in the global
+    /// [`TypeRegistry`], e.g. ``"f64"``.
+    pub fn new_by_name(name: &str) -> Option<AnyVec> {
+        TypeRegistry::with_global(|registry| registry.new_by_name(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypeRegistry;
+    use crate::{AnyVec, CapabilitySet};
+
+    use std::any::TypeId;
+
+    #[test]
+    fn test_register() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<u32>().register::<String>();
+
+        assert!(registry.contains("u32"));
+        assert!(!registry.contains("f64"));
+        let names: Vec<&str> = registry.names().collect();
+        assert_eq!(names, vec!["alloc::string::String", "u32"]);
+        assert_eq!(registry.name_of(TypeId::of::<u32>()), Some("u32"));
+        assert_eq!(registry.name_of(TypeId::of::<u64>()), None);
+    }
+
+    #[test]
+    fn test_new_by_name() {
+        let mut floats = AnyVec::new_by_name("f64").unwrap();
+        floats.push(1.5f64);
+        assert!(floats.capabilities().contains(CapabilitySet::NUMERIC));
+        assert_eq!(floats.into_vec::<f64>(), vec![1.5]);
+
+        let mut strings = AnyVec::new_by_name("String").unwrap();
+        strings.push(String::from("a"));
+        assert!(AnyVec::new_by_name("f16").is_none());
+    }
+
+    #[test]
+    fn test_global_registration() {
+        struct Custom;
+        let name = std::any::type_name::<Custom>();
+        assert!(AnyVec::new_by_name(name).is_none());
+
+        TypeRegistry::with_global(|registry| {
+            registry.register::<Custom>().alias("custom", name);
+        });
+        let mut custom = AnyVec::new_by_name("custom").unwrap();
+        custom.push(Custom);
+        assert_eq!(custom.len(), 1);
+    }
+
+    #[test]
+    fn test_register_macro() {
+        struct First;
+        struct Second;
+        crate::register_anyvec_type!(First as "first", Second,);
+
+        assert!(AnyVec::new_by_name("first").is_some());
+        assert!(AnyVec::new_by_name(std::any::type_name::<First>()).is_some());
+        assert!(AnyVec::new_by_name(std::any::type_name::<Second>()).is_some());
+    }
+}
@@ -0,0 +1,115 @@
+//! Dropping expired prefixes of sorted columns, e.g. for time-based
+//! retention.
+
+use std::ptr;
+
+use crate::{Allocator, AnyValue, AnyVec};
+
+impl<A: Allocator> AnyVec<A> {
+    /// Number of leading elements that compare less than ``watermark``. We
+    /// must be sorted in ascending order.
+    fn count_before(&self, watermark: &AnyValue) -> usize {
+        self.lower_bound(watermark.as_any_ref())
+    }
+
+    /// Drop our first ``count`` elements, shifting the rest down.
+    fn remove_prefix(&mut self, count: usize) {
+        assert!(count <= self.length);
+        let remaining = self.length - count;
+        let size = self.vtable.size;
+
+        // If a destructor panics we leak the tail rather than risk dropping
+        // anything twice.
+        self.length = 0;
+        if self.vtable.needs_drop {
+            (self.vtable.drop_slice)(self.data, count);
+        }
+        unsafe { ptr::copy(self.data.add(count * size), self.data, remaining * size) };
+        self.length = remaining;
+        self.record_drops(count);
+        self.apply_shrink_policy();
+    }
+
+    /// Remove every element less than ``watermark`` and return how many were
+    /// removed. We must be sorted in ascending order.
+    ///
+    /// Panics if ``watermark``'s type doesn't match ours, or if our type
+    /// doesn't support ordering.
+    pub fn expire_before(&mut self, watermark: &AnyValue) -> usize {
+        let count = self.count_before(watermark);
+        self.remove_prefix(count);
+        count
+    }
+}
+
+/// Remove every row whose value in ``columns[key]`` is less than
+/// ``watermark``, from all of the columns, and return how many rows were
+/// removed. The key column must be sorted in ascending order.
+///
+/// Panics if the columns' lengths differ, or under the same conditions as
+/// [`AnyVec::expire_before`].
+pub fn expire_rows_before<A: Allocator>(
+    columns: &mut [AnyVec<A>],
+    key: usize,
+    watermark: &AnyValue,
+) -> usize {
+    let length = columns[key].len();
+    for column in columns.iter() {
+        if column.len() != length {
+            panic!(
+                "Columns have different lengths ({} != {})",
+                column.len(),
+                length
+            );
+        }
+    }
+
+    let count = columns[key].count_before(watermark);
+    for column in columns.iter_mut() {
+        column.remove_prefix(count);
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expire_rows_before;
+    use crate::{AnyValue, AnyVec};
+
+    use std::rc::Rc;
+
+    #[test]
+    fn test_expire_before() {
+        let mut timestamps = AnyVec::from_vec::<u64>(vec![10, 20, 20, 30, 40]);
+
+        assert_eq!(timestamps.expire_before(&AnyValue::new(5u64)), 0);
+        assert_eq!(timestamps.expire_before(&AnyValue::new(20u64)), 1);
+        assert_eq!(timestamps.expire_before(&AnyValue::new(35u64)), 3);
+        assert_eq!(timestamps.into_vec::<u64>(), vec![40]);
+    }
+
+    #[test]
+    fn test_expire_rows_before() {
+        let chan = Rc::new(());
+        let mut columns = [
+            AnyVec::from_vec::<i64>(vec![1, 2, 3, 4]),
+            AnyVec::from_vec(vec![chan.clone(), chan.clone(), chan.clone(), chan.clone()]),
+        ];
+
+        assert_eq!(expire_rows_before(&mut columns, 0, &AnyValue::new(3i64)), 2);
+        assert_eq!(Rc::strong_count(&chan), 3);
+
+        let [timestamps, payloads] = columns;
+        assert_eq!(timestamps.into_vec::<i64>(), vec![3, 4]);
+        assert_eq!(payloads.len(), 2);
+        drop(payloads);
+        assert_eq!(Rc::strong_count(&chan), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match")]
+    fn test_expire_before_typecheck() {
+        let mut timestamps = AnyVec::from_vec::<u64>(vec![10, 20]);
+        timestamps.expire_before(&AnyValue::new(15i64));
+    }
+}
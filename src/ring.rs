@@ -0,0 +1,186 @@
+//! A fixed-capacity vector that overwrites its oldest elements once full.
+
+use std::any::Any;
+use std::ptr;
+use std::slice;
+
+use crate::vtable::VTable;
+use crate::{AnyRef, AnyValue, AnyVec, Global};
+
+/// A bounded ``AnyVec``. Once it holds ``capacity`` elements, each push drops
+/// the oldest element to make room.
+///
+/// Indices are logical: index 0 is always the oldest element still held.
+pub struct RingAnyVec {
+    // Holds up to ``capacity`` elements in physical order.
+    buffer: AnyVec,
+    capacity: usize,
+    // Physical index of the oldest element, once we've wrapped around.
+    head: usize,
+}
+
+impl RingAnyVec {
+    /// Panics if ``capacity`` is zero.
+    pub fn with_fixed_capacity<T: Any>(capacity: usize) -> RingAnyVec {
+        RingAnyVec::from_vtable(VTable::new::<T>(), capacity)
+    }
+
+    fn from_vtable(vtable: &'static VTable, capacity: usize) -> RingAnyVec {
+        if capacity == 0 {
+            panic!("RingAnyVec capacity must be positive");
+        }
+        let mut buffer = AnyVec::from_vtable_in(vtable, Global);
+        buffer.reserve(capacity);
+        RingAnyVec {
+            buffer,
+            capacity,
+            head: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+
+    fn physical_index(&self, index: usize) -> usize {
+        (self.head + index) % self.capacity
+    }
+
+    /// Pointer to the slot the next element goes in. If we're full, the
+    /// oldest element is dropped first.
+    fn next_slot(&mut self) -> *mut u8 {
+        if !self.is_full() {
+            return unsafe {
+                self.buffer
+                    .data
+                    .add(self.buffer.length * self.buffer.vtable.size)
+            };
+        }
+        let slot = unsafe { self.buffer.data.add(self.head * self.buffer.vtable.size) };
+        if self.buffer.vtable.needs_drop {
+            (self.buffer.vtable.drop_slice)(slot, 1);
+        }
+        slot
+    }
+
+    /// Record that ``next_slot`` has been filled.
+    fn advance(&mut self) {
+        if self.is_full() {
+            self.head = (self.head + 1) % self.capacity;
+        } else {
+            self.buffer.length += 1;
+        }
+    }
+
+    pub fn push<T: Any>(&mut self, value: T) {
+        self.buffer.vtable.assert_typecheck::<T>();
+        let slot = self.next_slot();
+        unsafe { ptr::write(slot as *mut T, value) };
+        self.advance();
+    }
+
+    /// Append an erased value. Panics if its type doesn't match ours.
+    pub fn push_value(&mut self, value: AnyValue) {
+        self.buffer.vtable.assert_same_type(value.vtable());
+        let slot = self.next_slot();
+        let (boxed, _) = value.into_raw();
+        (self.buffer.vtable.unbox_into)(boxed, slot);
+        self.advance();
+    }
+
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T> {
+        self.buffer.vtable.assert_typecheck::<T>();
+        self.get_ref(index).and_then(|item| item.downcast_ref())
+    }
+
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'_>> {
+        if index < self.len() {
+            self.buffer.get_ref(self.physical_index(index))
+        } else {
+            None
+        }
+    }
+
+    /// Our elements, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = AnyRef<'_>> {
+        (0..self.len()).map(move |i| self.get_ref(i).unwrap())
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.head = 0;
+    }
+
+    /// Convert into an ``AnyVec`` holding our elements, oldest first.
+    pub fn into_anyvec(self) -> AnyVec {
+        let size = self.buffer.vtable.size;
+        let bytes =
+            unsafe { slice::from_raw_parts_mut(self.buffer.data, self.buffer.length * size) };
+        bytes.rotate_left(self.head * size);
+        self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RingAnyVec;
+    use crate::AnyValue;
+
+    use std::rc::Rc;
+
+    #[test]
+    fn test_overwrite_oldest() {
+        let mut ring = RingAnyVec::with_fixed_capacity::<u32>(3);
+        ring.push(1u32);
+        ring.push(2u32);
+        assert!(!ring.is_full());
+        assert_eq!(ring.get::<u32>(1), Some(&2));
+
+        for i in 3..=7 {
+            ring.push(i as u32);
+        }
+        assert!(ring.is_full());
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.get::<u32>(0), Some(&5));
+        assert_eq!(ring.get::<u32>(3), None);
+
+        let values: Vec<u32> = ring.iter().map(|x| *x.downcast_ref().unwrap()).collect();
+        assert_eq!(values, vec![5, 6, 7]);
+        assert_eq!(ring.into_anyvec().into_vec::<u32>(), vec![5, 6, 7]);
+    }
+
+    #[test]
+    fn test_drops_overwritten() {
+        let chan = Rc::new(());
+        let mut ring = RingAnyVec::with_fixed_capacity::<Rc<()>>(2);
+        for _ in 0..5 {
+            ring.push_value(AnyValue::new(chan.clone()));
+        }
+        assert_eq!(Rc::strong_count(&chan), 3);
+        ring.clear();
+        assert_eq!(Rc::strong_count(&chan), 1);
+
+        ring.push(chan.clone());
+        drop(ring);
+        assert_eq!(Rc::strong_count(&chan), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_typecheck() {
+        let mut ring = RingAnyVec::with_fixed_capacity::<u32>(2);
+        ring.push(1u64);
+    }
+}
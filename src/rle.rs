@@ -0,0 +1,151 @@
+//! Run-length encoded vectors.
+
+use std::any::Any;
+use std::ptr;
+
+use crate::{AnyRef, AnyVec};
+
+/// A run-length encoded ``AnyVec``: one stored value per run of equal
+/// elements, so long, mostly-constant columns take little space.
+///
+/// Elements are found by binary search over the runs, so random access is
+/// logarithmic in the number of runs rather than constant.
+pub struct RleAnyVec {
+    // One value per run.
+    values: AnyVec,
+    // The exclusive end of each run, so ``ends.last()`` is our length.
+    ends: Vec<usize>,
+}
+
+impl RleAnyVec {
+    pub fn len(&self) -> usize {
+        self.ends.last().copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    pub fn num_runs(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// The run holding element ``index``, which must be in bounds.
+    fn run_of(&self, index: usize) -> usize {
+        self.ends.partition_point(|&end| end <= index)
+    }
+
+    /// Panics if the element type isn't ``T``.
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T> {
+        self.values.vtable.assert_typecheck::<T>();
+        self.get_ref(index)
+            .map(|value| value.downcast_ref::<T>().unwrap())
+    }
+
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'_>> {
+        if index < self.len() {
+            self.values.get_ref(self.run_of(index))
+        } else {
+            None
+        }
+    }
+
+    /// Each run's value and length, in order.
+    pub fn runs(&self) -> impl Iterator<Item = (AnyRef<'_>, usize)> + '_ {
+        let starts = std::iter::once(0).chain(self.ends.iter().copied());
+        let lengths = self.ends.iter().zip(starts).map(|(end, start)| end - start);
+        self.values.as_any_slice().iter().zip(lengths)
+    }
+
+    /// Every element, with each run's value repeated.
+    pub fn iter(&self) -> impl Iterator<Item = AnyRef<'_>> + '_ {
+        self.runs()
+            .flat_map(|(value, length)| std::iter::repeat_n(value, length))
+    }
+
+    /// Expand back into a plain vector.
+    ///
+    /// Panics if the element type doesn't support cloning.
+    pub fn to_anyvec(&self) -> AnyVec {
+        let mut indices = Vec::with_capacity(self.len());
+        for (run, (_, length)) in self.runs().enumerate() {
+            indices.extend(std::iter::repeat_n(run, length));
+        }
+        self.values.take(&indices)
+    }
+}
+
+/// Panics if the element type doesn't support equality.
+impl From<AnyVec> for RleAnyVec {
+    fn from(mut vec: AnyVec) -> RleAnyVec {
+        let eq = vec.vtable.require_eq();
+        let size = vec.vtable.size;
+        let length = vec.length;
+        // Each run's first element is moved down to the front of the buffer
+        // and the rest are dropped. Until that's done the buffer has holes,
+        // so leak everything rather than double-drop if ``eq`` panics.
+        vec.length = 0;
+        let mut ends: Vec<usize> = Vec::new();
+        for i in 0..length {
+            let element = unsafe { vec.data.add(i * size) };
+            let run = ends.len();
+            let continues = run > 0 && eq(unsafe { vec.data.add((run - 1) * size) }, element);
+            if continues {
+                (vec.vtable.drop_slice)(element, 1);
+                ends[run - 1] = i + 1;
+            } else {
+                if run != i {
+                    unsafe { ptr::copy_nonoverlapping(element, vec.data.add(run * size), size) };
+                }
+                ends.push(i + 1);
+            }
+        }
+        vec.length = ends.len();
+        RleAnyVec { values: vec, ends }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RleAnyVec;
+    use crate::AnyVec;
+
+    #[test]
+    fn test_runs() {
+        let rle = RleAnyVec::from(AnyVec::from_vec::<u8>(vec![1, 1, 1, 2, 1, 1]));
+        assert_eq!((rle.len(), rle.num_runs()), (6, 3));
+        let runs: Vec<(u8, usize)> = rle
+            .runs()
+            .map(|(value, length)| (*value.downcast_ref::<u8>().unwrap(), length))
+            .collect();
+        assert_eq!(runs, vec![(1, 3), (2, 1), (1, 2)]);
+        assert_eq!(rle.get::<u8>(3), Some(&2));
+        assert_eq!(rle.get::<u8>(5), Some(&1));
+        assert_eq!(rle.get::<u8>(6), None);
+    }
+
+    #[test]
+    fn test_round_trip_drops_duplicates() {
+        let names: Vec<String> = ["a", "a", "b", "b", "b"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let vec = AnyVec::from_vec(names.clone()).with_clone::<String>();
+        let rle = RleAnyVec::from(vec);
+        assert_eq!(rle.num_runs(), 2);
+        let elements: Vec<&String> = rle
+            .iter()
+            .map(|value| value.downcast_ref::<String>().unwrap())
+            .collect();
+        assert_eq!(elements, names.iter().collect::<Vec<_>>());
+        assert_eq!(rle.to_anyvec().into_vec::<String>(), names);
+    }
+
+    #[test]
+    fn test_empty() {
+        let rle = RleAnyVec::from(AnyVec::new::<u8>());
+        assert!(rle.is_empty());
+        assert_eq!(rle.get_ref(0).map(|_| ()), None);
+        assert!(rle.to_anyvec().is_empty());
+    }
+}
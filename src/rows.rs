@@ -0,0 +1,256 @@
+//! Conversion between parallel columns and rows, for boundary code that
+//! talks to row-oriented APIs and for record-oriented ingestion.
+
+use std::any::Any;
+use std::array;
+
+use crate::{AnyRef, AnyValue, AnyVec, Global};
+
+/// A record of values whose types are only known at runtime, one per column.
+///
+/// Unlike the fixed-size arrays taken by [`to_rows`] and [`from_rows`], a row's
+/// width is chosen at runtime, to match a schema.
+#[derive(Debug, Default, PartialEq)]
+pub struct AnyRow(Vec<AnyValue>);
+
+impl AnyRow {
+    pub fn new() -> AnyRow {
+        AnyRow::default()
+    }
+
+    /// Append ``value`` as the next slot.
+    pub fn with<T: Any>(mut self, value: T) -> AnyRow {
+        self.0.push(AnyValue::new(value));
+        self
+    }
+
+    pub fn push_value(&mut self, value: AnyValue) {
+        self.0.push(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The value in slot ``index``, if it's a ``T``.
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T> {
+        self.0.get(index).and_then(AnyValue::downcast_ref)
+    }
+
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'_>> {
+        self.0.get(index).map(AnyValue::as_any_ref)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = AnyRef<'_>> {
+        self.0.iter().map(AnyValue::as_any_ref)
+    }
+
+    pub fn into_values(self) -> Vec<AnyValue> {
+        self.0
+    }
+}
+
+impl From<Vec<AnyValue>> for AnyRow {
+    fn from(values: Vec<AnyValue>) -> AnyRow {
+        AnyRow(values)
+    }
+}
+
+/// Append one value of ``row`` onto each of ``columns``.
+///
+/// Panics if the row's width or types don't match the columns, in which case
+/// the columns are left unchanged.
+pub fn push_row(columns: &mut [AnyVec], row: AnyRow) {
+    if row.len() != columns.len() {
+        panic!(
+            "Row has {} values but there are {} columns",
+            row.len(),
+            columns.len()
+        );
+    }
+    for (column, value) in columns.iter().zip(&row.0) {
+        column.vtable.assert_same_type(value.vtable());
+    }
+    for (column, value) in columns.iter_mut().zip(row.0) {
+        column.push_value(value);
+    }
+}
+
+/// Clone the values at ``index`` out of each of ``columns``, or ``None`` if
+/// it's out of bounds for any of them.
+///
+/// Panics if a column's type doesn't support cloning.
+pub fn get_row(columns: &[AnyVec], index: usize) -> Option<AnyRow> {
+    columns
+        .iter()
+        .map(|column| column.get_ref(index).map(|value| value.to_value()))
+        .collect::<Option<Vec<AnyValue>>>()
+        .map(AnyRow)
+}
+
+/// Transpose ``N`` same-length columns into rows of ``N`` values.
+///
+/// Panics if the columns' lengths differ.
+pub fn to_rows<const N: usize>(columns: [AnyVec; N]) -> Vec<[AnyValue; N]> {
+    let length = columns.first().map_or(0, AnyVec::len);
+    for column in &columns {
+        if column.len() != length {
+            panic!(
+                "Columns have different lengths ({} != {})",
+                column.len(),
+                length
+            );
+        }
+    }
+
+    let mut columns = columns.map(|column| column.into_values().into_iter());
+    let mut rows = Vec::with_capacity(length);
+    for _ in 0..length {
+        rows.push(array::from_fn(|j| columns[j].next().unwrap()));
+    }
+    rows
+}
+
+/// Transpose rows of ``N`` values into ``N`` columns, whose types are taken
+/// from the first row.
+///
+/// Panics if ``rows`` is empty (use [`extend_from_rows`] with empty columns
+/// instead), or if any row's types differ from the first row's.
+pub fn from_rows<const N: usize>(rows: Vec<[AnyValue; N]>) -> [AnyVec; N] {
+    let first = rows
+        .first()
+        .expect("Can't infer column types from an empty list of rows");
+    let mut columns: [AnyVec; N] =
+        array::from_fn(|j| AnyVec::from_vtable_in(first[j].vtable(), Global));
+    extend_from_rows(&mut columns, rows);
+    columns
+}
+
+/// Append rows of ``N`` values onto ``N`` columns.
+///
+/// Every row is typechecked before anything is appended, so on a panic the
+/// columns are left unchanged.
+pub fn extend_from_rows<const N: usize>(columns: &mut [AnyVec; N], rows: Vec<[AnyValue; N]>) {
+    for row in &rows {
+        for (column, value) in columns.iter().zip(row) {
+            column.vtable.assert_same_type(value.vtable());
+        }
+    }
+
+    for column in columns.iter_mut() {
+        column.reserve(rows.len());
+    }
+    for row in rows {
+        for (column, value) in columns.iter_mut().zip(row) {
+            column.push_value(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extend_from_rows, from_rows, get_row, push_row, to_rows, AnyRow};
+    use crate::{AnyValue, AnyVec};
+
+    #[test]
+    fn test_round_trip() {
+        let ids = AnyVec::from_vec::<u64>(vec![1, 2, 3]);
+        let names = AnyVec::from_vec(vec![
+            String::from("a"),
+            String::from("b"),
+            String::from("c"),
+        ]);
+
+        let rows = to_rows([ids, names]);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1][0].downcast_ref::<u64>(), Some(&2));
+        assert_eq!(rows[1][1].downcast_ref::<String>().unwrap(), "b");
+
+        let [ids, names] = from_rows(rows);
+        assert_eq!(ids.into_vec::<u64>(), vec![1, 2, 3]);
+        assert_eq!(names.into_vec::<String>(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_extend_from_rows() {
+        let mut columns = [AnyVec::new::<i32>(), AnyVec::new::<f64>()];
+        extend_from_rows(
+            &mut columns,
+            vec![
+                [AnyValue::new(1i32), AnyValue::new(1.5f64)],
+                [AnyValue::new(2i32), AnyValue::new(2.5f64)],
+            ],
+        );
+
+        let [ints, floats] = columns;
+        assert_eq!(ints.into_vec::<i32>(), vec![1, 2]);
+        assert_eq!(floats.into_vec::<f64>(), vec![1.5, 2.5]);
+    }
+
+    #[test]
+    fn test_extend_from_rows_typechecks_first() {
+        let mut columns = [AnyVec::new::<i32>()];
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            extend_from_rows(
+                &mut columns,
+                vec![[AnyValue::new(1i32)], [AnyValue::new(2i64)]],
+            )
+        }));
+        assert!(result.is_err());
+        assert!(columns[0].is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_to_rows_length_mismatch() {
+        to_rows([
+            AnyVec::from_vec::<u64>(vec![1, 2]),
+            AnyVec::from_vec::<u64>(vec![1]),
+        ]);
+    }
+
+    #[test]
+    fn test_push_and_get_row() {
+        let mut columns = vec![
+            AnyVec::new::<u32>(),
+            AnyVec::new::<String>().with_clone::<String>(),
+        ];
+        push_row(
+            &mut columns,
+            AnyRow::new().with(1u32).with(String::from("a")),
+        );
+        push_row(
+            &mut columns,
+            AnyRow::new().with(2u32).with(String::from("b")),
+        );
+        assert_eq!(columns[1].len(), 2);
+
+        let row = get_row(&columns, 1).unwrap();
+        assert_eq!(row.len(), 2);
+        assert_eq!(row.get::<u32>(0), Some(&2));
+        assert_eq!(row.get::<String>(1).unwrap(), "b");
+        assert_eq!(row.get::<u32>(1), None);
+        assert!(get_row(&columns, 2).is_none());
+    }
+
+    #[test]
+    fn test_push_row_checks_schema() {
+        let mut columns = vec![AnyVec::new::<u32>(), AnyVec::new::<f64>()];
+        let row = AnyRow::from(vec![AnyValue::new(1u32), AnyValue::new(1i64)]);
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| push_row(&mut columns, row)));
+        assert!(result.is_err());
+        assert!(columns[0].is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Row has 1 values but there are 2 columns")]
+    fn test_push_row_width() {
+        let mut columns = vec![AnyVec::new::<u32>(), AnyVec::new::<f64>()];
+        push_row(&mut columns, AnyRow::new().with(1u32));
+    }
+}
@@ -0,0 +1,83 @@
+//! Random sampling used by the approximate kernels.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::determinism::is_deterministic;
+
+// Seed used in deterministic mode, so that sampled results are reproducible.
+const DETERMINISTIC_SEED: u64 = 0x5eed_5eed_5eed_5eed;
+
+/// A small SplitMix64 generator. Statistical quality is more than enough for
+/// picking sample indices, and it keeps us free of a ``rand`` dependency.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new() -> Rng {
+        if is_deterministic() {
+            Rng(DETERMINISTIC_SEED)
+        } else {
+            Rng(RandomState::new().build_hasher().finish())
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniformly-distributed value in ``0..n``.
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// Choose ``k`` distinct indices from ``0..n``, returned in increasing order.
+///
+/// If ``k >= n``, every index is returned.
+pub fn sample_indices(rng: &mut Rng, n: usize, k: usize) -> Vec<usize> {
+    if k >= n {
+        return (0..n).collect();
+    }
+
+    // Floyd's algorithm: k iterations, no matter how large n is.
+    let mut chosen: HashSet<usize> = HashSet::with_capacity(k);
+    for j in (n - k)..n {
+        let t = rng.below(j + 1);
+        if !chosen.insert(t) {
+            chosen.insert(j);
+        }
+    }
+
+    let mut indices: Vec<usize> = chosen.into_iter().collect();
+    indices.sort_unstable();
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sample_indices, Rng};
+    use crate::determinism::deterministic;
+
+    #[test]
+    fn test_sample_indices() {
+        let mut rng = Rng::new();
+
+        let indices = sample_indices(&mut rng, 1000, 100);
+        assert_eq!(indices.len(), 100);
+        assert!(indices.windows(2).all(|w| w[0] < w[1]));
+        assert!(indices.iter().all(|&i| i < 1000));
+
+        assert_eq!(sample_indices(&mut rng, 5, 10), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_deterministic_seed() {
+        let draw = || sample_indices(&mut Rng::new(), 1_000_000, 20);
+        assert_eq!(deterministic(true, draw), deterministic(true, draw));
+    }
+}
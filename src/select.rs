@@ -0,0 +1,225 @@
+//! Gathering selected elements of vectors into new ones, and scattering them
+//! back.
+
+use std::ptr;
+
+use crate::{Allocator, AnyVec};
+
+impl<A: Allocator + Clone> AnyVec<A> {
+    /// Clone the elements at ``indices`` into a new vector, in that order.
+    /// Indices may repeat.
+    ///
+    /// Panics if any index is out of bounds (before cloning anything), or if
+    /// the element type doesn't support cloning.
+    pub fn take(&self, indices: &[usize]) -> AnyVec<A> {
+        if let Some(&index) = indices.iter().find(|&&i| i >= self.length) {
+            panic!(
+                "Index {} out of bounds for vector of length {}",
+                index, self.length
+            );
+        }
+        self.gather(indices.iter().copied(), indices.len())
+    }
+
+    /// Clone the elements where ``mask`` is true into a new vector.
+    ///
+    /// Panics if ``mask``'s length differs from ours, or if the element type
+    /// doesn't support cloning.
+    pub fn filter_by_mask(&self, mask: &[bool]) -> AnyVec<A> {
+        if mask.len() != self.length {
+            panic!(
+                "Mask has length {} but vector has length {}",
+                mask.len(),
+                self.length
+            );
+        }
+        let count = mask.iter().filter(|&&keep| keep).count();
+        let indices = mask.iter().enumerate().filter(|&(_, &keep)| keep);
+        self.gather(indices.map(|(i, _)| i), count)
+    }
+
+    /// Clone the ``count`` elements at ``indices``, which must be in bounds,
+    /// into a new vector.
+    fn gather<I: Iterator<Item = usize>>(&self, indices: I, count: usize) -> AnyVec<A> {
+        let clone_into = self.vtable.require_clone().clone_into;
+        let mut result = AnyVec::from_vtable_in(self.vtable, self.alloc.clone());
+        result.reserve(count);
+        for i in indices {
+            let dest = unsafe { result.data.add(result.length * self.vtable.size) };
+            clone_into(self.element_ptr(i), dest, 1);
+            // Only count each clone once it's made, in case the next panics.
+            result.length += 1;
+        }
+        result.record_clones(result.length);
+        result
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Clone-assign ``source[k]`` to position ``indices[k]`` for every ``k``,
+    /// dropping the elements replaced. If an index repeats, the last
+    /// assignment wins.
+    ///
+    /// Panics if the element types or lengths differ, if any index is out of
+    /// bounds (before changing anything), or if the element type doesn't
+    /// support cloning.
+    pub fn scatter_from<B: Allocator + Clone>(&mut self, indices: &[usize], source: &AnyVec<B>) {
+        self.vtable.assert_same_type(source.vtable);
+        if indices.len() != source.length {
+            panic!(
+                "Indices have length {} but source has length {}",
+                indices.len(),
+                source.length
+            );
+        }
+        if let Some(&index) = indices.iter().find(|&&i| i >= self.length) {
+            panic!(
+                "Index {} out of bounds for vector of length {}",
+                index, self.length
+            );
+        }
+        // Clone everything up front, so a panicking clone leaves us untouched.
+        let displaced = source.clone();
+        // Swap each clone in, leaving the element it replaces in its place.
+        // Nothing is dropped until every slot holds its new element, so a
+        // panicking destructor can't leave us with a dropped element.
+        let size = self.vtable.size;
+        for (k, &i) in indices.iter().enumerate() {
+            unsafe {
+                ptr::swap_nonoverlapping(
+                    displaced.data.add(k * size),
+                    self.data.add(i * size),
+                    size,
+                )
+            };
+        }
+        self.record_clones(indices.len());
+        self.record_drops(indices.len());
+        drop(displaced);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_take() {
+        let dynamic = AnyVec::from_vec::<u32>(vec![10, 20, 30, 40]);
+        let taken = dynamic.take(&[3, 0, 0, 2]);
+        assert_eq!(taken.into_vec::<u32>(), vec![40, 10, 10, 30]);
+        assert!(dynamic.take(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_take_clones() {
+        let names =
+            AnyVec::from_vec(vec![String::from("a"), String::from("b")]).with_clone::<String>();
+        let taken = names.take(&[1, 1]);
+        assert_eq!(taken.into_vec::<String>(), vec!["b", "b"]);
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 4 out of bounds for vector of length 4")]
+    fn test_take_out_of_bounds() {
+        AnyVec::from_vec::<u32>(vec![10, 20, 30, 40]).take(&[0, 4]);
+    }
+
+    #[test]
+    fn test_filter_by_mask() {
+        let prices = AnyVec::from_vec::<f64>(vec![9.5, 10.0, 11.5]);
+        let volumes = AnyVec::from_vec::<u32>(vec![100, 0, 250]);
+        let mask: Vec<bool> = volumes
+            .get::<u32, _>(..)
+            .unwrap()
+            .iter()
+            .map(|&v| v > 0)
+            .collect();
+        assert_eq!(
+            prices.filter_by_mask(&mask).into_vec::<f64>(),
+            vec![9.5, 11.5]
+        );
+        assert!(prices.filter_by_mask(&[false; 3]).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Mask has length 2 but vector has length 3")]
+    fn test_filter_by_mask_length() {
+        AnyVec::from_vec::<u32>(vec![1, 2, 3]).filter_by_mask(&[true, false]);
+    }
+
+    #[test]
+    fn test_scatter_from() {
+        let mut names = AnyVec::from_vec(vec![
+            String::from("a"),
+            String::from("b"),
+            String::from("c"),
+        ])
+        .with_clone::<String>();
+        let updates =
+            AnyVec::from_vec(vec![String::from("x"), String::from("y")]).with_clone::<String>();
+        names.scatter_from(&[2, 0], &updates);
+        assert_eq!(names.into_vec::<String>(), vec!["y", "b", "x"]);
+        assert_eq!(updates.len(), 2);
+    }
+
+    // Counts its drops, and panics when dropped if it's armed. Clones are
+    // never armed.
+    struct Bomb {
+        armed: bool,
+        drops: Rc<Cell<usize>>,
+    }
+
+    impl Clone for Bomb {
+        fn clone(&self) -> Bomb {
+            Bomb {
+                armed: false,
+                drops: self.drops.clone(),
+            }
+        }
+    }
+
+    impl Drop for Bomb {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+            if self.armed {
+                panic!("drop failed");
+            }
+        }
+    }
+
+    #[test]
+    fn test_scatter_from_panicking_drop() {
+        let drops = Rc::new(Cell::new(0));
+        let bomb = |armed| Bomb {
+            armed,
+            drops: drops.clone(),
+        };
+        let mut dynamic = AnyVec::from_vec(vec![bomb(true), bomb(false)]).with_clone::<Bomb>();
+        let updates = AnyVec::from_vec(vec![bomb(false)]).with_clone::<Bomb>();
+        let result = catch_unwind(AssertUnwindSafe(|| dynamic.scatter_from(&[0], &updates)));
+        assert!(result.is_err());
+        assert_eq!((dynamic.len(), drops.get()), (2, 1));
+        // The replaced element isn't dropped a second time.
+        drop((dynamic, updates));
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 out of bounds for vector of length 3")]
+    fn test_scatter_from_out_of_bounds() {
+        let mut dynamic = AnyVec::from_vec::<u32>(vec![1, 2, 3]);
+        dynamic.scatter_from(&[0, 3], &AnyVec::from_vec::<u32>(vec![7, 8]));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support cloning")]
+    fn test_take_requires_clone() {
+        AnyVec::from_vec(vec![vec![1u8]]).take(&[0]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Vectors that can cross threads.
+//!
+//! ``AnyVec`` can't implement ``Send`` or ``Sync``, since whether that's safe
+//! depends on the element type, which is only known at runtime. Instead,
+//! [`SendAnyVec`] checks the ``SEND`` and ``SYNC`` capabilities once, when
+//! it's created, and implements both traits.
+
+use std::any::Any;
+use std::mem;
+use std::ops::Deref;
+use std::thread;
+
+use crate::vtable::VTable;
+use crate::{Allocator, AnyVec, CapabilitySet, Global};
+
+/// An ``AnyVec`` whose element type is known to be ``Send`` and ``Sync``.
+///
+/// It derefs to the vector for reading. Mutation goes through
+/// [`SendAnyVec::with_mut`], which makes sure the vector keeps its element
+/// type.
+pub struct SendAnyVec<A: Allocator = Global>(AnyVec<A>);
+
+// We only hold vectors whose vtables have the ``SEND`` and ``SYNC``
+// capabilities.
+unsafe impl<A: Allocator + Send> Send for SendAnyVec<A> {}
+unsafe impl<A: Allocator + Sync> Sync for SendAnyVec<A> {}
+
+fn thread_safe() -> CapabilitySet {
+    CapabilitySet::SEND | CapabilitySet::SYNC
+}
+
+impl SendAnyVec {
+    pub fn new<T: Any + Send + Sync>() -> SendAnyVec {
+        SendAnyVec::from_vec(Vec::<T>::new())
+    }
+
+    pub fn from_vec<T: Any + Send + Sync>(vec: Vec<T>) -> SendAnyVec {
+        SendAnyVec(AnyVec::from_vec(vec).with_send::<T>().with_sync::<T>())
+    }
+}
+
+impl<A: Allocator> SendAnyVec<A> {
+    /// Wrap ``vec``. Panics if its element type doesn't have the ``SEND`` and
+    /// ``SYNC`` capabilities.
+    pub fn from_anyvec(vec: AnyVec<A>) -> SendAnyVec<A> {
+        vec.assert_capabilities(thread_safe());
+        SendAnyVec(vec)
+    }
+
+    pub fn into_inner(self) -> AnyVec<A> {
+        self.0
+    }
+}
+
+impl<A: Allocator + Clone> SendAnyVec<A> {
+    /// Run ``f`` with mutable access to the vector.
+    ///
+    /// Panics if ``f`` replaces the vector with one whose element type isn't
+    /// ``Send`` and ``Sync``, leaving us empty. We're left empty too if ``f``
+    /// makes such a replacement and then panics.
+    pub fn with_mut<R, F: FnOnce(&mut AnyVec<A>) -> R>(&mut self, f: F) -> R {
+        let guard = Recheck {
+            vtable: self.0.vtable,
+            vec: &mut self.0,
+        };
+        f(&mut *guard.vec)
+    }
+}
+
+/// Empties the vector unless it still holds ``Send`` and ``Sync`` elements,
+/// when dropped. Being a guard, it runs even if ``with_mut``'s closure
+/// panics.
+struct Recheck<'a, A: Allocator + Clone> {
+    vtable: &'static VTable,
+    vec: &'a mut AnyVec<A>,
+}
+
+impl<A: Allocator + Clone> Drop for Recheck<'_, A> {
+    fn drop(&mut self) {
+        if self.vec.capabilities().contains(thread_safe()) {
+            return;
+        }
+        // Drop the offending elements here, on the thread that made them.
+        let empty = AnyVec::from_vtable_in(self.vtable, self.vec.alloc.clone());
+        let offending = mem::replace(self.vec, empty);
+        if !thread::panicking() {
+            offending.assert_capabilities(thread_safe());
+        }
+    }
+}
+
+impl<A: Allocator> Deref for SendAnyVec<A> {
+    type Target = AnyVec<A>;
+
+    fn deref(&self) -> &AnyVec<A> {
+        &self.0
+    }
+}
+
+impl<A: Allocator> From<SendAnyVec<A>> for AnyVec<A> {
+    fn from(vec: SendAnyVec<A>) -> AnyVec<A> {
+        vec.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SendAnyVec;
+    use crate::AnyVec;
+
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_send_to_thread() {
+        let mut dynamic = SendAnyVec::from_vec::<u64>(vec![1, 2, 3]);
+        dynamic.with_mut(|vec| vec.push(4u64));
+        let handle = thread::spawn(move || {
+            assert_eq!(dynamic.len(), 4);
+            dynamic
+        });
+        let dynamic = handle.join().unwrap().into_inner();
+        assert_eq!(dynamic.into_vec::<u64>(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_share_between_threads() {
+        let shared = Arc::new(SendAnyVec::from_anyvec(AnyVec::from_vec(vec![
+            String::from("a"),
+            String::from("b"),
+        ])));
+        let handles: Vec<_> = (0..2)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || shared.get::<String, _>(i).unwrap().clone())
+            })
+            .collect();
+        let found: Vec<String> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(found, vec!["a", "b"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing capabilities: SEND | SYNC")]
+    fn test_requires_send_and_sync() {
+        SendAnyVec::from_anyvec(AnyVec::from_vec(vec![Rc::new(1)]));
+    }
+
+    #[test]
+    fn test_with_mut_rejects_replacement() {
+        let mut dynamic = SendAnyVec::from_vec::<u8>(vec![1, 2]);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            dynamic.with_mut(|vec| *vec = AnyVec::from_vec(vec![Rc::new(1)]));
+        }));
+        assert!(result.is_err());
+        assert!(dynamic.is_empty());
+        assert_eq!(dynamic.as_any_slice().downcast::<u8>(), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_with_mut_rejects_replacement_on_panic() {
+        let mut dynamic = SendAnyVec::from_vec::<u8>(vec![1, 2]);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            dynamic.with_mut(|vec| {
+                *vec = AnyVec::from_vec(vec![Rc::new(1)]);
+                panic!("after replacing");
+            });
+        }));
+        assert!(result.is_err());
+        assert!(dynamic.is_empty());
+        assert_eq!(dynamic.as_any_slice().downcast::<u8>(), Some(&[][..]));
+    }
+}
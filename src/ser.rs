@@ -0,0 +1,177 @@
+//! Serialization of vectors, enabled by the ``serde`` feature.
+//!
+//! A vector serializes as a struct holding its element type's name and a
+//! sequence of its elements:
+//!
+//! ```text
+//! {"type": "u64", "values": [1, 2, 3]}
+//! ```
+//!
+//! A single element serializes as the element itself, or, through
+//! [`AnyRef::tagged`], as a struct holding its type's name too:
+//!
+//! ```text
+//! {"type": "u64", "value": 1}
+//! ```
+
+use std::any::Any;
+
+use serde::ser::{Error, SerializeSeq, SerializeStruct};
+use serde::{Serialize, Serializer};
+
+use crate::vtable::{SerializeFn, VTable};
+use crate::{Allocator, AnyRef, AnyValue, AnyVec};
+
+pub(crate) fn serialize<T: Any + Serialize>(
+    data: *const u8,
+) -> &'static dyn erased_serde::Serialize {
+    unsafe { &*(data as *const T) }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Enable serialization of this vector. Primitive types and ``String``
+    /// have this enabled already.
+    pub fn with_serialize<T: Any + Serialize>(mut self) -> AnyVec<A> {
+        self.vtable = self.vtable.with_serialize::<T>();
+        self
+    }
+}
+
+fn require_serialize<E: Error>(vtable: &VTable) -> Result<SerializeFn, E> {
+    vtable.serialize.ok_or_else(|| {
+        E::custom(format!(
+            "{} does not support serialization",
+            vtable.display_name
+        ))
+    })
+}
+
+/// A vector's elements, serialized as a sequence without their type.
+pub(crate) struct Values<'a, A: Allocator>(pub &'a AnyVec<A>);
+
+impl<A: Allocator> Serialize for Values<'_, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let vec = self.0;
+        let serialize = require_serialize::<S::Error>(vec.vtable)?;
+
+        let mut seq = serializer.serialize_seq(Some(vec.length))?;
+        for i in 0..vec.length {
+            seq.serialize_element(serialize(vec.element_ptr(i)))?;
+        }
+        seq.end()
+    }
+}
+
+impl<A: Allocator> Serialize for AnyVec<A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AnyVec", 2)?;
+        state.serialize_field("type", self.vtable.display_name)?;
+        state.serialize_field("values", &Values(self))?;
+        state.end()
+    }
+}
+
+/// Serializes the element alone, without its type.
+impl Serialize for AnyRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let serialize = require_serialize::<S::Error>(self.vtable())?;
+        serialize(self.data()).serialize(serializer)
+    }
+}
+
+impl Serialize for AnyValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_any_ref().serialize(serializer)
+    }
+}
+
+/// An element that serializes along with its type's name. See
+/// [`AnyRef::tagged`].
+#[derive(Clone, Copy, Debug)]
+pub struct TaggedRef<'a>(AnyRef<'a>);
+
+impl<'a> AnyRef<'a> {
+    /// Serialize the element as ``{"type": ..., "value": ...}``, like a
+    /// vector's ``{"type": ..., "values": [...]}``.
+    pub fn tagged(self) -> TaggedRef<'a> {
+        TaggedRef(self)
+    }
+}
+
+impl Serialize for TaggedRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AnyValue", 2)?;
+        state.serialize_field("type", self.0.type_name())?;
+        state.serialize_field("value", &self.0)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyRef, AnyValue, AnyVec};
+
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_serialize_primitives() {
+        let dynamic = AnyVec::from_vec::<u64>(vec![1, 2, 3]);
+        assert_eq!(
+            serde_json::to_string(&dynamic).unwrap(),
+            r#"{"type":"u64","values":[1,2,3]}"#
+        );
+
+        let dynamic = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        assert_eq!(
+            serde_json::to_string(&dynamic).unwrap(),
+            r#"{"type":"alloc::string::String","values":["a","b"]}"#
+        );
+    }
+
+    #[test]
+    fn test_with_serialize() {
+        #[derive(Serialize)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let dynamic = AnyVec::from_vec(vec![Point { x: 1, y: 2 }]);
+        let err = serde_json::to_string(&dynamic).unwrap_err();
+        assert!(err.to_string().contains("does not support serialization"));
+
+        let dynamic = dynamic.with_serialize::<Point>();
+        let json = serde_json::to_value(&dynamic).unwrap();
+        assert_eq!(json["values"], serde_json::json!([{"x": 1, "y": 2}]));
+    }
+
+    #[test]
+    fn test_serialize_row_fields() {
+        let ids = AnyVec::from_vec::<u64>(vec![1, 2]);
+        let symbols = AnyVec::from_vec(vec![String::from("A"), String::from("B")]);
+        let mut row: BTreeMap<&str, AnyRef> = BTreeMap::new();
+        row.insert("id", ids.get_ref(1).unwrap());
+        row.insert("symbol", symbols.get_ref(1).unwrap());
+        assert_eq!(
+            serde_json::to_string(&row).unwrap(),
+            r#"{"id":2,"symbol":"B"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&AnyValue::new(1.5f64)).unwrap(),
+            "1.5"
+        );
+    }
+
+    #[test]
+    fn test_serialize_tagged() {
+        let ids = AnyVec::from_vec::<u64>(vec![7]);
+        assert_eq!(
+            serde_json::to_string(&ids.get_ref(0).unwrap().tagged()).unwrap(),
+            r#"{"type":"u64","value":7}"#
+        );
+
+        let err = serde_json::to_string(&AnyValue::new(vec![1u8])).unwrap_err();
+        assert!(err.to_string().contains("does not support serialization"));
+    }
+}
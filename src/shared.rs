@@ -0,0 +1,118 @@
+//! Vectors that several threads can read and update at once.
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::{Allocator, AnyRef, AnyValue, AnyVec, Conflict, Global, SendAnyVec};
+
+/// A handle to a vector shared between threads, e.g. a column edited by
+/// several clients at once. Cloning the handle shares the vector.
+///
+/// Readers run concurrently. Writers change single elements with
+/// [`SharedAnyVec::update_if`], which detects conflicting writes instead of
+/// overwriting them.
+pub struct SharedAnyVec<A: Allocator = Global>(Arc<RwLock<AnyVec<A>>>);
+
+// We're only built from ``SendAnyVec``s, whose element types are ``Send`` and
+// ``Sync``, and never hand out the vector mutably, so it keeps that type.
+unsafe impl<A: Allocator + Send + Sync> Send for SharedAnyVec<A> {}
+unsafe impl<A: Allocator + Send + Sync> Sync for SharedAnyVec<A> {}
+
+impl<A: Allocator> Clone for SharedAnyVec<A> {
+    fn clone(&self) -> SharedAnyVec<A> {
+        SharedAnyVec(Arc::clone(&self.0))
+    }
+}
+
+impl<A: Allocator> SharedAnyVec<A> {
+    pub fn new(vec: SendAnyVec<A>) -> SharedAnyVec<A> {
+        SharedAnyVec(Arc::new(RwLock::new(vec.into_inner())))
+    }
+
+    /// Run ``f`` with the vector, while writers wait.
+    pub fn read<R, F: FnOnce(&AnyVec<A>) -> R>(&self, f: F) -> R {
+        f(&self.0.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    pub fn len(&self) -> usize {
+        self.read(AnyVec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Like [`AnyVec::update_if`], but callable from any thread holding a
+    /// handle. The comparison and the write happen together, so of several
+    /// threads expecting the same value, only one succeeds.
+    pub fn update_if(
+        &self,
+        index: usize,
+        expected: &AnyRef,
+        new: AnyValue,
+    ) -> Result<(), Conflict> {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .update_if(index, expected, new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedAnyVec;
+    use crate::{AnyValue, SendAnyVec};
+
+    use std::thread;
+
+    #[test]
+    fn test_concurrent_update_if() {
+        let counters = SharedAnyVec::new(SendAnyVec::from_vec::<u64>(vec![0, 0]));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let counters = counters.clone();
+                thread::spawn(move || {
+                    let mut conflicts = 0;
+                    for _ in 0..1000 {
+                        // Retry until our increment isn't lost to another
+                        // thread's.
+                        loop {
+                            let current = counters.read(|vec| *vec.get::<u64, _>(1).unwrap());
+                            let expected = AnyValue::new(current);
+                            match counters.update_if(
+                                1,
+                                &expected.as_any_ref(),
+                                AnyValue::new(current + 1),
+                            ) {
+                                Ok(()) => break,
+                                Err(_) => conflicts += 1,
+                            }
+                        }
+                    }
+                    conflicts
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(counters.len(), 2);
+        assert_eq!(
+            counters.read(|vec| vec.as_any_slice().downcast::<u64>().unwrap().to_vec()),
+            vec![0, 4000]
+        );
+    }
+
+    #[test]
+    fn test_conflict_returns_value() {
+        let names = SharedAnyVec::new(SendAnyVec::from_vec(vec![String::from("a")]));
+        let expected = AnyValue::new(String::from("b"));
+        let conflict = names
+            .update_if(0, &expected.as_any_ref(), AnyValue::new(String::from("c")))
+            .unwrap_err();
+        assert_eq!(conflict.value.downcast::<String>().unwrap(), "c");
+        assert_eq!(
+            names.read(|vec| vec.get::<String, _>(0).cloned()).unwrap(),
+            "a"
+        );
+    }
+}
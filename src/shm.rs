@@ -0,0 +1,312 @@
+//! Handing plain-old-data columns between processes through named
+//! shared-memory segments, enabled by the ``mmap`` feature on Unix.
+//!
+//! A segment starts with a header recording the element type's name, size and
+//! alignment, and the number of elements, followed by the elements
+//! themselves. Type names come from ``std::any::type_name``, so both processes
+//! should be built from the same source with the same compiler.
+
+use std::cell::RefCell;
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+
+use bytemuck::Pod;
+use memmap2::{MmapMut, MmapOptions};
+
+use crate::mmap::{MmapAnyVec, MmapStore};
+use crate::vtable::VTable;
+use crate::{Allocator, AnyVec};
+
+const MAGIC: &[u8; 8] = b"ANYVEC01";
+
+// Elements start at a multiple of this, so any reasonable alignment is met.
+const DATA_ALIGN: usize = 64;
+
+fn segment_path(name: &str) -> io::Result<PathBuf> {
+    if name.is_empty() || name.contains('/') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid shared memory segment name {:?}", name),
+        ));
+    }
+    let shm = PathBuf::from("/dev/shm");
+    let dir = if shm.is_dir() {
+        shm
+    } else {
+        std::env::temp_dir()
+    };
+    Ok(dir.join(name))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct Header<'a> {
+    type_name: &'a str,
+    size: usize,
+    align: usize,
+    length: usize,
+}
+
+impl<'a> Header<'a> {
+    fn data_offset(&self) -> usize {
+        (MAGIC.len() + 4 * 8 + self.type_name.len()).div_ceil(DATA_ALIGN) * DATA_ALIGN
+    }
+
+    fn write(&self, out: &mut [u8]) {
+        let mut fields = [0u8; 32];
+        for (chunk, value) in
+            fields
+                .chunks_mut(8)
+                .zip([self.size, self.align, self.length, self.type_name.len()])
+        {
+            chunk.copy_from_slice(&(value as u64).to_ne_bytes());
+        }
+        out[..8].copy_from_slice(MAGIC);
+        out[8..40].copy_from_slice(&fields);
+        out[40..40 + self.type_name.len()].copy_from_slice(self.type_name.as_bytes());
+    }
+
+    fn read(bytes: &'a [u8]) -> io::Result<Header<'a>> {
+        if bytes.len() < 40 || &bytes[..8] != MAGIC {
+            return Err(invalid("Not an AnyVec shared memory segment".into()));
+        }
+        let field = |i: usize| {
+            let start = 8 + 8 * i;
+            u64::from_ne_bytes(bytes[start..start + 8].try_into().unwrap()) as usize
+        };
+        let name_len = field(3);
+        let type_name = name_len
+            .checked_add(40)
+            .and_then(|end| bytes.get(40..end))
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| invalid("Corrupt shared memory segment header".into()))?;
+        Ok(Header {
+            type_name,
+            size: field(0),
+            align: field(1),
+            length: field(2),
+        })
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Copy our elements into a new shared-memory segment called ``name``,
+    /// replacing any existing segment of that name, and return a vector
+    /// backed by the segment.
+    ///
+    /// Writes through the returned vector are visible to other processes
+    /// attached to the segment, but the header's length is fixed when the
+    /// segment is created, and growing the vector detaches it onto the heap.
+    ///
+    /// Fails if our type's alignment is over 64 bytes. Panics if our type
+    /// isn't plain old data.
+    pub fn to_shared_memory(&self, name: &str) -> io::Result<MmapAnyVec> {
+        let bytes = self.as_bytes();
+        let header = Header {
+            type_name: self.vtable.display_name,
+            size: self.vtable.size,
+            align: self.vtable.align,
+            length: self.length,
+        };
+        if header.align > DATA_ALIGN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is aligned to {}, more than shared memory supports ({})",
+                    header.type_name, header.align, DATA_ALIGN
+                ),
+            ));
+        }
+        let offset = header.data_offset();
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(segment_path(name)?)?;
+        file.set_len((offset + bytes.len()) as u64)?;
+        let mut map = unsafe { MmapOptions::new().map_mut(&file)? };
+        header.write(&mut map[..offset]);
+        map[offset..].copy_from_slice(bytes);
+
+        Ok(AnyVec::from_segment(
+            self.vtable,
+            file,
+            map,
+            offset,
+            self.length,
+        ))
+    }
+}
+
+impl AnyVec<MmapStore> {
+    /// Attach to the shared-memory segment called ``name``, which must hold
+    /// ``T``s.
+    ///
+    /// # Safety
+    ///
+    /// The returned vector views memory that other processes, or other
+    /// vectors attached in this one, can write at any time. No one else may
+    /// write to the segment's elements while this vector is alive, or read
+    /// them while it's being written, and no one may truncate the segment.
+    pub unsafe fn attach_shared<T: Pod>(name: &str) -> io::Result<MmapAnyVec> {
+        let vtable = VTable::new::<T>().with_pod::<T>();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(segment_path(name)?)?;
+        let map = unsafe { MmapOptions::new().map_mut(&file)? };
+
+        let header = Header::read(&map)?;
+        if header.type_name != vtable.display_name
+            || header.size != vtable.size
+            || header.align != vtable.align
+        {
+            return Err(invalid(format!(
+                "Segment {:?} holds {}, not {}",
+                name, header.type_name, vtable.display_name
+            )));
+        }
+        // The header was written by another process, so its length can't
+        // be trusted not to overflow.
+        let (offset, length) = (header.data_offset(), header.length);
+        let end = length
+            .checked_mul(vtable.size)
+            .and_then(|bytes| bytes.checked_add(offset));
+        if end.is_none_or(|end| map.len() < end) {
+            return Err(invalid(format!("Segment {:?} is truncated", name)));
+        }
+
+        Ok(AnyVec::from_segment(vtable, file, map, offset, length))
+    }
+
+    fn from_segment(
+        vtable: &'static VTable,
+        file: File,
+        mut map: MmapMut,
+        offset: usize,
+        length: usize,
+    ) -> MmapAnyVec {
+        let data = unsafe { map.as_mut_ptr().add(offset) };
+        let store = MmapStore {
+            file,
+            growable: false,
+            map: RefCell::new(Some(map)),
+            offset,
+        };
+        let mut result = AnyVec::from_vtable_in(vtable, store);
+        result.data = data;
+        result.length = length;
+        result.capacity = length;
+        result
+    }
+}
+
+/// Remove the shared-memory segment called ``name``. Processes that are
+/// already attached keep their mappings.
+pub fn unlink_shared(name: &str) -> io::Result<()> {
+    fs::remove_file(segment_path(name)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unlink_shared;
+    use crate::{AnyVec, MmapAnyVec};
+
+    fn segment_name(test: &str) -> String {
+        format!("anyvector-{}-{}", test, std::process::id())
+    }
+
+    #[test]
+    fn test_share_and_attach() {
+        let name = segment_name("share");
+        let values: Vec<i64> = (0..500).map(|x| x * x).collect();
+        let mut producer = AnyVec::from_vec(values.clone())
+            .to_shared_memory(&name)
+            .unwrap();
+        *producer.first_mut::<i64>().unwrap() = -1;
+        drop(producer);
+
+        // Only one vector is attached at a time, as ``attach_shared``
+        // requires.
+        let mut consumer = unsafe { MmapAnyVec::attach_shared::<i64>(&name) }.unwrap();
+        assert_eq!(consumer.first::<i64>(), Some(&-1));
+        assert_eq!(consumer.cast_slice::<i64>()[1..], values[1..]);
+        *consumer.first_mut::<i64>().unwrap() = -2;
+        drop(consumer);
+
+        let reattached = unsafe { MmapAnyVec::attach_shared::<i64>(&name) }.unwrap();
+        assert_eq!(reattached.first::<i64>(), Some(&-2));
+        drop(reattached);
+        unlink_shared(&name).unwrap();
+    }
+
+    #[test]
+    fn test_attach_wrong_type() {
+        let name = segment_name("wrong-type");
+        AnyVec::from_vec::<u32>(vec![1, 2, 3])
+            .to_shared_memory(&name)
+            .unwrap();
+
+        let err = unsafe { MmapAnyVec::attach_shared::<f32>(&name) }
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("holds u32, not f32"));
+        unlink_shared(&name).unwrap();
+    }
+
+    #[test]
+    fn test_share_overaligned() {
+        #[derive(Clone, Copy)]
+        #[repr(C, align(128))]
+        struct Wide([u8; 128]);
+
+        unsafe impl bytemuck::Zeroable for Wide {}
+        unsafe impl bytemuck::Pod for Wide {}
+
+        let err = AnyVec::from_vec(vec![Wide([0; 128])])
+            .with_pod::<Wide>()
+            .to_shared_memory(&segment_name("overaligned"))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_attach_corrupt_length() {
+        let name = segment_name("corrupt");
+        AnyVec::from_vec::<u64>(vec![1, 2, 3])
+            .to_shared_memory(&name)
+            .unwrap();
+        let path = super::segment_path(&name).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[24..32].copy_from_slice(&u64::MAX.to_ne_bytes());
+        std::fs::write(&path, bytes).unwrap();
+
+        let err = unsafe { MmapAnyVec::attach_shared::<u64>(&name) }
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        unlink_shared(&name).unwrap();
+    }
+
+    #[test]
+    fn test_growing_detaches() {
+        let name = segment_name("grow");
+        let mut shared = AnyVec::from_vec::<u8>(vec![1, 2])
+            .to_shared_memory(&name)
+            .unwrap();
+        shared.push(3u8);
+
+        // ``shared`` no longer views the segment, so this doesn't alias it.
+        let attached = unsafe { MmapAnyVec::attach_shared::<u8>(&name) }.unwrap();
+        assert_eq!(attached.cast_slice::<u8>(), &[1, 2]);
+        assert_eq!(shared.cast_slice::<u8>(), &[1, 2, 3]);
+        unlink_shared(&name).unwrap();
+    }
+}
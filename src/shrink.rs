@@ -0,0 +1,124 @@
+//! Releasing unused capacity, on request or automatically after removals.
+
+use crate::{Allocator, AnyVec};
+
+/// When a vector gives back unused capacity on its own, after elements are
+/// removed by ``truncate``, ``clear`` or ``expire_before``.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShrinkPolicy {
+    /// Keep our capacity until shrunk explicitly. The default.
+    #[default]
+    Never,
+    /// Once fewer than ``1 / n`` of our capacity is in use, shrink to twice
+    /// our length, leaving room to grow again without reallocating at once.
+    BelowFraction(usize),
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// The number of elements we can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Shrink our buffer to fit our length, or ``min_capacity`` if that's
+    /// larger, like ``Vec::shrink_to``. Does nothing if we're already
+    /// smaller than that.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        if self.capacity <= min_capacity.max(self.length) {
+            return;
+        }
+        let (data, capacity) = (self.vtable.shrink_to)(
+            self.data,
+            self.length,
+            self.capacity,
+            min_capacity,
+            &self.alloc,
+        );
+        let (old_data, old_capacity) = (self.data, self.capacity);
+        self.data = data;
+        self.capacity = capacity;
+        self.record_realloc(old_data, old_capacity);
+        self.debug_assert_invariants();
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    pub fn shrink_policy(&self) -> ShrinkPolicy {
+        self.shrink_policy
+    }
+
+    pub fn set_shrink_policy(&mut self, policy: ShrinkPolicy) {
+        self.shrink_policy = policy;
+        self.apply_shrink_policy();
+    }
+
+    /// Use ``policy`` from now on, e.g. for long-lived columns whose
+    /// high-water marks would otherwise hold on to memory.
+    pub fn with_shrink_policy(mut self, policy: ShrinkPolicy) -> AnyVec<A> {
+        self.set_shrink_policy(policy);
+        self
+    }
+
+    /// Shrink if our policy says to. Called after removing elements.
+    pub(crate) fn apply_shrink_policy(&mut self) {
+        if let ShrinkPolicy::BelowFraction(n) = self.shrink_policy {
+            // Zero-sized elements never use any memory.
+            if self.vtable.size > 0 && self.length.saturating_mul(n) < self.capacity {
+                self.shrink_to(self.length * 2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShrinkPolicy;
+    use crate::AnyVec;
+
+    #[test]
+    fn test_shrink_to() {
+        let mut ids = AnyVec::from_vec::<u32>(Vec::with_capacity(100));
+        ids.extend_exact(0..10u32);
+        ids.shrink_to(50);
+        assert_eq!(ids.capacity(), 50);
+        ids.shrink_to(60);
+        assert_eq!(ids.capacity(), 50);
+        ids.shrink_to_fit();
+        assert_eq!(ids.capacity(), 10);
+        assert_eq!(ids.into_vec::<u32>(), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_policy() {
+        let mut names = AnyVec::from_vec((0..100).map(|i| i.to_string()).collect::<Vec<_>>())
+            .with_shrink_policy(ShrinkPolicy::BelowFraction(4));
+        names.truncate(30);
+        assert_eq!(names.capacity(), 100);
+        names.truncate(20);
+        assert_eq!(names.capacity(), 40);
+        assert_eq!(
+            names.clone().shrink_policy(),
+            ShrinkPolicy::BelowFraction(4)
+        );
+
+        names.clear();
+        assert_eq!(names.capacity(), 0);
+        names.push(String::from("a"));
+        assert_eq!(names.into_vec::<String>(), vec!["a"]);
+    }
+
+    #[test]
+    fn test_never_shrinks_by_default() {
+        let mut ids = AnyVec::from_vec::<u8>(vec![0; 100]);
+        assert_eq!(ids.shrink_policy(), ShrinkPolicy::Never);
+        ids.clear();
+        assert_eq!(ids.capacity(), 100);
+
+        let mut units =
+            AnyVec::from_vec(vec![(); 10]).with_shrink_policy(ShrinkPolicy::BelowFraction(2));
+        units.clear();
+        assert_eq!(units.capacity(), usize::MAX);
+    }
+}
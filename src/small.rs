@@ -0,0 +1,289 @@
+//! A type-erased vector that stores a few elements inline.
+
+use std::any::Any;
+use std::mem::{self, MaybeUninit};
+use std::ptr;
+
+use crate::vtable::VTable;
+use crate::{AnyRef, AnyValue, AnyVec, Global};
+
+// Largest element alignment we can store inline. Types with stricter
+// alignment always live on the heap.
+const INLINE_ALIGN: usize = 16;
+
+#[repr(C, align(16))]
+struct InlineBuf<const N: usize>([MaybeUninit<u8>; N]);
+
+enum Storage<const N: usize> {
+    Inline { buf: InlineBuf<N>, length: usize },
+    Heap(AnyVec),
+}
+
+/// An ``AnyVec`` that keeps up to ``N_BYTES`` bytes of elements inline before
+/// spilling to the heap.
+///
+/// Useful when storing many tiny erased vectors, where a heap allocation per
+/// vector would dominate.
+pub struct SmallAnyVec<const N_BYTES: usize> {
+    storage: Storage<N_BYTES>,
+    vtable: &'static VTable,
+}
+
+impl<const N_BYTES: usize> SmallAnyVec<N_BYTES> {
+    pub fn new<T: Any>() -> SmallAnyVec<N_BYTES> {
+        SmallAnyVec {
+            storage: Storage::Inline {
+                buf: InlineBuf([MaybeUninit::uninit(); N_BYTES]),
+                length: 0,
+            },
+            vtable: VTable::new::<T>(),
+        }
+    }
+
+    /// How many elements fit before we spill to the heap.
+    pub fn inline_capacity(&self) -> usize {
+        if self.vtable.align > INLINE_ALIGN {
+            0
+        } else {
+            // Zero-sized elements never need space.
+            N_BYTES.checked_div(self.vtable.size).unwrap_or(usize::MAX)
+        }
+    }
+
+    pub fn spilled(&self) -> bool {
+        matches!(self.storage, Storage::Heap(_))
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Inline { length, .. } => *length,
+            Storage::Heap(vec) => vec.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        match &self.storage {
+            Storage::Inline { buf, .. } => buf.0.as_ptr() as *const u8,
+            Storage::Heap(vec) => vec.data,
+        }
+    }
+
+    /// Like ``as_ptr``, but valid for writes. Inline writes must go through
+    /// this: a pointer derived from ``&self`` only permits reads.
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        match &mut self.storage {
+            Storage::Inline { buf, .. } => buf.0.as_mut_ptr() as *mut u8,
+            Storage::Heap(vec) => vec.data,
+        }
+    }
+
+    /// Move our inline elements to the heap, with room for ``additional``
+    /// more.
+    fn spill(&mut self, additional: usize) {
+        if let Storage::Inline { buf, length } = &self.storage {
+            let mut heap = AnyVec::from_vtable_in(self.vtable, Global);
+            heap.reserve(length + additional);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    buf.0.as_ptr() as *const u8,
+                    heap.data,
+                    length * self.vtable.size,
+                );
+            }
+            heap.length = *length;
+            // The inline buffer doesn't drop anything, so the elements are now
+            // owned solely by ``heap``.
+            self.storage = Storage::Heap(heap);
+        }
+    }
+
+    /// Pointer to a free slot for one more element, spilling if necessary.
+    fn reserve_one(&mut self) -> *mut u8 {
+        let inline_capacity = self.inline_capacity();
+        if let Storage::Inline { length, .. } = self.storage {
+            if length >= inline_capacity {
+                self.spill(length.max(1));
+            }
+        }
+
+        if let Storage::Heap(vec) = &mut self.storage {
+            vec.reserve(1);
+        }
+        let offset = self.len() * self.vtable.size;
+        unsafe { self.as_mut_ptr().add(offset) }
+    }
+
+    fn increment_length(&mut self) {
+        match &mut self.storage {
+            Storage::Inline { length, .. } => *length += 1,
+            Storage::Heap(vec) => vec.length += 1,
+        }
+    }
+
+    pub fn push<T: Any>(&mut self, value: T) {
+        self.vtable.assert_typecheck::<T>();
+        let slot = self.reserve_one();
+        unsafe { ptr::write(slot as *mut T, value) };
+        self.increment_length();
+    }
+
+    /// Append an erased value. Panics if its type doesn't match ours.
+    pub fn push_value(&mut self, value: AnyValue) {
+        self.vtable.assert_same_type(value.vtable());
+        let slot = self.reserve_one();
+        let (boxed, _) = value.into_raw();
+        (self.vtable.unbox_into)(boxed, slot);
+        self.increment_length();
+    }
+
+    pub fn as_slice<T: Any>(&self) -> &[T] {
+        self.vtable.assert_typecheck::<T>();
+        unsafe { std::slice::from_raw_parts(self.as_ptr() as *const T, self.len()) }
+    }
+
+    pub fn as_mut_slice<T: Any>(&mut self) -> &mut [T] {
+        self.vtable.assert_typecheck::<T>();
+        let length = self.len();
+        unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr() as *mut T, length) }
+    }
+
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T> {
+        self.as_slice().get(index)
+    }
+
+    pub fn get_ref(&self, index: usize) -> Option<AnyRef<'_>> {
+        if index < self.len() {
+            let data = unsafe { self.as_ptr().add(index * self.vtable.size) };
+            Some(unsafe { AnyRef::new(data, self.vtable) })
+        } else {
+            None
+        }
+    }
+
+    pub fn truncate(&mut self, length: usize) {
+        match &mut self.storage {
+            Storage::Inline {
+                length: current, ..
+            } => {
+                if length >= *current {
+                    return;
+                }
+                let ndropped = *current - length;
+                *current = length;
+                if !self.vtable.needs_drop {
+                    return;
+                }
+                unsafe {
+                    let tail = self.as_mut_ptr().add(length * self.vtable.size);
+                    (self.vtable.drop_slice)(tail, ndropped);
+                }
+            }
+            Storage::Heap(vec) => vec.truncate(length),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Convert into a heap-backed ``AnyVec``.
+    pub fn into_anyvec(mut self) -> AnyVec {
+        self.spill(0);
+        let mut this = mem::ManuallyDrop::new(self);
+        match mem::replace(
+            &mut this.storage,
+            Storage::Inline {
+                buf: InlineBuf([MaybeUninit::uninit(); N_BYTES]),
+                length: 0,
+            },
+        ) {
+            Storage::Heap(vec) => vec,
+            Storage::Inline { .. } => unreachable!(),
+        }
+    }
+}
+
+impl<const N_BYTES: usize> Drop for SmallAnyVec<N_BYTES> {
+    fn drop(&mut self) {
+        // Heap storage drops itself.
+        self.truncate(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SmallAnyVec;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_push_inline_then_spill() {
+        let mut small = SmallAnyVec::<16>::new::<u32>();
+        assert_eq!(small.inline_capacity(), 4);
+
+        for i in 0..4 {
+            small.push(i as u32);
+        }
+        assert!(!small.spilled());
+        assert_eq!(small.as_slice::<u32>(), &[0, 1, 2, 3]);
+
+        small.push(4u32);
+        assert!(small.spilled());
+        assert_eq!(small.as_slice::<u32>(), &[0, 1, 2, 3, 4]);
+        assert_eq!(small.get::<u32>(4), Some(&4));
+        assert_eq!(small.get_ref(2).unwrap(), &2u32);
+
+        let vec = small.into_anyvec();
+        assert_eq!(vec.into_vec::<u32>(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_into_anyvec_inline() {
+        let mut small = SmallAnyVec::<32>::new::<u64>();
+        small.push(7u64);
+        small.as_mut_slice::<u64>()[0] += 1;
+        assert_eq!(small.into_anyvec().into_vec::<u64>(), vec![8]);
+    }
+
+    #[test]
+    fn test_overaligned_spills_immediately() {
+        #[repr(align(32))]
+        struct Wide(u8);
+
+        let mut small = SmallAnyVec::<64>::new::<Wide>();
+        assert_eq!(small.inline_capacity(), 0);
+        small.push(Wide(1));
+        assert!(small.spilled());
+        assert_eq!(small.get::<Wide>(0).unwrap().0, 1);
+    }
+
+    #[test]
+    fn test_drop_inline_and_spilled() {
+        for count in [2, 5] {
+            let chan: Rc<RefCell<Vec<i64>>> = Rc::new(RefCell::new(vec![]));
+            let mut small = SmallAnyVec::<32>::new::<Rc<RefCell<Vec<i64>>>>();
+            for _ in 0..count {
+                small.push(chan.clone());
+            }
+            assert_eq!(small.spilled(), count > 4);
+            assert_eq!(Rc::strong_count(&chan), count + 1);
+
+            small.truncate(1);
+            assert_eq!(Rc::strong_count(&chan), 2);
+            drop(small);
+            assert_eq!(Rc::strong_count(&chan), 1);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_typecheck() {
+        let mut small = SmallAnyVec::<16>::new::<u32>();
+        small.push(1u64);
+    }
+}
@@ -0,0 +1,431 @@
+//! Checkpointing vectors to a compact binary format, enabled by the
+//! ``snapshot`` feature.
+//!
+//! A snapshot is a header followed by a payload. All integers are
+//! little-endian:
+//!
+//! ```text
+//! magic      b"AVSNAP"
+//! version    u16
+//! encoding   u8        0: raw element bytes, 1: serde
+//! type name  u32 length, then UTF-8
+//! elem size  u64
+//! length     u64
+//! payload    raw: length * elem size bytes
+//!            serde: for each element, u64 byte length, then the element as
+//!            JSON
+//! ```
+//!
+//! Plain-old-data elements are written as their raw bytes; everything else
+//! goes through the element type's ``Serialize`` impl, and must be
+//! registered for deserialization to be loaded. Snapshots in any other
+//! version of the format are rejected.
+//!
+//! Snapshots may come from untrusted sources, so loading never allocates
+//! more than the input actually holds: a header claiming more elements than
+//! follow is reported as corrupt.
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::slice;
+
+use serde::de::{self, DeserializeSeed, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::{Allocator, AnyVec, Global, TypeRegistry};
+
+const MAGIC: &[u8] = b"AVSNAP";
+const VERSION: u16 = 2;
+
+// Raw payloads are read this many bytes at a time, so a corrupt length can't
+// make us allocate more than is really there.
+const CHUNK: usize = 1 << 16;
+
+const RAW: u8 = 0;
+const SERDE: u8 = 1;
+
+/// Why saving or loading a snapshot failed.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The element type can't be saved, or the snapshot was written in
+    /// another version of this format.
+    Unsupported(String),
+    /// The snapshot's element type isn't registered (for deserialization,
+    /// if its elements went through serde).
+    UnknownType(String),
+    /// The snapshot is malformed, or doesn't match the registered type.
+    Corrupt(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "{}", err),
+            SnapshotError::Unsupported(what) => write!(f, "Unsupported: {}", what),
+            SnapshotError::UnknownType(name) => write!(f, "{} is not registered", name),
+            SnapshotError::Corrupt(what) => write!(f, "Corrupt snapshot: {}", what),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> SnapshotError {
+        SnapshotError::Io(err)
+    }
+}
+
+fn corrupt(what: &str) -> SnapshotError {
+    SnapshotError::Corrupt(what.to_string())
+}
+
+fn truncated(err: io::Error) -> SnapshotError {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        corrupt("snapshot is truncated")
+    } else {
+        SnapshotError::Io(err)
+    }
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N], SnapshotError> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes).map_err(truncated)?;
+    Ok(bytes)
+}
+
+// Read ``length`` bytes, growing the result as they arrive rather than
+// trusting ``length`` up front.
+fn read_bytes<R: Read>(reader: &mut R, length: usize) -> Result<Vec<u8>, SnapshotError> {
+    let mut bytes = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < length {
+        return Err(corrupt("snapshot is truncated"));
+    }
+    Ok(bytes)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<usize, SnapshotError> {
+    u64::from_le_bytes(read_array(reader)?)
+        .try_into()
+        .map_err(|_| corrupt("length overflows usize"))
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Write a snapshot of our elements to ``writer``.
+    ///
+    /// Fails if our type is neither plain old data nor serializable.
+    pub fn save_to<W: Write>(&self, mut writer: W) -> Result<(), SnapshotError> {
+        let vtable = self.vtable;
+        let encoding = if vtable.pod {
+            // Our raw bytes are in native order, which we only write on
+            // little-endian targets.
+            if cfg!(target_endian = "big") && vtable.size > 1 {
+                return Err(SnapshotError::Unsupported(format!(
+                    "raw {} on a big-endian target",
+                    vtable.display_name
+                )));
+            }
+            RAW
+        } else if vtable.serialize.is_some() {
+            SERDE
+        } else {
+            return Err(SnapshotError::Unsupported(format!(
+                "{} is neither plain old data nor serializable",
+                vtable.display_name
+            )));
+        };
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[encoding])?;
+        let name = vtable.display_name.as_bytes();
+        writer.write_all(&(name.len() as u32).to_le_bytes())?;
+        writer.write_all(name)?;
+        writer.write_all(&(vtable.size as u64).to_le_bytes())?;
+        writer.write_all(&(self.length as u64).to_le_bytes())?;
+
+        if encoding == RAW {
+            let bytes = unsafe { slice::from_raw_parts(self.data, self.length * vtable.size) };
+            writer.write_all(bytes)?;
+        } else {
+            let serialize = vtable.serialize.unwrap();
+            let mut json = Vec::new();
+            for i in 0..self.length {
+                json.clear();
+                serde_json::to_writer(&mut json, serialize(self.element_ptr(i)))
+                    .map_err(|err| SnapshotError::Unsupported(err.to_string()))?;
+                writer.write_all(&(json.len() as u64).to_le_bytes())?;
+                writer.write_all(&json)?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl AnyVec {
+    /// Read a snapshot written by [`AnyVec::save_to`], looking up its element
+    /// type in ``registry``.
+    pub fn load_from<R: Read>(
+        mut reader: R,
+        registry: &TypeRegistry,
+    ) -> Result<AnyVec, SnapshotError> {
+        if read_array::<_, 6>(&mut reader)? != MAGIC {
+            return Err(corrupt("missing magic number"));
+        }
+        let version = u16::from_le_bytes(read_array(&mut reader)?);
+        if version != VERSION {
+            return Err(SnapshotError::Unsupported(format!(
+                "snapshot version {}",
+                version
+            )));
+        }
+        let [encoding] = read_array(&mut reader)?;
+        let name_len = u32::from_le_bytes(read_array(&mut reader)?) as usize;
+        let name = read_bytes(&mut reader, name_len)?;
+        let name = String::from_utf8(name).map_err(|_| corrupt("type name isn't UTF-8"))?;
+        let size = read_u64(&mut reader)?;
+        let length = read_u64(&mut reader)?;
+
+        let entry = registry
+            .get(&name)
+            .ok_or_else(|| SnapshotError::UnknownType(name.clone()))?;
+        if entry.vtable.size != size {
+            return Err(SnapshotError::Corrupt(format!(
+                "{} has size {}, not {}",
+                name, entry.vtable.size, size
+            )));
+        }
+
+        match encoding {
+            RAW => {
+                if !entry.vtable.pod {
+                    return Err(SnapshotError::Corrupt(format!(
+                        "{} is not plain old data",
+                        name
+                    )));
+                }
+                length
+                    .checked_mul(size)
+                    .ok_or_else(|| corrupt("length overflows usize"))?;
+                let mut result = AnyVec::from_vtable_in(entry.vtable, Global);
+                if size == 0 {
+                    result.length = length;
+                    return Ok(result);
+                }
+                while result.length < length {
+                    let count = (length - result.length).min((CHUNK / size).max(1));
+                    result.reserve(count);
+                    // Zero the spare capacity first, since it's uninitialized.
+                    let buffer = unsafe {
+                        let start = result.data.add(result.length * size);
+                        start.write_bytes(0, count * size);
+                        slice::from_raw_parts_mut(start, count * size)
+                    };
+                    reader.read_exact(buffer).map_err(truncated)?;
+                    // Any bytes are valid plain old data.
+                    result.length += count;
+                }
+                Ok(result)
+            }
+            SERDE => {
+                let deserialize = entry
+                    .deserialize
+                    .ok_or_else(|| SnapshotError::UnknownType(name.clone()))?;
+                let mut error = None;
+                let elements = Elements {
+                    reader: &mut reader,
+                    remaining: length,
+                    error: &mut error,
+                };
+                let mut erased = <dyn erased_serde::Deserializer>::erase(elements);
+                let mut result = match (deserialize(&mut erased), error) {
+                    (_, Some(err)) => return Err(err),
+                    (result, None) => {
+                        result.map_err(|err| SnapshotError::Corrupt(err.to_string()))?
+                    }
+                };
+                if result.length != length {
+                    return Err(corrupt("payload length doesn't match header"));
+                }
+                // Pick up any capabilities the registered vtable has.
+                result.vtable = entry.vtable;
+                Ok(result)
+            }
+            other => Err(SnapshotError::Unsupported(format!("encoding {}", other))),
+        }
+    }
+}
+
+/// A serde payload, deserialized as a sequence of length-prefixed elements.
+struct Elements<'a, R> {
+    reader: &'a mut R,
+    remaining: usize,
+    // Where read errors go, so they aren't flattened into serde errors.
+    error: &'a mut Option<SnapshotError>,
+}
+
+impl<'de, R: Read> de::Deserializer<'de> for Elements<'_, R> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de, R: Read> SeqAccess<'de> for Elements<'_, R> {
+    type Error = de::value::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        let json = match read_u64(self.reader).and_then(|len| read_bytes(self.reader, len)) {
+            Ok(json) => json,
+            Err(err) => {
+                *self.error = Some(err);
+                return Err(de::Error::custom("failed to read element"));
+            }
+        };
+        // A reader-backed deserializer, since the element can't borrow from
+        // ``json``.
+        let mut deserializer = serde_json::Deserializer::from_reader(&json[..]);
+        let value = seed
+            .deserialize(&mut deserializer)
+            .and_then(|value| deserializer.end().map(|()| value))
+            .map_err(de::Error::custom)?;
+        self.remaining -= 1;
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        // Serde caps how much it preallocates from this.
+        Some(self.remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotError;
+    use crate::{AnyVec, TypeRegistry};
+
+    use serde::{Deserialize, Serialize};
+
+    fn round_trip(dynamic: &AnyVec, registry: &TypeRegistry) -> Result<AnyVec, SnapshotError> {
+        let mut bytes = Vec::new();
+        dynamic.save_to(&mut bytes).unwrap();
+        AnyVec::load_from(&bytes[..], registry)
+    }
+
+    #[test]
+    fn test_raw_round_trip() {
+        let registry = TypeRegistry::with_primitives();
+        let prices = AnyVec::from_vec::<f64>(vec![9.5, 10.0, -1.25]);
+        let mut bytes = Vec::new();
+        prices.save_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 6 + 2 + 1 + 4 + 3 + 8 + 8 + 3 * 8);
+
+        let loaded = AnyVec::load_from(&bytes[..], &registry).unwrap();
+        assert_eq!(loaded.into_vec::<f64>(), vec![9.5, 10.0, -1.25]);
+        let empty = round_trip(&AnyVec::new::<u16>(), &registry).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let names = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        let loaded = round_trip(&names, &TypeRegistry::with_primitives()).unwrap();
+        assert_eq!(loaded.into_vec::<String>(), vec!["a", "b"]);
+
+        let points = AnyVec::from_vec(vec![Point { x: 1, y: 2 }]).with_serialize::<Point>();
+        let error = round_trip(&points, &TypeRegistry::new()).err().unwrap();
+        assert!(matches!(error, SnapshotError::UnknownType(_)));
+
+        let mut registry = TypeRegistry::new();
+        registry.register_deserialize::<Point>();
+        let loaded = round_trip(&points, &registry).unwrap();
+        assert_eq!(loaded.into_vec::<Point>(), vec![Point { x: 1, y: 2 }]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = AnyVec::from_vec(vec![vec![1u8]])
+            .save_to(Vec::new())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported: alloc::vec::Vec<u8> is neither plain old data nor serializable"
+        );
+
+        let error = AnyVec::load_from(&b"AVSNAQ"[..], &TypeRegistry::new())
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Corrupt snapshot: missing magic number");
+
+        let error = AnyVec::load_from(&b"AVSNAP\x01\x00"[..], &TypeRegistry::new())
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Unsupported: snapshot version 1");
+
+        let mut bytes = Vec::new();
+        AnyVec::from_vec::<u32>(vec![1, 2])
+            .save_to(&mut bytes)
+            .unwrap();
+        bytes.truncate(bytes.len() - 1);
+        let error = AnyVec::load_from(&bytes[..], &TypeRegistry::with_primitives())
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Corrupt snapshot: snapshot is truncated");
+    }
+
+    #[test]
+    fn test_untrusted_lengths() {
+        let registry = TypeRegistry::with_primitives();
+        // Headers claiming far more data than follows fail without
+        // allocating it.
+        for dynamic in [
+            AnyVec::from_vec::<u64>(vec![1]),
+            AnyVec::from_vec(vec![String::from("a")]),
+        ] {
+            let mut bytes = Vec::new();
+            dynamic.save_to(&mut bytes).unwrap();
+            let length_at = 6 + 2 + 1 + 4 + dynamic.vtable.display_name.len() + 8;
+            bytes[length_at..length_at + 8].copy_from_slice(&(u64::MAX / 16).to_le_bytes());
+            let error = AnyVec::load_from(&bytes[..], &registry).err().unwrap();
+            assert!(matches!(error, SnapshotError::Corrupt(_)), "{}", error);
+        }
+
+        let mut bytes = Vec::new();
+        AnyVec::from_vec::<u8>(vec![1]).save_to(&mut bytes).unwrap();
+        bytes[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = AnyVec::load_from(&bytes[..], &registry).err().unwrap();
+        assert_eq!(error.to_string(), "Corrupt snapshot: snapshot is truncated");
+    }
+}
@@ -0,0 +1,487 @@
+//! Sorting, and set operations on sorted vectors, by the ``cmp`` capability.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::ptr;
+
+use crate::{Allocator, AnyRef, AnyVec};
+
+impl<A: Allocator> AnyVec<A> {
+    /// The permutation that would sort us in ascending order: the index of
+    /// our smallest element, then the next smallest, and so on. Equal
+    /// elements keep their relative order.
+    ///
+    /// Pass the result to [`AnyVec::take`] to sort several parallel columns
+    /// by this one. Panics if the element type doesn't support ordering.
+    pub fn argsort(&self) -> Vec<usize> {
+        let cmp = self.vtable.require_cmp();
+        let mut order: Vec<usize> = (0..self.length).collect();
+        order.sort_by(|&i, &j| cmp(self.element_ptr(i), self.element_ptr(j)));
+        order
+    }
+
+    /// Sort our elements in place by the key ``f`` extracts from each, like
+    /// ``slice::sort_by_key``. The sort is stable.
+    ///
+    /// Panics if our element type isn't ``T``.
+    pub fn sort_by_key<T: Any, K: Ord>(&mut self, f: impl FnMut(&T) -> K) {
+        self.with_typed_mut(|values: &mut [T]| values.sort_by_key(f));
+    }
+
+    /// Like [`AnyVec::sort_by_key`], but calling ``f`` only once per element
+    /// and caching the keys, for when they're expensive to compute.
+    pub fn sort_by_cached_key<T: Any, K: Ord>(&mut self, f: impl FnMut(&T) -> K) {
+        self.with_typed_mut(|values: &mut [T]| values.sort_by_cached_key(f));
+    }
+
+    /// Whether our elements are in ascending order, e.g. to check that
+    /// [`AnyVec::lower_bound`] can be used. Panics if the element type
+    /// doesn't support ordering.
+    pub fn is_sorted(&self) -> bool {
+        let cmp = self.vtable.require_cmp();
+        (1..self.length)
+            .all(|i| cmp(self.element_ptr(i - 1), self.element_ptr(i)) != Ordering::Greater)
+    }
+
+    /// Whether our elements are in ascending order under ``compare``, which
+    /// needn't agree with the element type's own ordering.
+    pub fn is_sorted_by<F>(&self, mut compare: F) -> bool
+    where
+        F: FnMut(AnyRef<'_>, AnyRef<'_>) -> Ordering,
+    {
+        (1..self.length).all(|i| unsafe {
+            let a = AnyRef::new(self.element_ptr(i - 1), self.vtable);
+            let b = AnyRef::new(self.element_ptr(i), self.vtable);
+            compare(a, b) != Ordering::Greater
+        })
+    }
+
+    /// The index of the first element for which ``pred`` is false, given
+    /// that it's true for every element before that and false for every one
+    /// after, like ``slice::partition_point``. Found by binary search.
+    pub fn partition_point<P>(&self, mut pred: P) -> usize
+    where
+        P: FnMut(AnyRef<'_>) -> bool,
+    {
+        let (mut low, mut high) = (0, self.length);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if pred(unsafe { AnyRef::new(self.element_ptr(mid), self.vtable) }) {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+
+    /// The index of our first element that isn't less than ``value``, i.e.
+    /// where to insert it before any equal elements. We must be sorted in
+    /// ascending order.
+    ///
+    /// Panics if ``value``'s type doesn't match ours, or doesn't support
+    /// ordering.
+    pub fn lower_bound(&self, value: AnyRef<'_>) -> usize {
+        self.vtable.assert_same_type(value.vtable());
+        let cmp = self.vtable.require_cmp();
+        self.partition_point(|element| cmp(element.data(), value.data()) == Ordering::Less)
+    }
+
+    /// The index of our first element that's greater than ``value``, i.e.
+    /// where to insert it after any equal elements. See
+    /// [`AnyVec::lower_bound`].
+    pub fn upper_bound(&self, value: AnyRef<'_>) -> usize {
+        self.vtable.assert_same_type(value.vtable());
+        let cmp = self.vtable.require_cmp();
+        self.partition_point(|element| cmp(element.data(), value.data()) != Ordering::Greater)
+    }
+
+    /// Reorder our elements so the one at ``n`` is where it would be if we
+    /// were sorted, with none greater before it and none less after it, and
+    /// return it. Like ``slice::select_nth_unstable``, this takes linear
+    /// time on average, e.g. for medians and percentiles without a full
+    /// sort, and doesn't preserve the order of equal elements.
+    ///
+    /// Elements are only ever swapped, so if a comparison panics we're left
+    /// in some permutation of our original order. Panics if ``n`` is out of
+    /// bounds, or if the element type doesn't support ordering.
+    pub fn select_nth_unstable(&mut self, n: usize) -> AnyRef<'_> {
+        if n >= self.length {
+            panic!(
+                "Index {} out of bounds for vector of length {}",
+                n, self.length
+            );
+        }
+        let cmp = self.vtable.require_cmp();
+        let less = |this: &Self, a: usize, b: usize| {
+            cmp(this.element_ptr(a), this.element_ptr(b)) == Ordering::Less
+        };
+        let swap = |this: &mut Self, a: usize, b: usize| {
+            if a != b {
+                let size = this.vtable.size;
+                unsafe {
+                    ptr::swap_nonoverlapping(this.data.add(a * size), this.data.add(b * size), size)
+                };
+            }
+        };
+
+        let (mut low, mut high) = (0, self.length);
+        while high - low > 1 {
+            // Move the median of the first, middle and last elements to
+            // ``low`` as the pivot, so sorted input stays linear.
+            let (mid, last) = (low + (high - low) / 2, high - 1);
+            if less(self, mid, low) {
+                swap(self, mid, low);
+            }
+            if less(self, last, mid) {
+                swap(self, last, mid);
+                if less(self, mid, low) {
+                    swap(self, mid, low);
+                }
+            }
+            swap(self, low, mid);
+
+            // Partition three ways, so runs of equal elements finish early:
+            // ``low..lt`` is less than the pivot, ``lt..i`` equal to it, and
+            // ``gt..high`` greater. ``lt`` always holds a copy of the pivot.
+            let (mut lt, mut i, mut gt) = (low, low + 1, high);
+            while i < gt {
+                match cmp(self.element_ptr(i), self.element_ptr(lt)) {
+                    Ordering::Less => {
+                        swap(self, i, lt);
+                        lt += 1;
+                        i += 1;
+                    }
+                    Ordering::Greater => {
+                        gt -= 1;
+                        swap(self, i, gt);
+                    }
+                    Ordering::Equal => i += 1,
+                }
+            }
+            if n < lt {
+                high = lt;
+            } else if n >= gt {
+                low = gt;
+            } else {
+                break;
+            }
+        }
+        unsafe { AnyRef::new(self.element_ptr(n), self.vtable) }
+    }
+}
+
+impl<A: Allocator + Clone> AnyVec<A> {
+    /// The elements of either vector, both of which must be sorted
+    /// ascending. An element equal to one in the other vector appears once.
+    ///
+    /// Equal elements are paired off one for one, so duplicates within a
+    /// vector are treated as a multiset. Panics if the element types differ,
+    /// or if the type doesn't support ordering and cloning.
+    pub fn sorted_union<B: Allocator>(&self, other: &AnyVec<B>) -> AnyVec<A> {
+        self.merge(other, true, true, true)
+    }
+
+    /// The elements of ``self`` that equal one in ``other``. Both must be
+    /// sorted ascending; see [`AnyVec::sorted_union`].
+    pub fn sorted_intersection<B: Allocator>(&self, other: &AnyVec<B>) -> AnyVec<A> {
+        self.merge(other, false, true, false)
+    }
+
+    /// The elements of ``self`` that don't equal one in ``other``. Both must
+    /// be sorted ascending; see [`AnyVec::sorted_union`].
+    pub fn sorted_difference<B: Allocator>(&self, other: &AnyVec<B>) -> AnyVec<A> {
+        self.merge(other, true, false, false)
+    }
+
+    /// Every element of both vectors, which must be sorted ascending, in
+    /// ascending order. The merge is stable: equal elements keep their
+    /// order, with ours before ``other``'s.
+    ///
+    /// Panics if the element types differ, or if the type doesn't support
+    /// ordering and cloning.
+    pub fn merge_sorted<B: Allocator>(&self, other: &AnyVec<B>) -> AnyVec<A> {
+        self.vtable.assert_same_type(other.vtable);
+        let cmp = self.vtable.require_cmp();
+        let clone_into = self.vtable.require_clone().clone_into;
+        let mut result = AnyVec::from_vtable_in(self.vtable, self.alloc.clone());
+        result.reserve(self.length + other.length);
+        let size = self.vtable.size;
+        let mut push = |src: *const u8, count: usize| {
+            let dest = unsafe { result.data.add(result.length * size) };
+            clone_into(src, dest, count);
+            result.length += count;
+        };
+        let (mut i, mut j) = (0, 0);
+        while i < self.length && j < other.length {
+            let (left, right) = (self.element_ptr(i), other.element_ptr(j));
+            if cmp(right, left) == Ordering::Less {
+                push(right, 1);
+                j += 1;
+            } else {
+                push(left, 1);
+                i += 1;
+            }
+        }
+        // At most one of these is non-empty, and can be cloned in one go.
+        if i < self.length {
+            push(self.element_ptr(i), self.length - i);
+        }
+        if j < other.length {
+            push(other.element_ptr(j), other.length - j);
+        }
+        result.record_clones(result.length);
+        result
+    }
+
+    /// Merge two sorted vectors, cloning the elements only in ``self``, in
+    /// both (from ``self``) and only in ``other`` as requested.
+    fn merge<B: Allocator>(
+        &self,
+        other: &AnyVec<B>,
+        keep_left: bool,
+        keep_both: bool,
+        keep_right: bool,
+    ) -> AnyVec<A> {
+        self.vtable.assert_same_type(other.vtable);
+        let cmp = self.vtable.require_cmp();
+        let clone_into = self.vtable.require_clone().clone_into;
+        let mut result = AnyVec::from_vtable_in(self.vtable, self.alloc.clone());
+        let mut push = |src: *const u8| {
+            result.reserve(1);
+            let dest = unsafe { result.data.add(result.length * self.vtable.size) };
+            clone_into(src, dest, 1);
+            result.length += 1;
+        };
+        let (mut i, mut j) = (0, 0);
+        while i < self.length && j < other.length {
+            let (left, right) = (self.element_ptr(i), other.element_ptr(j));
+            match cmp(left, right) {
+                Ordering::Less => {
+                    if keep_left {
+                        push(left);
+                    }
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    if keep_right {
+                        push(right);
+                    }
+                    j += 1;
+                }
+                Ordering::Equal => {
+                    if keep_both {
+                        push(left);
+                    }
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        if keep_left {
+            (i..self.length).for_each(|i| push(self.element_ptr(i)));
+        }
+        if keep_right {
+            (j..other.length).for_each(|j| push(other.element_ptr(j)));
+        }
+        result.record_clones(result.length);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyRef, AnyValue, AnyVec};
+
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_argsort_is_stable() {
+        let keys = AnyVec::from_vec::<i32>(vec![3, -1, 3, 0, -1]);
+        assert_eq!(keys.argsort(), vec![1, 4, 3, 0, 2]);
+        assert!(AnyVec::new::<i32>().argsort().is_empty());
+    }
+
+    #[test]
+    fn test_sort_by_key() {
+        let mut names = AnyVec::from_vec(vec![
+            String::from("ccc"),
+            String::from("a"),
+            String::from("bb"),
+            String::from("d"),
+        ]);
+        names.sort_by_key(|name: &String| name.len());
+        assert_eq!(
+            names.clone().into_vec::<String>(),
+            vec!["a", "d", "bb", "ccc"]
+        );
+
+        let mut calls = 0;
+        names.sort_by_cached_key(|name: &String| {
+            calls += 1;
+            name.to_uppercase()
+        });
+        assert_eq!(calls, 4);
+        assert_eq!(names.into_vec::<String>(), vec!["a", "bb", "ccc", "d"]);
+    }
+
+    #[test]
+    fn test_is_sorted() {
+        assert!(AnyVec::from_vec::<u32>(vec![1, 2, 2, 3]).is_sorted());
+        assert!(!AnyVec::from_vec::<u32>(vec![2, 1]).is_sorted());
+        assert!(AnyVec::new::<u32>().is_sorted());
+        // Floats are ordered totally, with NaN last.
+        assert!(AnyVec::from_vec(vec![0.0, f64::NAN]).is_sorted());
+
+        let names = AnyVec::from_vec(vec![String::from("bb"), String::from("a")]);
+        assert!(!names.is_sorted());
+        let by_length = |a: AnyRef, b: AnyRef| {
+            let (a, b) = (
+                a.downcast_ref::<String>().unwrap(),
+                b.downcast_ref::<String>().unwrap(),
+            );
+            b.len().cmp(&a.len())
+        };
+        assert!(names.is_sorted_by(by_length));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let times = AnyVec::from_vec::<u32>(vec![10, 20, 20, 20, 30]);
+        let twenty = AnyValue::new(20u32);
+        assert_eq!(times.lower_bound(twenty.as_any_ref()), 1);
+        assert_eq!(times.upper_bound(twenty.as_any_ref()), 4);
+        assert_eq!(times.lower_bound(AnyValue::new(5u32).as_any_ref()), 0);
+        assert_eq!(times.upper_bound(AnyValue::new(30u32).as_any_ref()), 5);
+
+        let point = times.partition_point(|t| *t.downcast_ref::<u32>().unwrap() < 25);
+        assert_eq!(point, 4);
+        assert_eq!(AnyVec::new::<u32>().partition_point(|_| true), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match (u32 != u64)")]
+    fn test_bounds_type() {
+        AnyVec::new::<u32>().lower_bound(AnyValue::new(1u64).as_any_ref());
+    }
+
+    #[test]
+    fn test_select_nth_unstable() {
+        let mut prices = AnyVec::from_vec::<i64>((0..101).map(|i| (i * 37) % 101 - 50).collect());
+        assert_eq!(prices.select_nth_unstable(50), &0i64);
+        prices.with_typed(|prices: &[i64]| {
+            assert!(prices[..50].iter().all(|&p| p <= 0));
+            assert!(prices[51..].iter().all(|&p| p >= 0));
+        });
+        assert_eq!(prices.select_nth_unstable(0), &-50i64);
+        assert_eq!(prices.select_nth_unstable(100), &50i64);
+
+        let mut flags = AnyVec::from_vec(vec![true; 1000]);
+        flags.push(false);
+        assert_eq!(flags.select_nth_unstable(0), &false);
+        assert_eq!(flags.select_nth_unstable(500), &true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 out of bounds for vector of length 3")]
+    fn test_select_nth_out_of_bounds() {
+        AnyVec::from_vec::<u8>(vec![1, 2, 3]).select_nth_unstable(3);
+    }
+
+    #[test]
+    fn test_merge_sorted() {
+        let right = AnyVec::from_vec::<u32>(vec![2, 3, 3, 9]);
+        let merged = AnyVec::from_vec::<u32>(vec![1, 3]).merge_sorted(&right);
+        assert_eq!(merged.into_vec::<u32>(), vec![1, 2, 3, 3, 3, 9]);
+        assert!(AnyVec::new::<u8>()
+            .merge_sorted(&AnyVec::new::<u8>())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_merge_sorted_is_stable() {
+        // Ordered by key alone.
+        #[derive(Clone, Debug)]
+        struct Entry(u32, &'static str);
+
+        impl PartialEq for Entry {
+            fn eq(&self, other: &Entry) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Entry {}
+        impl PartialOrd for Entry {
+            fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Entry {
+            fn cmp(&self, other: &Entry) -> Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let ours = AnyVec::from_vec(vec![Entry(1, "ours"), Entry(2, "ours")])
+            .with_ord::<Entry>()
+            .with_clone::<Entry>();
+        let theirs = AnyVec::from_vec(vec![Entry(1, "theirs"), Entry(2, "theirs")]);
+        let sources: Vec<_> = ours
+            .merge_sorted(&theirs)
+            .into_vec::<Entry>()
+            .into_iter()
+            .map(|entry| entry.1)
+            .collect();
+        assert_eq!(sources, vec!["ours", "theirs", "ours", "theirs"]);
+    }
+
+    #[test]
+    fn test_sort_parallel_columns() {
+        let keys = AnyVec::from_vec(vec!["pear", "apple", "fig"]).with_ord::<&str>();
+        let prices = AnyVec::from_vec::<f64>(vec![1.5, 0.5, 2.0]);
+        let order = keys.argsort();
+        assert_eq!(prices.take(&order).into_vec::<f64>(), vec![0.5, 2.0, 1.5]);
+    }
+
+    #[test]
+    fn test_sorted_set_operations() {
+        let before = AnyVec::from_vec::<u32>(vec![1, 3, 5, 7]);
+        let after = AnyVec::from_vec::<u32>(vec![2, 3, 7, 8, 9]);
+        assert_eq!(
+            before.sorted_union(&after).into_vec::<u32>(),
+            vec![1, 2, 3, 5, 7, 8, 9]
+        );
+        assert_eq!(
+            before.sorted_intersection(&after).into_vec::<u32>(),
+            vec![3, 7]
+        );
+        assert_eq!(
+            before.sorted_difference(&after).into_vec::<u32>(),
+            vec![1, 5]
+        );
+        assert_eq!(
+            after.sorted_difference(&before).into_vec::<u32>(),
+            vec![2, 8, 9]
+        );
+    }
+
+    #[test]
+    fn test_sorted_set_operations_with_duplicates() {
+        let a = AnyVec::from_vec::<i8>(vec![1, 1, 1, 2]);
+        let b = AnyVec::from_vec::<i8>(vec![1, 2, 2]);
+        assert_eq!(a.sorted_union(&b).into_vec::<i8>(), vec![1, 1, 1, 2, 2]);
+        assert_eq!(a.sorted_intersection(&b).into_vec::<i8>(), vec![1, 2]);
+        assert_eq!(a.sorted_difference(&b).into_vec::<i8>(), vec![1, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match (u32 != i32)")]
+    fn test_sorted_union_types() {
+        AnyVec::from_vec::<u32>(vec![1]).sorted_union(&AnyVec::from_vec::<i32>(vec![1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support ordering")]
+    fn test_argsort_requires_ord() {
+        AnyVec::from_vec(vec![vec![1u8]]).argsort();
+    }
+}
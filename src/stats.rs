@@ -0,0 +1,100 @@
+//! Per-vector counters of buffer traffic, for checking that a code path is
+//! amortized O(1) per element. Counting only happens with the ``stats``
+//! feature; without it the hooks below compile to nothing.
+
+use crate::{Allocator, AnyVec};
+
+/// What a vector has done to its buffer since it was created (or since
+/// [`AnyVec::reset_stats`]).
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Times the buffer was allocated or resized.
+    pub reallocations: u64,
+    /// Bytes of elements copied to a new buffer when one moved.
+    pub bytes_moved: u64,
+    /// Elements cloned into the vector.
+    pub elements_cloned: u64,
+    /// Elements dropped while the vector was in use. Elements still held
+    /// when it's dropped aren't counted.
+    pub elements_dropped: u64,
+}
+
+#[cfg(feature = "stats")]
+impl<A: Allocator> AnyVec<A> {
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Count a reallocation if our buffer changed from ``old_data`` holding
+    /// ``old_capacity`` elements.
+    pub(crate) fn record_realloc(&mut self, _old_data: *mut u8, _old_capacity: usize) {
+        #[cfg(feature = "stats")]
+        if self.capacity != _old_capacity {
+            self.stats.reallocations += 1;
+            if self.data != _old_data && _old_capacity != 0 {
+                self.stats.bytes_moved += (self.length * self.vtable.size) as u64;
+            }
+        }
+    }
+
+    pub(crate) fn record_clones(&mut self, _count: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.elements_cloned += _count as u64;
+        }
+    }
+
+    pub(crate) fn record_drops(&mut self, _count: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.elements_dropped += _count as u64;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "stats"))]
+mod tests {
+    use super::Stats;
+    use crate::AnyVec;
+
+    #[test]
+    fn test_pushes_are_amortized() {
+        let mut dynamic = AnyVec::new::<u64>();
+        for i in 0..1000u64 {
+            dynamic.push(i);
+        }
+        let stats = dynamic.stats();
+        assert!(stats.reallocations <= 11, "{:?}", stats);
+        assert!(stats.bytes_moved < 2 * 1000 * 8, "{:?}", stats);
+
+        dynamic.reset_stats();
+        dynamic.reserve(10_000);
+        assert_eq!(dynamic.stats().reallocations, 1);
+        // Zero if the allocator grew the buffer in place.
+        assert!(matches!(dynamic.stats().bytes_moved, 0 | 8000));
+    }
+
+    #[test]
+    fn test_clones_and_drops() {
+        let names = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        let mut copy = names.clone();
+        assert_eq!(copy.stats().elements_cloned, 2);
+        copy.push_cloned(names.get_ref(0).unwrap());
+        copy.truncate(1);
+        assert_eq!(
+            copy.stats(),
+            Stats {
+                elements_cloned: 3,
+                elements_dropped: 2,
+                ..copy.stats()
+            }
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Kernels that pick the best implementation an element type's capabilities
+//! allow.
+//!
+//! Rather than panicking when a type lacks the capability for the fastest
+//! approach, these degrade to slower ones: finding unique elements hashes
+//! when it can, sorts when it can't, and falls back to comparing every pair.
+//! The ``*_at`` variants report which [`Strategy`] ran, and take a minimum
+//! [`Tier`] for callers that would rather fail than silently go slow.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::vtable::{HashKey, VTable};
+use crate::{Allocator, AnyVec, CapabilitySet};
+
+/// How fast a strategy is, ordered from slowest to fastest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Tier {
+    Slow,
+    Standard,
+    Fast,
+}
+
+/// One implementation of a kernel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// Hash every element. Linear, needs ``HASH``.
+    Hash,
+    /// Sort, then scan runs of equal elements. ``O(n log n)``, needs ``ORD``.
+    Sort,
+    /// Compare every pair of elements. Quadratic, needs ``EQ``.
+    Pairwise,
+    /// Compare whole buffers at once. Only for types whose equality is
+    /// bytewise (integers, ``bool`` and ``char``).
+    Memcmp,
+    /// Compare one element at a time. Needs ``EQ``.
+    Elementwise,
+}
+
+impl Strategy {
+    pub fn tier(self) -> Tier {
+        match self {
+            Strategy::Hash | Strategy::Memcmp => Tier::Fast,
+            Strategy::Sort | Strategy::Elementwise => Tier::Standard,
+            Strategy::Pairwise => Tier::Slow,
+        }
+    }
+
+    /// The capabilities this strategy needs.
+    pub fn requires(self) -> CapabilitySet {
+        match self {
+            Strategy::Hash => CapabilitySet::EQ | CapabilitySet::HASH,
+            Strategy::Sort => CapabilitySet::ORD,
+            Strategy::Pairwise | Strategy::Memcmp | Strategy::Elementwise => CapabilitySet::EQ,
+        }
+    }
+
+    fn is_available(self, vtable: &VTable) -> bool {
+        vtable.capabilities().contains(self.requires())
+            && (self != Strategy::Memcmp || vtable.bytewise_eq)
+    }
+}
+
+const UNIQUE: &[Strategy] = &[Strategy::Hash, Strategy::Sort, Strategy::Pairwise];
+const EQ: &[Strategy] = &[Strategy::Memcmp, Strategy::Elementwise];
+
+// The first of ``ladder`` that ``vtable`` supports, checked against
+// ``min_tier``.
+fn choose(vtable: &VTable, kernel: &str, ladder: &[Strategy], min_tier: Tier) -> Strategy {
+    let strategy = match ladder.iter().find(|s| s.is_available(vtable)) {
+        Some(strategy) => *strategy,
+        None => {
+            // The last rung needs the least, so report what it's missing.
+            vtable.assert_capabilities(ladder[ladder.len() - 1].requires());
+            unreachable!()
+        }
+    };
+    if strategy.tier() < min_tier {
+        panic!(
+            "{} can't run {} at tier {:?}: the best available strategy is {:?}",
+            vtable.display_name, kernel, min_tier, strategy
+        );
+    }
+    strategy
+}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Indices of the first occurrence of each distinct element, in order.
+    ///
+    /// Panics if the element type doesn't support equality.
+    pub fn unique_indices(&self) -> Vec<usize> {
+        self.unique_indices_at(Tier::Slow).0
+    }
+
+    /// Like [`AnyVec::unique_indices`], also returning the strategy used.
+    ///
+    /// The sort-based strategy groups elements by their ordering, which for
+    /// floats is ``total_cmp``, so ``NaN``s are grouped together and ``-0.0``
+    /// is distinct from ``0.0``. Panics if the best available strategy is
+    /// slower than ``min_tier``.
+    pub fn unique_indices_at(&self, min_tier: Tier) -> (Vec<usize>, Strategy) {
+        let strategy = choose(self.vtable, "unique_indices", UNIQUE, min_tier);
+        let n = self.length;
+        let indices = match strategy {
+            Strategy::Hash => {
+                let fns = self.vtable.require_hash();
+                let mut seen = HashSet::new();
+                (0..n)
+                    .filter(|&i| seen.insert(HashKey::new(self.element_ptr(i), fns)))
+                    .collect()
+            }
+            Strategy::Sort => {
+                let cmp = self.vtable.require_cmp();
+                let mut order: Vec<usize> = (0..n).collect();
+                // Stable, so each run starts with its first occurrence.
+                order.sort_by(|&i, &j| cmp(self.element_ptr(i), self.element_ptr(j)));
+                let mut firsts: Vec<usize> = order
+                    .iter()
+                    .enumerate()
+                    .filter(|&(k, &i)| {
+                        k == 0
+                            || cmp(self.element_ptr(order[k - 1]), self.element_ptr(i))
+                                != Ordering::Equal
+                    })
+                    .map(|(_, &i)| i)
+                    .collect();
+                firsts.sort_unstable();
+                firsts
+            }
+            _ => {
+                let eq = self.vtable.require_eq();
+                let mut firsts: Vec<usize> = Vec::new();
+                for i in 0..n {
+                    if !firsts
+                        .iter()
+                        .any(|&j| eq(self.element_ptr(j), self.element_ptr(i)))
+                    {
+                        firsts.push(i);
+                    }
+                }
+                firsts
+            }
+        };
+        (indices, strategy)
+    }
+
+    /// Compare with ``other`` like ``==``, also returning the strategy used.
+    ///
+    /// Panics if our element type doesn't support equality, or if the best
+    /// available strategy is slower than ``min_tier``.
+    pub fn eq_at<B: Allocator>(&self, other: &AnyVec<B>, min_tier: Tier) -> (bool, Strategy) {
+        let strategy = choose(self.vtable, "eq", EQ, min_tier);
+        if self.length != other.length || !self.vtable.same_type(other.vtable) {
+            return (false, strategy);
+        }
+        let equal = match strategy {
+            Strategy::Memcmp => self.bytes_eq(other),
+            _ => {
+                let eq = self.vtable.require_eq();
+                (0..self.length).all(|i| eq(self.element_ptr(i), other.element_ptr(i)))
+            }
+        };
+        (equal, strategy)
+    }
+
+    // Whether our buffer holds the same bytes as ``other``'s, which must have
+    // the same type and length.
+    pub(crate) fn bytes_eq<B: Allocator>(&self, other: &AnyVec<B>) -> bool {
+        let size = self.length * self.vtable.size;
+        unsafe {
+            std::slice::from_raw_parts(self.data, size)
+                == std::slice::from_raw_parts(other.data, size)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Strategy, Tier};
+    use crate::AnyVec;
+
+    #[test]
+    fn test_unique_ladder() {
+        let hashed = AnyVec::from_vec::<u32>(vec![3, 1, 3, 2, 1]);
+        assert_eq!(
+            hashed.unique_indices_at(Tier::Fast),
+            (vec![0, 1, 3], Strategy::Hash)
+        );
+
+        let sorted = AnyVec::from_vec::<f64>(vec![2.0, 1.0, 2.0, 0.5]);
+        assert_eq!(
+            sorted.unique_indices_at(Tier::Slow),
+            (vec![0, 1, 3], Strategy::Sort)
+        );
+
+        #[derive(PartialEq)]
+        struct Opaque(u8);
+        let pairwise = AnyVec::from_vec(vec![Opaque(1), Opaque(1), Opaque(2)]).with_eq::<Opaque>();
+        assert_eq!(
+            pairwise.unique_indices_at(Tier::Slow),
+            (vec![0, 2], Strategy::Pairwise)
+        );
+        assert_eq!(pairwise.unique_indices(), vec![0, 2]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "can't run unique_indices at tier Fast: the best available strategy is Sort"
+    )]
+    fn test_min_tier() {
+        AnyVec::from_vec::<f32>(vec![1.0]).unique_indices_at(Tier::Fast);
+    }
+
+    #[test]
+    fn test_eq_ladder() {
+        let a = AnyVec::from_vec::<i64>(vec![1, -2, 3]);
+        let b = AnyVec::from_vec::<i64>(vec![1, -2, 3]);
+        assert_eq!(a.eq_at(&b, Tier::Fast), (true, Strategy::Memcmp));
+
+        // Floats can't use memcmp: -0.0 == 0.0 despite the different bits.
+        let a = AnyVec::from_vec::<f64>(vec![-0.0]);
+        let b = AnyVec::from_vec::<f64>(vec![0.0]);
+        assert_eq!(a.eq_at(&b, Tier::Slow), (true, Strategy::Elementwise));
+        assert!(a == b);
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing capabilities: EQ")]
+    fn test_unique_requires_eq() {
+        struct Opaque;
+        AnyVec::from_vec(vec![Opaque]).unique_indices();
+    }
+}
@@ -0,0 +1,199 @@
+//! Named columns of equal length.
+
+use std::any::Any;
+
+use crate::rows::{get_row, push_row};
+use crate::{AnyRow, AnyVec};
+
+/// A table of named ``AnyVec`` columns, which are always the same length.
+///
+/// The column names and element types make up the table's schema: rows
+/// pushed with [`AnyTable::push_row`] must match it slot for slot.
+#[derive(Default)]
+pub struct AnyTable {
+    names: Vec<String>,
+    // Parallel to ``names``.
+    columns: Vec<AnyVec>,
+}
+
+impl AnyTable {
+    /// A table with no columns.
+    pub fn new() -> AnyTable {
+        AnyTable::default()
+    }
+
+    /// Panics if the columns' lengths differ or a name repeats.
+    pub fn from_columns<S: Into<String>>(columns: Vec<(S, AnyVec)>) -> AnyTable {
+        let mut table = AnyTable::new();
+        for (name, column) in columns {
+            table.add_column(name, column);
+        }
+        table
+    }
+
+    /// Add ``column`` after our existing columns.
+    ///
+    /// Panics if we already have a column called ``name``, or if ``column``'s
+    /// length differs from ours.
+    pub fn add_column<S: Into<String>>(&mut self, name: S, column: AnyVec) {
+        let name = name.into();
+        if self.position(&name).is_some() {
+            panic!("Table already has a column named {}", name);
+        }
+        if let Some(first) = self.columns.first() {
+            if column.len() != first.len() {
+                panic!(
+                    "Columns have different lengths ({} != {})",
+                    column.len(),
+                    first.len()
+                );
+            }
+        }
+        self.names.push(name);
+        self.columns.push(column);
+    }
+
+    pub fn remove_column(&mut self, name: &str) -> Option<AnyVec> {
+        let i = self.position(name)?;
+        self.names.remove(i);
+        Some(self.columns.remove(i))
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.columns.first().map_or(0, AnyVec::len)
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn column_names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// Each column's name and element type name, in order.
+    pub fn schema(&self) -> Vec<(&str, &'static str)> {
+        self.names
+            .iter()
+            .zip(&self.columns)
+            .map(|(name, column)| (name.as_str(), column.vtable.display_name))
+            .collect()
+    }
+
+    pub fn column(&self, name: &str) -> Option<&AnyVec> {
+        self.position(name).map(|i| &self.columns[i])
+    }
+
+    /// The column called ``name`` as a slice of ``T``s.
+    ///
+    /// Panics if the column's element type isn't ``T``.
+    pub fn get<T: Any>(&self, name: &str) -> Option<&[T]> {
+        self.column(name)
+            .map(|column| column.get::<T, _>(..).unwrap())
+    }
+
+    /// Append a row with one value per column, in column order.
+    ///
+    /// Panics if the row doesn't match our schema, leaving us unchanged.
+    pub fn push_row(&mut self, row: AnyRow) {
+        push_row(&mut self.columns, row);
+    }
+
+    /// Clone row ``index`` out of every column.
+    ///
+    /// Panics if a column's type doesn't support cloning.
+    pub fn row(&self, index: usize) -> Option<AnyRow> {
+        get_row(&self.columns, index)
+    }
+
+    /// A new table holding clones of the columns called ``names``, in that
+    /// order.
+    ///
+    /// Panics if a column is missing or its type doesn't support cloning.
+    pub fn select(&self, names: &[&str]) -> AnyTable {
+        let mut table = AnyTable::new();
+        for &name in names {
+            match self.column(name) {
+                Some(column) => table.add_column(name, column.clone()),
+                None => panic!("Table has no column named {}", name),
+            }
+        }
+        table
+    }
+
+    pub fn into_columns(self) -> Vec<(String, AnyVec)> {
+        self.names.into_iter().zip(self.columns).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnyTable;
+    use crate::{AnyRow, AnyVec};
+
+    fn trades() -> AnyTable {
+        AnyTable::from_columns(vec![
+            ("id", AnyVec::from_vec::<u64>(vec![1, 2])),
+            ("price", AnyVec::from_vec::<f64>(vec![9.5, 10.0])),
+            (
+                "symbol",
+                AnyVec::from_vec(vec![String::from("A"), String::from("B")]).with_clone::<String>(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_push_and_read_rows() {
+        let mut table = trades();
+        table.push_row(
+            AnyRow::new()
+                .with(3u64)
+                .with(11.5f64)
+                .with(String::from("C")),
+        );
+        assert_eq!(table.num_rows(), 3);
+        assert_eq!(table.get::<f64>("price"), Some(&[9.5, 10.0, 11.5][..]));
+        assert_eq!(table.get::<f64>("volume"), None);
+
+        let row = table.row(2).unwrap();
+        assert_eq!(row.get::<String>(2).unwrap(), "C");
+        assert_eq!(
+            table.schema(),
+            vec![
+                ("id", "u64"),
+                ("price", "f64"),
+                ("symbol", "alloc::string::String")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let mut table = trades();
+        let selected = table.select(&["symbol", "id"]);
+        let names: Vec<&str> = selected.column_names().collect();
+        assert_eq!(names, vec!["symbol", "id"]);
+        assert_eq!(selected.num_rows(), 2);
+
+        let price = table.remove_column("price").unwrap();
+        assert_eq!(price.len(), 2);
+        assert_eq!(table.num_columns(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match (f64 != u32)")]
+    fn test_push_row_enforces_schema() {
+        let mut table = trades();
+        table.push_row(AnyRow::new().with(3u64).with(1u32).with(String::from("C")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Columns have different lengths (1 != 2)")]
+    fn test_add_column_length() {
+        trades().add_column("volume", AnyVec::from_vec::<u32>(vec![1]));
+    }
+}
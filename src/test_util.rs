@@ -0,0 +1,231 @@
+//! ``proptest`` strategies for generating vectors and sequences of operations
+//! on them, for property-testing code built on erased containers.
+
+use std::any::Any;
+use std::fmt;
+use std::ops::Range;
+
+use proptest::arbitrary::{any, Arbitrary};
+use proptest::collection::vec;
+use proptest::sample::select;
+use proptest::strategy::{BoxedStrategy, Just, LazyJust, Strategy};
+
+use crate::vtable::VTable;
+use crate::{AnyValue, AnyVec, Global};
+
+/// An element type that generated vectors can hold. Each supports cloning,
+/// equality and ``Debug``.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElementKind {
+    Bool,
+    U8,
+    I32,
+    U64,
+    /// Finite values only, so that elements always equal themselves.
+    F64,
+    String,
+    /// A zero-sized type, to exercise buffers that never allocate.
+    Unit,
+}
+
+impl ElementKind {
+    pub const ALL: &'static [ElementKind] = &[
+        ElementKind::Bool,
+        ElementKind::U8,
+        ElementKind::I32,
+        ElementKind::U64,
+        ElementKind::F64,
+        ElementKind::String,
+        ElementKind::Unit,
+    ];
+
+    /// An empty vector of this type.
+    pub fn empty(self) -> AnyVec {
+        AnyVec::from_vtable_in(self.vtable(), Global)
+    }
+
+    fn vtable(self) -> &'static VTable {
+        fn vtable<T: Any + Clone + PartialEq + fmt::Debug>() -> &'static VTable {
+            VTable::new::<T>()
+                .with_clone::<T>()
+                .with_eq::<T>()
+                .with_debug::<T>()
+        }
+        match self {
+            ElementKind::Bool => vtable::<bool>(),
+            ElementKind::U8 => vtable::<u8>(),
+            ElementKind::I32 => vtable::<i32>(),
+            ElementKind::U64 => vtable::<u64>(),
+            ElementKind::F64 => vtable::<f64>(),
+            ElementKind::String => vtable::<String>(),
+            ElementKind::Unit => vtable::<()>(),
+        }
+    }
+
+    /// Values of this type, with the same capabilities as [`empty`]
+    /// vectors.
+    ///
+    /// [`empty`]: ElementKind::empty
+    pub fn values(self) -> BoxedStrategy<AnyValue> {
+        fn boxed<T: Any>(value: T) -> *mut u8 {
+            Box::into_raw(Box::new(value)) as *mut u8
+        }
+        let data = match self {
+            ElementKind::Bool => any::<bool>().prop_map(boxed).boxed(),
+            ElementKind::U8 => any::<u8>().prop_map(boxed).boxed(),
+            ElementKind::I32 => any::<i32>().prop_map(boxed).boxed(),
+            ElementKind::U64 => any::<u64>().prop_map(boxed).boxed(),
+            ElementKind::F64 => (-1e9..1e9f64).prop_map(boxed).boxed(),
+            ElementKind::String => any::<String>().prop_map(boxed).boxed(),
+            ElementKind::Unit => Just(()).prop_map(boxed).boxed(),
+        };
+        let vtable = self.vtable();
+        data.prop_map(move |data| unsafe { AnyValue::from_raw(data, vtable) })
+            .boxed()
+    }
+
+    /// Vectors of this type with a length in ``len``.
+    pub fn vecs(self, len: Range<usize>) -> BoxedStrategy<AnyVec> {
+        vec(self.values(), len)
+            .prop_map(move |values| {
+                let mut dynamic = self.empty();
+                for value in values {
+                    dynamic.push_value(value);
+                }
+                dynamic
+            })
+            .boxed()
+    }
+
+    /// Sequences of ``count`` operations on vectors of this type.
+    pub fn operations(self, count: Range<usize>) -> BoxedStrategy<Vec<Operation>> {
+        let operation = proptest::prop_oneof![
+            4 => self.values().prop_map(Operation::Push),
+            2 => (any::<usize>(), self.values())
+                .prop_map(|(index, value)| Operation::Insert(index, value)),
+            1 => (0..64usize).prop_map(Operation::Truncate),
+            1 => (0..64usize).prop_map(Operation::Reserve),
+            1 => LazyJust::new(|| Operation::Clear),
+        ];
+        vec(operation, count).boxed()
+    }
+}
+
+impl Arbitrary for ElementKind {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<ElementKind>;
+
+    fn arbitrary_with(_: ()) -> BoxedStrategy<ElementKind> {
+        select(ElementKind::ALL).boxed()
+    }
+}
+
+/// A mutation of a vector, generated by [`ElementKind::operations`] with
+/// values of that kind.
+#[derive(Debug)]
+pub enum Operation {
+    Push(AnyValue),
+    /// Insert a clone of the value. The index is reduced modulo ``len + 1``,
+    /// so it's always in bounds.
+    Insert(usize, AnyValue),
+    Truncate(usize),
+    Reserve(usize),
+    Clear,
+}
+
+impl Operation {
+    /// Panics if the vector's element type differs from the operation's
+    /// values'.
+    pub fn apply(&self, dynamic: &mut AnyVec) {
+        match self {
+            Operation::Push(value) => dynamic.push_cloned(value.as_any_ref()),
+            Operation::Insert(index, value) => {
+                dynamic.insert_cloned(index % (dynamic.len() + 1), value.as_any_ref())
+            }
+            &Operation::Truncate(length) => dynamic.truncate(length),
+            &Operation::Reserve(additional) => dynamic.reserve(additional),
+            Operation::Clear => dynamic.clear(),
+        }
+    }
+}
+
+/// Vectors of any of ``kinds``, with a length in ``len``.
+pub fn any_vec_of(kinds: &[ElementKind], len: Range<usize>) -> BoxedStrategy<AnyVec> {
+    select(kinds.to_vec())
+        .prop_flat_map(move |kind| kind.vecs(len.clone()))
+        .boxed()
+}
+
+/// A vector of any of ``kinds`` together with ``count`` operations to apply
+/// to it.
+pub fn any_vec_with_operations(
+    kinds: &[ElementKind],
+    len: Range<usize>,
+    count: Range<usize>,
+) -> BoxedStrategy<(AnyVec, Vec<Operation>)> {
+    select(kinds.to_vec())
+        .prop_flat_map(move |kind| (kind.vecs(len.clone()), kind.operations(count.clone())))
+        .boxed()
+}
+
+/// Vectors of any [`ElementKind`], of length below 32.
+impl Arbitrary for AnyVec {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<AnyVec>;
+
+    fn arbitrary_with(_: ()) -> BoxedStrategy<AnyVec> {
+        any_vec_of(ElementKind::ALL, 0..32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{any_vec_with_operations, ElementKind, Operation};
+    use crate::{AnyValue, AnyVec};
+
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_arbitrary_vecs_are_valid(dynamic in any::<AnyVec>()) {
+            dynamic.debug_assert_invariants();
+            prop_assert!(dynamic.len() < 32);
+            prop_assert!(dynamic.clone() == dynamic);
+        }
+
+        #[test]
+        fn test_operations_match_model(
+            (mut dynamic, operations) in any_vec_with_operations(ElementKind::ALL, 0..8, 0..32)
+        ) {
+            let mut model: Vec<AnyValue> = (0..dynamic.len())
+                .map(|i| dynamic.get_ref(i).unwrap().to_value())
+                .collect();
+            for operation in &operations {
+                operation.apply(&mut dynamic);
+                dynamic.debug_assert_invariants();
+                match operation {
+                    Operation::Push(value) => model.push(value.as_any_ref().to_value()),
+                    Operation::Insert(index, value) => {
+                        model.insert(index % (model.len() + 1), value.as_any_ref().to_value())
+                    }
+                    &Operation::Truncate(length) => model.truncate(length),
+                    Operation::Reserve(_) => {}
+                    Operation::Clear => model.clear(),
+                }
+            }
+            prop_assert_eq!(dynamic.len(), model.len());
+            for (i, value) in model.iter().enumerate() {
+                prop_assert!(dynamic.get_ref(i).unwrap() == value.as_any_ref());
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_kinds() {
+        for &kind in ElementKind::ALL {
+            let dynamic = kind.empty();
+            assert!(dynamic.is_empty());
+            assert!(dynamic.clone() == dynamic);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Deferred deletion: rows are tombstoned on delete and only physically
+//! removed by an explicit compaction pass.
+
+use std::ptr;
+
+use crate::{Allocator, AnyRef, AnyValue, AnyVec, Global};
+
+impl<A: Allocator> AnyVec<A> {
+    /// Remove every element whose index ``keep`` rejects, preserving the order
+    /// of the rest. Returns the number removed.
+    pub(crate) fn retain_indices<F: FnMut(usize) -> bool>(&mut self, mut keep: F) -> usize {
+        let (length, size) = (self.length, self.vtable.size);
+        // If a destructor panics we leak the remaining elements rather than
+        // risk dropping anything twice.
+        self.length = 0;
+
+        let mut kept = 0;
+        for i in 0..length {
+            let src = unsafe { self.data.add(i * size) };
+            if keep(i) {
+                if kept != i {
+                    unsafe { ptr::copy_nonoverlapping(src, self.data.add(kept * size), size) };
+                }
+                kept += 1;
+            } else if self.vtable.needs_drop {
+                (self.vtable.drop_slice)(src, 1);
+            }
+        }
+        self.length = kept;
+        self.record_drops(length - kept);
+        length - kept
+    }
+}
+
+/// Parallel columns whose rows can be deleted cheaply.
+///
+/// ``delete_row`` only marks a row as deleted. Reads skip deleted rows, and
+/// ``compact`` removes them from every column in one pass. Row indices are
+/// physical, so they stay stable until the next compaction.
+pub struct TombstoneTable<A: Allocator = Global> {
+    columns: Vec<AnyVec<A>>,
+    // One bit per physical row, set if the row is deleted.
+    deleted: Vec<u64>,
+    ndeleted: usize,
+}
+
+impl<A: Allocator> TombstoneTable<A> {
+    /// Panics if the columns' lengths differ.
+    pub fn new(columns: Vec<AnyVec<A>>) -> TombstoneTable<A> {
+        let length = columns.first().map_or(0, AnyVec::len);
+        for column in &columns {
+            if column.len() != length {
+                panic!(
+                    "Columns have different lengths ({} != {})",
+                    column.len(),
+                    length
+                );
+            }
+        }
+        TombstoneTable {
+            columns,
+            deleted: vec![0; length.div_ceil(64)],
+            ndeleted: 0,
+        }
+    }
+
+    /// Number of rows, including deleted ones.
+    pub fn physical_rows(&self) -> usize {
+        self.columns.first().map_or(0, AnyVec::len)
+    }
+
+    /// Number of rows that haven't been deleted.
+    pub fn live_rows(&self) -> usize {
+        self.physical_rows() - self.ndeleted
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Panics if ``row`` is out of bounds.
+    pub fn is_deleted(&self, row: usize) -> bool {
+        self.assert_in_bounds(row);
+        self.deleted[row / 64] & (1 << (row % 64)) != 0
+    }
+
+    /// Mark ``row`` as deleted, returning false if it already was.
+    ///
+    /// Panics if ``row`` is out of bounds.
+    pub fn delete_row(&mut self, row: usize) -> bool {
+        if self.is_deleted(row) {
+            return false;
+        }
+        self.deleted[row / 64] |= 1 << (row % 64);
+        self.ndeleted += 1;
+        true
+    }
+
+    fn assert_in_bounds(&self, row: usize) {
+        if row >= self.physical_rows() {
+            panic!(
+                "Row {} out of bounds for table of {} rows",
+                row,
+                self.physical_rows()
+            );
+        }
+    }
+
+    /// Append a row with one value per column.
+    ///
+    /// Panics if the row's length or types don't match our columns.
+    pub fn push_row(&mut self, row: Vec<AnyValue>) {
+        if row.len() != self.columns.len() {
+            panic!(
+                "Row has {} values, but table has {} columns",
+                row.len(),
+                self.columns.len()
+            );
+        }
+        for (column, value) in self.columns.iter().zip(&row) {
+            column.vtable.assert_same_type(value.vtable());
+        }
+
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push_value(value);
+        }
+        if self.physical_rows() > self.deleted.len() * 64 {
+            self.deleted.push(0);
+        }
+    }
+
+    /// The value at ``row`` in ``column``, or ``None`` if the row is deleted
+    /// or out of bounds.
+    pub fn get_ref(&self, column: usize, row: usize) -> Option<AnyRef<'_>> {
+        if row >= self.physical_rows() || self.is_deleted(row) {
+            return None;
+        }
+        self.columns[column].get_ref(row)
+    }
+
+    /// Physical indices of the rows that haven't been deleted.
+    pub fn live_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.physical_rows()).filter(move |&row| !self.is_deleted(row))
+    }
+
+    /// Values of ``column``, skipping deleted rows.
+    pub fn column_values(&self, column: usize) -> impl Iterator<Item = AnyRef<'_>> {
+        let values = &self.columns[column];
+        self.live_indices()
+            .map(move |row| values.get_ref(row).unwrap())
+    }
+
+    /// Physically remove deleted rows from every column, returning how many
+    /// were removed. Row indices are renumbered densely afterwards.
+    pub fn compact(&mut self) -> usize {
+        if self.ndeleted == 0 {
+            return 0;
+        }
+        let deleted = &self.deleted;
+        for column in &mut self.columns {
+            column.retain_indices(|row| deleted[row / 64] & (1 << (row % 64)) == 0);
+        }
+
+        let removed = self.ndeleted;
+        self.deleted = vec![0; self.physical_rows().div_ceil(64)];
+        self.ndeleted = 0;
+        removed
+    }
+
+    /// Compact, then return our columns.
+    pub fn into_columns(mut self) -> Vec<AnyVec<A>> {
+        self.compact();
+        self.columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TombstoneTable;
+    use crate::{AnyValue, AnyVec};
+
+    use std::rc::Rc;
+
+    #[test]
+    fn test_delete_and_compact() {
+        let ids: Vec<u32> = (0..100).collect();
+        let mut table = TombstoneTable::new(vec![
+            AnyVec::from_vec(ids.clone()),
+            AnyVec::from_vec(ids.iter().map(|&i| i as f64 * 0.5).collect::<Vec<f64>>()),
+        ]);
+
+        for row in (0..100).filter(|row| row % 3 == 0) {
+            assert!(table.delete_row(row));
+        }
+        assert!(!table.delete_row(0));
+        assert_eq!(table.physical_rows(), 100);
+        assert_eq!(table.live_rows(), 66);
+        assert!(table.get_ref(0, 3).is_none());
+        assert_eq!(table.get_ref(0, 4).unwrap(), &4u32);
+
+        let live: Vec<u32> = table
+            .column_values(0)
+            .map(|x| *x.downcast_ref().unwrap())
+            .collect();
+        let expected: Vec<u32> = ids.iter().copied().filter(|i| i % 3 != 0).collect();
+        assert_eq!(live, expected);
+
+        assert_eq!(table.compact(), 34);
+        assert_eq!(table.physical_rows(), 66);
+        assert_eq!(table.get_ref(1, 0).unwrap(), &0.5f64);
+
+        table.push_row(vec![AnyValue::new(100u32), AnyValue::new(50.0f64)]);
+        assert!(table.delete_row(66));
+        let columns = table.into_columns();
+        assert_eq!(columns[0].len(), 66);
+    }
+
+    #[test]
+    fn test_compact_drops_deleted() {
+        let chan = Rc::new(());
+        let mut table = TombstoneTable::new(vec![AnyVec::from_vec(vec![chan.clone(); 4])]);
+        table.delete_row(1);
+        table.delete_row(2);
+        assert_eq!(Rc::strong_count(&chan), 5);
+
+        table.compact();
+        assert_eq!(Rc::strong_count(&chan), 3);
+        drop(table);
+        assert_eq!(Rc::strong_count(&chan), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_delete_out_of_bounds() {
+        let mut table = TombstoneTable::new(vec![AnyVec::from_vec::<u8>(vec![1, 2])]);
+        table.delete_row(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Row 2 out of bounds for table of 2 rows")]
+    fn test_is_deleted_out_of_bounds() {
+        let table = TombstoneTable::new(vec![AnyVec::from_vec::<u8>(vec![1, 2])]);
+        table.is_deleted(2);
+    }
+}
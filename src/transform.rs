@@ -0,0 +1,69 @@
+//! Deriving new vectors from typed functions of existing ones.
+
+use std::any::Any;
+use std::slice;
+
+use crate::{Allocator, AnyRef, AnyVec};
+
+impl<A: Allocator> AnyVec<A> {
+    /// A new vector holding ``f`` of each element, in order.
+    ///
+    /// The type is checked once up front, and the output is sized before
+    /// ``f`` is first called. Panics if the element type isn't ``T``.
+    pub fn map<T: Any, U: Any, F: FnMut(&T) -> U>(&self, f: F) -> AnyVec {
+        let values = self.get::<T, _>(..).unwrap();
+        let mut mapped = Vec::with_capacity(values.len());
+        mapped.extend(values.iter().map(f));
+        AnyVec::from_vec(mapped)
+    }
+
+    /// Call ``f`` on each element, letting it modify the element in place.
+    ///
+    /// Panics if the element type isn't ``T``.
+    pub fn map_in_place<T: Any, F: FnMut(&mut T)>(&mut self, f: F) {
+        self.assert_typecheck::<T>();
+        let values = unsafe { slice::from_raw_parts_mut(self.data as *mut T, self.length) };
+        values.iter_mut().for_each(f);
+    }
+
+    /// Call ``f`` on each element, in order, without knowing its type.
+    pub fn for_each<F: FnMut(AnyRef<'_>)>(&self, f: F) {
+        self.as_any_slice().iter().for_each(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    #[test]
+    fn test_map() {
+        let prices = AnyVec::from_vec::<f64>(vec![9.5, 10.25]);
+        let cents = prices.map(|&price: &f64| (price * 100.0) as u64);
+        assert_eq!(cents.into_vec::<u64>(), vec![950, 1025]);
+
+        let labels = AnyVec::from_vec::<u8>(vec![1, 2]).map(|n: &u8| format!("#{}", n));
+        assert_eq!(labels.into_vec::<String>(), vec!["#1", "#2"]);
+    }
+
+    #[test]
+    fn test_map_in_place() {
+        let mut names = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        names.map_in_place(|name: &mut String| name.push('!'));
+        assert_eq!(names.into_vec::<String>(), vec!["a!", "b!"]);
+    }
+
+    #[test]
+    fn test_for_each() {
+        let dynamic = AnyVec::from_vec::<u32>(vec![3, 4]);
+        let mut seen = Vec::new();
+        dynamic.for_each(|value| seen.push(value.to_string()));
+        assert_eq!(seen, vec!["3", "4"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match runtime type")]
+    fn test_map_typecheck() {
+        AnyVec::from_vec::<f64>(vec![1.0]).map(|&v: &f32| v);
+    }
+}
@@ -0,0 +1,104 @@
+//! Conditional single-element updates, in the style of compare-and-swap.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::{Allocator, AnyRef, AnyValue, AnyVec};
+
+/// Returned by [`AnyVec::update_if`] when the element didn't hold the
+/// expected value. Carries the rejected value back so it can be retried.
+pub struct Conflict {
+    pub value: AnyValue,
+}
+
+impl fmt::Debug for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Conflict")
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "element did not hold the expected value")
+    }
+}
+
+impl Error for Conflict {}
+
+impl<A: Allocator> AnyVec<A> {
+    /// Replace the element at ``index`` with ``new``, but only if it currently
+    /// equals ``expected``.
+    ///
+    /// Panics if ``index`` is out of bounds, if ``expected`` or ``new`` has a
+    /// different type from ours, or if our type doesn't support equality.
+    ///
+    /// This needs ``&mut self``, so it can't race. For vectors that several
+    /// threads update at once, see
+    /// [`SharedAnyVec::update_if`](crate::SharedAnyVec::update_if).
+    pub fn update_if(
+        &mut self,
+        index: usize,
+        expected: &AnyRef,
+        new: AnyValue,
+    ) -> Result<(), Conflict> {
+        if index >= self.length {
+            panic!(
+                "Index {} out of bounds for vector of length {}",
+                index, self.length
+            );
+        }
+        self.vtable.assert_same_type(expected.vtable());
+        self.vtable.assert_same_type(new.vtable());
+
+        let slot = self.element_ptr(index) as *mut u8;
+        if !(self.vtable.require_eq())(slot, expected.data()) {
+            return Err(Conflict { value: new });
+        }
+        if self.vtable.needs_drop {
+            (self.vtable.drop_slice)(slot, 1);
+        }
+        self.record_drops(1);
+        let (boxed, _) = new.into_raw();
+        (self.vtable.unbox_into)(boxed, slot);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyValue, AnyVec};
+
+    #[test]
+    fn test_update_if() {
+        let mut dynamic = AnyVec::from_vec(vec![String::from("a"), String::from("b")]);
+        let expected = AnyValue::new(String::from("b"));
+
+        assert!(dynamic
+            .update_if(1, &expected.as_any_ref(), AnyValue::new(String::from("c")))
+            .is_ok());
+
+        let conflict = dynamic
+            .update_if(1, &expected.as_any_ref(), AnyValue::new(String::from("d")))
+            .unwrap_err();
+        assert_eq!(conflict.value.downcast::<String>().unwrap(), "d");
+        assert_eq!(dynamic.into_vec::<String>(), vec!["a", "c"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support equality")]
+    fn test_update_if_requires_eq() {
+        let mut dynamic = AnyVec::from_vec(vec![vec![1u8]]);
+        let expected = AnyValue::new(vec![1u8]);
+        let _ = dynamic.update_if(0, &expected.as_any_ref(), AnyValue::new(vec![2u8]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Runtime types do not match")]
+    fn test_update_if_typecheck() {
+        let mut dynamic = AnyVec::from_vec::<u32>(vec![1]);
+        let expected = AnyValue::new(1u32);
+        let _ = dynamic.update_if(0, &expected.as_any_ref(), AnyValue::new(2u64));
+    }
+}
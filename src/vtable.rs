@@ -0,0 +1,894 @@
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec as AllocVec;
+
+use crate::arith::{arith, ArithOp, Arithmetic};
+use crate::capability::CapabilitySet;
+use crate::cast::{cast_into, Cast, CastPolicy};
+use crate::guard::InitGuard;
+use crate::memory::HeapSize;
+use crate::numeric::{Number, Numeric};
+use crate::parse::ParseError;
+use crate::AnyValue;
+
+pub type EqFn = fn(*const u8, *const u8) -> bool;
+pub type HashFn = fn(*const u8, &mut dyn Hasher);
+pub type CmpFn = fn(*const u8, *const u8) -> Ordering;
+pub type FmtFn = fn(*const u8, &mut fmt::Formatter) -> fmt::Result;
+/// Parse a string into uninitialized memory, leaving it uninitialized on
+/// failure.
+pub type ParseFn = fn(&str, *mut u8) -> Result<(), ParseError>;
+// The returned reference really borrows from the element, so it mustn't
+// outlive it.
+#[cfg(feature = "serde")]
+pub type SerializeFn = fn(*const u8) -> &'static dyn erased_serde::Serialize;
+pub type ReserveFn = fn(*mut u8, usize, usize, usize, &dyn Allocator) -> (*mut u8, usize);
+
+// Every distinct vtable is built once and leaked, so containers can hold a
+// cheap ``&'static VTable``. A vtable is fully determined by its element type
+// and the set of capabilities it has been given.
+//
+// Interning, rather than building a vtable per container, is what lets type
+// checks and capability upgrades compare pointers, and keeps containers to
+// one word of type information. The cost is this lock, so ``VTable::new``,
+// which runs every time a vector is created, first checks a per-thread cache
+// of the vtables it has already returned. See the ``new`` benchmarks in
+// benches/anyvec.rs.
+static VTABLES: Mutex<BTreeMap<(TypeId, u32), &'static VTable>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    static NEW_VTABLES: RefCell<BTreeMap<TypeId, &'static VTable>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+#[derive(Clone)]
+pub struct VTable {
+    id: TypeId,
+    pub display_name: &'static str,
+    pub drop_vec: fn(*mut u8, usize, usize, &dyn Allocator),
+    pub drop_slice: fn(*mut u8, usize),
+    pub drop_box: fn(*mut u8),
+    pub reserve: ReserveFn,
+    /// Like ``reserve``, but shrinking the buffer to the given minimum
+    /// capacity, or our length if that's larger.
+    pub shrink_to: ReserveFn,
+    pub box_value: fn(*const u8) -> *mut u8,
+    pub unbox_into: fn(*mut u8, *mut u8),
+    pub size: usize,
+    pub align: usize,
+    /// Whether dropping an element does anything. When it doesn't, we can
+    /// discard elements without visiting them.
+    pub needs_drop: bool,
+
+    // Optional capabilities. These can only be filled in when the element
+    // type implements the corresponding trait, so they're opted into with the
+    // ``with_*`` methods below. Primitive types get theirs automatically.
+    pub eq: Option<EqFn>,
+    pub hash: Option<HashFn>,
+    pub cmp: Option<CmpFn>,
+    pub clone: Option<CloneFns>,
+    pub numeric: Option<NumericFns>,
+    pub debug: Option<FmtFn>,
+    pub display: Option<FmtFn>,
+    pub parse: Option<ParseFn>,
+    /// The heap memory an element owns, beyond its inline size.
+    pub heap_size: Option<fn(*const u8) -> usize>,
+    pub send: bool,
+    pub sync: bool,
+    /// Whether any bit pattern is a valid element and elements have no
+    /// padding, so our buffer can be viewed as plain bytes.
+    pub pod: bool,
+    /// Whether two elements are equal exactly when their bytes are, so runs
+    /// of elements can be compared with a memcmp. Floats aren't (``NaN`` and
+    /// ``-0.0``), nor is anything with padding or indirection.
+    pub bytewise_eq: bool,
+    /// Typed loops over whole runs of elements, for the primitive number
+    /// types, in place of calling ``eq`` or ``clone`` per element.
+    pub slice_kernels: Option<SliceKernels>,
+    #[cfg(feature = "serde")]
+    pub serialize: Option<SerializeFn>,
+}
+
+#[derive(Clone, Copy)]
+pub struct CloneFns {
+    /// Clone ``count`` elements into uninitialized memory.
+    pub clone_into: fn(*const u8, *mut u8, usize),
+    /// Clone one element into a new box.
+    pub clone_box: fn(*const u8) -> *mut u8,
+    /// Whether the type is ``Copy``, so ``clone_into`` is a plain memcpy.
+    pub trivial: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct NumericFns {
+    pub to_number: fn(*const u8) -> Number,
+    pub from_number: fn(Number) -> Option<AnyValue>,
+    /// Typed loops over whole runs of elements, which the compiler can
+    /// vectorize. Only the primitive number types have them; others fall
+    /// back to calling ``to_number`` and ``cmp`` per element.
+    pub kernels: Option<NumericKernels>,
+}
+
+#[derive(Clone, Copy)]
+pub struct NumericKernels {
+    /// Sum ``count`` elements on the cast lattice.
+    pub sum: fn(*const u8, usize) -> Number,
+    /// The index of the least of ``count`` elements under ``cmp``, the first
+    /// if there are ties.
+    pub argmin: fn(*const u8, usize) -> Option<usize>,
+    /// The index of the greatest of ``count`` elements under ``cmp``, the
+    /// first if there are ties.
+    pub argmax: fn(*const u8, usize) -> Option<usize>,
+    /// Apply an operation to ``count`` pairs of elements, in place in the
+    /// first run.
+    pub arith: fn(ArithOp, *mut u8, *const u8, usize),
+    /// Write a number into uninitialized memory under a policy. Returns
+    /// false, writing nothing, if a checked cast would lose information.
+    pub cast: fn(Number, CastPolicy, *mut u8) -> bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct SliceKernels {
+    /// Whether ``count`` pairs of elements are all equal under ``eq``.
+    pub eq_slice: fn(*const u8, *const u8, usize) -> bool,
+    /// Whether any of ``count`` elements is equal to the needle, the last
+    /// argument.
+    pub contains: fn(*const u8, usize, *const u8) -> bool,
+    /// Overwrite ``count`` elements with copies of the last argument.
+    pub fill: fn(*mut u8, usize, *const u8),
+}
+
+impl VTable {
+    pub fn new<T: Any>() -> &'static VTable {
+        let id = TypeId::of::<T>();
+        if let Some(vtable) = NEW_VTABLES.with(|cache| cache.borrow().get(&id).copied()) {
+            return vtable;
+        }
+        let vtable = VTable::build::<T>();
+        NEW_VTABLES.with(|cache| cache.borrow_mut().insert(id, vtable));
+        vtable
+    }
+
+    fn build<T: Any>() -> &'static VTable {
+        let vtable = VTable {
+            id: TypeId::of::<T>(),
+            display_name: type_name::<T>(),
+            drop_vec: drop_vec::<T>,
+            drop_slice: drop_slice::<T>,
+            drop_box: drop_box::<T>,
+            reserve: reserve::<T>,
+            shrink_to: shrink_to::<T>,
+            box_value: box_value::<T>,
+            unbox_into: unbox_into::<T>,
+            size: std::mem::size_of::<T>(),
+            align: std::mem::align_of::<T>(),
+            needs_drop: std::mem::needs_drop::<T>(),
+            eq: None,
+            hash: None,
+            cmp: None,
+            clone: None,
+            numeric: None,
+            debug: None,
+            display: None,
+            parse: None,
+            heap_size: None,
+            send: false,
+            sync: false,
+            pod: false,
+            bytewise_eq: false,
+            slice_kernels: None,
+            #[cfg(feature = "serde")]
+            serialize: None,
+        }
+        .with_primitive_capabilities();
+        #[cfg(feature = "serde")]
+        let vtable = vtable.with_primitive_serialize();
+        vtable.intern()
+    }
+
+    fn intern(self) -> &'static VTable {
+        self.assert_well_formed();
+        let key = (self.id, self.capabilities().bits());
+        let mut vtables = VTABLES.lock().unwrap_or_else(PoisonError::into_inner);
+        vtables
+            .entry(key)
+            .or_insert_with(|| Box::leak(Box::new(self)))
+    }
+
+    /// Panic unless our layout is valid, our flags agree with each other, and
+    /// we're the interned vtable for our type and capabilities.
+    #[cfg(test)]
+    pub fn assert_consistent(&self) {
+        self.assert_well_formed();
+        let key = (self.id, self.capabilities().bits());
+        let vtables = VTABLES.lock().unwrap_or_else(PoisonError::into_inner);
+        if !vtables.get(&key).is_some_and(|&v| std::ptr::eq(v, self)) {
+            panic!(
+                "Invariant violated: vtable for {} is not interned",
+                self.display_name
+            );
+        }
+    }
+
+    // Every vtable is checked once, as it's interned, so vectors don't need to
+    // recheck theirs.
+    fn assert_well_formed(&self) {
+        if !self.align.is_power_of_two() || !self.size.is_multiple_of(self.align) {
+            panic!(
+                "Invariant violated: {} has size {} and alignment {}",
+                self.display_name, self.size, self.align
+            );
+        }
+        let copy = self.clone.is_some_and(|clone| clone.trivial);
+        if self.needs_drop && (copy || self.pod) {
+            panic!(
+                "Invariant violated: {} needs dropping but is marked trivial",
+                self.display_name
+            );
+        }
+    }
+
+    pub fn capabilities(&self) -> CapabilitySet {
+        let copy = self.clone.is_some_and(|clone| clone.trivial);
+        #[cfg(feature = "serde")]
+        let serialize = self.serialize.is_some();
+        #[cfg(not(feature = "serde"))]
+        let serialize = false;
+
+        CapabilitySet::empty()
+            .with_if(CapabilitySet::CLONE, self.clone.is_some())
+            .with_if(CapabilitySet::COPY, copy)
+            .with_if(CapabilitySet::EQ, self.eq.is_some())
+            .with_if(CapabilitySet::ORD, self.cmp.is_some())
+            .with_if(CapabilitySet::HASH, self.hash.is_some())
+            .with_if(CapabilitySet::DEBUG, self.debug.is_some())
+            .with_if(CapabilitySet::DISPLAY, self.display.is_some())
+            .with_if(CapabilitySet::PARSE, self.parse.is_some())
+            .with_if(CapabilitySet::HEAP_SIZE, self.heap_size.is_some())
+            .with_if(CapabilitySet::SEND, self.send)
+            .with_if(CapabilitySet::SYNC, self.sync)
+            .with_if(CapabilitySet::POD, self.pod)
+            .with_if(CapabilitySet::NUMERIC, self.numeric.is_some())
+            .with_if(CapabilitySet::SERIALIZE, serialize)
+    }
+
+    /// Panic, listing what's missing, unless we have every capability in
+    /// ``required``.
+    pub fn assert_capabilities(&self, required: CapabilitySet) {
+        let missing = required - self.capabilities();
+        if !missing.is_empty() {
+            panic!(
+                "{} is missing capabilities: {:?}",
+                self.display_name, missing
+            );
+        }
+    }
+
+    // Without specialization we can't ask whether an arbitrary ``T``
+    // implements a trait, but we can recognize the primitive types by id.
+    fn with_primitive_capabilities(mut self) -> VTable {
+        macro_rules! dispatch {
+            ($($method:ident::<$t:ty>),*) => {
+                $(
+                    if self.is::<$t>() {
+                        self.$method::<$t>();
+                        self.set_parse::<$t>();
+                        return self;
+                    }
+                )*
+            };
+        }
+
+        dispatch!(
+            set_integer::<i8>,
+            set_integer::<i16>,
+            set_integer::<i32>,
+            set_integer::<i64>,
+            set_integer::<isize>,
+            set_integer::<u8>,
+            set_integer::<u16>,
+            set_integer::<u32>,
+            set_integer::<u64>,
+            set_integer::<usize>,
+            set_float::<f32>,
+            set_float::<f64>,
+            set_scalar::<bool>,
+            set_scalar::<char>,
+            set_string::<String>
+        );
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    fn with_primitive_serialize(mut self) -> VTable {
+        macro_rules! dispatch {
+            ($($t:ty),*) => {
+                $(
+                    if self.is::<$t>() {
+                        self.set_serialize::<$t>();
+                        return self;
+                    }
+                )*
+            };
+        }
+
+        dispatch!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, bool, char, String);
+        self
+    }
+
+    fn set_basic<T: Any + fmt::Debug + fmt::Display + Send + Sync>(&mut self) {
+        self.set_debug::<T>();
+        self.set_display::<T>();
+        self.send = true;
+        self.sync = true;
+    }
+
+    fn set_ordered<T: Any + Ord + Hash + Clone + fmt::Debug + fmt::Display + Send + Sync>(
+        &mut self,
+    ) {
+        self.set_basic::<T>();
+        self.set_hash::<T>();
+        self.set_ord::<T>();
+        self.set_clone::<T>();
+    }
+
+    fn set_scalar<T: Any + Ord + Hash + Copy + fmt::Debug + fmt::Display + Send + Sync>(&mut self) {
+        self.set_ordered::<T>();
+        self.set_copy::<T>();
+        self.bytewise_eq = true;
+    }
+
+    fn set_integer<T: Arithmetic + Cast + Ord + Hash + fmt::Debug + fmt::Display + Send + Sync>(
+        &mut self,
+    ) {
+        self.set_scalar::<T>();
+        self.set_numeric::<T>();
+        self.set_kernels(NumericKernels {
+            sum: sum_integers::<T>,
+            argmin: argmin::<T>,
+            argmax: argmax::<T>,
+            arith: arith::<T>,
+            cast: cast_into::<T>,
+        });
+        self.set_slice_kernels::<T>();
+        self.pod = true;
+    }
+
+    fn set_float<T: Float>(&mut self) {
+        self.set_basic::<T>();
+        self.set_eq::<T>();
+        self.cmp = Some(total_cmp::<T>);
+        self.set_copy::<T>();
+        self.set_numeric::<T>();
+        self.set_kernels(NumericKernels {
+            sum: sum_floats::<T>,
+            argmin: argmin_float::<T>,
+            argmax: argmax_float::<T>,
+            arith: arith::<T>,
+            cast: cast_into::<T>,
+        });
+        self.set_slice_kernels::<T>();
+        self.pod = true;
+    }
+
+    fn set_string<
+        T: Any + Ord + Hash + Clone + HeapSize + fmt::Debug + fmt::Display + Send + Sync,
+    >(
+        &mut self,
+    ) {
+        self.set_ordered::<T>();
+        self.set_heap_size::<T>();
+    }
+
+    fn set_eq<T: Any + PartialEq>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.eq = Some(eq::<T>);
+    }
+
+    fn set_hash<T: Any + Hash + Eq>(&mut self) {
+        self.set_eq::<T>();
+        self.hash = Some(hash::<T>);
+    }
+
+    fn set_ord<T: Any + Ord>(&mut self) {
+        self.set_eq::<T>();
+        self.cmp = Some(cmp::<T>);
+    }
+
+    fn set_clone<T: Any + Clone>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.clone = Some(CloneFns {
+            clone_into: clone_into::<T>,
+            clone_box: clone_box::<T>,
+            trivial: false,
+        });
+    }
+
+    fn set_copy<T: Any + Copy>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.clone = Some(CloneFns {
+            clone_into: copy_into::<T>,
+            clone_box: clone_box::<T>,
+            trivial: true,
+        });
+    }
+
+    fn set_debug<T: Any + fmt::Debug>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.debug = Some(debug::<T>);
+    }
+
+    fn set_display<T: Any + fmt::Display>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.display = Some(display::<T>);
+    }
+
+    fn set_parse<T: Any + FromStr>(&mut self)
+    where
+        T::Err: fmt::Display,
+    {
+        self.assert_typecheck::<T>();
+        self.parse = Some(parse::<T>);
+    }
+
+    fn set_heap_size<T: Any + HeapSize>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.heap_size = Some(heap_size::<T>);
+    }
+
+    fn set_numeric<T: Numeric>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.numeric = Some(NumericFns {
+            to_number: to_number::<T>,
+            from_number: from_number::<T>,
+            kernels: None,
+        });
+    }
+
+    // Must follow ``set_numeric``.
+    fn set_kernels(&mut self, kernels: NumericKernels) {
+        self.numeric.as_mut().unwrap().kernels = Some(kernels);
+    }
+
+    fn set_slice_kernels<T: Any + Copy + PartialEq>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.slice_kernels = Some(SliceKernels {
+            eq_slice: eq_slice::<T>,
+            contains: contains::<T>,
+            fill: fill::<T>,
+        });
+    }
+
+    #[cfg(feature = "serde")]
+    fn set_serialize<T: Any + serde::Serialize>(&mut self) {
+        self.assert_typecheck::<T>();
+        self.serialize = Some(crate::ser::serialize::<T>);
+    }
+
+    fn with(&self, set: impl FnOnce(&mut VTable)) -> &'static VTable {
+        let mut vtable = self.clone();
+        set(&mut vtable);
+        vtable.intern()
+    }
+
+    pub fn with_eq<T: Any + PartialEq>(&self) -> &'static VTable {
+        self.with(VTable::set_eq::<T>)
+    }
+
+    pub fn with_hash<T: Any + Hash + Eq>(&self) -> &'static VTable {
+        self.with(VTable::set_hash::<T>)
+    }
+
+    pub fn with_ord<T: Any + Ord>(&self) -> &'static VTable {
+        self.with(VTable::set_ord::<T>)
+    }
+
+    pub fn with_clone<T: Any + Clone>(&self) -> &'static VTable {
+        self.with(VTable::set_clone::<T>)
+    }
+
+    pub fn with_copy<T: Any + Copy>(&self) -> &'static VTable {
+        self.with(VTable::set_copy::<T>)
+    }
+
+    pub fn with_debug<T: Any + fmt::Debug>(&self) -> &'static VTable {
+        self.with(VTable::set_debug::<T>)
+    }
+
+    pub fn with_display<T: Any + fmt::Display>(&self) -> &'static VTable {
+        self.with(VTable::set_display::<T>)
+    }
+
+    pub fn with_heap_size<T: Any + HeapSize>(&self) -> &'static VTable {
+        self.with(VTable::set_heap_size::<T>)
+    }
+
+    pub fn with_parse<T: Any + FromStr>(&self) -> &'static VTable
+    where
+        T::Err: fmt::Display,
+    {
+        self.with(VTable::set_parse::<T>)
+    }
+
+    pub fn with_send<T: Any + Send>(&self) -> &'static VTable {
+        self.assert_typecheck::<T>();
+        self.with(|vtable| vtable.send = true)
+    }
+
+    pub fn with_sync<T: Any + Sync>(&self) -> &'static VTable {
+        self.assert_typecheck::<T>();
+        self.with(|vtable| vtable.sync = true)
+    }
+
+    pub fn with_numeric<T: Numeric>(&self) -> &'static VTable {
+        self.with(VTable::set_numeric::<T>)
+    }
+
+    #[cfg(feature = "bytemuck")]
+    pub fn with_pod<T: bytemuck::Pod>(&self) -> &'static VTable {
+        self.with(|vtable| {
+            vtable.set_copy::<T>();
+            vtable.pod = true;
+        })
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn with_serialize<T: Any + serde::Serialize>(&self) -> &'static VTable {
+        self.with(VTable::set_serialize::<T>)
+    }
+
+    pub fn id(&self) -> TypeId {
+        self.id
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        TypeId::of::<T>() == self.id
+    }
+
+    fn typecheck<T: Any>(&self) -> bool {
+        self.is::<T>()
+    }
+
+    pub fn assert_typecheck<T: Any>(&self) {
+        if !self.typecheck::<T>() {
+            panic!(
+                "Static type ({}) does not match runtime type ({})",
+                self.display_name,
+                type_name::<T>()
+            );
+        }
+    }
+
+    pub fn same_type(&self, other: &VTable) -> bool {
+        self.id == other.id
+    }
+
+    pub fn assert_same_type(&self, other: &VTable) {
+        if !self.same_type(other) {
+            panic!(
+                "Runtime types do not match ({} != {})",
+                self.display_name, other.display_name
+            );
+        }
+    }
+
+    pub fn require_eq(&self) -> EqFn {
+        match self.eq {
+            Some(eq) => eq,
+            None => panic!("{} does not support equality", self.display_name),
+        }
+    }
+
+    pub fn require_cmp(&self) -> CmpFn {
+        match self.cmp {
+            Some(cmp) => cmp,
+            None => panic!("{} does not support ordering", self.display_name),
+        }
+    }
+
+    pub fn require_clone(&self) -> CloneFns {
+        match self.clone {
+            Some(clone) => clone,
+            None => panic!("{} does not support cloning", self.display_name),
+        }
+    }
+
+    pub fn require_numeric(&self) -> NumericFns {
+        match self.numeric {
+            Some(numeric) => numeric,
+            None => panic!("{} is not numeric", self.display_name),
+        }
+    }
+
+    pub fn require_display(&self) -> FmtFn {
+        match self.display {
+            Some(display) => display,
+            None => panic!("{} does not support display", self.display_name),
+        }
+    }
+
+    pub fn require_hash(&self) -> (EqFn, HashFn) {
+        match (self.eq, self.hash) {
+            (Some(eq), Some(hash)) => (eq, hash),
+            _ => panic!("{} does not support hashing", self.display_name),
+        }
+    }
+}
+
+fn drop_vec<T>(data: *mut u8, length: usize, capacity: usize, alloc: &dyn Allocator) {
+    unsafe { AllocVec::from_raw_parts_in(data as *mut T, length, capacity, alloc) };
+}
+
+fn drop_slice<T>(data: *mut u8, length: usize) {
+    unsafe {
+        let s: &mut [T] = std::slice::from_raw_parts_mut(data as *mut T, length);
+        std::ptr::drop_in_place(s);
+    }
+}
+
+fn drop_box<T>(data: *mut u8) {
+    unsafe { drop(Box::from_raw(data as *mut T)) };
+}
+
+// Grow the buffer described by the first three arguments to fit
+// ``additional`` more elements, returning the new buffer and capacity.
+fn reserve<T>(
+    data: *mut u8,
+    length: usize,
+    capacity: usize,
+    additional: usize,
+    alloc: &dyn Allocator,
+) -> (*mut u8, usize) {
+    let mut vec = std::mem::ManuallyDrop::new(unsafe {
+        AllocVec::from_raw_parts_in(data as *mut T, length, capacity, alloc)
+    });
+    vec.reserve(additional);
+    (vec.as_mut_ptr() as *mut u8, vec.capacity())
+}
+
+fn shrink_to<T>(
+    data: *mut u8,
+    length: usize,
+    capacity: usize,
+    min_capacity: usize,
+    alloc: &dyn Allocator,
+) -> (*mut u8, usize) {
+    let mut vec = std::mem::ManuallyDrop::new(unsafe {
+        AllocVec::from_raw_parts_in(data as *mut T, length, capacity, alloc)
+    });
+    vec.shrink_to(min_capacity);
+    (vec.as_mut_ptr() as *mut u8, vec.capacity())
+}
+
+// Move the value at ``data`` into a new box. The source is left logically
+// uninitialized.
+fn box_value<T>(data: *const u8) -> *mut u8 {
+    Box::into_raw(Box::new(unsafe { std::ptr::read(data as *const T) })) as *mut u8
+}
+
+// Move the value out of a box created by ``box_value`` (or ``Box::new``) into
+// ``dest``, freeing the box.
+fn unbox_into<T>(boxed: *mut u8, dest: *mut u8) {
+    unsafe {
+        let value = *Box::from_raw(boxed as *mut T);
+        std::ptr::write(dest as *mut T, value);
+    }
+}
+
+fn eq<T: PartialEq>(a: *const u8, b: *const u8) -> bool {
+    unsafe { *(a as *const T) == *(b as *const T) }
+}
+
+fn hash<T: Hash>(data: *const u8, mut state: &mut dyn Hasher) {
+    unsafe { (*(data as *const T)).hash(&mut state) }
+}
+
+fn cmp<T: Ord>(a: *const u8, b: *const u8) -> Ordering {
+    unsafe { (*(a as *const T)).cmp(&*(b as *const T)) }
+}
+
+// Floats aren't ``Ord``, so we order them by IEEE 754 total order instead.
+trait Float: Arithmetic + Cast + PartialEq + fmt::Debug + fmt::Display + Send + Sync {
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+
+impl Float for f32 {
+    fn total_cmp(&self, other: &f32) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+}
+
+impl Float for f64 {
+    fn total_cmp(&self, other: &f64) -> Ordering {
+        f64::total_cmp(self, other)
+    }
+}
+
+fn total_cmp<T: Float>(a: *const u8, b: *const u8) -> Ordering {
+    unsafe { (*(a as *const T)).total_cmp(&*(b as *const T)) }
+}
+
+fn debug<T: fmt::Debug>(data: *const u8, f: &mut fmt::Formatter) -> fmt::Result {
+    unsafe { fmt::Debug::fmt(&*(data as *const T), f) }
+}
+
+fn display<T: fmt::Display>(data: *const u8, f: &mut fmt::Formatter) -> fmt::Result {
+    unsafe { fmt::Display::fmt(&*(data as *const T), f) }
+}
+
+fn parse<T: FromStr>(input: &str, dest: *mut u8) -> Result<(), ParseError>
+where
+    T::Err: fmt::Display,
+{
+    match input.parse::<T>() {
+        Ok(value) => {
+            unsafe { std::ptr::write(dest as *mut T, value) };
+            Ok(())
+        }
+        Err(err) => Err(ParseError {
+            type_name: type_name::<T>(),
+            input: input.to_owned(),
+            message: err.to_string(),
+        }),
+    }
+}
+
+// If a clone panics, the clones already made are dropped, leaving ``dest``
+// uninitialized.
+fn clone_into<T: Clone>(src: *const u8, dest: *mut u8, count: usize) {
+    let src = src as *const T;
+    let mut guard = InitGuard::new(dest, std::mem::size_of::<T>(), drop_slice::<T>);
+    for i in 0..count {
+        unsafe { std::ptr::write(guard.next() as *mut T, (*src.add(i)).clone()) };
+        guard.wrote_one();
+    }
+    guard.finish();
+}
+
+fn copy_into<T: Copy>(src: *const u8, dest: *mut u8, count: usize) {
+    unsafe { std::ptr::copy_nonoverlapping(src as *const T, dest as *mut T, count) };
+}
+
+fn clone_box<T: Clone>(src: *const u8) -> *mut u8 {
+    Box::into_raw(Box::new(unsafe { (*(src as *const T)).clone() })) as *mut u8
+}
+
+fn heap_size<T: HeapSize>(data: *const u8) -> usize {
+    unsafe { (*(data as *const T)).heap_size() }
+}
+
+fn to_number<T: Numeric>(data: *const u8) -> Number {
+    unsafe { (*(data as *const T)).to_number() }
+}
+
+fn from_number<T: Numeric>(number: Number) -> Option<AnyValue> {
+    T::from_number(number).map(AnyValue::new)
+}
+
+fn sum_integers<T: Numeric>(data: *const u8, count: usize) -> Number {
+    let values = unsafe { std::slice::from_raw_parts(data as *const T, count) };
+    // No run of primitive integers is long enough to overflow an ``i128``.
+    let sum = values.iter().map(|value| match value.to_number() {
+        Number::Int(i) => i,
+        Number::Float(_) => unreachable!(),
+    });
+    Number::Int(sum.sum())
+}
+
+fn sum_floats<T: Numeric>(data: *const u8, count: usize) -> Number {
+    let values = unsafe { std::slice::from_raw_parts(data as *const T, count) };
+    let sum = values.iter().map(|value| match value.to_number() {
+        Number::Float(f) => f,
+        Number::Int(_) => unreachable!(),
+    });
+    Number::Float(sum.sum())
+}
+
+// The slice kernels compare a block of elements at a time without branching,
+// which the compiler can vectorize, and only check the result between
+// blocks.
+const BLOCK: usize = 64;
+
+fn eq_slice<T: PartialEq>(a: *const u8, b: *const u8, count: usize) -> bool {
+    let (a, b) = unsafe {
+        (
+            std::slice::from_raw_parts(a as *const T, count),
+            std::slice::from_raw_parts(b as *const T, count),
+        )
+    };
+    a.chunks(BLOCK)
+        .zip(b.chunks(BLOCK))
+        .all(|(a, b)| a.iter().zip(b).fold(true, |equal, (x, y)| equal & (x == y)))
+}
+
+fn contains<T: PartialEq>(data: *const u8, count: usize, needle: *const u8) -> bool {
+    let values = unsafe { std::slice::from_raw_parts(data as *const T, count) };
+    let needle = unsafe { &*(needle as *const T) };
+    values
+        .chunks(BLOCK)
+        .any(|block| block.iter().fold(false, |found, x| found | (x == needle)))
+}
+
+fn fill<T: Copy>(data: *mut u8, count: usize, value: *const u8) {
+    let values = unsafe { std::slice::from_raw_parts_mut(data as *mut T, count) };
+    values.fill(unsafe { *(value as *const T) });
+}
+
+/// The index of the first element that no other compares ``better`` than.
+fn extreme_index<T>(
+    data: *const u8,
+    count: usize,
+    cmp: impl Fn(&T, &T) -> Ordering,
+    better: Ordering,
+) -> Option<usize> {
+    let values = unsafe { std::slice::from_raw_parts(data as *const T, count) };
+    if values.is_empty() {
+        return None;
+    }
+    let mut best = 0;
+    for (i, value) in values.iter().enumerate().skip(1) {
+        if cmp(value, &values[best]) == better {
+            best = i;
+        }
+    }
+    Some(best)
+}
+
+fn argmin<T: Ord>(data: *const u8, count: usize) -> Option<usize> {
+    extreme_index(data, count, T::cmp, Ordering::Less)
+}
+
+fn argmax<T: Ord>(data: *const u8, count: usize) -> Option<usize> {
+    extreme_index(data, count, T::cmp, Ordering::Greater)
+}
+
+fn argmin_float<T: Float>(data: *const u8, count: usize) -> Option<usize> {
+    extreme_index(data, count, T::total_cmp, Ordering::Less)
+}
+
+fn argmax_float<T: Float>(data: *const u8, count: usize) -> Option<usize> {
+    extreme_index(data, count, T::total_cmp, Ordering::Greater)
+}
+
+/// A borrowed element that hashes and compares through its vtable, so that
+/// erased elements can be used as keys in a ``HashMap``.
+pub struct HashKey<'a> {
+    data: *const u8,
+    eq: EqFn,
+    hash: HashFn,
+    _marker: PhantomData<&'a u8>,
+}
+
+impl<'a> HashKey<'a> {
+    pub fn new(data: *const u8, (eq, hash): (EqFn, HashFn)) -> HashKey<'a> {
+        HashKey {
+            data,
+            eq,
+            hash,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Hash for HashKey<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.hash)(self.data, state)
+    }
+}
+
+impl PartialEq for HashKey<'_> {
+    fn eq(&self, other: &HashKey) -> bool {
+        (self.eq)(self.data, other.data)
+    }
+}
+
+impl Eq for HashKey<'_> {}
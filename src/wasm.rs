@@ -0,0 +1,140 @@
+//! Exchanging numeric vectors with JavaScript as typed arrays, enabled by the
+//! ``wasm`` feature.
+//!
+//! Only the element types with a typed array counterpart are supported:
+//! ``i8``, ``i16``, ``i32``, ``i64``, ``u8``, ``u16``, ``u32``, ``u64``,
+//! ``f32`` and ``f64``. Conversions copy the whole buffer at once, rather
+//! than element by element. Calling them outside of WebAssembly panics.
+
+use js_sys::wasm_bindgen::{JsCast, JsValue};
+use js_sys::{
+    BigInt64Array, BigUint64Array, Float32Array, Float64Array, Int16Array, Int32Array, Int8Array,
+    Uint16Array, Uint32Array, Uint8Array,
+};
+
+use crate::{Allocator, AnyVec};
+
+macro_rules! typed_arrays {
+    ($($t:ty => $array:ident),*) => {
+        impl<A: Allocator> AnyVec<A> {
+            /// Copy our elements into a new JavaScript typed array of the
+            /// matching kind, e.g. a ``Float64Array`` for ``f64``s. There's no
+            /// result if our element type has no typed array counterpart.
+            pub fn to_typed_array(&self) -> Option<JsValue> {
+                $(
+                    if let Some(values) = self.as_any_slice().downcast::<$t>() {
+                        return Some($array::from(values).into());
+                    }
+                )*
+                None
+            }
+
+            /// A typed array viewing our buffer directly in WebAssembly
+            /// memory, without copying.
+            ///
+            /// # Safety
+            ///
+            /// The view must not be used after we're modified or dropped, or
+            /// after WebAssembly memory grows (which any allocation can
+            /// cause), since its contents would then be stale or invalid.
+            pub unsafe fn view_typed_array(&self) -> Option<JsValue> {
+                $(
+                    if let Some(values) = self.as_any_slice().downcast::<$t>() {
+                        return Some($array::view(values).into());
+                    }
+                )*
+                None
+            }
+        }
+
+        impl AnyVec {
+            /// Copy the elements of a JavaScript typed array into a new
+            /// vector of the matching element type. There's no result if
+            /// ``value`` isn't a supported typed array.
+            pub fn from_typed_array(value: &JsValue) -> Option<AnyVec> {
+                $(
+                    if let Some(array) = value.dyn_ref::<$array>() {
+                        return Some(AnyVec::from_vec::<$t>(array.to_vec()));
+                    }
+                )*
+                None
+            }
+        }
+    };
+}
+
+typed_arrays!(
+    i8 => Int8Array,
+    i16 => Int16Array,
+    i32 => Int32Array,
+    i64 => BigInt64Array,
+    u8 => Uint8Array,
+    u16 => Uint16Array,
+    u32 => Uint32Array,
+    u64 => BigUint64Array,
+    f32 => Float32Array,
+    f64 => Float64Array
+);
+
+// In WebAssembly, run these with ``wasm-bindgen-test-runner`` as the test
+// runner, e.g. ``cargo test --target wasm32-unknown-unknown --features wasm``
+// with ``CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER`` set.
+#[cfg(test)]
+mod tests {
+    use crate::AnyVec;
+
+    #[cfg(target_arch = "wasm32")]
+    use js_sys::wasm_bindgen::{JsCast, JsValue};
+    #[cfg(target_arch = "wasm32")]
+    use js_sys::{BigInt64Array, Float64Array};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    // Unsupported types are turned away before touching JavaScript, so this
+    // also runs natively.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_unsupported_types() {
+        let names = AnyVec::from_vec(vec![String::from("a")]);
+        assert!(names.to_typed_array().is_none());
+        assert!(unsafe { names.view_typed_array() }.is_none());
+        let flags = AnyVec::from_vec(vec![true]);
+        assert!(flags.to_typed_array().is_none());
+        assert!(AnyVec::new::<usize>().to_typed_array().is_none());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_round_trip() {
+        let prices = AnyVec::from_vec::<f64>(vec![9.5, 10.0]);
+        let array = prices.to_typed_array().unwrap();
+        assert_eq!(
+            array.dyn_ref::<Float64Array>().unwrap().to_vec(),
+            [9.5, 10.0]
+        );
+        assert!(AnyVec::from_typed_array(&array).unwrap() == prices);
+
+        let ids = AnyVec::from_vec::<i64>(vec![-1, i64::MAX]);
+        let array = ids.to_typed_array().unwrap();
+        assert!(array.is_instance_of::<BigInt64Array>());
+        assert!(AnyVec::from_typed_array(&array).unwrap() == ids);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_view() {
+        let counts = AnyVec::from_vec::<u32>(vec![1, 2, 3]);
+        let view = unsafe { counts.view_typed_array() }.unwrap();
+        assert_eq!(
+            view.dyn_ref::<js_sys::Uint32Array>().unwrap().to_vec(),
+            [1, 2, 3]
+        );
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_from_non_typed_array() {
+        assert!(AnyVec::from_typed_array(&JsValue::from_str("1, 2")).is_none());
+        assert!(AnyVec::from_typed_array(&js_sys::Array::new().into()).is_none());
+    }
+}